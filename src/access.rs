@@ -0,0 +1,34 @@
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+/// What a session is allowed to do. Guests can search and view status but
+/// cannot add or remove torrents or change settings.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Role {
+    #[default]
+    Admin,
+    Guest,
+}
+
+impl Role {
+    pub fn can_manage(self) -> bool {
+        self == Role::Admin
+    }
+
+    /// Fail with a user-facing message when a guest attempts `action`.
+    pub fn require_manage(self, action: &str) -> Result<()> {
+        if self.can_manage() {
+            Ok(())
+        } else {
+            Err(anyhow::anyhow!("Guests can't {}; ask an admin", action))
+        }
+    }
+}
+
+/// Name recorded against requests made from this account.
+pub fn current_user() -> String {
+    std::env::var("USER")
+        .or_else(|_| std::env::var("USERNAME"))
+        .unwrap_or_else(|_| "guest".to_string())
+}
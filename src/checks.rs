@@ -0,0 +1,205 @@
+use anyhow::Result;
+use std::time::Duration;
+
+use crate::config::NotificationsConfig;
+use crate::digest;
+use crate::downloader::Grabber;
+use crate::feeds;
+use crate::models::EvaluatedResult;
+use crate::notifications::Notifier;
+use crate::pacing;
+use crate::safety;
+use crate::smart_search::SearchService;
+use crate::state::{DigestOutcome, Feed, QueueStatus, StateDb, WatchItem, WatchRule, WatchStatus};
+use crate::watch;
+
+/// Watchlist matches below this relevance wait for a better release.
+const WATCHLIST_MIN_RELEVANCE: f32 = 0.9;
+
+/// How a check of the watchlist, watch rules or feeds is going, for the
+/// caller to report: the daemon logs it, the CLI prints it. `label` names
+/// the title, rule or feed, e.g. `Watch #3 "dune 2160p"`.
+pub enum CheckEvent<'a> {
+    Started { label: &'a str },
+    Failed { label: &'a str, error: &'a str },
+    /// Nothing grabbed this time, and why
+    Skipped { label: &'a str, reason: &'a str },
+    /// Results a watch rule hadn't reported before, best first
+    Found { label: &'a str, new: &'a [EvaluatedResult] },
+    Grabbing { label: &'a str, title: &'a str },
+    Queued { label: &'a str, id: i64, title: &'a str },
+}
+
+/// Search for each released watchlist title and grab its best match when
+/// it's confident and has no red flags. Searches start spread over
+/// `window`; one title failing doesn't hold up the rest.
+pub async fn watchlist(
+    notifications: &NotificationsConfig,
+    items: &[WatchItem],
+    searcher: &impl SearchService,
+    grabber: &dyn Grabber,
+    window: Duration,
+    on_event: &(impl Fn(&CheckEvent) + Send + Sync),
+) -> Result<()> {
+    let started = tokio::time::Instant::now();
+    for (item, offset) in items.iter().zip(pacing::spread(items.len(), window)) {
+        tokio::time::sleep_until(started + offset).await;
+        let label = &format!("Watchlist #{} {}", item.id, item.title);
+        let record = |outcome, detail: &str| digest::record(notifications, "watchlist", &item.title, outcome, Some(detail));
+        let skip = |reason: &str| {
+            on_event(&CheckEvent::Skipped { label, reason });
+            record(DigestOutcome::Skipped, reason);
+        };
+        let fail = |e: anyhow::Error| {
+            on_event(&CheckEvent::Failed { label, error: &e.to_string() });
+            record(DigestOutcome::Failed, &e.to_string());
+        };
+
+        on_event(&CheckEvent::Started { label });
+        let results = match searcher.search(&item.query).await {
+            Ok(results) => results,
+            Err(e) => {
+                fail(e);
+                continue;
+            }
+        };
+        let best = match results.first() {
+            None => {
+                skip("no releases yet");
+                continue;
+            }
+            Some(best) if best.relevance_score < WATCHLIST_MIN_RELEVANCE => {
+                skip("no confident match yet");
+                continue;
+            }
+            Some(best) if !safety::is_safe(&best.torrent) => {
+                skip("best match has red flags");
+                continue;
+            }
+            Some(best) => best,
+        };
+
+        on_event(&CheckEvent::Grabbing { label, title: &best.torrent.title });
+        if let Err(e) = grabber.grab(&best.torrent.title, &best.torrent.magnet_link).await {
+            fail(e);
+            continue;
+        }
+        StateDb::open()?.set_watch_status(item.id, WatchStatus::Grabbed)?;
+        record(DigestOutcome::Grabbed, &best.torrent.title);
+    }
+    Ok(())
+}
+
+/// Run each watch rule once, notifying about new results and grabbing the
+/// best above the rule's threshold. Rules start spread over `window`; one
+/// failing doesn't hold up the others.
+pub async fn watch_rules(
+    notifications: &NotificationsConfig,
+    rules: &[WatchRule],
+    searcher: &impl SearchService,
+    grabber: &dyn Grabber,
+    notifier: &Notifier,
+    window: Duration,
+    on_event: &(impl Fn(&CheckEvent) + Send + Sync),
+) -> Result<()> {
+    let started = tokio::time::Instant::now();
+    for (rule, offset) in rules.iter().zip(pacing::spread(rules.len(), window)) {
+        tokio::time::sleep_until(started + offset).await;
+        let subject = format!("\"{}\"", rule.query);
+        let label = &format!("Watch #{} {}", rule.id, subject);
+        let record = |outcome, detail: &str| digest::record(notifications, "watch", &subject, outcome, Some(detail));
+        let fail = |e: anyhow::Error| {
+            on_event(&CheckEvent::Failed { label, error: &e.to_string() });
+            record(DigestOutcome::Failed, &e.to_string());
+        };
+
+        on_event(&CheckEvent::Started { label });
+        let checked = match watch::check(rule, searcher, notifier).await {
+            Ok(checked) => checked,
+            Err(e) => {
+                fail(e);
+                continue;
+            }
+        };
+        if checked.new.is_empty() {
+            on_event(&CheckEvent::Skipped { label, reason: "nothing new" });
+            continue;
+        }
+
+        on_event(&CheckEvent::Found { label, new: &checked.new });
+        let Some(best) = checked.download else {
+            record(DigestOutcome::Found, &watch::describe_new(&checked.new));
+            continue;
+        };
+        on_event(&CheckEvent::Grabbing { label, title: &best.torrent.title });
+        match grabber.grab(&best.torrent.title, &best.torrent.magnet_link).await {
+            Ok(()) => record(DigestOutcome::Grabbed, &best.torrent.title),
+            Err(e) => fail(e),
+        }
+    }
+    Ok(())
+}
+
+/// Poll each feed once and queue its new items that pass the feed's
+/// filter. With a `grabber`, queued items start straight away; without
+/// one they wait for `queue run`. One feed failing doesn't hold up the
+/// others.
+pub async fn feeds(
+    notifications: &NotificationsConfig,
+    due: &[Feed],
+    searcher: &impl SearchService,
+    grabber: Option<&dyn Grabber>,
+    on_event: &(impl Fn(&CheckEvent) + Send + Sync),
+) -> Result<()> {
+    for feed in due {
+        let subject = feeds::redact(&feed.url);
+        let label = &format!("Feed #{} {}", feed.id, subject);
+        let record = |outcome, detail: &str| digest::record(notifications, "feed", &subject, outcome, Some(detail));
+
+        on_event(&CheckEvent::Started { label });
+        let grabs = match feeds::poll(feed, searcher).await {
+            Ok(grabs) => grabs,
+            Err(e) => {
+                on_event(&CheckEvent::Failed { label, error: &e.to_string() });
+                record(DigestOutcome::Failed, &e.to_string());
+                continue;
+            }
+        };
+        if grabs.is_empty() {
+            on_event(&CheckEvent::Skipped { label, reason: "nothing new to queue" });
+            continue;
+        }
+
+        for item in grabs {
+            let torrent = &item.result;
+            let id = {
+                let state = StateDb::open()?;
+                let id = state.enqueue(&torrent.title, &torrent.magnet_link)?;
+                feeds::mark_handled(&state, feed, &item)?;
+                id
+            };
+            on_event(&CheckEvent::Queued { label, id, title: &torrent.title });
+            let Some(grabber) = grabber else {
+                record(DigestOutcome::Grabbed, &torrent.title);
+                continue;
+            };
+
+            on_event(&CheckEvent::Grabbing { label, title: &torrent.title });
+            let grabbed = grabber.grab(&torrent.title, &torrent.magnet_link).await;
+            let state = StateDb::open()?;
+            match grabbed {
+                // The grabber takes it from here
+                Ok(()) => {
+                    state.set_queue_status(id, QueueStatus::Downloading, None)?;
+                    record(DigestOutcome::Grabbed, &torrent.title);
+                }
+                Err(e) => {
+                    on_event(&CheckEvent::Failed { label, error: &e.to_string() });
+                    state.set_queue_status(id, QueueStatus::Failed, Some(&e.to_string()))?;
+                    record(DigestOutcome::Failed, &e.to_string());
+                }
+            }
+        }
+    }
+    Ok(())
+}
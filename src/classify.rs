@@ -0,0 +1,247 @@
+use lazy_static::lazy_static;
+use regex::Regex;
+use std::path::{Path, PathBuf};
+use tracing::warn;
+
+use crate::llm_service::LlmService;
+use crate::models::ContentType;
+use crate::organize;
+use crate::ranker::episode_marker;
+use crate::state::TorrentRecord;
+use crate::storage::format_bytes;
+
+lazy_static! {
+    static ref TV_RE: Regex = Regex::new(r"(?i)\b(s\d{1,2}(e\d{1,3})?|season\s*\d+|complete\s+series|\d{1,2}x\d{2})\b").unwrap();
+    static ref GAME_RE: Regex = Regex::new(
+        r"(?i)\b(fitgirl|dodi|codex|plaza|skidrow|elamigos|tenoke|empress|rune|flt|razor1911|gog|repack|nsp|xci|ps[345]|switch)\b"
+    ).unwrap();
+    static ref MUSIC_RE: Regex = Regex::new(r"(?i)\b(flac|mp3|aac|320\s?kbps|v0|discography|album|lossless|24bit|vinyl)\b").unwrap();
+    static ref BOOK_RE: Regex = Regex::new(r"(?i)\b(epub|mobi|azw3|pdf|ebook|audiobook|m4b|cbz|cbr)\b").unwrap();
+    static ref SOFTWARE_RE: Regex = Regex::new(
+        r"(?i)\b(x64|x86|win(dows)?\s?\d*|macos|linux|portable|crack(ed)?|keygen|activat\w*|setup|v\d+\.\d+)\b"
+    ).unwrap();
+}
+
+pub const VIDEO_EXTENSIONS: &[&str] = &["mkv", "mp4", "avi", "m4v", "mov", "wmv", "ts", "webm", "mpg", "mpeg", "flv"];
+pub const AUDIO_EXTENSIONS: &[&str] = &["mp3", "flac", "m4a", "aac", "ogg", "opus", "wav", "alac", "ape", "wv"];
+pub const BOOK_EXTENSIONS: &[&str] = &["epub", "pdf", "mobi", "azw3", "cbz", "cbr", "djvu", "m4b"];
+const SOFTWARE_EXTENSIONS: &[&str] = &["exe", "msi", "dmg", "pkg", "deb", "rpm", "appimage", "iso", "apk", "bin"];
+
+/// What a download most likely is, worked out without knowing what was asked for.
+#[derive(Debug, Clone)]
+pub struct Classification {
+    pub content_type: ContentType,
+    /// False when the guess rests on weak hints; worth asking the LLM about
+    pub confident: bool,
+}
+
+/// Classify a torrent from its release name and files (relative paths with
+/// sizes). Most of the bytes decide the kind of content; the name then tells
+/// a series from a movie or a game from other software, and stands in for
+/// the files when there are none to go on.
+pub fn classify(name: &str, files: &[(PathBuf, u64)]) -> Classification {
+    let total: u64 = files.iter().map(|(_, len)| len).sum();
+    let share = |extensions: &[&str]| -> f64 {
+        let bytes: u64 = files.iter()
+            .filter(|(path, _)| has_extension(path, extensions))
+            .map(|(_, len)| len)
+            .sum();
+        if total == 0 { 0.0 } else { bytes as f64 / total as f64 }
+    };
+
+    let episodes = files.iter()
+        .filter(|(path, _)| has_extension(path, VIDEO_EXTENSIONS))
+        .filter(|(path, _)| path.file_name().is_some_and(|n| episode_marker(&n.to_string_lossy()).is_some()))
+        .count();
+    let confident = |content_type| Classification { content_type, confident: true };
+
+    if share(VIDEO_EXTENSIONS) > 0.5 {
+        return if episodes > 0 || TV_RE.is_match(name) {
+            confident(ContentType::TVShow)
+        } else {
+            confident(ContentType::Movie)
+        };
+    }
+    if share(AUDIO_EXTENSIONS) > 0.5 {
+        return confident(ContentType::Music);
+    }
+    if share(BOOK_EXTENSIONS) > 0.5 {
+        return confident(ContentType::Book);
+    }
+    if share(SOFTWARE_EXTENSIONS) > 0.5 {
+        return Classification {
+            content_type: if GAME_RE.is_match(name) { ContentType::Game } else { ContentType::Software },
+            confident: GAME_RE.is_match(name) || SOFTWARE_RE.is_match(name),
+        };
+    }
+
+    // Nothing dominates, or no files yet: go by the name alone
+    let content_type = if TV_RE.is_match(name) {
+        ContentType::TVShow
+    } else if GAME_RE.is_match(name) {
+        ContentType::Game
+    } else if MUSIC_RE.is_match(name) {
+        ContentType::Music
+    } else if BOOK_RE.is_match(name) {
+        ContentType::Book
+    } else if SOFTWARE_RE.is_match(name) {
+        ContentType::Software
+    } else if share(VIDEO_EXTENSIONS) > 0.0 {
+        ContentType::Movie
+    } else {
+        ContentType::Other("unknown".to_string())
+    };
+    Classification { content_type, confident: false }
+}
+
+/// Classify files already on disk, named after their common folder.
+pub fn classify_paths(paths: &[PathBuf]) -> Classification {
+    let files: Vec<(PathBuf, u64)> = paths.iter()
+        .map(|path| (path.clone(), std::fs::metadata(path).map(|m| m.len()).unwrap_or(0)))
+        .collect();
+    let name = release_name(paths).unwrap_or_default();
+    classify(&name, &files)
+}
+
+/// The folder every path shares, or the single file's name.
+pub fn release_name(paths: &[PathBuf]) -> Option<String> {
+    let first = paths.first()?;
+    if paths.len() == 1 {
+        return Some(first.file_name()?.to_string_lossy().into_owned());
+    }
+    let mut common = first.parent()?;
+    while !paths.iter().all(|p| p.starts_with(common)) {
+        common = common.parent()?;
+    }
+    Some(common.file_name()?.to_string_lossy().into_owned())
+}
+
+pub fn has_extension(path: &Path, extensions: &[&str]) -> bool {
+    path.extension()
+        .map(|e| extensions.contains(&e.to_string_lossy().to_lowercase().as_str()))
+        .unwrap_or(false)
+}
+
+/// A completed download's files on disk with their sizes: its folder under
+/// the output directory, or the single file.
+pub fn download_files(torrent: &TorrentRecord) -> Vec<(PathBuf, u64)> {
+    organize::collect_files(&torrent.output_dir.join(&torrent.name))
+        .unwrap_or_default()
+        .into_iter()
+        .map(|path| {
+            let len = std::fs::metadata(&path).map(|m| m.len()).unwrap_or(0);
+            (path, len)
+        })
+        .collect()
+}
+
+/// Classify a download from its name and files, asking `llm` when the
+/// heuristics aren't sure. The heuristic guess stands if the LLM fails.
+pub async fn classify_download(torrent: &TorrentRecord, llm: Option<&LlmService>) -> ContentType {
+    let mut files = download_files(torrent);
+    let classification = classify(&torrent.name, &files);
+    let Some(llm) = llm.filter(|_| !classification.confident) else {
+        return classification.content_type;
+    };
+
+    files.sort_by_key(|(_, len)| std::cmp::Reverse(*len));
+    let largest: Vec<String> = files.iter()
+        .take(10)
+        .map(|(path, len)| format!("{} ({})", path.strip_prefix(&torrent.output_dir).unwrap_or(path).display(), format_bytes(*len)))
+        .collect();
+    match llm.classify_torrent(&torrent.name, &largest).await {
+        Ok(content_type) => content_type,
+        Err(e) => {
+            warn!("Could not classify {} with the LLM, using the heuristic guess: {}", torrent.name, e);
+            classification.content_type
+        }
+    }
+}
+
+/// A video worth keeping: right extension and not a release sample.
+pub fn is_video(path: &Path) -> bool {
+    has_extension(path, VIDEO_EXTENSIONS)
+        && !path.file_name().is_some_and(|name| name.to_string_lossy().to_lowercase().contains("sample"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MB: u64 = 1024 * 1024;
+
+    /// The label and confidence `classify` gives a release.
+    fn classified(name: &str, files: &[(&str, u64)]) -> (String, bool) {
+        let files: Vec<(PathBuf, u64)> = files.iter().map(|(path, len)| (PathBuf::from(path), *len)).collect();
+        let classification = classify(name, &files);
+        (classification.content_type.label().to_string(), classification.confident)
+    }
+
+    fn label(content_type: ContentType) -> String {
+        content_type.label().to_string()
+    }
+
+    #[test]
+    fn movies() {
+        let files = [
+            ("Dune.2021.1080p.BluRay.x264/Dune.2021.1080p.BluRay.x264.mkv", 8000 * MB),
+            ("Dune.2021.1080p.BluRay.x264/Sample/sample.mkv", 50 * MB),
+            ("Dune.2021.1080p.BluRay.x264/Subs/English.srt", 1),
+        ];
+        assert_eq!(classified("Dune.2021.1080p.BluRay.x264", &files), (label(ContentType::Movie), true));
+        // A name alone with no files to go on isn't enough to be sure
+        assert!(!classified("Dune.2021.1080p.BluRay.x264", &[]).1);
+    }
+
+    #[test]
+    fn tv_shows() {
+        let episodes = [
+            ("The.Bear.S02.1080p.WEB/The.Bear.S02E01.1080p.WEB.mkv", 1500 * MB),
+            ("The.Bear.S02.1080p.WEB/The.Bear.S02E02.1080p.WEB.mkv", 1400 * MB),
+        ];
+        assert_eq!(classified("The.Bear.S02.1080p.WEB", &episodes), (label(ContentType::TVShow), true));
+        // Episode files mark a series even when the pack's name doesn't
+        assert_eq!(classified("The Bear Complete", &episodes).0, label(ContentType::TVShow));
+        assert_eq!(classified("Breaking Bad Season 3 720p", &[]), (label(ContentType::TVShow), false));
+    }
+
+    #[test]
+    fn music() {
+        let files = [
+            ("Radiohead - OK Computer (1997) [FLAC]/01 - Airbag.flac", 40 * MB),
+            ("Radiohead - OK Computer (1997) [FLAC]/02 - Paranoid Android.flac", 55 * MB),
+            ("Radiohead - OK Computer (1997) [FLAC]/cover.jpg", 2 * MB),
+        ];
+        assert_eq!(classified("Radiohead - OK Computer (1997) [FLAC]", &files), (label(ContentType::Music), true));
+        assert_eq!(classified("Radiohead Discography 320kbps", &[]), (label(ContentType::Music), false));
+    }
+
+    #[test]
+    fn software() {
+        let files = [
+            ("GIMP.2.10.36.x64/gimp-2.10.36-setup.exe", 300 * MB),
+            ("GIMP.2.10.36.x64/readme.txt", 1),
+        ];
+        assert_eq!(classified("GIMP.2.10.36.x64", &files), (label(ContentType::Software), true));
+        // Installers under a name with no software tags are a weaker guess
+        assert_eq!(classified("Tools Collection", &[("Tools/tool.exe", 30 * MB)]), (label(ContentType::Software), false));
+        // Repackers and scene groups make it a game
+        let game = [("Elden.Ring-FitGirl.Repack/setup.exe", 50 * MB), ("Elden.Ring-FitGirl.Repack/data.bin", 40000 * MB)];
+        assert_eq!(classified("Elden.Ring-FitGirl.Repack", &game), (label(ContentType::Game), true));
+    }
+
+    #[test]
+    fn most_bytes_decide() {
+        // A movie with a soundtrack bundled is still a movie
+        let files = [("Film/film.mkv", 4000 * MB), ("Film/OST/01.mp3", 8 * MB), ("Film/OST/02.mp3", 8 * MB)];
+        assert_eq!(classified("Film 2019 720p", &files).0, label(ContentType::Movie));
+        // A music video among the album's tracks doesn't make it a movie
+        let files = [("Album/01.flac", 40 * MB), ("Album/02.flac", 40 * MB), ("Album/video.mp4", 30 * MB)];
+        assert_eq!(classified("Album", &files).0, label(ContentType::Music));
+    }
+
+    #[test]
+    fn nothing_to_go_on() {
+        assert_eq!(classified("random upload", &[]), (label(ContentType::Other("unknown".to_string())), false));
+    }
+}
@@ -0,0 +1,2137 @@
+use anyhow::Result;
+use clap::{Parser, Subcommand};
+use std::path::PathBuf;
+use tracing_subscriber::filter::{filter_fn, LevelFilter};
+use tracing_subscriber::prelude::*;
+
+use crate::{
+    access, checks, classify, config, daemon, digest, downloader, export, feeds, filter, inbox, library,
+    llm_service, metadata, migrations, models, nettest, notifications, organize, output, pacing,
+    pirate_bay_scraper, progress, safety, secrets, selftest, smart_search, state, stats, storage,
+    stream, subtitles, trace, trackers, tui, watch, yts_scraper, SourceRegistry,
+};
+
+/// Below this, the best match is only suggested, not downloaded or streamed.
+const AUTO_DOWNLOAD_MIN_RELEVANCE: f32 = 0.9;
+
+/// The `torrentai` command line.
+#[derive(Parser)]
+#[command(name = "torrentai")]
+#[command(about = "Natural Language BitTorrent Client", long_about = None)]
+pub struct Cli {
+    /// Config profile to use (defaults to $TORRENTAI_PROFILE)
+    #[arg(long, global = true)]
+    profile: Option<String>,
+    
+    #[command(subcommand)]
+    command: Commands,
+}
+
+#[derive(Subcommand)]
+enum Commands {
+    /// Download torrents from magnet links or .torrent files
+    Download {
+        /// Magnet links or paths to .torrent files; several download at once
+        #[arg(required = true)]
+        torrents: Vec<String>,
+        
+        /// Download directory (defaults to a configured download root)
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+        
+        /// Fetch subtitles from OpenSubtitles once finished (e.g. en,es)
+        #[arg(long, value_delimiter = ',')]
+        subtitles: Vec<String>,
+        
+        /// Move the finished download into the media library
+        #[arg(long)]
+        organize: bool,
+    },
+    
+    /// Download a video in playback order and serve it over local HTTP for VLC or mpv
+    Stream {
+        /// Magnet link, .torrent URL or file, or a natural language query to search for
+        torrent: String,
+        
+        /// Local port to serve on (0 picks a free one)
+        #[arg(long, default_value = "8888")]
+        port: u16,
+        
+        /// Download directory (defaults to a configured download root)
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+        
+        /// Minimum confidence for a searched result (0.0-1.0)
+        #[arg(long, default_value = "0.7")]
+        min_confidence: f32,
+        
+        /// LLM model to use when searching, overriding the profile's
+        #[arg(long)]
+        model: Option<String>,
+        
+        /// Rank search results with deterministic heuristics instead of the LLM
+        #[arg(long)]
+        no_llm: bool,
+        
+        /// Stream the best match even if the title is already in the library
+        #[arg(long)]
+        force: bool,
+    },
+    
+    /// Search for torrents on ThePirateBay
+    Search {
+        /// Search query
+        query: String,
+        
+        #[command(flatten)]
+        filter: filter::ResultFilter,
+        
+        #[command(flatten)]
+        order: filter::ResultOrder,
+        
+        #[command(flatten)]
+        format: output::ResultOutput,
+        
+        /// Also save the results to this JSON file, for `torrentai import`
+        #[arg(long)]
+        export: Option<PathBuf>,
+    },
+    
+    /// Search for movies on YTS
+    SearchYts {
+        /// Search query
+        query: String,
+        
+        #[command(flatten)]
+        yts: yts_scraper::YtsFilter,
+        
+        #[command(flatten)]
+        filter: filter::ResultFilter,
+        
+        #[command(flatten)]
+        order: filter::ResultOrder,
+        
+        #[command(flatten)]
+        format: output::ResultOutput,
+        
+        /// Also save the results to this JSON file, for `torrentai import`
+        #[arg(long)]
+        export: Option<PathBuf>,
+    },
+    
+    /// Search every source at once, ThePirateBay and YTS by default
+    SearchAll {
+        /// Search query
+        query: String,
+        
+        #[command(flatten)]
+        filter: filter::ResultFilter,
+        
+        #[command(flatten)]
+        order: filter::ResultOrder,
+        
+        #[command(flatten)]
+        format: output::ResultOutput,
+        
+        /// Also save the results to this JSON file, for `torrentai import`
+        #[arg(long)]
+        export: Option<PathBuf>,
+    },
+    
+    /// Show status of active downloads
+    Status,
+    
+    /// List downloaded content
+    List {
+        /// Only show one content type (movie, tv_show, music, software, book, game)
+        #[arg(long = "type")]
+        content_type: Option<String>,
+        
+        /// Classify and remember the content type of downloads that have none
+        #[arg(long)]
+        classify: bool,
+        
+        /// Ollama model to use for classifications the heuristics aren't sure of
+        #[arg(long)]
+        model: Option<String>,
+        
+        /// Classify with heuristics only
+        #[arg(long)]
+        no_llm: bool,
+        
+        /// List archived downloads instead
+        #[arg(long)]
+        archived: bool,
+    },
+    
+    /// Delete a finished download's files but keep it listed as archived, so
+    /// it still counts as already downloaded
+    Archive {
+        /// Info hash (or its first characters, as `list` shows) or part of the name
+        id: String,
+        
+        /// Only archive once it has uploaded this many times its size
+        #[arg(long)]
+        ratio: Option<f64>,
+        
+        /// Show what would be deleted without deleting it
+        #[arg(long)]
+        dry_run: bool,
+    },
+    
+    /// Smart search using natural language
+    SmartSearch {
+        /// Natural language search query
+        query: String,
+        
+        /// Automatically download the best match
+        #[arg(long, conflicts_with_all = ["format", "template"])]
+        auto_download: bool,
+        
+        /// Minimum confidence threshold (0.0-1.0)
+        #[arg(long, default_value = "0.7")]
+        min_confidence: f32,
+        
+        /// Relax or tighten the threshold to fit the spread of result confidences
+        #[arg(long)]
+        adaptive_threshold: bool,
+        
+        /// LLM model to use, overriding the profile's
+        #[arg(long)]
+        model: Option<String>,
+        
+        /// Show detailed evaluation reasoning
+        #[arg(long)]
+        verbose: bool,
+        
+        /// Rank results with deterministic heuristics instead of the LLM
+        #[arg(long)]
+        no_llm: bool,
+        
+        /// Keep the search open for follow-up refinements and pick a result to download
+        #[arg(short, long, conflicts_with_all = ["auto_download", "format", "template"])]
+        interactive: bool,
+        
+        /// Download directory (if auto-download is enabled; defaults to a configured download root)
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+        
+        /// Fetch subtitles from OpenSubtitles after auto-download (e.g. en,es)
+        #[arg(long, value_delimiter = ',')]
+        subtitles: Vec<String>,
+        
+        /// Move the auto-downloaded files into the media library
+        #[arg(long)]
+        organize: bool,
+        
+        #[command(flatten)]
+        filter: filter::ResultFilter,
+        
+        #[command(flatten)]
+        format: output::ResultOutput,
+        
+        /// Add the title to the watchlist without asking if it isn't released yet
+        #[arg(long)]
+        watch: bool,
+        
+        /// Auto-download even if the title is already in the library
+        #[arg(long, requires = "auto_download")]
+        force: bool,
+        
+        /// Also save the results to this JSON file, for `torrentai import`
+        #[arg(long)]
+        export: Option<PathBuf>,
+        
+        /// Record every pipeline step to this JSON file: intent, queries, raw results, LLM prompts and responses, and scores
+        #[arg(long)]
+        trace_file: Option<PathBuf>,
+    },
+    
+    /// Rename and move downloaded files into a Plex/Jellyfin library layout
+    Organize {
+        /// Downloaded file or folder
+        path: PathBuf,
+        
+        /// Show the planned moves without touching any files
+        #[arg(long)]
+        dry_run: bool,
+        
+        /// Library root (defaults to the configured library)
+        #[arg(long)]
+        library: Option<PathBuf>,
+    },
+    
+    /// Interactive terminal UI for searching and downloading
+    Tui {
+        /// Search to run on startup
+        query: Option<String>,
+        
+        /// Minimum confidence threshold (0.0-1.0)
+        #[arg(long, default_value = "0.7")]
+        min_confidence: f32,
+        
+        /// Relax or tighten the threshold to fit the spread of result confidences
+        #[arg(long)]
+        adaptive_threshold: bool,
+        
+        /// LLM model to use, overriding the profile's
+        #[arg(long)]
+        model: Option<String>,
+        
+        /// Rank results with deterministic heuristics instead of the LLM
+        #[arg(long)]
+        no_llm: bool,
+        
+        /// Download directory (defaults to a configured download root)
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+        
+        /// Read-only session: search and view downloads, but never start one
+        #[arg(long)]
+        guest: bool,
+    },
+    
+    /// Re-add every download left unfinished by an earlier run and wait for them
+    ResumeAll,
+    
+    /// Run in the background, serving search and downloads over HTTP and checking the watchlist
+    Daemon {
+        /// Minimum confidence threshold (0.0-1.0)
+        #[arg(long, default_value = "0.7")]
+        min_confidence: f32,
+        
+        /// LLM model to use, overriding the profile's
+        #[arg(long)]
+        model: Option<String>,
+        
+        /// Rank results with deterministic heuristics instead of the LLM
+        #[arg(long)]
+        no_llm: bool,
+    },
+    
+    /// Show or process the download queue
+    Queue {
+        #[command(subcommand)]
+        action: QueueAction,
+    },
+    
+    /// Unreleased titles to download once they come out
+    Watchlist {
+        #[command(subcommand)]
+        action: WatchlistAction,
+    },
+    
+    /// Re-run smart searches on a schedule and report results not seen before
+    Watch {
+        #[command(subcommand)]
+        action: WatchAction,
+    },
+    
+    /// RSS and Torznab feeds whose new items are queued for download
+    Feed {
+        #[command(subcommand)]
+        action: FeedAction,
+    },
+    
+    /// Show what the next notification digest will contain, or send it now
+    Digest {
+        /// Send it now instead of waiting for the schedule
+        #[arg(long)]
+        send: bool,
+    },
+    
+    /// Browse or download results saved with --export, or a file of magnet links
+    Import {
+        /// Exported JSON, or text with one magnet link per line
+        file: PathBuf,
+        
+        /// Queue these results for download: "all", "3" or "1,4-6"
+        #[arg(long)]
+        queue: Option<String>,
+        
+        /// Download what was queued straight away
+        #[arg(long, requires = "queue")]
+        download: bool,
+        
+        /// Download directory (defaults to a configured download root)
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+        
+        #[command(flatten)]
+        filter: filter::ResultFilter,
+        
+        #[command(flatten)]
+        format: output::ResultOutput,
+    },
+    
+    /// Past smart searches, their top results and which one was downloaded
+    History {
+        /// How many searches to show
+        #[arg(long, default_value = "20")]
+        limit: usize,
+        
+        /// Forget every past search
+        #[arg(long)]
+        clear: bool,
+    },
+    
+    /// Ask an admin to download something (searches now, downloads on approval)
+    Request {
+        /// Natural language request
+        query: String,
+        
+        /// Minimum confidence threshold (0.0-1.0)
+        #[arg(long, default_value = "0.7")]
+        min_confidence: f32,
+        
+        /// Relax or tighten the threshold to fit the spread of result confidences
+        #[arg(long)]
+        adaptive_threshold: bool,
+        
+        /// LLM model to use, overriding the profile's
+        #[arg(long)]
+        model: Option<String>,
+        
+        /// Rank results with deterministic heuristics instead of the LLM
+        #[arg(long)]
+        no_llm: bool,
+    },
+    
+    /// Review requests made by guests
+    Inbox {
+        #[command(subcommand)]
+        action: InboxAction,
+    },
+    
+    /// Manage encrypted credentials referenced from config
+    Secret {
+        #[command(subcommand)]
+        action: SecretAction,
+    },
+    
+    /// Download a well-seeded reference torrent briefly to check throughput, peer sources and port reachability
+    Nettest {
+        /// Magnet, .torrent URL or file to use instead of the configured reference
+        #[arg(long)]
+        torrent: Option<String>,
+        
+        /// How long to download for
+        #[arg(long)]
+        seconds: Option<u64>,
+    },
+    
+    /// Download, resume and reseed generated content from a local tracker and seeder, without touching real swarms
+    #[command(long_flag = "selftest")]
+    Selftest,
+    
+    /// Maintain the local state database
+    Db {
+        #[command(subcommand)]
+        action: DbAction,
+    },
+}
+
+#[derive(Subcommand)]
+enum DbAction {
+    /// Check integrity, clean up orphaned rows and compact the database
+    Doctor,
+    
+    /// Save a copy of the database to ~/.torrentai/backups
+    Backup,
+    
+    /// List backups, newest first
+    Backups,
+    
+    /// Replace the database with a backup (the newest if none is given)
+    Restore {
+        backup: Option<PathBuf>,
+    },
+}
+
+#[derive(Subcommand)]
+enum WatchlistAction {
+    /// List watched titles and their release dates
+    List,
+    
+    /// Search for every watched title that has come out and grab the best match
+    Check {
+        /// LLM model to use, overriding the profile's
+        #[arg(long)]
+        model: Option<String>,
+        
+        /// Rank results with deterministic heuristics instead of the LLM
+        #[arg(long)]
+        no_llm: bool,
+        
+        /// Download directory (defaults to a configured download root)
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+    
+    /// Stop watching a title
+    Remove {
+        /// Watchlist entry ID
+        id: i64,
+    },
+}
+
+#[derive(Subcommand)]
+enum WatchAction {
+    /// Watch a natural language query; the daemon re-runs it on the interval
+    Add {
+        /// Natural language search query
+        query: String,
+        
+        /// How often to search, e.g. 30m, 6h or 1d
+        #[arg(long, default_value = "6h", value_parser = watch::parse_interval)]
+        interval: u64,
+        
+        /// Download the best new result when its confidence is at least this (0.0-1.0)
+        #[arg(long)]
+        auto_download_above: Option<f32>,
+    },
+    
+    /// List watch rules and when they next run
+    List,
+    
+    /// Run due watch rules once, for use without the daemon (e.g. from cron)
+    Check {
+        /// Run every rule, due or not
+        #[arg(long)]
+        all: bool,
+        
+        /// LLM model to use, overriding the profile's
+        #[arg(long)]
+        model: Option<String>,
+        
+        /// Rank results with deterministic heuristics instead of the LLM
+        #[arg(long)]
+        no_llm: bool,
+        
+        /// Download directory (defaults to a configured download root)
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+    
+    /// Delete a watch rule
+    Remove {
+        /// Watch rule ID
+        id: i64,
+    },
+}
+
+#[derive(Subcommand)]
+enum FeedAction {
+    /// Follow a feed; the daemon polls it on the interval
+    Add {
+        /// RSS or Torznab feed URL
+        url: String,
+        
+        /// Only take items matching this description, e.g. "severance in 1080p"
+        #[arg(long)]
+        filter: Option<String>,
+        
+        /// How often to poll, e.g. 15m or 1h
+        #[arg(long, default_value = "15m", value_parser = watch::parse_interval)]
+        interval: u64,
+        
+        /// Also take what's in the feed now, not only items published from here on
+        #[arg(long)]
+        grab_existing: bool,
+    },
+    
+    /// List feeds and how their last poll went
+    List,
+    
+    /// Poll due feeds once and queue their new items, for use without the daemon
+    Check {
+        /// Poll every feed, due or not
+        #[arg(long)]
+        all: bool,
+        
+        /// Download the queue afterwards
+        #[arg(long)]
+        download: bool,
+        
+        /// LLM model to use for filters, overriding the profile's
+        #[arg(long)]
+        model: Option<String>,
+        
+        /// Match filters with deterministic heuristics instead of the LLM
+        #[arg(long)]
+        no_llm: bool,
+    },
+    
+    /// Stop following a feed
+    Remove {
+        /// Feed ID
+        id: i64,
+    },
+}
+
+#[derive(Subcommand)]
+enum QueueAction {
+    /// List queued, running and finished entries
+    List,
+    
+    /// Download everything still queued, all at once
+    Run {
+        /// Download directory (defaults to a configured download root)
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+}
+
+#[derive(Subcommand)]
+enum InboxAction {
+    /// Show pending and failed requests with their parsed intent and top result
+    List {
+        /// Include approved and rejected requests
+        #[arg(long)]
+        all: bool,
+    },
+    
+    /// Approve a request and download its top result
+    Approve {
+        id: i64,
+        
+        /// Download directory (defaults to a configured download root)
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+    
+    /// Reject a request
+    Reject {
+        id: i64,
+        
+        /// Reason shown to the requester
+        #[arg(long)]
+        reason: Option<String>,
+    },
+}
+
+#[derive(Subcommand)]
+enum SecretAction {
+    /// Store a secret (prompts for the value if not given)
+    Set {
+        /// Name used to reference the secret from config
+        name: String,
+        
+        /// Secret value; omit to type it without echo
+        value: Option<String>,
+    },
+    
+    /// Print a secret's value
+    Get {
+        name: String,
+    },
+    
+    /// List stored secret names
+    List,
+    
+    /// Delete a secret
+    Remove {
+        name: String,
+    },
+}
+
+/// Run the command `cli` was parsed into.
+pub async fn run(cli: Cli) -> Result<()> {
+    // Log lines would corrupt the TUI's screen, so send them to a file instead.
+    // Either way, tracker health comes from librqbit's own announce logging,
+    // below what's printed, through a layer filtered on its own
+    if matches!(cli.command, Commands::Tui { .. }) {
+        let dir = config::config_dir();
+        std::fs::create_dir_all(&dir)?;
+        let log = std::fs::File::create(dir.join("tui.log"))?;
+        tracing_subscriber::registry()
+            .with(tracing_subscriber::fmt::layer()
+                .with_writer(std::sync::Mutex::new(log))
+                .with_ansi(false)
+                .with_filter(LevelFilter::INFO))
+            .with(trackers::TrackerLog.with_filter(filter_fn(trackers::TrackerLog::wants)))
+            .init();
+    } else {
+        tracing_subscriber::registry()
+            .with(tracing_subscriber::fmt::layer().with_filter(LevelFilter::INFO))
+            .with(trackers::TrackerLog.with_filter(filter_fn(trackers::TrackerLog::wants)))
+            .init();
+    }
+    
+    let profile = cli.profile.or_else(|| std::env::var("TORRENTAI_PROFILE").ok());
+    
+    match cli.command {
+        Commands::Download { torrents, output, subtitles, organize } => {
+            let config = config::Config::load()?;
+            let state = state::StateDb::open()?;
+            let torrents = torrents.into_iter()
+                .map(|torrent| Ok((torrent, downloader::resolve_output_dir(output.clone(), &config, &state)?)))
+                .collect::<Result<Vec<_>>>()?;
+            let outcomes = downloader::download_all(&config, &torrents).await?;
+            let mut failed = Vec::new();
+            for outcome in outcomes {
+                let mut files = match outcome {
+                    Ok(files) => files,
+                    Err(e) => {
+                        failed.push(e);
+                        continue;
+                    }
+                };
+                files.extend(fetch_subtitles(&files, &subtitles, &config).await);
+                if organize || config.organize.enabled {
+                    organize_files(&files, None, &config.organize.library, false)?;
+                }
+            }
+            match failed.len() {
+                0 => {}
+                1 if torrents.len() == 1 => return Err(failed.remove(0)),
+                count => return Err(anyhow::anyhow!("{} of {} downloads failed", count, torrents.len())),
+            }
+        }
+        Commands::Stream { torrent, port, output, min_confidence, model, no_llm, force } => {
+            use crate::smart_search::{SmartSearcher, SmartSearchEvent};
+            
+            let config = config::Config::load()?;
+            let is_torrent = torrent.starts_with("magnet:") || torrent.starts_with("http://")
+                || torrent.starts_with("https://") || std::path::Path::new(&torrent).is_file();
+            let source = if is_torrent {
+                torrent
+            } else {
+                let llm = connect_llm(model, no_llm, &config.llm_for(profile.as_deref())?).await?;
+                let searcher = SmartSearcher::new(llm, min_confidence)
+                    .with_metadata(metadata::MetadataClient::from_config(&config.metadata)?)
+                    .with_cache(config.cache.ttl())
+                    .with_language(config.language.clone());
+                println!("🔍 Searching for \"{}\"...", torrent);
+                let intent = std::sync::Mutex::new(None);
+                let results = searcher.search(&torrent, |event| {
+                    if let SmartSearchEvent::IntentParsed(parsed) | SmartSearchEvent::LatestResolved { intent: parsed, .. } = event {
+                        *intent.lock().unwrap() = Some(parsed.clone());
+                    }
+                }).await?;
+                let intent = intent.into_inner().unwrap();
+                let Some(best) = results.into_iter().next() else {
+                    println!("❌ No results found with confidence >= {}", min_confidence);
+                    return Ok(());
+                };
+                println!("✅ Best match: {} ({:.0}%)", best.torrent.title, best.relevance_score * 100.0);
+                // Nobody picked this release, so it gets the same checks as --auto-download
+                if !safety::is_safe(&best.torrent) {
+                    println!("🚩 Not streaming: the best match has red flags");
+                    println!("If you're sure, run: torrentai stream \"{}\"", best.torrent.magnet_link);
+                    return Ok(());
+                }
+                if best.relevance_score < AUTO_DOWNLOAD_MIN_RELEVANCE {
+                    println!("⚠️  Best match has relevance {:.0}% - manual confirmation required", best.relevance_score * 100.0);
+                    println!("To stream it, run: torrentai stream \"{}\"", best.torrent.magnet_link);
+                    return Ok(());
+                }
+                if !force {
+                    if let Some(owned) = library::find_owned(&best.torrent, intent.as_ref(), &config.organize.library)? {
+                        println!("📚 Already {}", owned.describe());
+                        println!("Use --force to stream it anyway");
+                        return Ok(());
+                    }
+                }
+                best.torrent.magnet_link
+            };
+            
+            let state = state::StateDb::open()?;
+            let output = downloader::resolve_output_dir(output, &config, &state)?;
+            drop(state);
+            stream::run(&source, output, port).await?;
+        }
+        Commands::Organize { path, dry_run, library } => {
+            let config = config::Config::load()?;
+            let library = library.unwrap_or(config.organize.library);
+            let files = organize::collect_files(&path)?;
+            organize_files(&files, None, &library, dry_run)?;
+        }
+        Commands::Search { query, filter, order, format, export } => {
+            use crate::scraper::PirateBayScraper;
+            
+            let scraper = PirateBayScraper::new();
+            let found = scraper.search(&query).await?;
+            let hidden = found.len();
+            let results = order.apply(filter.apply(found));
+            let hidden = hidden - results.len();
+            
+            export_results(export.as_deref(), &export::ResultSet::plain(&query, &results), !format.is_set())?;
+            
+            let rows: Vec<_> = results.iter().map(|r| output::Row::new("ThePirateBay", r)).collect();
+            if let Some(rendered) = format.render(&query, &rows)? {
+                print!("{}", rendered);
+                return Ok(());
+            }
+            
+            if results.is_empty() {
+                println!("No results found for: {}", query);
+            } else {
+                println!("\nThePirateBay search results for: {}\n", query);
+                println!("{:-<120}", "");
+                
+                for (i, result) in results.iter().enumerate() {
+                    print_result(i, result);
+                }
+                
+                println!("\nTotal results: {}", results.len());
+            }
+            print_hidden(hidden, &filter);
+        }
+        Commands::SearchYts { query, yts, filter, order, format, export } => {
+            use crate::scraper::YtsScraper;
+            
+            let scraper = YtsScraper::new();
+            let found = scraper.search_filtered(&query, &yts).await?;
+            let hidden = found.len();
+            let results = order.apply(filter.apply(found));
+            let hidden = hidden - results.len();
+            
+            export_results(export.as_deref(), &export::ResultSet::plain(&query, &results), !format.is_set())?;
+            
+            let rows: Vec<_> = results.iter().map(|r| output::Row::new("YTS", r)).collect();
+            if let Some(rendered) = format.render(&query, &rows)? {
+                print!("{}", rendered);
+                return Ok(());
+            }
+            
+            if results.is_empty() {
+                println!("No results found for: {}", query);
+            } else {
+                println!("\nYTS search results for: {}\n", query);
+                println!("{:-<120}", "");
+                
+                for (i, result) in results.iter().enumerate() {
+                    print_result(i, result);
+                }
+                
+                println!("\nTotal results: {}", results.len());
+            }
+            print_hidden(hidden, &filter);
+        }
+        Commands::SearchAll { query, filter, order, format, export } => {
+            if !format.is_set() {
+                println!("\nSearching every source for: {}\n", query);
+            }
+            
+            // Search every source concurrently; one failing still shows the rest
+            let mut hidden = 0;
+            let mut by_source = Vec::new();
+            for (source, found) in SourceRegistry::default().search_all(&crate::SourceQuery::new(&query)).await {
+                match found {
+                    Ok(found) => {
+                        hidden += found.len();
+                        let results = order.apply(filter.apply(found));
+                        hidden -= results.len();
+                        by_source.push((source, Ok(results)));
+                    }
+                    Err(e) => by_source.push((source, Err(e))),
+                }
+            }
+            let shown = order.limit.unwrap_or(10);
+            
+            let all: Vec<_> = by_source.iter()
+                .filter_map(|(_, results)| results.as_ref().ok())
+                .flatten()
+                .cloned()
+                .collect();
+            export_results(export.as_deref(), &export::ResultSet::plain(&query, &all), !format.is_set())?;
+            
+            let rows: Vec<_> = by_source.iter()
+                .filter_map(|(source, results)| Some(results.as_ref().ok()?.iter().map(|r| output::Row::new(source, r))))
+                .flatten()
+                .collect();
+            if let Some(rendered) = format.render(&query, &rows)? {
+                print!("{}", rendered);
+                return Ok(());
+            }
+            
+            for (source, results) in &by_source {
+                let results = match results {
+                    Ok(results) if !results.is_empty() => results,
+                    Ok(_) => {
+                        println!("📦 {}: No results found\n", source);
+                        continue;
+                    }
+                    Err(e) => {
+                        println!("❌ {}: search failed: {}\n", source, e);
+                        continue;
+                    }
+                };
+                println!("📦 {} Results ({}):", source, results.len());
+                println!("{:-<120}", "");
+                
+                for (i, result) in results.iter().take(shown).enumerate() {
+                    print_result(i, result);
+                }
+                
+                if results.len() > shown {
+                    println!("... and {} more results", results.len() - shown);
+                }
+                println!();
+            }
+            
+            let counts: Vec<String> = by_source.iter()
+                .filter_map(|(source, results)| Some(format!("{}: {}", source, results.as_ref().ok()?.len())))
+                .collect();
+            if counts.is_empty() {
+                println!("Total results: 0");
+            } else {
+                println!("Total results: {} ({})", all.len(), counts.join(", "));
+            }
+            print_hidden(hidden, &filter);
+        }
+        Commands::Status => {
+            use crate::state::{StateDb, TorrentStatus};
+            
+            // A running daemon knows how its downloads have been going lately
+            let config = config::Config::load()?;
+            if let Ok(live) = stats::fetch(&config.daemon.listen).await {
+                if !live.is_empty() {
+                    println!("\nDaemon downloads:\n");
+                    for torrent in &live {
+                        torrent.print();
+                    }
+                }
+            }
+            
+            let state = StateDb::open()?;
+            let torrents = state.torrents()?;
+            let active: Vec<_> = torrents.iter()
+                .filter(|t| !matches!(t.status, TorrentStatus::Completed | TorrentStatus::Archived))
+                .collect();
+            
+            if active.is_empty() {
+                println!("No active downloads");
+            } else {
+                println!("\nActive downloads:\n");
+                println!("{:-<120}", "");
+                for torrent in &active {
+                    let percent = if torrent.total_bytes > 0 {
+                        torrent.progress_bytes as f64 / torrent.total_bytes as f64 * 100.0
+                    } else {
+                        0.0
+                    };
+                    println!("{}", torrent.name);
+                    println!("   Status: {} | Progress: {:.1}% of {} | Updated: {}",
+                             torrent.status.as_str(),
+                             percent,
+                             storage::format_bytes(torrent.total_bytes),
+                             torrent.updated_at);
+                    println!("   Disk: {} | Path: {}",
+                             torrent.disk_root.display(),
+                             torrent.output_dir.display());
+                    
+                    for tracker in state.trackers(&torrent.info_hash)? {
+                        println!("   [tier {}] {}: {}", tracker.tier, tracker.url, trackers::describe(&tracker));
+                    }
+                    println!("{:-<120}", "");
+                }
+            }
+            
+            // Flagged by the daemon when their files didn't check out on startup
+            let unhealthy: Vec<_> = torrents.iter()
+                .filter(|t| t.status == TorrentStatus::Completed)
+                .filter_map(|t| Some((t, t.problem.as_ref()?)))
+                .collect();
+            if !unhealthy.is_empty() {
+                println!("\nNot seeding:");
+                for (torrent, problem) in &unhealthy {
+                    println!("   ⚠️  {} {}: {}", library::short_hash(&torrent.info_hash), torrent.name, problem);
+                }
+                println!("   Restore the files and restart the daemon, or `torrentai archive` them");
+            }
+            
+            let mirrors = state.mirror_health()?;
+            if !mirrors.is_empty() {
+                println!("\nThePirateBay mirrors:");
+                for mirror in &mirrors {
+                    println!("   {}: {}", mirror.url, pirate_bay_scraper::describe(mirror));
+                }
+            }
+        }
+        Commands::List { content_type, classify, model, no_llm, archived } => {
+            use crate::state::{StateDb, TorrentStatus};
+            
+            let config = config::Config::load()?;
+            let wanted = content_type.as_deref().map(models::ContentType::from_label);
+            let state = StateDb::open()?;
+            let completed: Vec<_> = state.torrents()?
+                .into_iter()
+                .filter(|t| t.status == TorrentStatus::Completed)
+                .collect();
+            
+            if classify {
+                let unknown: Vec<_> = completed.iter().filter(|t| t.content_type.is_none()).collect();
+                if !unknown.is_empty() {
+                    let llm = connect_llm(model, no_llm, &config.llm_for(profile.as_deref())?).await?;
+                    for torrent in unknown {
+                        let content_type = classify::classify_download(torrent, llm.as_ref()).await;
+                        state.set_content_type(&torrent.info_hash, &content_type)?;
+                        println!("🏷️  {}: {}", torrent.name, content_type.label());
+                    }
+                }
+            }
+            
+            // Re-read so fresh classifications show; unknown types get a
+            // heuristic guess, marked as such
+            let shown = if archived { TorrentStatus::Archived } else { TorrentStatus::Completed };
+            let all = state.torrents()?;
+            let archived_count = all.iter().filter(|t| t.status == TorrentStatus::Archived).count();
+            let listed: Vec<_> = all
+                .into_iter()
+                .filter(|t| t.status == shown)
+                .map(|t| {
+                    let (kind, guessed) = match &t.content_type {
+                        Some(kind) => (kind.clone(), false),
+                        None => (classify::classify(&t.name, &classify::download_files(&t)).content_type, true),
+                    };
+                    (t, kind, guessed)
+                })
+                .filter(|(_, kind, _)| wanted.as_ref().is_none_or(|w| w.label() == kind.label()))
+                .collect();
+            
+            if listed.is_empty() {
+                println!("{}", if archived { "No archived downloads" } else { "No downloaded content" });
+            } else {
+                println!("\n{}:\n", if archived { "Archived downloads" } else { "Downloaded content" });
+                println!("{:-<120}", "");
+                for (torrent, kind, guessed) in &listed {
+                    println!("{} {}", library::short_hash(&torrent.info_hash), torrent.name);
+                    if archived {
+                        println!("   Type: {}{} | Size: {} | Ratio: {:.2} | Archived: {}",
+                                 kind.label(),
+                                 if *guessed { "?" } else { "" },
+                                 storage::format_bytes(torrent.total_bytes),
+                                 torrent.ratio(),
+                                 torrent.updated_at.get(..10).unwrap_or(&torrent.updated_at));
+                    } else {
+                        println!("   Type: {}{} | Size: {} | Ratio: {:.2} | Disk: {} | Path: {}",
+                                 kind.label(),
+                                 if *guessed { "?" } else { "" },
+                                 storage::format_bytes(torrent.total_bytes),
+                                 torrent.ratio(),
+                                 torrent.disk_root.display(),
+                                 torrent.output_dir.display());
+                        if let Some(problem) = &torrent.problem {
+                            println!("   ⚠️  Not seeding: {}", problem);
+                        }
+                    }
+                    println!("{:-<120}", "");
+                }
+                println!("\nTotal: {}", listed.len());
+                if listed.iter().any(|(_, _, guessed)| *guessed) {
+                    println!("💡 Types marked ? are guesses; run `torrentai list --classify` to settle them");
+                }
+            }
+            if !archived && archived_count > 0 {
+                println!("📦 {} archived; `torrentai list --archived` shows them", archived_count);
+            }
+            
+            println!("\nDownload roots:");
+            for root in &config.download.roots {
+                match storage::free_space(&root.path) {
+                    Ok(free) => println!("   {} ({} free, min {:.1} GB)",
+                                         root.path.display(), storage::format_bytes(free), root.min_free_gb),
+                    Err(e) => println!("   {} (unavailable: {})", root.path.display(), e),
+                }
+            }
+        }
+        Commands::Archive { id, ratio, dry_run } => {
+            let record = library::find_download(&id)?;
+            
+            // A daemon seeding it would go on serving files that are gone
+            let config = config::Config::load()?;
+            if let Ok(live) = stats::fetch(&config.daemon.listen).await {
+                if live.iter().any(|t| t.info_hash.as_deref() == Some(record.info_hash.as_str())) {
+                    return Err(anyhow::anyhow!("The daemon is still seeding {}; stop it before archiving", record.name));
+                }
+            }
+            
+            let archived = library::archive(&record, ratio, dry_run)?;
+            let what = archived.removed.as_ref()
+                .map(|path| format!("{} ({})", path.display(), storage::format_bytes(archived.freed_bytes)))
+                .unwrap_or_else(|| "nothing (its files were already gone)".to_string());
+            if dry_run {
+                println!("Would delete {} and archive {}", what, record.name);
+            } else {
+                println!("📦 Archived {} (ratio {:.2}), deleted {}", record.name, record.ratio(), what);
+            }
+        }
+        Commands::SmartSearch { query, auto_download, min_confidence, adaptive_threshold, model, verbose, no_llm, interactive, output, subtitles, organize, watch, filter, format, force, export, trace_file } => {
+            use crate::smart_search::{SmartSearcher, SmartSearchEvent, display_evaluated_result, print_diagnostics, print_progress};
+            use futures::StreamExt;
+            use std::sync::Arc;
+            
+            // Initialize LLM service, falling back to heuristics if it is unavailable
+            let config = config::Config::load()?;
+            let llm = connect_llm(model, no_llm, &config.llm_for(profile.as_deref())?).await?;
+            
+            // Create searcher
+            let tracer = trace_file.map(|path| trace::Tracer::new(path, &query));
+            let searcher = Arc::new(SmartSearcher::new(llm, min_confidence)
+                .with_metadata(metadata::MetadataClient::from_config(&config.metadata)?)
+                .with_cache(config.cache.ttl())
+                .with_language(config.language.clone())
+                .with_filter(filter)
+                .with_adaptive_threshold(adaptive_threshold)
+                .with_history(true)
+                .with_tracer(tracer.clone()));
+            
+            if interactive {
+                let result = interactive_search(&searcher, &query, verbose, output).await;
+                write_trace(tracer.as_ref(), true);
+                return result;
+            }
+            
+            // Templated output is for pipelines: results only, no progress or prompts
+            if format.is_set() {
+                let results = searcher.search(&query, |_| {}).await;
+                write_trace(tracer.as_ref(), false);
+                let results = results?;
+                export_results(export.as_deref(), &export::ResultSet::evaluated(&query, &results), false)?;
+                let rows: Vec<_> = results.iter()
+                    .map(|r| output::Row::new("", &r.torrent).with_score(r.relevance_score))
+                    .collect();
+                print!("{}", format.render(&query, &rows)?.unwrap_or_default());
+                return Ok(());
+            }
+            
+            // Perform search, rendering progress as events arrive
+            println!("🤖 Understanding your request...");
+            let mut stream = searcher.search_stream(query.clone());
+            let mut results = Vec::new();
+            let mut episode_plan = None;
+            let mut diagnostics = None;
+            let mut intent = None;
+            let mut unreleased = None;
+            while let Some(event) = stream.next().await {
+                let event = event.inspect_err(|_| write_trace(tracer.as_ref(), true))?;
+                print_progress(&event);
+                match event {
+                    SmartSearchEvent::IntentParsed(parsed) | SmartSearchEvent::LatestResolved { intent: parsed, .. } => intent = Some(parsed),
+                    SmartSearchEvent::NotYetReleased { title, release_date } => unreleased = Some((title, release_date)),
+                    SmartSearchEvent::EpisodesPlanned(plan) => episode_plan = Some(plan),
+                    SmartSearchEvent::Diagnostics(found) => diagnostics = Some(found),
+                    SmartSearchEvent::Finished(finished) => results = finished,
+                    _ => {}
+                }
+            }
+            write_trace(tracer.as_ref(), true);
+            
+            export_results(export.as_deref(), &export::ResultSet::evaluated(&query, &results), true)?;
+            
+            if let Some((title, release_date)) = unreleased {
+                return offer_watch(&query, &title, release_date, watch);
+            }
+            
+            // No season pack: queue the individually matched episodes instead
+            if let Some(plan) = episode_plan {
+                if auto_download {
+                    let mut queued = 0;
+                    for (episode, result) in plan.matched() {
+                        if !safety::is_safe(&result.torrent) {
+                            println!("🚩 Skipping episode {}: {} has red flags", episode, result.torrent.title);
+                            continue;
+                        }
+                        let episode_intent = intent.clone().map(|mut intent| {
+                            if let Some(tv) = &mut intent.tv_details {
+                                tv.season = Some(plan.season);
+                                tv.episode = Some(episode);
+                            }
+                            intent
+                        });
+                        if !force {
+                            if let Some(owned) = library::find_owned(&result.torrent, episode_intent.as_ref(), &config.organize.library)? {
+                                println!("⏭️  Episode {} already {}", episode, owned.describe());
+                                continue;
+                            }
+                        }
+                        state::StateDb::open()?.enqueue(&result.torrent.title, &result.torrent.magnet_link)?;
+                        queued += 1;
+                    }
+                    if queued == 0 {
+                        println!("\n✅ Every matched episode is already in the library; use --force to download again");
+                        return Ok(());
+                    }
+                    println!("\n📥 Queued {} episodes for download", queued);
+                    downloader::run_queue(&config, output).await?;
+                } else {
+                    println!("\n💡 Re-run with --auto-download to queue all {} matched episodes", 
+                             plan.matched().count());
+                }
+                return Ok(());
+            }
+            
+            if results.is_empty() {
+                println!("\n❌ No results found with confidence >= {}", min_confidence);
+                if let Some(diagnostics) = &diagnostics {
+                    print_diagnostics(diagnostics);
+                }
+                return Ok(());
+            }
+            
+            // Display results
+            println!("\n📊 Top Results (ranked by relevance):");
+            for (i, result) in results.iter().take(5).enumerate() {
+                display_evaluated_result(i + 1, result, verbose);
+            }
+            
+            // Auto-download logic
+            if auto_download && !results.is_empty() {
+                let best = &results[0];
+                if !safety::is_safe(&best.torrent) {
+                    println!("\n🚩 Not auto-downloading: the best match has red flags");
+                    println!("If you're sure, run: torrentai download \"{}\"", best.torrent.magnet_link);
+                } else if best.relevance_score >= AUTO_DOWNLOAD_MIN_RELEVANCE {
+                    if !force {
+                        if let Some(owned) = library::find_owned(&best.torrent, intent.as_ref(), &config.organize.library)? {
+                            println!("\n📚 Already {}", owned.describe());
+                            println!("Use --force to download it again");
+                            return Ok(());
+                        }
+                    }
+                    let state = state::StateDb::open()?;
+                    let output = downloader::resolve_output_dir(output, &config, &state)?;
+                    if let Some(bytes) = best.torrent.size_bytes().filter(|&bytes| !storage::fits(&output, bytes)) {
+                        println!("\n💾 Not auto-downloading: it needs {} and {} has less free", storage::format_bytes(bytes), output.display());
+                        return Ok(());
+                    }
+                    println!("\n✅ Auto-downloading best match...");
+                    let mut files = downloader::download_torrent(&best.torrent.magnet_link, output).await?;
+                    // The intent says what this is better than the files do
+                    if let (Some(intent), Some(info_hash)) = (&intent, trackers::magnet_info_hash(&best.torrent.magnet_link)) {
+                        state.set_content_type(&info_hash, &intent.content_type)?;
+                    }
+                    files.extend(fetch_subtitles(&files, &subtitles, &config).await);
+                    if organize || config.organize.enabled {
+                        organize_files(&files, intent.as_ref(), &config.organize.library, false)?;
+                    }
+                } else {
+                    println!("\n⚠️  Best match has relevance {:.0}% - manual confirmation required", 
+                             best.relevance_score * 100.0);
+                    println!("To download, run: torrentai download \"{}\"", best.torrent.magnet_link);
+                }
+            }
+        }
+        Commands::Tui { query, min_confidence, adaptive_threshold, model, no_llm, output, guest } => {
+            use crate::smart_search::SmartSearcher;
+            use std::sync::Arc;
+            
+            // Connect before taking over the terminal so fallback notices stay readable
+            let config = config::Config::load()?;
+            let llm = connect_llm(model, no_llm, &config.llm_for(profile.as_deref())?).await?;
+            let searcher = Arc::new(SmartSearcher::new(llm, min_confidence)
+                .with_metadata(metadata::MetadataClient::from_config(&config.metadata)?)
+                .with_cache(config.cache.ttl())
+                .with_language(config.language.clone())
+                .with_adaptive_threshold(adaptive_threshold)
+                .with_history(true));
+            
+            let role = if guest { access::Role::Guest } else { access::Role::Admin };
+            tui::run(role, searcher, config, output, query).await?;
+        }
+        Commands::ResumeAll => {
+            let config = config::Config::load()?;
+            let state = state::StateDb::open()?;
+            let default_dir = downloader::resolve_output_dir(None, &config, &state)?;
+            let downloads = downloader::DownloadManager::new(config, default_dir).await?;
+            
+            let resumed = downloads.resume_all().await?;
+            if resumed == 0 {
+                println!("✅ Nothing to resume");
+                return Ok(());
+            }
+            println!("▶️  Resumed {} download(s)", resumed);
+            
+            loop {
+                tokio::time::sleep(std::time::Duration::from_secs(5)).await;
+                downloads.sync_state(&state)?;
+                downloads.retrack().await;
+                let progress = downloads.progress();
+                for download in &progress {
+                    let percent = if download.total_bytes > 0 {
+                        download.progress_bytes as f64 / download.total_bytes as f64 * 100.0
+                    } else {
+                        0.0
+                    };
+                    let eta = download.eta_secs
+                        .map(|secs| progress::format_duration(std::time::Duration::from_secs(secs)))
+                        .unwrap_or_else(|| "--".to_string());
+                    println!("   {:5.1}% {:>6.2} MiB/s  ETA {:>7}  {} peers  {}", percent, download.download_mbps, eta, download.peers, download.name);
+                }
+                if progress.iter().all(|d| d.finished || d.error.is_some()) {
+                    break;
+                }
+            }
+            
+            let finished = downloads.progress();
+            let failed: Vec<_> = finished.iter().filter(|d| d.error.is_some()).collect();
+            for download in &failed {
+                println!("❌ {}: {}", download.name, download.error.as_deref().unwrap_or_default());
+            }
+            println!("✅ {} of {} resumed download(s) finished", finished.len() - failed.len(), finished.len());
+        }
+        Commands::Daemon { min_confidence, model, no_llm } => {
+            use crate::smart_search::SmartSearcher;
+            use std::sync::Arc;
+            
+            let config = config::Config::load()?;
+            let llm = connect_llm(model.clone(), no_llm, &config.llm_for(profile.as_deref())?).await?;
+            let searcher = Arc::new(SmartSearcher::new(llm, min_confidence)
+                .with_metadata(metadata::MetadataClient::from_config(&config.metadata)?)
+                .with_cache(config.cache.ttl())
+                .with_language(config.language.clone()));
+            
+            // Watchlist re-checks can run on a lighter model of their own, and
+            // are held to a per-source budget so a big batch doesn't hammer the sites
+            let watchlist_profile = match (&profile, &config.watchlist.profile) {
+                (None, Some(watchlist_profile)) => Some(watchlist_profile.as_str()),
+                _ => profile.as_deref(),
+            };
+            let llm = connect_llm(model, no_llm, &config.llm_for(watchlist_profile)?).await?;
+            let watchlist_searcher = Arc::new(unattended_searcher(llm, min_confidence, &config)?);
+            daemon::run(searcher, watchlist_searcher, config).await?;
+        }
+        Commands::Queue { action } => {
+            match action {
+                QueueAction::List => {
+                    let state = state::StateDb::open()?;
+                    let items = state.queue()?;
+                    
+                    if items.is_empty() {
+                        println!("📭 Download queue is empty");
+                        return Ok(());
+                    }
+                    
+                    for item in items {
+                        println!("#{} [{}] {} (added {})", item.id, item.status.as_str(), item.name, item.added_at);
+                        if let Some(error) = &item.error {
+                            println!("   ⚠ {}", error);
+                        }
+                    }
+                }
+                QueueAction::Run { output } => {
+                    downloader::run_queue(&config::Config::load()?, output).await?;
+                }
+            }
+        }
+        Commands::Watchlist { action } => {
+            match action {
+                WatchlistAction::List => {
+                    let state = state::StateDb::open()?;
+                    let items = state.watchlist()?;
+                    
+                    if items.is_empty() {
+                        println!("📭 Watchlist is empty");
+                        return Ok(());
+                    }
+                    
+                    for item in items {
+                        println!("#{} [{}] {} - out {} (\"{}\")", item.id, item.status.as_str(), item.title,
+                                 item.release_date.format("%B %-d, %Y"), item.query);
+                    }
+                }
+                WatchlistAction::Check { model, no_llm, output } => {
+                    check_watchlist(model, no_llm, output, profile.as_deref()).await?;
+                }
+                WatchlistAction::Remove { id } => {
+                    let state = state::StateDb::open()?;
+                    if !state.remove_watch(id)? {
+                        return Err(anyhow::anyhow!("No watchlist entry #{}", id));
+                    }
+                    println!("🗑️  Removed #{} from the watchlist", id);
+                }
+            }
+        }
+        Commands::Watch { action } => {
+            match action {
+                WatchAction::Add { query, interval, auto_download_above } => {
+                    if auto_download_above.is_some_and(|min| !(0.0..=1.0).contains(&min)) {
+                        return Err(anyhow::anyhow!("--auto-download-above must be between 0.0 and 1.0"));
+                    }
+                    let id = state::StateDb::open()?.add_watch_rule(&query, interval, auto_download_above)?;
+                    println!("👀 Watch #{}: \"{}\" every {}", id, query, watch::format_interval(interval));
+                    if let Some(min) = auto_download_above {
+                        println!("   New results at {:.0}% confidence or more are downloaded", min * 100.0);
+                    }
+                    println!("💡 `torrentai daemon` runs it on schedule; `torrentai watch check` runs it now");
+                }
+                WatchAction::List => {
+                    let rules = state::StateDb::open()?.watch_rules()?;
+                    if rules.is_empty() {
+                        println!("📭 No watch rules");
+                        return Ok(());
+                    }
+                    
+                    for rule in rules {
+                        print!("#{} every {} \"{}\"", rule.id, watch::format_interval(rule.interval_mins), rule.query);
+                        if let Some(min) = rule.auto_download_above {
+                            print!(" (auto-download ≥ {:.0}%)", min * 100.0);
+                        }
+                        println!();
+                        let next = match rule.next_check() {
+                            Some(next) if !rule.is_due() => next.with_timezone(&chrono::Local).format("%Y-%m-%d %H:%M").to_string(),
+                            _ => "now".to_string(),
+                        };
+                        println!("   {} result(s) seen, next check {}", rule.seen, next);
+                    }
+                }
+                WatchAction::Check { all, model, no_llm, output } => {
+                    check_watch_rules(all, model, no_llm, output, profile.as_deref()).await?;
+                }
+                WatchAction::Remove { id } => {
+                    if !state::StateDb::open()?.remove_watch_rule(id)? {
+                        return Err(anyhow::anyhow!("No watch rule #{}", id));
+                    }
+                    println!("🗑️  Removed watch #{}", id);
+                }
+            }
+        }
+        Commands::Feed { action } => {
+            match action {
+                FeedAction::Add { url, filter, interval, grab_existing } => {
+                    // Catch typos and login pages now rather than on every poll
+                    let items = feeds::fetch(&url).await
+                        .map_err(|e| anyhow::anyhow!("Could not read {}: {}", feeds::redact(&url), e))?;
+                    // The key goes to the secret store first, so it's never written to state.db
+                    let (stored_url, key_param, key_secret) = match feeds::store_key(&url)? {
+                        Some((stripped, param, secret)) => (stripped, Some(param), Some(secret)),
+                        None => (url.clone(), None, None),
+                    };
+                    let id = state::StateDb::open()?.add_feed(
+                        &stored_url, key_param.as_deref(), key_secret.as_deref(), filter.as_deref(), interval, grab_existing,
+                    )?;
+                    println!("📡 Feed #{}: {} ({} items now), polled every {}", id, feeds::redact(&url), items.len(),
+                             watch::format_interval(interval));
+                    if let Some(filter) = &filter {
+                        println!("   Only items matching \"{}\" are queued", filter);
+                    }
+                    if !grab_existing {
+                        println!("   Items already in the feed are skipped; only new ones are queued");
+                    }
+                }
+                FeedAction::List => {
+                    let feeds = state::StateDb::open()?.feeds()?;
+                    if feeds.is_empty() {
+                        println!("📭 No feeds");
+                        return Ok(());
+                    }
+                    
+                    for feed in feeds {
+                        println!("#{} every {} {}", feed.id, watch::format_interval(feed.interval_mins), feeds::redact(&feed.url));
+                        if let Some(filter) = &feed.filter {
+                            println!("   Filter: \"{}\"", filter);
+                        }
+                        let polled = match feed.last_polled {
+                            Some(t) => t.with_timezone(&chrono::Local).format("%Y-%m-%d %H:%M").to_string(),
+                            None => "never".to_string(),
+                        };
+                        println!("   {} item(s) seen, last polled {}", feed.seen, polled);
+                        if let Some(error) = &feed.last_error {
+                            println!("   ⚠ {}", error);
+                        }
+                    }
+                }
+                FeedAction::Check { all, download, model, no_llm } => {
+                    check_feeds(all, model, no_llm, profile.as_deref()).await?;
+                    if download {
+                        downloader::run_queue(&config::Config::load()?, None).await?;
+                    }
+                }
+                FeedAction::Remove { id } => {
+                    let state = state::StateDb::open()?;
+                    let secret = state.feeds()?.into_iter()
+                        .find(|feed| feed.id == id)
+                        .and_then(|feed| feed.key_secret);
+                    if !state.remove_feed(id)? {
+                        return Err(anyhow::anyhow!("No feed #{}", id));
+                    }
+                    if let Some(secret) = secret {
+                        secrets::SecretStore::open()?.remove(&secret)?;
+                    }
+                    println!("🗑️  Removed feed #{}", id);
+                }
+            }
+        }
+        Commands::Digest { send } => {
+            let config = config::Config::load()?;
+            if config.notifications.digest == config::DigestSchedule::Off {
+                println!("📭 Digests are off; set [notifications] digest = \"daily\" or \"weekly\"");
+                return Ok(());
+            }
+            
+            if send {
+                let notifier = notifications::Notifier::from_config(&config.notifications);
+                match digest::send_if_due(&config.notifications, &notifier, true).await? {
+                    Some(0) | None => println!("📭 Nothing to report yet"),
+                    Some(count) => println!("📨 Sent a digest of {} entries", count),
+                }
+                return Ok(());
+            }
+            
+            let (entries, _) = state::StateDb::open()?.pending_digest()?;
+            println!("{}", digest::render(&entries));
+            if let Some(next) = digest::next_due(&config.notifications)? {
+                println!("\nNext digest: {}", next.with_timezone(&chrono::Local).format("%Y-%m-%d %H:%M"));
+            }
+        }
+        Commands::Import { file, queue, download, output, filter, format } => {
+            let set = export::ResultSet::load(&file)?;
+            let total = set.results.len();
+            let results: Vec<_> = set.results.into_iter()
+                .filter(|r| filter.matches(r.torrent()))
+                .collect();
+            
+            let rows: Vec<_> = results.iter()
+                .map(|r| {
+                    let row = output::Row::new("", r.torrent());
+                    match r.relevance_score() {
+                        Some(score) => row.with_score(score),
+                        None => row,
+                    }
+                })
+                .collect();
+            if let Some(rendered) = format.render(&set.query, &rows)? {
+                print!("{}", rendered);
+                return Ok(());
+            }
+            
+            let Some(picks) = queue else {
+                println!("\n📂 {} result(s) for \"{}\", exported {}\n", results.len(), set.query, set.exported_at);
+                for (i, result) in results.iter().enumerate() {
+                    let torrent = result.torrent();
+                    match result.relevance_score() {
+                        Some(score) => println!("{}. {} ({:.0}%)", i + 1, torrent.title, score * 100.0),
+                        None => println!("{}. {}", i + 1, torrent.title),
+                    }
+                    let details: Vec<String> = [
+                        torrent.size.as_ref().map(|size| format!("Size: {}", size)),
+                        torrent.seeders.map(|seeders| format!("Seeders: {}", seeders)),
+                        torrent.category.as_ref().map(|category| format!("Category: {}", category)),
+                    ].into_iter().flatten().collect();
+                    if !details.is_empty() {
+                        println!("   {}", details.join(" | "));
+                    }
+                    for flag in safety::red_flags(torrent) {
+                        println!("   🚩 {}", flag);
+                    }
+                }
+                print_hidden(total - results.len(), &filter);
+                if !results.is_empty() {
+                    println!("\n💡 Queue some with: torrentai import {} --queue 1,3-5 (or all)", file.display());
+                }
+                return Ok(());
+            };
+            
+            let state = state::StateDb::open()?;
+            for index in export::parse_picks(&picks, results.len())? {
+                let torrent = results[index].torrent();
+                let id = state.enqueue(&torrent.title, &torrent.magnet_link)?;
+                println!("📥 Queued #{}: {}", id, torrent.title);
+            }
+            if download {
+                downloader::run_queue(&config::Config::load()?, output).await?;
+            } else {
+                println!("💡 Run `torrentai queue run` to download them");
+            }
+        }
+        Commands::History { limit, clear } => {
+            let state = state::StateDb::open()?;
+            if clear {
+                println!("🗑️  Forgot {} search(es)", state.clear_history()?);
+                return Ok(());
+            }
+            
+            let entries = state.history(limit)?;
+            if entries.is_empty() {
+                println!("📭 No searches yet");
+                return Ok(());
+            }
+            
+            for entry in entries.iter().rev() {
+                let when = entry.searched_at.get(..16).unwrap_or(&entry.searched_at).replace('T', " ");
+                println!("#{} {} \"{}\" ({} result(s))", entry.id, when, entry.query, entry.results.len());
+                match &entry.chosen {
+                    Some((result, status)) => {
+                        let outcome = match status {
+                            state::TorrentStatus::Completed => "✅ downloaded",
+                            state::TorrentStatus::Downloading => "⬇️  downloading",
+                            state::TorrentStatus::Failed => "❌ download failed",
+                            state::TorrentStatus::Archived => "📦 downloaded, since archived",
+                        };
+                        println!("   {}: {}", outcome, result.title);
+                    }
+                    None => match entry.results.first() {
+                        Some(best) => println!("   best: {} ({:.0}%), not downloaded", best.title, best.relevance_score * 100.0),
+                        None => println!("   nothing found"),
+                    },
+                }
+            }
+        }
+        Commands::Request { query, min_confidence, adaptive_threshold, model, no_llm } => {
+            use crate::smart_search::{SmartSearcher, SmartSearchEvent, print_progress};
+            use std::sync::Mutex;
+            
+            let config = config::Config::load()?;
+            let llm = connect_llm(model, no_llm, &config.llm_for(profile.as_deref())?).await?;
+            let searcher = SmartSearcher::new(llm, min_confidence)
+                .with_metadata(metadata::MetadataClient::from_config(&config.metadata)?)
+                .with_cache(config.cache.ttl())
+                .with_language(config.language.clone())
+                .with_adaptive_threshold(adaptive_threshold);
+            
+            // Keep the parsed intent so the admin sees how the request was understood
+            println!("🤖 Understanding your request...");
+            let intent = Mutex::new(None);
+            let results = searcher.search(&query, |event| {
+                print_progress(event);
+                if let SmartSearchEvent::IntentParsed(parsed) | SmartSearchEvent::LatestResolved { intent: parsed, .. } = event {
+                    *intent.lock().unwrap() = Some(parsed.clone());
+                }
+            }).await?;
+            
+            let state = state::StateDb::open()?;
+            let intent = intent.into_inner().unwrap();
+            let id = state.add_request(&query, &access::current_user(), intent.as_ref(), results.first())?;
+            
+            match results.first() {
+                Some(best) => println!("\n📨 Request #{} submitted; best match so far: {}", id, best.torrent.title),
+                None => println!("\n📨 Request #{} submitted (no confident match found yet)", id),
+            }
+        }
+        Commands::Inbox { action } => {
+            match action {
+                InboxAction::List { all } => {
+                    let state = state::StateDb::open()?;
+                    let mut requests = state.requests(None)?;
+                    if !all {
+                        // Failed approvals still need an admin's attention
+                        requests.retain(|r| matches!(r.status, state::RequestStatus::Pending | state::RequestStatus::Failed));
+                    }
+                    
+                    if requests.is_empty() {
+                        println!("📭 No requests");
+                        return Ok(());
+                    }
+                    
+                    for request in requests {
+                        println!("\n#{} [{}] \"{}\" from {} ({})",
+                                 request.id, request.status.as_str(), request.query,
+                                 request.requested_by, request.created_at);
+                        if let Some(intent) = &request.intent {
+                            let season = intent.tv_details.as_ref()
+                                .and_then(|tv| tv.season)
+                                .map(|s| format!(" season {}", s))
+                                .unwrap_or_default();
+                            println!("   Understood as: {:?} \"{}\"{}", intent.content_type, intent.title, season);
+                        }
+                        match &request.top_result {
+                            Some(result) => println!("   Top result: [{}% match] {} ({})",
+                                                     (result.relevance_score * 100.0) as u8,
+                                                     result.torrent.title,
+                                                     result.torrent.size.as_deref().unwrap_or("unknown size")),
+                            None => println!("   Top result: none"),
+                        }
+                        if let Some(decided_at) = &request.decided_at {
+                            match &request.note {
+                                Some(note) => println!("   Decided {}: {}", decided_at, note),
+                                None => println!("   Decided {}", decided_at),
+                            }
+                        }
+                    }
+                }
+                InboxAction::Approve { id, output } => {
+                    println!("✅ Approving request #{}...", id);
+                    inbox::approve(id, output).await?;
+                }
+                InboxAction::Reject { id, reason } => {
+                    inbox::reject(id, reason.as_deref())?;
+                    println!("🚫 Rejected request #{}", id);
+                }
+            }
+        }
+        Commands::Nettest { torrent, seconds } => {
+            let config = config::Config::load()?;
+            let torrent = torrent.unwrap_or_else(|| config.nettest.torrent.clone());
+            let seconds = seconds.unwrap_or(config.nettest.seconds).max(1);
+            println!("🧪 Downloading {} for {}s...", notifications::display_name(&torrent), seconds);
+            
+            let report = nettest::run(&torrent, seconds, &config).await?;
+            println!("\n📦 {}", report.name);
+            match report.metadata_secs {
+                Some(secs) => println!("   Ready after {:.1}s", secs),
+                None => println!("   ⚠️  Metadata never arrived"),
+            }
+            println!("   Downloaded {} in {}s: {:.2} MiB/s average, {:.2} MiB/s peak",
+                     storage::format_bytes(report.downloaded_bytes),
+                     report.seconds,
+                     report.average_mib_per_sec,
+                     report.peak_mib_per_sec);
+            println!("   Peers: {} seen, up to {} connected", report.peers_seen, report.max_live_peers);
+            
+            println!("\n🔎 Peer sources:");
+            println!("   Trackers: {} peer(s) from {} of {} tracker(s)",
+                     report.tracker_peers(), report.trackers_ok(), report.trackers.len());
+            for probe in &report.trackers {
+                match &probe.error {
+                    Some(error) => println!("      ❌ {}: {}", probe.url, error),
+                    None => println!("      ✅ {}: {} peer(s), {} seeders / {} leechers",
+                                     probe.url,
+                                     probe.peers.len(),
+                                     probe.seeders.map_or("?".to_string(), |n| n.to_string()),
+                                     probe.leechers.map_or("?".to_string(), |n| n.to_string())),
+                }
+            }
+            match report.dht_peers {
+                Some(count) => println!("   DHT: {} peer(s)", count),
+                None => println!("   DHT: disabled"),
+            }
+            println!("   PEX and incoming: ~{} peer(s)", report.other_peers);
+            
+            println!("\n🔌 Port reachability:");
+            match &report.reachability {
+                nettest::Reachability::Open { public_ip, port } => {
+                    println!("   ✅ {}:{} accepts connections", public_ip, port);
+                }
+                nettest::Reachability::Closed { public_ip, port, error } => {
+                    println!("   ⚠️  {}:{} did not accept a connection ({})", public_ip, port, error);
+                    println!("      Forward TCP port {} to this machine so more peers can reach you.", port);
+                    println!("      Some routers can't loop back to their own public IP, so check from outside if you already have.");
+                }
+                nettest::Reachability::Unknown(reason) => println!("   ❓ Unknown: {}", reason),
+            }
+            
+            println!("\n{}", report.verdict());
+        }
+        Commands::Selftest => {
+            let config = config::Config::load()?;
+            println!("🧪 Running against a local tracker and seeder...\n");
+            
+            let report = selftest::run(&config).await?;
+            for step in &report.steps {
+                match &step.outcome {
+                    Ok(shown) => println!("✅ {} ({:.1}s): {}", step.name, step.elapsed.as_secs_f64(), shown),
+                    Err(error) => println!("❌ {} ({:.1}s): {}", step.name, step.elapsed.as_secs_f64(), error),
+                }
+            }
+            
+            if !report.passed() {
+                return Err(anyhow::anyhow!("Self-test failed; downloads on this machine are likely to fail the same way"));
+            }
+            println!("\n✅ Downloading, resuming and seeding all work on this machine");
+        }
+        Commands::Db { action } => {
+            let dir = config::config_dir();
+            match action {
+                DbAction::Doctor => {
+                    let report = state::StateDb::open()?.doctor()?;
+                    println!("Schema version {} (latest {})", report.schema_version, migrations::latest_version());
+                    
+                    if !report.problems.is_empty() {
+                        println!("❌ Integrity check failed:");
+                        for problem in &report.problems {
+                            println!("   {}", problem);
+                        }
+                        println!("\n💡 Restore a backup with `torrentai db restore` (see `torrentai db backups`)");
+                        return Ok(());
+                    }
+                    
+                    println!("✅ Integrity check passed");
+                    if report.orphaned_trackers > 0 {
+                        println!("🧹 Removed {} orphaned tracker rows", report.orphaned_trackers);
+                    }
+                    println!("📦 {} → {} after vacuum",
+                             storage::format_bytes(report.size_before),
+                             storage::format_bytes(report.size_after));
+                }
+                DbAction::Backup => {
+                    let path = state::StateDb::open()?.backup()?;
+                    println!("💾 Backed up to {}", path.display());
+                }
+                DbAction::Backups => {
+                    let backups = migrations::backups(&migrations::backups_dir(&dir))?;
+                    if backups.is_empty() {
+                        println!("📭 No backups yet");
+                    }
+                    for backup in backups {
+                        println!("{}", backup.display());
+                    }
+                }
+                DbAction::Restore { backup } => {
+                    let backup = match backup {
+                        Some(path) => path,
+                        None => migrations::backups(&migrations::backups_dir(&dir))?.into_iter().next()
+                            .ok_or_else(|| anyhow::anyhow!("No backups to restore"))?,
+                    };
+                    let aside = migrations::restore(&state::db_path(&dir), &backup, &migrations::backups_dir(&dir))?;
+                    println!("♻️  Restored {}", backup.display());
+                    println!("   The replaced database was kept at {}", aside.display());
+                }
+            }
+        }
+        Commands::Secret { action } => {
+            use crate::secrets::SecretStore;
+            
+            let mut store = SecretStore::open()?;
+            match action {
+                SecretAction::Set { name, value } => {
+                    let value = match value {
+                        Some(value) => value,
+                        None => rpassword::prompt_password(format!("Value for {}: ", name))?,
+                    };
+                    store.set(&name, &value)?;
+                    println!("Stored secret '{}'", name);
+                }
+                SecretAction::Get { name } => {
+                    println!("{}", store.require(&name)?);
+                }
+                SecretAction::List => {
+                    for name in store.names() {
+                        println!("{}", name);
+                    }
+                }
+                SecretAction::Remove { name } => {
+                    if store.remove(&name)? {
+                        println!("Removed secret '{}'", name);
+                    } else {
+                        println!("No secret named '{}'", name);
+                    }
+                }
+            }
+        }
+    }
+    
+    Ok(())
+}
+
+/// Save results for `torrentai import` when `--export` was given; `announce`
+/// is off for templated output, which must stay clean for pipelines.
+fn export_results(path: Option<&std::path::Path>, set: &export::ResultSet, announce: bool) -> Result<()> {
+    let Some(path) = path else {
+        return Ok(());
+    };
+    set.save(path)?;
+    if announce {
+        println!("💾 Exported {} result(s) to {}", set.results.len(), path.display());
+    }
+    Ok(())
+}
+
+/// Save the `--trace-file` report, if one was asked for, whether or not the
+/// search succeeded; a failed search is when it's wanted most.
+fn write_trace(tracer: Option<&trace::Tracer>, announce: bool) {
+    let Some(tracer) = tracer else {
+        return;
+    };
+    match tracer.save() {
+        Ok(path) if announce => println!("📝 Search trace written to {}", path.display()),
+        Ok(_) => {}
+        Err(e) => println!("⚠️  Couldn't write the search trace: {}", e),
+    }
+}
+
+/// Mention results the filters hid, so an empty list isn't mistaken for no matches.
+/// One numbered entry of a plain search listing, with its red flags.
+fn print_result(i: usize, result: &pirate_bay_scraper::TorrentResult) {
+    println!("{}. {}", i + 1, result.title);
+    
+    if let Some(size) = &result.size {
+        print!("   Size: {}", size);
+    }
+    if let Some(seeders) = result.seeders {
+        print!(" | Seeders: {}", seeders);
+    }
+    if let Some(leechers) = result.leechers {
+        print!(" | Leechers: {}", leechers);
+    }
+    if let Some(uploaded) = &result.uploaded {
+        print!(" | Uploaded: {}", uploaded);
+    }
+    if let Some(category) = &result.category {
+        print!(" | Category: {}", category);
+    }
+    if let Some(uploader) = &result.uploader {
+        print!(" | Uploader: {}", uploader);
+    }
+    println!();
+    
+    for flag in safety::red_flags(result) {
+        println!("   🚩 {}", flag);
+    }
+    println!("   Magnet: {}", result.magnet_link);
+    println!("{:-<120}", "");
+}
+
+fn print_hidden(hidden: usize, filter: &filter::ResultFilter) {
+    if let Some(note) = filter.hidden_note(hidden) {
+        println!("{}", note);
+    }
+}
+
+/// Connect to the LLM described by `config` unless `--no-llm` was given,
+/// falling back to heuristic ranking when Ollama or the model is unavailable.
+async fn connect_llm(model: Option<String>, no_llm: bool, config: &config::LlmConfig) -> Result<Option<llm_service::LlmService>> {
+    if no_llm {
+        println!("🧮 Using heuristic ranking (--no-llm)");
+        return Ok(None);
+    }
+    if config.backend == config::LlmBackend::Heuristic {
+        println!("🧮 Using heuristic ranking (profile backend)");
+        return Ok(None);
+    }
+    
+    let mut llm = llm_service::LlmService::new(model.unwrap_or_else(|| config.model.clone()))?
+        .with_hosts(&config.hosts)?
+        .with_max_attempts(config.max_attempts)
+        .with_evaluation_chunking(config.evaluation_chunk_size,
+                                  config.max_concurrent_evaluations);
+    
+    println!("🔍 Checking LLM service...");
+    let available = match llm.health_check().await {
+        Ok(_) => llm.ensure_model().await,
+        Err(e) => Err(e),
+    };
+    
+    match available {
+        Ok(()) => Ok(Some(llm)),
+        Err(e) => {
+            println!("⚠️  {}", e);
+            println!("🧮 Falling back to heuristic ranking");
+            Ok(None)
+        }
+    }
+}
+
+/// Smart search that stays open: follow-ups refine the current results and a
+/// result number starts that download.
+async fn interactive_search(
+    searcher: &smart_search::SmartSearcher,
+    query: &str,
+    verbose: bool,
+    output: Option<PathBuf>,
+) -> Result<()> {
+    use crate::smart_search::{display_evaluated_result, print_diagnostics, print_progress};
+    use std::io::Write;
+    use tokio::io::{AsyncBufReadExt, BufReader};
+    
+    println!("🤖 Understanding your request...");
+    let mut session = searcher.start_session(query, print_progress).await?;
+    let mut lines = BufReader::new(tokio::io::stdin()).lines();
+    
+    loop {
+        if let Some(release_date) = session.diagnostics.unreleased {
+            println!("\n📅 Not released yet; enter w to add it to the watchlist for {}", release_date.format("%B %-d, %Y"));
+        } else if session.results.is_empty() {
+            println!("\n❌ No results found above the confidence threshold");
+            print_diagnostics(&session.diagnostics);
+        } else {
+            println!("\n📊 Top Results (ranked by relevance):");
+            for (i, result) in session.results.iter().take(5).enumerate() {
+                display_evaluated_result(i + 1, result, verbose);
+            }
+        }
+        
+        println!("\n💬 Refine (e.g. \"only 1080p\"), enter a result number to download, or press Enter to quit");
+        print!("> ");
+        std::io::stdout().flush()?;
+        
+        let Some(line) = lines.next_line().await? else {
+            return Ok(());
+        };
+        let line = line.trim();
+        if line.is_empty() || line == "q" || line == "quit" {
+            return Ok(());
+        }
+        
+        if line == "w" {
+            if let (Some(canonical), Some(release_date)) = (&session.canonical, session.diagnostics.unreleased) {
+                return offer_watch(query, &canonical.title, release_date, true);
+            }
+        }
+        
+        if let Ok(number) = line.parse::<usize>() {
+            let Some(result) = number.checked_sub(1).and_then(|i| session.results.get(i)) else {
+                println!("⚠️  No result number {}", number);
+                continue;
+            };
+            
+            println!("\n✅ Downloading {}...", result.torrent.title);
+            let config = config::Config::load()?;
+            let state = state::StateDb::open()?;
+            let output = downloader::resolve_output_dir(output, &config, &state)?;
+            downloader::download_torrent(&result.torrent.magnet_link, output).await?;
+            return Ok(());
+        }
+        
+        if let Err(e) = searcher.refine(&mut session, line, print_progress).await {
+            println!("⚠️  Couldn't apply that refinement: {}", e);
+        }
+    }
+}
+
+/// Offer to watch for an unreleased title, adding it straight away with
+/// `watch` and asking only when someone is at the terminal.
+fn offer_watch(query: &str, title: &str, release_date: chrono::NaiveDate, watch: bool) -> Result<()> {
+    use std::io::{IsTerminal, Write};
+    
+    let add = if watch {
+        true
+    } else if std::io::stdin().is_terminal() {
+        print!("\n👀 Add {} to the watchlist and grab it when it comes out? [y/N] ", title);
+        std::io::stdout().flush()?;
+        let mut answer = String::new();
+        std::io::stdin().read_line(&mut answer)?;
+        matches!(answer.trim().to_lowercase().as_str(), "y" | "yes")
+    } else {
+        println!("\n💡 Re-run with --watch to grab it when it comes out");
+        false
+    };
+    
+    if add {
+        let id = state::StateDb::open()?.add_watch(query, title, release_date)?;
+        println!("👀 Watching #{}: {} (run `torrentai watchlist check` after {})", id, title, release_date);
+    }
+    Ok(())
+}
+
+/// Search for watched titles that have come out, downloading the best match
+/// when it is confident enough and leaving the rest for the next check.
+async fn check_watchlist(model: Option<String>, no_llm: bool, output: Option<PathBuf>, profile: Option<&str>) -> Result<()> {
+    let config = config::Config::load()?;
+    let due = state::StateDb::open()?.due_watchlist()?;
+    
+    if due.is_empty() {
+        println!("📭 Nothing on the watchlist has come out yet");
+        return Ok(());
+    }
+    
+    // An explicit --profile wins over the one configured for watchlist checks
+    let profile = profile.or(config.watchlist.profile.as_deref());
+    let llm = connect_llm(model, no_llm, &config.llm_for(profile)?).await?;
+    let searcher = unattended_searcher(llm, 0.7, &config)?;
+    let grabber = downloader::DownloadNow { output };
+    checks::watchlist(&config.notifications, &due, &searcher, &grabber, std::time::Duration::ZERO, &print_check).await
+}
+
+/// Run watch rules once, reporting new results and downloading those above
+/// a rule's threshold.
+async fn check_watch_rules(all: bool, model: Option<String>, no_llm: bool, output: Option<PathBuf>, profile: Option<&str>) -> Result<()> {
+    let config = config::Config::load()?;
+    let state = state::StateDb::open()?;
+    let rules = if all { state.watch_rules()? } else { state.due_watch_rules()? };
+    drop(state);
+    
+    if rules.is_empty() {
+        println!("📭 No watch rules are due");
+        return Ok(());
+    }
+    
+    // Same model choice as watchlist checks: both run unattended
+    let profile = profile.or(config.watchlist.profile.as_deref());
+    let llm = connect_llm(model, no_llm, &config.llm_for(profile)?).await?;
+    let searcher = unattended_searcher(llm, 0.7, &config)?;
+    let grabber = downloader::DownloadNow { output };
+    let notifier = notifications::Notifier::from_config(&config.notifications);
+    checks::watch_rules(&config.notifications, &rules, &searcher, &grabber, &notifier, std::time::Duration::ZERO, &print_check).await
+}
+
+/// Poll feeds once, queueing the new items that pass each feed's filter.
+async fn check_feeds(all: bool, model: Option<String>, no_llm: bool, profile: Option<&str>) -> Result<()> {
+    use crate::smart_search::SmartSearcher;
+    
+    let config = config::Config::load()?;
+    let state = state::StateDb::open()?;
+    let feeds = if all { state.feeds()? } else { state.due_feeds()? };
+    drop(state);
+    
+    if feeds.is_empty() {
+        println!("📭 No feeds are due");
+        return Ok(());
+    }
+    
+    // The LLM is only needed to match filters
+    let llm = if feeds.iter().any(|feed| feed.filter.is_some()) {
+        let profile = profile.or(config.watchlist.profile.as_deref());
+        connect_llm(model, no_llm, &config.llm_for(profile)?).await?
+    } else {
+        None
+    };
+    let searcher = SmartSearcher::new(llm, 0.7)
+        .with_language(config.language.clone());
+    // Queued items wait for `queue run`
+    checks::feeds(&config.notifications, &feeds, &searcher, None, &print_check).await
+}
+
+/// The searcher for unattended checks, held to the per-source budget so a
+/// big batch doesn't hammer the sites.
+fn unattended_searcher(llm: Option<llm_service::LlmService>, min_confidence: f32, config: &config::Config) -> Result<smart_search::SmartSearcher> {
+    Ok(smart_search::SmartSearcher::new(llm, min_confidence)
+        .with_metadata(metadata::MetadataClient::from_config(&config.metadata)?)
+        .with_cache(config.cache.ttl())
+        .with_language(config.language.clone())
+        .with_sources(pacing::rate_limited(SourceRegistry::default(), config.watchlist.source_requests_per_hour)))
+}
+
+fn print_check(event: &checks::CheckEvent) {
+    use checks::CheckEvent;
+    
+    match event {
+        CheckEvent::Started { label } => println!("\n🔍 {}", label),
+        CheckEvent::Failed { error, .. } => println!("❌ {}", error),
+        CheckEvent::Skipped { reason, .. } => println!("⏳ Skipped: {}", reason),
+        CheckEvent::Found { new, .. } => {
+            for result in new.iter() {
+                println!("🆕 {} ({:.0}%)", result.torrent.title, result.relevance_score * 100.0);
+            }
+        }
+        CheckEvent::Grabbing { title, .. } => println!("✅ Downloading {}", title),
+        CheckEvent::Queued { id, title, .. } => println!("📥 Queued #{}: {}", id, title),
+    }
+}
+
+/// Fetch subtitles for finished downloads when languages were requested,
+/// returning the files written. The download itself succeeded, so problems
+/// are reported rather than returned.
+async fn fetch_subtitles(files: &[PathBuf], languages: &[String], config: &config::Config) -> Vec<PathBuf> {
+    if languages.is_empty() {
+        return Vec::new();
+    }
+    
+    println!("\n💬 Fetching {} subtitles...", languages.join(", "));
+    match subtitles::fetch_subtitles(files, languages, &config.subtitles).await {
+        Ok(written) if written.is_empty() => {
+            println!("   No matching subtitles found");
+            Vec::new()
+        }
+        Ok(written) => {
+            for path in &written {
+                println!("   ✓ {}", path.display());
+            }
+            written
+        }
+        Err(e) => {
+            println!("⚠️  Subtitle download failed: {}", e);
+            Vec::new()
+        }
+    }
+}
+
+/// Move media files into the library layout, or just list the moves for a dry run.
+fn organize_files(files: &[PathBuf], intent: Option<&models::SearchIntent>, library: &std::path::Path, dry_run: bool) -> Result<()> {
+    let moves = if dry_run {
+        organize::plan(files, intent, library)
+    } else {
+        organize::organize(files, intent, library, &state::StateDb::open()?)?
+    };
+    if moves.is_empty() {
+        println!("\n📁 Nothing to organize");
+        return Ok(());
+    }
+    
+    println!("\n📁 {} into {}:", if dry_run { "Planned moves" } else { "Organized" }, library.display());
+    for planned in &moves {
+        println!("   {} → {}", planned.from.display(), planned.to.display());
+    }
+    Ok(())
+}
@@ -0,0 +1,521 @@
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+/// User configuration loaded from `~/.torrentai/config.toml`.
+///
+/// Every section is optional; a missing file yields the defaults.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    pub download: DownloadConfig,
+    pub trackers: TrackerConfig,
+    pub llm: LlmConfig,
+    pub metadata: MetadataConfig,
+    pub subtitles: SubtitlesConfig,
+    pub organize: OrganizeConfig,
+    pub notifications: NotificationsConfig,
+    pub daemon: DaemonConfig,
+    pub nettest: NettestConfig,
+    pub cache: CacheConfig,
+    pub watchlist: WatchlistConfig,
+    pub pirate_bay: PirateBayConfig,
+    pub yts: YtsConfig,
+    pub language: LanguageConfig,
+    /// Named overrides, chosen with `--profile` or `TORRENTAI_PROFILE`
+    pub profiles: HashMap<String, ProfileConfig>,
+    /// Set by [`with_state_dir`](Self::with_state_dir), never read from the file
+    #[serde(skip)]
+    state_dir: Option<PathBuf>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DownloadConfig {
+    /// Candidate download roots, typically one per disk
+    pub roots: Vec<DownloadRoot>,
+    /// How to choose between roots that have enough free space
+    pub selection: RootSelection,
+    /// Torrents downloaded at once by `download` and `queue run`; the rest wait their turn
+    pub max_concurrent: usize,
+    /// Find peers through the DHT as well as trackers
+    pub dht: bool,
+}
+
+impl Default for DownloadConfig {
+    fn default() -> Self {
+        Self {
+            roots: vec![DownloadRoot {
+                path: PathBuf::from("./downloads"),
+                min_free_gb: 0.0,
+            }],
+            selection: RootSelection::MostFree,
+            max_concurrent: 4,
+            dht: true,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DownloadRoot {
+    pub path: PathBuf,
+    /// Skip this root once free space drops below this many gigabytes
+    #[serde(default)]
+    pub min_free_gb: f64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RootSelection {
+    MostFree,
+    RoundRobin,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TrackerConfig {
+    /// Drop a tracker from a torrent after this many consecutive failures (0 = never)
+    pub drop_after_failures: u32,
+    /// Floor on the reannounce interval, whatever the tracker asks for
+    pub min_interval_secs: u64,
+    /// How long a failing tracker is left out when its torrent is next added;
+    /// doubles with each further failure
+    pub retry_base_secs: u64,
+    pub max_backoff_secs: u64,
+}
+
+impl Default for TrackerConfig {
+    fn default() -> Self {
+        Self {
+            drop_after_failures: 0,
+            min_interval_secs: 60,
+            retry_base_secs: 30,
+            max_backoff_secs: 3600,
+        }
+    }
+}
+
+/// ThePirateBay proxies come and go; each search tries the JSON API, then
+/// the HTML mirrors in order.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PirateBayConfig {
+    /// apibay-style JSON API, tried before the HTML mirrors; empty to skip it
+    pub api_url: String,
+    pub mirrors: Vec<String>,
+    /// Per-request timeout, so a hanging mirror doesn't hold up the next
+    pub timeout_secs: u64,
+    /// How long a failed mirror is skipped; doubles with each further failure
+    pub retry_base_secs: u64,
+    pub max_backoff_secs: u64,
+    /// Save results pages that parse to nothing under `~/.torrentai/unparsed`
+    pub dump_unparsed: bool,
+}
+
+impl Default for PirateBayConfig {
+    fn default() -> Self {
+        Self {
+            api_url: "https://apibay.org".to_string(),
+            mirrors: vec![
+                "https://thepiratebay10.info".to_string(),
+                "https://thepiratebay7.com".to_string(),
+                "https://tpb.party".to_string(),
+            ],
+            timeout_secs: 15,
+            retry_base_secs: 60,
+            max_backoff_secs: 6 * 3600,
+            dump_unparsed: false,
+        }
+    }
+}
+
+/// The YTS API returns at most 50 movies a page; popular search terms need
+/// several pages to be complete.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct YtsConfig {
+    /// Movies fetched per search at most, across all pages
+    pub max_results: u32,
+    /// Pages requested at once
+    pub max_concurrent_pages: usize,
+}
+
+impl Default for YtsConfig {
+    fn default() -> Self {
+        Self {
+            max_results: 200,
+            max_concurrent_pages: 4,
+        }
+    }
+}
+
+/// The audio language results should have when a request doesn't say.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LanguageConfig {
+    /// Name or code, e.g. "English" or "en"; unset accepts any language
+    pub preferred: Option<String>,
+    /// Drop releases dubbed into another language instead of ranking them down
+    pub strict: bool,
+}
+
+impl Default for LanguageConfig {
+    fn default() -> Self {
+        Self {
+            preferred: Some("en".to_string()),
+            strict: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LlmConfig {
+    pub backend: LlmBackend,
+    /// Ollama server URLs; evaluation is spread across all of them. The local
+    /// default when empty
+    pub hosts: Vec<String>,
+    /// Used unless `--model` is given
+    pub model: String,
+    /// Generations per request before giving up on unparseable output
+    pub max_attempts: usize,
+    /// Results per evaluation prompt
+    pub evaluation_chunk_size: usize,
+    /// Evaluation prompts sent to each backend at once
+    pub max_concurrent_evaluations: usize,
+}
+
+impl Default for LlmConfig {
+    fn default() -> Self {
+        Self {
+            backend: LlmBackend::Ollama,
+            hosts: Vec::new(),
+            model: "deepseek-r1:7b".to_string(),
+            max_attempts: 3,
+            evaluation_chunk_size: 8,
+            max_concurrent_evaluations: 2,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LlmBackend {
+    Ollama,
+    /// Deterministic ranking, as with `--no-llm`
+    Heuristic,
+}
+
+/// Settings a profile can override. Unset fields keep the top-level value.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ProfileConfig {
+    pub llm: LlmOverrides,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LlmOverrides {
+    pub backend: Option<LlmBackend>,
+    pub hosts: Option<Vec<String>>,
+    pub model: Option<String>,
+    pub max_attempts: Option<usize>,
+    pub evaluation_chunk_size: Option<usize>,
+    pub max_concurrent_evaluations: Option<usize>,
+}
+
+/// Optional lookup of canonical titles to cross-check search results.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MetadataConfig {
+    pub provider: Option<MetadataProvider>,
+    /// Name of the stored secret holding the API key (defaults to the provider name)
+    pub api_key_secret: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MetadataProvider {
+    Tmdb,
+    Omdb,
+}
+
+impl MetadataProvider {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            MetadataProvider::Tmdb => "tmdb",
+            MetadataProvider::Omdb => "omdb",
+        }
+    }
+}
+
+/// OpenSubtitles.com credentials, all given as secret names.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SubtitlesConfig {
+    pub api_key_secret: String,
+    /// Log in to raise the download quota; anonymous when unset
+    pub username: Option<String>,
+    pub password_secret: String,
+}
+
+impl Default for SubtitlesConfig {
+    fn default() -> Self {
+        Self {
+            api_key_secret: "opensubtitles".to_string(),
+            username: None,
+            password_secret: "opensubtitles_password".to_string(),
+        }
+    }
+}
+
+/// Moving finished downloads into a media-server library.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct OrganizeConfig {
+    /// Organize every download, as if `--organize` were always passed
+    pub enabled: bool,
+    /// Library root holding the `Movies` and `TV` folders
+    pub library: PathBuf,
+}
+
+impl Default for OrganizeConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            library: PathBuf::from("./library"),
+        }
+    }
+}
+
+/// Where to announce download events. Secrets are given by name.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct NotificationsConfig {
+    /// Which events to send
+    pub events: Vec<NotificationEvent>,
+    /// Native desktop notifications
+    pub desktop: bool,
+    /// Receives each event as JSON
+    pub webhook_url: Option<String>,
+    pub discord_webhook_secret: Option<String>,
+    pub telegram_bot_token_secret: Option<String>,
+    pub telegram_chat_id: Option<String>,
+    /// Collect watchlist and watch rule outcomes into one periodic summary
+    /// instead of notifying about each match
+    pub digest: DigestSchedule,
+}
+
+impl Default for NotificationsConfig {
+    fn default() -> Self {
+        Self {
+            events: vec![NotificationEvent::Completed, NotificationEvent::Failed, NotificationEvent::WatchMatched],
+            desktop: false,
+            webhook_url: None,
+            discord_webhook_secret: None,
+            telegram_bot_token_secret: None,
+            telegram_chat_id: None,
+            digest: DigestSchedule::Off,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NotificationEvent {
+    Started,
+    MetadataResolved,
+    Completed,
+    Failed,
+    /// A watch rule found results it hadn't seen before
+    WatchMatched,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DigestSchedule {
+    Off,
+    Daily,
+    Weekly,
+}
+
+impl DigestSchedule {
+    /// Time between digests; `None` when digests are off.
+    pub fn period(&self) -> Option<chrono::Duration> {
+        match self {
+            DigestSchedule::Off => None,
+            DigestSchedule::Daily => Some(chrono::Duration::days(1)),
+            DigestSchedule::Weekly => Some(chrono::Duration::weeks(1)),
+        }
+    }
+}
+
+/// Background service exposing search and downloads over HTTP.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DaemonConfig {
+    /// Address the HTTP API binds to
+    pub listen: String,
+    /// Secret holding the bearer token for admin requests; without one every client is an admin
+    pub admin_token_secret: Option<String>,
+    pub watchlist_interval_mins: u64,
+    /// Seed finished downloads again on startup, once their files check out
+    pub reseed: bool,
+    /// Hash every piece before seeding again, rather than only comparing sizes
+    pub verify_hashes: bool,
+}
+
+impl Default for DaemonConfig {
+    fn default() -> Self {
+        Self {
+            listen: "127.0.0.1:7879".to_string(),
+            admin_token_secret: None,
+            watchlist_interval_mins: 60,
+            reseed: true,
+            verify_hashes: false,
+        }
+    }
+}
+
+/// Scheduled re-checks of the watchlist and watch rules.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WatchlistConfig {
+    /// Profile for re-checking the watchlist, usually one with a small, fast model
+    pub profile: Option<String>,
+    /// Checks due at once are spread over this long; 0 runs them back to back
+    pub spread_mins: u64,
+    /// Searches each source gets from scheduled checks at most; 0 for no limit
+    pub source_requests_per_hour: u32,
+}
+
+impl Default for WatchlistConfig {
+    fn default() -> Self {
+        Self {
+            profile: None,
+            spread_mins: 60,
+            source_requests_per_hour: 120,
+        }
+    }
+}
+
+impl WatchlistConfig {
+    /// How long a batch of checks may take, never past the next batch.
+    pub fn spread_window(&self, interval_mins: u64) -> std::time::Duration {
+        std::time::Duration::from_secs(self.spread_mins.min(interval_mins) * 60)
+    }
+}
+
+/// Scraper results are reused for this long, so refining a search or
+/// searching again soon after doesn't hit the sites again.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CacheConfig {
+    /// 0 disables the cache
+    pub ttl_mins: u64,
+}
+
+impl Default for CacheConfig {
+    fn default() -> Self {
+        Self { ttl_mins: 30 }
+    }
+}
+
+impl CacheConfig {
+    pub fn ttl(&self) -> Option<chrono::Duration> {
+        (self.ttl_mins > 0).then(|| chrono::Duration::minutes(self.ttl_mins as i64))
+    }
+}
+
+/// Reference download used by `nettest`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct NettestConfig {
+    /// A well-seeded magnet, .torrent URL or file; distro ISOs work well
+    pub torrent: String,
+    /// How long to download for
+    pub seconds: u64,
+}
+
+impl Default for NettestConfig {
+    fn default() -> Self {
+        Self {
+            torrent: "https://releases.ubuntu.com/24.04/ubuntu-24.04.3-desktop-amd64.iso.torrent".to_string(),
+            seconds: 30,
+        }
+    }
+}
+
+impl Config {
+    pub fn load() -> Result<Self> {
+        let path = config_dir().join("config.toml");
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+
+        let content = std::fs::read_to_string(&path)?;
+        let mut config: Config = toml::from_str(&content)
+            .map_err(|e| anyhow::anyhow!("Invalid config file {}: {}", path.display(), e))?;
+
+        for root in &mut config.download.roots {
+            root.path = expand_tilde(&root.path);
+        }
+        config.organize.library = expand_tilde(&config.organize.library);
+
+        Ok(config)
+    }
+
+    /// Keep the state database and its backups in `dir` rather than
+    /// `~/.torrentai`.
+    pub fn with_state_dir(mut self, dir: PathBuf) -> Self {
+        self.state_dir = Some(dir);
+        self
+    }
+
+    /// Where the state database and its backups live.
+    pub fn state_dir(&self) -> PathBuf {
+        self.state_dir.clone().unwrap_or_else(config_dir)
+    }
+
+    /// LLM settings with the named profile applied on top.
+    pub fn llm_for(&self, profile: Option<&str>) -> Result<LlmConfig> {
+        let mut llm = self.llm.clone();
+        let Some(name) = profile else {
+            return Ok(llm);
+        };
+        let overrides = &self.profiles.get(name)
+            .ok_or_else(|| anyhow::anyhow!("No profile \"{}\" in config.toml", name))?
+            .llm;
+
+        if let Some(backend) = overrides.backend {
+            llm.backend = backend;
+        }
+        if let Some(hosts) = &overrides.hosts {
+            llm.hosts = hosts.clone();
+        }
+        if let Some(model) = &overrides.model {
+            llm.model = model.clone();
+        }
+        llm.max_attempts = overrides.max_attempts.unwrap_or(llm.max_attempts);
+        llm.evaluation_chunk_size = overrides.evaluation_chunk_size.unwrap_or(llm.evaluation_chunk_size);
+        llm.max_concurrent_evaluations = overrides.max_concurrent_evaluations.unwrap_or(llm.max_concurrent_evaluations);
+        Ok(llm)
+    }
+}
+
+/// Directory holding the config file and local state (`~/.torrentai`).
+pub fn config_dir() -> PathBuf {
+    dirs::home_dir()
+        .unwrap_or_else(|| PathBuf::from("."))
+        .join(".torrentai")
+}
+
+pub fn expand_tilde(path: &Path) -> PathBuf {
+    match path.strip_prefix("~") {
+        Ok(rest) => dirs::home_dir()
+            .map(|home| home.join(rest))
+            .unwrap_or_else(|| path.to_path_buf()),
+        Err(_) => path.to_path_buf(),
+    }
+}
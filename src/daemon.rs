@@ -0,0 +1,554 @@
+use anyhow::Result;
+use axum::{
+    extract::{Path, Query, State},
+    http::{HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+    routing::{get, post},
+    Json, Router,
+};
+use futures::future::{BoxFuture, FutureExt, Shared};
+use serde::Deserialize;
+use sha1::{Digest, Sha1};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use subtle::ConstantTimeEq;
+use tracing::{info, warn};
+
+use crate::access::Role;
+use crate::checks::{self, CheckEvent};
+use crate::config::{Config, DigestSchedule};
+use crate::digest;
+use crate::downloader::{self, DownloadManager};
+use crate::inbox;
+use crate::models::{EvaluatedResult, SearchIntent};
+use crate::notifications::Notifier;
+use crate::pirate_bay_scraper::TorrentResult;
+use crate::secrets::SecretStore;
+use crate::smart_search::{SearchService, SmartSearchEvent, SmartSearcher};
+use crate::state::{DigestOutcome, RequestStatus, StateDb};
+use crate::stats::StatsHistory;
+
+/// How often download progress is written back to the state database and
+/// snapshotted for `/stats`.
+const STATE_SYNC_INTERVAL: Duration = Duration::from_secs(5);
+/// How often watch rules are looked at; each runs on its own interval.
+const WATCH_POLL_INTERVAL: Duration = Duration::from_secs(60);
+/// How often to see whether the notification digest is due.
+const DIGEST_POLL_INTERVAL: Duration = Duration::from_secs(15 * 60);
+
+type SharedSearch = Shared<BoxFuture<'static, Result<Arc<Searched>, String>>>;
+
+/// What a coalesced search found, shared by everyone who asked for it.
+pub struct Searched {
+    /// How the query was understood; `None` for feed matches
+    pub intent: Option<SearchIntent>,
+    pub results: Vec<EvaluatedResult>,
+}
+
+/// Runs each distinct query once at a time. A search for a query already in
+/// flight waits for that search and shares its results instead of scraping
+/// and evaluating again. Feed matches are coalesced the same way, by filter
+/// and items.
+pub struct SearchCoalescer {
+    searcher: Arc<SmartSearcher>,
+    in_flight: Arc<Mutex<HashMap<String, SharedSearch>>>,
+}
+
+impl SearchCoalescer {
+    pub fn new(searcher: Arc<SmartSearcher>) -> Self {
+        Self {
+            searcher,
+            in_flight: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    pub async fn search(&self, query: &str) -> Result<Arc<Searched>> {
+        let owned = query.to_string();
+        self.coalesce(normalize_query(query), query, move |searcher| async move {
+            let intent = Mutex::new(None);
+            let results = searcher.search(&owned, |event| {
+                if let SmartSearchEvent::IntentParsed(parsed) | SmartSearchEvent::LatestResolved { intent: parsed, .. } = event {
+                    *intent.lock().unwrap() = Some(parsed.clone());
+                }
+            }).await?;
+            Ok(Searched { intent: intent.into_inner().unwrap(), results })
+        }.boxed()).await
+    }
+
+    pub async fn match_results(&self, description: &str, results: Vec<TorrentResult>) -> Result<Arc<Searched>> {
+        let key = format!(
+            "match {}\n{}",
+            normalize_query(description),
+            results.iter().map(|r| r.magnet_link.as_str()).collect::<Vec<_>>().join("\n")
+        );
+        let owned = description.to_string();
+        self.coalesce(key, description, move |searcher| async move {
+            let results = searcher.match_results(&owned, results).await?;
+            Ok(Searched { intent: None, results })
+        }.boxed()).await
+    }
+
+    async fn coalesce(
+        &self,
+        key: String,
+        label: &str,
+        run: impl FnOnce(Arc<SmartSearcher>) -> BoxFuture<'static, Result<Searched>>,
+    ) -> Result<Arc<Searched>> {
+        let search = {
+            let mut in_flight = self.in_flight.lock().unwrap();
+            match in_flight.get(&key) {
+                Some(search) => {
+                    info!("Joining in-flight search for \"{}\"", label);
+                    search.clone()
+                }
+                None => {
+                    // Spawned so the search finishes even if the client that
+                    // started it goes away; it clears its own entry when done
+                    let entries = self.in_flight.clone();
+                    let task_key = key.clone();
+                    let work = run(self.searcher.clone());
+                    let task = tokio::spawn(async move {
+                        let results = work.await;
+                        entries.lock().unwrap().remove(&task_key);
+                        results
+                    });
+                    let search = async move {
+                        match task.await {
+                            Ok(Ok(results)) => Ok(Arc::new(results)),
+                            Ok(Err(e)) => Err(e.to_string()),
+                            Err(e) => Err(e.to_string()),
+                        }
+                    }.boxed().shared();
+                    in_flight.insert(key, search.clone());
+                    search
+                }
+            }
+        };
+
+        search.await.map_err(|e| anyhow::anyhow!(e))
+    }
+}
+
+impl SearchService for SearchCoalescer {
+    fn search<'a>(&'a self, query: &'a str) -> BoxFuture<'a, Result<Vec<EvaluatedResult>>> {
+        async move { Ok(SearchCoalescer::search(self, query).await?.results.clone()) }.boxed()
+    }
+
+    fn match_results<'a>(&'a self, description: &'a str, results: Vec<TorrentResult>) -> BoxFuture<'a, Result<Vec<EvaluatedResult>>> {
+        async move { Ok(SearchCoalescer::match_results(self, description, results).await?.results.clone()) }.boxed()
+    }
+}
+
+/// Queries that differ only in case or spacing are the same search.
+fn normalize_query(query: &str) -> String {
+    query.split_whitespace().collect::<Vec<_>>().join(" ").to_lowercase()
+}
+
+struct Daemon {
+    searches: SearchCoalescer,
+    /// For watchlist, watch rule and feed checks; may use a lighter model
+    /// than interactive searches
+    watchlist_searches: SearchCoalescer,
+    downloads: Arc<DownloadManager>,
+    /// Recent progress of each download, for `/stats`
+    stats: StatsHistory,
+    config: Config,
+    admin_token: Option<String>,
+}
+
+impl Daemon {
+    /// Admin when no token is configured or the bearer token matches.
+    fn role(&self, headers: &HeaderMap) -> Role {
+        let Some(expected) = &self.admin_token else {
+            return Role::Admin;
+        };
+        let Some(given) = headers.get("authorization")
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.strip_prefix("Bearer "))
+        else {
+            return Role::Guest;
+        };
+        // Digests, so neither the token's length nor how much of it matched
+        // shows in how long the comparison takes
+        let matches = Sha1::digest(given.as_bytes()).ct_eq(&Sha1::digest(expected.as_bytes()));
+        if bool::from(matches) { Role::Admin } else { Role::Guest }
+    }
+}
+
+/// Serve the HTTP API and check the watchlist until interrupted.
+pub async fn run(searcher: Arc<SmartSearcher>, watchlist_searcher: Arc<SmartSearcher>, config: Config) -> Result<()> {
+    let state = StateDb::open()?;
+    let default_dir = downloader::resolve_output_dir(None, &config, &state)?;
+    let admin_token = match &config.daemon.admin_token_secret {
+        Some(name) => Some(SecretStore::open()?.require(name)?.to_string()),
+        None => None,
+    };
+
+    let daemon = Arc::new(Daemon {
+        searches: SearchCoalescer::new(searcher),
+        watchlist_searches: SearchCoalescer::new(watchlist_searcher),
+        downloads: Arc::new(DownloadManager::new(config.clone(), default_dir).await?),
+        stats: StatsHistory::default(),
+        config: config.clone(),
+        admin_token,
+    });
+
+    // Pick up whatever was still downloading when the last run stopped
+    let downloads = daemon.downloads.clone();
+    tokio::spawn(async move {
+        match downloads.resume_all().await {
+            Ok(0) => {}
+            Ok(count) => info!("Resumed {} unfinished download(s)", count),
+            Err(e) => warn!("Failed to resume downloads: {}", e),
+        }
+    });
+    if config.daemon.reseed {
+        tokio::spawn(reseed(daemon.clone()));
+    }
+    tokio::spawn(sync_downloads(daemon.clone()));
+    tokio::spawn(watchlist_loop(daemon.clone()));
+    tokio::spawn(watch_rules_loop(daemon.clone()));
+    tokio::spawn(feeds_loop(daemon.clone()));
+    if config.notifications.digest != DigestSchedule::Off {
+        tokio::spawn(digest_loop(daemon.clone()));
+    }
+
+    let app = Router::new()
+        .route("/status", get(status))
+        .route("/stats", get(stats))
+        .route("/search", post(search))
+        .route("/downloads", post(add_download))
+        .route("/requests", get(list_requests).post(add_request))
+        .route("/requests/:id/approve", post(approve_request))
+        .route("/requests/:id/reject", post(reject_request))
+        .with_state(daemon);
+
+    let listener = tokio::net::TcpListener::bind(&config.daemon.listen).await?;
+    info!("Daemon listening on http://{}", config.daemon.listen);
+    println!("🛰️  torrentai daemon listening on http://{}", config.daemon.listen);
+    axum::serve(listener, app)
+        .with_graceful_shutdown(async {
+            let _ = tokio::signal::ctrl_c().await;
+        })
+        .await?;
+    Ok(())
+}
+
+/// Seed finished downloads again, flagging any whose files were moved,
+/// deleted or damaged while the daemon was down.
+async fn reseed(daemon: Arc<Daemon>) {
+    let (seeding, flagged) = match daemon.downloads.reseed_all().await {
+        Ok(outcome) => outcome,
+        Err(e) => {
+            warn!("Failed to seed finished downloads: {}", e);
+            return;
+        }
+    };
+    if seeding > 0 {
+        info!("Seeding {} finished download(s)", seeding);
+    }
+    for (record, problem) in &flagged {
+        warn!("Not seeding {}: {}", record.name, problem);
+        digest::record(&daemon.config.notifications, "seeding", &record.name, DigestOutcome::Failed, Some(problem));
+    }
+}
+
+async fn sync_downloads(daemon: Arc<Daemon>) {
+    let state = StateDb::open()
+        .inspect_err(|e| warn!("Download progress won't be saved: {}", e))
+        .ok();
+    loop {
+        tokio::time::sleep(STATE_SYNC_INTERVAL).await;
+        if let Some(state) = &state {
+            if let Err(e) = daemon.downloads.sync_state(state) {
+                warn!("Failed to save download progress: {}", e);
+            }
+        }
+        daemon.downloads.retrack().await;
+        daemon.stats.record(&daemon.downloads.progress());
+    }
+}
+
+/// Search for released watchlist titles on the configured interval, adding
+/// confident matches to the session.
+async fn watchlist_loop(daemon: Arc<Daemon>) {
+    let interval = Duration::from_secs(daemon.config.daemon.watchlist_interval_mins.max(1) * 60);
+    loop {
+        // Checks are spread over part of the interval; the next batch starts on time regardless
+        let started = tokio::time::Instant::now();
+        if let Err(e) = check_watchlist(&daemon).await {
+            warn!("Watchlist check failed: {}", e);
+        }
+        tokio::time::sleep_until(started + interval).await;
+    }
+}
+
+async fn check_watchlist(daemon: &Daemon) -> Result<()> {
+    let due = StateDb::open()?.due_watchlist()?;
+    let window = daemon.config.watchlist.spread_window(daemon.config.daemon.watchlist_interval_mins.max(1));
+    checks::watchlist(&daemon.config.notifications, &due, &daemon.watchlist_searches, daemon.downloads.as_ref(), window, &log_check).await
+}
+
+/// Run due watch rules, downloading new results above a rule's threshold.
+async fn watch_rules_loop(daemon: Arc<Daemon>) {
+    let notifier = Notifier::from_config(&daemon.config.notifications);
+    loop {
+        if let Err(e) = check_watch_rules(&daemon, &notifier).await {
+            warn!("Watch rule check failed: {}", e);
+        }
+        tokio::time::sleep(WATCH_POLL_INTERVAL).await;
+    }
+}
+
+async fn check_watch_rules(daemon: &Daemon, notifier: &Notifier) -> Result<()> {
+    let due = StateDb::open()?.due_watch_rules()?;
+    // Spread no longer than the most frequent rule, so none falls behind
+    let shortest = due.iter().map(|rule| rule.interval_mins).min().unwrap_or(0).max(1);
+    let window = daemon.config.watchlist.spread_window(shortest);
+    checks::watch_rules(&daemon.config.notifications, &due, &daemon.watchlist_searches, daemon.downloads.as_ref(), notifier, window, &log_check).await
+}
+
+/// Poll due feeds, queueing their new items and starting them straight away.
+async fn feeds_loop(daemon: Arc<Daemon>) {
+    loop {
+        if let Err(e) = poll_feeds(&daemon).await {
+            warn!("Feed poll failed: {}", e);
+        }
+        tokio::time::sleep(WATCH_POLL_INTERVAL).await;
+    }
+}
+
+async fn poll_feeds(daemon: &Daemon) -> Result<()> {
+    let due = StateDb::open()?.due_feeds()?;
+    checks::feeds(&daemon.config.notifications, &due, &daemon.watchlist_searches, Some(daemon.downloads.as_ref()), &log_check).await
+}
+
+fn log_check(event: &CheckEvent) {
+    match event {
+        CheckEvent::Started { .. } => {}
+        CheckEvent::Failed { label, error } => warn!("{} failed: {}", label, error),
+        CheckEvent::Skipped { label, reason } => info!("{}: {}", label, reason),
+        CheckEvent::Found { label, new } => info!("{}: {} new result(s)", label, new.len()),
+        CheckEvent::Grabbing { label, title } => info!("{}: grabbing {}", label, title),
+        CheckEvent::Queued { label, id, title } => info!("{}: queued #{} {}", label, id, title),
+    }
+}
+
+/// Send the notification digest whenever its period comes round.
+async fn digest_loop(daemon: Arc<Daemon>) {
+    let notifier = Notifier::from_config(&daemon.config.notifications);
+    loop {
+        match digest::send_if_due(&daemon.config.notifications, &notifier, false).await {
+            Ok(Some(count)) => info!("Sent digest with {} entries", count),
+            Ok(None) => {}
+            Err(e) => warn!("Digest failed: {}", e),
+        }
+        tokio::time::sleep(DIGEST_POLL_INTERVAL).await;
+    }
+}
+
+#[derive(Deserialize)]
+struct SearchRequest {
+    query: String,
+}
+
+#[derive(Deserialize)]
+struct DownloadRequest {
+    torrent: String,
+    /// Shown until metadata arrives
+    label: Option<String>,
+    output: Option<PathBuf>,
+}
+
+#[derive(Deserialize)]
+struct NewRequest {
+    query: String,
+    /// Shown to the admin after the caller's role, unless the caller is an
+    /// admin; defaults to the role alone
+    requested_by: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct RequestsQuery {
+    /// Include approved and rejected requests
+    #[serde(default)]
+    all: bool,
+}
+
+#[derive(Deserialize)]
+struct ApproveRequest {
+    output: Option<PathBuf>,
+}
+
+#[derive(Deserialize)]
+struct RejectRequest {
+    reason: Option<String>,
+}
+
+async fn status(State(daemon): State<Arc<Daemon>>) -> Response {
+    Json(daemon.downloads.progress()).into_response()
+}
+
+/// Recent samples of each download, with how long it has been in its state.
+async fn stats(State(daemon): State<Arc<Daemon>>) -> Response {
+    Json(daemon.stats.snapshot()).into_response()
+}
+
+async fn search(State(daemon): State<Arc<Daemon>>, Json(request): Json<SearchRequest>) -> Response {
+    match daemon.searches.search(&request.query).await {
+        Ok(searched) => Json(&searched.results).into_response(),
+        Err(e) => error_response(StatusCode::BAD_GATEWAY, e),
+    }
+}
+
+async fn add_download(
+    State(daemon): State<Arc<Daemon>>,
+    headers: HeaderMap,
+    Json(request): Json<DownloadRequest>,
+) -> Response {
+    if let Err(e) = daemon.role(&headers).require_manage("start downloads") {
+        return error_response(StatusCode::FORBIDDEN, e);
+    }
+
+    let output_dir = match StateDb::open()
+        .and_then(|state| downloader::resolve_output_dir(request.output, &daemon.config, &state))
+    {
+        Ok(dir) => dir,
+        Err(e) => return error_response(StatusCode::INSUFFICIENT_STORAGE, e),
+    };
+
+    // Magnets can take a while to resolve; progress shows them as pending meanwhile
+    let label = request.label.unwrap_or_else(|| request.torrent.clone());
+    tokio::spawn(async move {
+        if let Err(e) = daemon.downloads.add(&label, &request.torrent, output_dir).await {
+            warn!("Failed to add {}: {}", label, e);
+        }
+    });
+    StatusCode::ACCEPTED.into_response()
+}
+
+/// File a request; open to guests, who can't download directly.
+async fn add_request(
+    State(daemon): State<Arc<Daemon>>,
+    headers: HeaderMap,
+    Json(request): Json<NewRequest>,
+) -> Response {
+    let searched = match daemon.searches.search(&request.query).await {
+        Ok(searched) => searched,
+        Err(e) => return error_response(StatusCode::BAD_GATEWAY, e),
+    };
+
+    // Names aren't checked, so one given by a guest is shown as a guest's
+    let role = daemon.role(&headers);
+    let role_name = format!("{:?}", role).to_lowercase();
+    let requested_by = match request.requested_by {
+        Some(name) if role.can_manage() => name,
+        Some(name) => format!("{} ({})", role_name, name),
+        None => role_name,
+    };
+    let top_result = searched.results.first();
+    match StateDb::open().and_then(|state| state.add_request(&request.query, &requested_by, searched.intent.as_ref(), top_result)) {
+        Ok(id) => (StatusCode::CREATED, Json(serde_json::json!({ "id": id, "top_result": top_result }))).into_response(),
+        Err(e) => error_response(StatusCode::INTERNAL_SERVER_ERROR, e),
+    }
+}
+
+/// Pending and failed requests, or all of them with `?all=true`.
+async fn list_requests(
+    State(daemon): State<Arc<Daemon>>,
+    headers: HeaderMap,
+    Query(query): Query<RequestsQuery>,
+) -> Response {
+    if let Err(e) = daemon.role(&headers).require_manage("review requests") {
+        return error_response(StatusCode::FORBIDDEN, e);
+    }
+
+    match StateDb::open().and_then(|state| state.requests(None)) {
+        Ok(mut requests) => {
+            if !query.all {
+                requests.retain(|r| matches!(r.status, RequestStatus::Pending | RequestStatus::Failed));
+            }
+            Json(requests).into_response()
+        }
+        Err(e) => error_response(StatusCode::INTERNAL_SERVER_ERROR, e),
+    }
+}
+
+/// Approve a request by downloading its top result. The request is marked
+/// approved before the download is added, so a second approval gets a 409
+/// instead of another download, and failed if it couldn't be added.
+async fn approve_request(
+    State(daemon): State<Arc<Daemon>>,
+    headers: HeaderMap,
+    Path(id): Path<i64>,
+    body: Option<Json<ApproveRequest>>,
+) -> Response {
+    if let Err(e) = daemon.role(&headers).require_manage("approve requests") {
+        return error_response(StatusCode::FORBIDDEN, e);
+    }
+
+    let output = body.and_then(|Json(body)| body.output);
+    let (request, result, output_dir) = {
+        let state = match StateDb::open() {
+            Ok(state) => state,
+            Err(e) => return error_response(StatusCode::INTERNAL_SERVER_ERROR, e),
+        };
+        let (request, result) = match inbox::approvable(&state, id) {
+            Ok(approvable) => approvable,
+            Err(e) => return error_response(undecidable_status(&e), e),
+        };
+        let output_dir = match downloader::resolve_output_dir(output, &daemon.config, &state) {
+            Ok(dir) => dir,
+            Err(e) => return error_response(StatusCode::INSUFFICIENT_STORAGE, e),
+        };
+        if let Err(e) = inbox::claim(&state, id) {
+            return error_response(undecidable_status(&e), e);
+        }
+        (request, result, output_dir)
+    };
+
+    tokio::spawn(async move {
+        let download = daemon.downloads.add(&result.torrent.title, &result.torrent.magnet_link, output_dir).await;
+        if let Err(e) = download {
+            warn!("Failed to add {} for request #{}: {}", result.torrent.title, request.id, e);
+            if let Err(e) = StateDb::open().and_then(|state| inbox::record_failure(&state, id, &e)) {
+                warn!("Couldn't record the failed approval of request #{}: {}", id, e);
+            }
+        }
+    });
+    StatusCode::ACCEPTED.into_response()
+}
+
+async fn reject_request(
+    State(daemon): State<Arc<Daemon>>,
+    headers: HeaderMap,
+    Path(id): Path<i64>,
+    body: Option<Json<RejectRequest>>,
+) -> Response {
+    if let Err(e) = daemon.role(&headers).require_manage("reject requests") {
+        return error_response(StatusCode::FORBIDDEN, e);
+    }
+
+    let reason = body.and_then(|Json(body)| body.reason);
+    match inbox::reject(id, reason.as_deref()) {
+        Ok(()) => StatusCode::NO_CONTENT.into_response(),
+        Err(e) => error_response(undecidable_status(&e), e),
+    }
+}
+
+/// 409 only when the request was already decided; a missing request is a
+/// 404, and one with nothing to download can't be processed.
+fn undecidable_status(error: &anyhow::Error) -> StatusCode {
+    match error.downcast_ref::<inbox::Undecidable>() {
+        Some(inbox::Undecidable::Missing(_)) => StatusCode::NOT_FOUND,
+        Some(inbox::Undecidable::AlreadyDecided(..)) => StatusCode::CONFLICT,
+        Some(inbox::Undecidable::NoResult(..)) => StatusCode::UNPROCESSABLE_ENTITY,
+        None => StatusCode::INTERNAL_SERVER_ERROR,
+    }
+}
+
+fn error_response(status: StatusCode, error: anyhow::Error) -> Response {
+    (status, Json(serde_json::json!({ "error": error.to_string() }))).into_response()
+}
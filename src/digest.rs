@@ -0,0 +1,111 @@
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use tracing::warn;
+
+use crate::config::{DigestSchedule, NotificationsConfig};
+use crate::notifications::{DownloadEvent, Notifier};
+use crate::state::{DigestEntry, DigestOutcome, StateDb};
+
+/// Settings key holding when the last digest went out.
+const LAST_SENT_KEY: &str = "digest_last_sent";
+
+/// Note what an unattended search did, for the next digest. Does nothing
+/// when digests are off, and never fails the caller: losing a digest line is
+/// better than aborting a watchlist check.
+pub fn record(config: &NotificationsConfig, source: &str, subject: &str, outcome: DigestOutcome, detail: Option<&str>) {
+    if config.digest == DigestSchedule::Off {
+        return;
+    }
+    if let Err(e) = StateDb::open().and_then(|state| state.record_digest(source, subject, outcome, detail)) {
+        warn!("Could not record \"{}\" for the digest: {}", subject, e);
+    }
+}
+
+/// When the next digest is due; `None` when digests are off.
+pub fn next_due(config: &NotificationsConfig) -> Result<Option<DateTime<Utc>>> {
+    let Some(period) = config.digest.period() else {
+        return Ok(None);
+    };
+    Ok(last_sent()?.map(|t| t + period))
+}
+
+/// Send the digest if its period has passed, or straight away with `force`.
+/// Returns how many entries went out. An empty digest isn't sent, but still
+/// starts the next period. When no sink takes the digest, its entries are
+/// kept and the period left open, so the next check tries again.
+pub async fn send_if_due(config: &NotificationsConfig, notifier: &Notifier, force: bool) -> Result<Option<usize>> {
+    let Some(period) = config.digest.period() else {
+        return Ok(None);
+    };
+
+    let now = Utc::now();
+    let (entries, last_id) = {
+        let state = StateDb::open()?;
+        let due = match last_sent()? {
+            Some(sent) => now - sent >= period,
+            // First run: start the clock rather than sending a partial digest
+            None => {
+                state.set_setting(LAST_SENT_KEY, &now.to_rfc3339())?;
+                false
+            }
+        };
+        if !due && !force {
+            return Ok(None);
+        }
+        state.pending_digest()?
+    };
+
+    let Some(last_id) = last_id else {
+        StateDb::open()?.set_setting(LAST_SENT_KEY, &now.to_rfc3339())?;
+        return Ok(Some(0));
+    };
+    let count = entries.len();
+    notifier.try_send(DownloadEvent::Digest { schedule: config.digest, entries }).await
+        .map_err(|e| anyhow::anyhow!("Digest not sent, keeping its {} entries: {}", count, e))?;
+    let state = StateDb::open()?;
+    state.clear_digest(last_id)?;
+    state.set_setting(LAST_SENT_KEY, &now.to_rfc3339())?;
+    Ok(Some(count))
+}
+
+/// The digest as text: a tally, then one line per subject and outcome with
+/// grabs first and routine skips last.
+pub fn render(entries: &[DigestEntry]) -> String {
+    let order = [DigestOutcome::Grabbed, DigestOutcome::Found, DigestOutcome::Failed, DigestOutcome::Skipped];
+    let tally: Vec<String> = order.iter()
+        .map(|outcome| (outcome, entries.iter().filter(|e| e.outcome == *outcome).count()))
+        .filter(|(_, count)| *count > 0)
+        .map(|(outcome, count)| format!("{} {}", count, outcome.as_str()))
+        .collect();
+    if tally.is_empty() {
+        return "Nothing happened".to_string();
+    }
+
+    let mut lines = vec![tally.join(", ")];
+    for outcome in order {
+        for entry in entries.iter().filter(|e| e.outcome == outcome) {
+            let icon = match outcome {
+                DigestOutcome::Grabbed => "✅",
+                DigestOutcome::Found => "🆕",
+                DigestOutcome::Failed => "❌",
+                DigestOutcome::Skipped => "⏳",
+            };
+            let mut line = format!("{} {} ({})", icon, entry.subject, entry.source);
+            if entry.count > 1 {
+                line.push_str(&format!(" ×{}", entry.count));
+            }
+            if let Some(detail) = &entry.detail {
+                line.push_str(&format!(": {}", detail));
+            }
+            lines.push(line);
+        }
+    }
+    lines.join("\n")
+}
+
+fn last_sent() -> Result<Option<DateTime<Utc>>> {
+    Ok(StateDb::open()?
+        .get_setting(LAST_SENT_KEY)?
+        .and_then(|t| DateTime::parse_from_rfc3339(&t).ok())
+        .map(|t| t.with_timezone(&Utc)))
+}
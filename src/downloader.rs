@@ -1,36 +1,177 @@
 use anyhow::Result;
-use librqbit::{AddTorrent, AddTorrentOptions, Session};
-use std::path::PathBuf;
-use tracing::info;
+use chrono::Utc;
+use futures::future::{BoxFuture, FutureExt};
+use futures::StreamExt;
+use librqbit::api::TorrentIdOrHash;
+use librqbit::{AddTorrent, AddTorrentOptions, ManagedTorrent, Session, SessionOptions};
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use tracing::{debug, info, warn};
 
-pub async fn download_torrent(torrent: &str, output_dir: PathBuf) -> Result<()> {
-    info!("Starting download: {}", torrent);
+use crate::classify;
+use crate::config::Config;
+use crate::models::ContentType;
+use crate::notifications::{display_name, DownloadEvent, Notifier};
+use crate::progress::{self, SpeedMeter};
+use crate::organize;
+use crate::state::{OrganizedFile, QueueStatus, StateDb, TorrentRecord, TorrentStatus};
+use crate::storage;
+use crate::trackers::{self, TrackerSchedule};
+
+/// Resolve where a download should go: an explicit `--output` wins, otherwise
+/// a configured download root is picked based on free space.
+pub(crate) fn resolve_output_dir(output: Option<PathBuf>, config: &Config, state: &StateDb) -> Result<PathBuf> {
+    match output {
+        Some(dir) => Ok(dir),
+        None => storage::select_download_root(&config.download, state),
+    }
+}
+
+/// A session saving into `dir` by default, on the DHT unless `download.dht`
+/// is off.
+pub(crate) async fn new_session(config: &Config, dir: PathBuf) -> Result<Arc<Session>> {
+    Session::new_with_opts(dir, SessionOptions {
+        disable_dht: !config.download.dht,
+        disable_dht_persistence: !config.download.dht,
+        ..Default::default()
+    }).await
+}
+
+/// Download a torrent to completion, returning the paths of its files.
+pub async fn download_torrent(torrent: &str, output_dir: PathBuf) -> Result<Vec<PathBuf>> {
+    download_each(&Config::load()?, &[(torrent.to_string(), output_dir)], false).await?
+        .pop()
+        .expect("one outcome per torrent")
+}
+
+/// Like [`download_torrent`], for grabs by the watchlist or watch rules,
+/// whose completion waits for the digest when one is configured.
+pub async fn download_unattended(config: &Config, torrent: &str, output_dir: PathBuf) -> Result<Vec<PathBuf>> {
+    download_each(config, &[(torrent.to_string(), output_dir)], true).await?
+        .pop()
+        .expect("one outcome per torrent")
+}
+
+/// Where the watchlist, watch rules and feeds send what they grab.
+pub trait Grabber: Send + Sync {
+    /// Start downloading `torrent` with nobody watching, into a download
+    /// root of the grabber's choosing.
+    fn grab<'a>(&'a self, label: &'a str, torrent: &'a str) -> BoxFuture<'a, Result<()>>;
+}
+
+/// Downloads each grab to completion before the check moves on, as the
+/// CLI's checks do.
+pub struct DownloadNow {
+    /// A configured download root when `None`
+    pub output: Option<PathBuf>,
+}
+
+impl Grabber for DownloadNow {
+    fn grab<'a>(&'a self, _label: &'a str, torrent: &'a str) -> BoxFuture<'a, Result<()>> {
+        async move {
+            let config = Config::load()?;
+            let output_dir = resolve_output_dir(self.output.clone(), &config, &StateDb::open_in(&config.state_dir())?)?;
+            download_unattended(&config, torrent, output_dir).await?;
+            Ok(())
+        }.boxed()
+    }
+}
+
+/// Download several torrents in one session, each into its own directory,
+/// reporting their combined progress. At most `download.max_concurrent` run
+/// at once. Each torrent's files, or why it failed, come back in the order
+/// given; one failing doesn't stop the others.
+pub async fn download_all(config: &Config, torrents: &[(String, PathBuf)]) -> Result<Vec<Result<Vec<PathBuf>>>> {
+    download_each(config, torrents, false).await
+}
+
+async fn download_each(config: &Config, torrents: &[(String, PathBuf)], unattended: bool) -> Result<Vec<Result<Vec<PathBuf>>>> {
+    let Some((_, first_dir)) = torrents.first() else {
+        return Ok(Vec::new());
+    };
+    let mut notifier = Notifier::from_config(&config.notifications);
+    if unattended {
+        notifier = notifier.unattended();
+    }
+    let session = new_session(config, first_dir.clone()).await?;
+    let readings: Vec<Mutex<Reading>> = torrents.iter()
+        .map(|(torrent, _)| Mutex::new(Reading::new(display_name(torrent))))
+        .collect();
+    let solo = torrents.len() == 1;
     
-    // Create the session
-    let session = Session::new(output_dir).await?;
+    // Collected first, so the future stays Send when a caller spawns it
+    let each: Vec<_> = torrents.iter().zip(&readings).map(|((torrent, output_dir), reading)| {
+        let (session, notifier) = (&session, &notifier);
+        async move {
+            let outcome = download_in_session(session, torrent, output_dir, config, notifier, reading, solo).await;
+            reading.lock().unwrap().finish();
+            outcome
+        }
+    }).collect();
+    let downloads = futures::stream::iter(each)
+        .buffered(config.download.max_concurrent.max(1))
+        .collect::<Vec<_>>();
+    if solo {
+        return Ok(downloads.await);
+    }
     
-    // Prepare torrent addition
-    let add_torrent = if torrent.starts_with("magnet:") {
-        AddTorrent::from_url(torrent)
-    } else if torrent.starts_with("http://") || torrent.starts_with("https://") {
-        AddTorrent::from_url(torrent)
-    } else {
-        // Assume it's a local file path
-        AddTorrent::from_local_filename(torrent)?
+    let outcomes = tokio::select! {
+        outcomes = downloads => outcomes,
+        _ = report_combined(&readings) => unreachable!("combined progress is reported until the downloads finish"),
     };
+    let names: Vec<String> = readings.into_iter()
+        .map(|reading| reading.into_inner().unwrap().name)
+        .collect();
+    print_combined_summary(&names, &outcomes);
+    Ok(outcomes)
+}
+
+/// Download one torrent in a shared session. `reading` is kept up to date
+/// for the combined progress line; a lone download logs its own instead.
+async fn download_in_session(
+    session: &Arc<Session>,
+    torrent: &str,
+    output_dir: &Path,
+    config: &Config,
+    notifier: &Notifier,
+    reading: &Mutex<Reading>,
+    solo: bool,
+) -> Result<Vec<PathBuf>> {
+    info!("Starting download: {}", torrent);
+    
+    let state = StateDb::open_in(&config.state_dir())?;
+    let output_dir = output_dir.to_path_buf();
+    let disk_root = storage::root_for_path(&config.download.roots, &output_dir)
+        .map(|root| root.path.clone())
+        .unwrap_or_else(|| output_dir.clone());
     
-    // Add the torrent with options
-    let handle_result = session.add_torrent(add_torrent, Some(AddTorrentOptions::default())).await?;
+    // Handed to librqbit with only the trackers its schedule picks, leaving
+    // out those dropped after repeated failures or still backing off
+    let contents = read_torrent(torrent).await?;
+    let mut tracked = Tracked::new(contents, config, &state)?;
     
-    match handle_result {
-        librqbit::AddTorrentResponse::Added(id, managed_handle) => {
+    // Existing files are checked and kept, so an interrupted download picks
+    // up where it left off
+    let options = || AddTorrentOptions {
+        overwrite: true,
+        output_folder: Some(output_dir.to_string_lossy().to_string()),
+        ..Default::default()
+    };
+    let handle_result = session.add_torrent(tracked.add_torrent()?, Some(options())).await?;
+    
+    let files = match handle_result {
+        librqbit::AddTorrentResponse::Added(id, mut managed_handle) => {
             info!("Torrent added successfully with ID: {}", id);
+            notifier.send(DownloadEvent::Started { name: display_name(torrent) }).await;
             
             // Wait for metadata if needed
             if torrent.starts_with("magnet:") {
                 info!("Waiting for metadata...");
                 if let Err(e) = managed_handle.wait_until_initialized().await {
-                    return Err(anyhow::anyhow!("Failed to get metadata: {}", e));
+                    let error = format!("Failed to get metadata: {}", e);
+                    notifier.send(DownloadEvent::Failed { name: display_name(torrent), error: error.clone() }).await;
+                    return Err(anyhow::anyhow!(error));
                 }
                 info!("Metadata received");
             }
@@ -51,39 +192,834 @@ pub async fn download_torrent(torrent: &str, output_dir: PathBuf) -> Result<()>
             
             info!("Torrent name: {}", name);
             info!("Total size: {} bytes", total_size);
+            notifier.send(DownloadEvent::MetadataResolved { name: name.clone(), total_bytes: total_size }).await;
+            
+            if let Ok(free) = storage::free_space(&output_dir) {
+                if free < total_size {
+                    warn!("Only {} free on {} but torrent needs {}",
+                          storage::format_bytes(free),
+                          disk_root.display(),
+                          storage::format_bytes(total_size));
+                }
+            }
+            
+            let info_hash = managed_handle.info_hash().as_string();
+            let now = Utc::now().to_rfc3339();
+            
+            state.upsert_torrent(&TorrentRecord {
+                info_hash: info_hash.clone(),
+                name: name.clone(),
+                source: torrent.to_string(),
+                output_dir: output_dir.clone(),
+                disk_root: disk_root.clone(),
+                status: TorrentStatus::Downloading,
+                total_bytes: total_size,
+                progress_bytes: 0,
+                uploaded_bytes: 0,
+                problem: None,
+                selected_files: None,
+                content_type: classify_handle(&managed_handle),
+                added_at: now.clone(),
+                updated_at: now,
+            })?;
+            let mut uploaded_before = state.uploaded_before(&info_hash)?;
             
             // Note: start() is private, torrents start automatically when added
             info!("Download in progress...");
             
             // Monitor progress
+            let started = std::time::Instant::now();
+            let mut meter = SpeedMeter::default();
+            let mut fetched = 0;
+            let mut uploaded = 0;
+            // From before the torrent was last restarted
+            let (mut fetched_before, mut uploaded_earlier) = (0, 0);
             loop {
                 let stats = managed_handle.stats();
-                info!("{}", stats);
+                let (peers, seen) = match &stats.live {
+                    Some(live) => {
+                        // Bytes from peers, so verifying existing files on resume isn't counted as speed
+                        fetched = fetched_before + live.snapshot.fetched_bytes;
+                        uploaded = uploaded_earlier + live.snapshot.uploaded_bytes;
+                        meter.sample(fetched, uploaded);
+                        (live.snapshot.peer_stats.live, live.snapshot.peer_stats.seen)
+                    }
+                    None => (0, 0),
+                };
+                let line = progress::status_line(&name, stats.progress_bytes, stats.total_bytes, &meter, peers, seen);
+                if solo {
+                    info!("{}", line);
+                } else {
+                    debug!("{}", line);
+                }
+                *reading.lock().unwrap() = Reading {
+                    name: name.clone(),
+                    progress: stats.progress_bytes,
+                    total: stats.total_bytes,
+                    fetched,
+                    uploaded,
+                    peers,
+                    seen,
+                    done: false,
+                };
+                
+                if let Some(error) = &stats.error {
+                    state.update_progress(&info_hash, TorrentStatus::Failed, stats.progress_bytes, uploaded_before + stats.uploaded_bytes)?;
+                    notifier.send(DownloadEvent::Failed { name: name.clone(), error: error.clone() }).await;
+                    return Err(anyhow::anyhow!("Download failed: {}", error));
+                }
                 
                 // Check if download is complete by checking if all pieces are finished
                 if stats.finished {
+                    state.update_progress(&info_hash, TorrentStatus::Completed, stats.progress_bytes, uploaded_before + stats.uploaded_bytes)?;
                     info!("Download completed!");
+                    notifier.send(DownloadEvent::Completed { name: name.clone(), output_dir: output_dir.clone() }).await;
                     break;
                 }
                 
+                state.update_progress(&info_hash, TorrentStatus::Downloading, stats.progress_bytes, uploaded_before + stats.uploaded_bytes)?;
+                
+                // librqbit announces to the trackers it was handed until the
+                // torrent is added again, so a dropped tracker means a restart
+                if let Some(schedule) = &mut tracked.trackers {
+                    if schedule.update(&state)? {
+                        info!("Restarting {} without the dropped tracker", name);
+                        uploaded_before += stats.uploaded_bytes;
+                        (fetched_before, uploaded_earlier) = (fetched, uploaded);
+                        session.delete(TorrentIdOrHash::Hash(managed_handle.info_hash()), false).await?;
+                        managed_handle = match session.add_torrent(tracked.add_torrent()?, Some(options())).await? {
+                            librqbit::AddTorrentResponse::Added(_, handle)
+                            | librqbit::AddTorrentResponse::AlreadyManaged(_, handle) => handle,
+                            librqbit::AddTorrentResponse::ListOnly(_) => {
+                                return Err(anyhow::anyhow!("Torrent was added in list-only mode when restarted"));
+                            }
+                        };
+                    }
+                }
+                
                 tokio::time::sleep(tokio::time::Duration::from_secs(1)).await;
             }
+            
+            let files = downloaded_files(&managed_handle, &output_dir, &name);
+            progress::print_summary(&name, &output_dir, started.elapsed(), fetched, &files);
+            files
         }
         librqbit::AddTorrentResponse::AlreadyManaged(id, managed_handle) => {
             info!("Torrent already exists with ID: {}", id);
             
-            let stats = managed_handle.stats();
-            if stats.finished {
-                info!("This torrent is already downloaded");
-            } else {
-                info!("This torrent is already being downloaded");
-                // Note: Can't restart it as start() is private
+            // Given twice; the other download reports the progress
+            let name = torrent_name(&managed_handle).unwrap_or_else(|| display_name(torrent));
+            if !managed_handle.stats().finished {
+                return Err(anyhow::anyhow!("{} is already being downloaded", name));
             }
+            info!("This torrent is already downloaded");
+            downloaded_files(&managed_handle, &output_dir, &name)
         }
         librqbit::AddTorrentResponse::ListOnly(_list_response) => {
             return Err(anyhow::anyhow!("Torrent was added in list-only mode. Session might be read-only."));
         }
-    }
+    };
     
+    Ok(files)
+}
+
+/// A torrent's magnet link, or its .torrent file's contents.
+enum TorrentContents {
+    Magnet(String),
+    File(Vec<u8>),
+}
+
+/// Read `torrent`, a magnet link, a .torrent file's URL or a path to one.
+async fn read_torrent(torrent: &str) -> Result<TorrentContents> {
+    if torrent.starts_with("magnet:") {
+        Ok(TorrentContents::Magnet(torrent.to_string()))
+    } else if torrent.starts_with("http://") || torrent.starts_with("https://") {
+        Ok(TorrentContents::File(reqwest::get(torrent).await?.error_for_status()?.bytes().await?.to_vec()))
+    } else {
+        Ok(TorrentContents::File(std::fs::read(torrent)?))
+    }
+}
+
+/// A torrent with the schedule that picks which of its trackers librqbit
+/// announces to. Magnets without a hex info hash go to librqbit untouched.
+struct Tracked {
+    contents: TorrentContents,
+    trackers: Option<TrackerSchedule>,
+}
+
+impl Tracked {
+    fn new(contents: TorrentContents, config: &Config, state: &StateDb) -> Result<Self> {
+        let (info_hash, tiers) = match &contents {
+            TorrentContents::Magnet(magnet) => (trackers::magnet_info_hash(magnet), trackers::tiers_from_magnet(magnet)),
+            TorrentContents::File(bytes) => (trackers::torrent_info_hash(bytes), trackers::tiers_from_torrent_file(bytes)),
+        };
+        let trackers = info_hash
+            .map(|hash| TrackerSchedule::load(&hash, tiers, config.trackers.clone(), state))
+            .transpose()?;
+        Ok(Self { contents, trackers })
+    }
+
+    /// The torrent for librqbit, announcing only to the trackers picked now.
+    fn add_torrent(&self) -> Result<AddTorrent<'static>> {
+        let now = Utc::now();
+        Ok(match (&self.contents, &self.trackers) {
+            (TorrentContents::Magnet(magnet), Some(schedule)) => {
+                AddTorrent::from_url(trackers::strip_trackers(magnet, &schedule.inactive(now)))
+            }
+            (TorrentContents::Magnet(magnet), None) => AddTorrent::from_url(magnet.clone()),
+            (TorrentContents::File(bytes), Some(schedule)) => {
+                AddTorrent::from_bytes(trackers::with_trackers(bytes, &schedule.active(now))?)
+            }
+            (TorrentContents::File(bytes), None) => AddTorrent::from_bytes(bytes.clone()),
+        })
+    }
+}
+
+/// Latest numbers from one of several downloads running together.
+#[derive(Debug, Default)]
+struct Reading {
+    name: String,
+    progress: u64,
+    total: u64,
+    /// Bytes from peers, as opposed to verified from disk
+    fetched: u64,
+    uploaded: u64,
+    peers: usize,
+    seen: usize,
+    done: bool,
+}
+
+impl Reading {
+    fn new(name: String) -> Self {
+        Self { name, ..Default::default() }
+    }
+
+    /// Stop counting towards what's left, whether it finished or failed.
+    fn finish(&mut self) {
+        self.done = true;
+        self.total = self.progress;
+    }
+}
+
+/// Log one line for every download together each second, until cancelled.
+async fn report_combined(readings: &[Mutex<Reading>]) {
+    let mut meter = SpeedMeter::default();
+    loop {
+        tokio::time::sleep(tokio::time::Duration::from_secs(1)).await;
+        let mut all = Reading::default();
+        let mut done = 0;
+        for reading in readings {
+            let reading = reading.lock().unwrap();
+            all.progress += reading.progress;
+            all.total += reading.total;
+            all.fetched += reading.fetched;
+            all.uploaded += reading.uploaded;
+            all.peers += reading.peers;
+            all.seen += reading.seen;
+            if reading.done {
+                done += 1;
+            }
+        }
+        meter.sample(all.fetched, all.uploaded);
+        let label = format!("{} downloads, {} done", readings.len(), done);
+        info!("{}", progress::status_line(&label, all.progress, all.total, &meter, all.peers, all.seen));
+    }
+}
+
+/// Which of several downloads finished and why the others didn't.
+fn print_combined_summary(names: &[String], outcomes: &[Result<Vec<PathBuf>>]) {
+    let failed = outcomes.iter().filter(|outcome| outcome.is_err()).count();
+    println!("\n📦 {} downloads: {} finished, {} failed", outcomes.len(), outcomes.len() - failed, failed);
+    for (name, outcome) in names.iter().zip(outcomes) {
+        if let Err(e) = outcome {
+            println!("   ❌ {}: {}", name, e);
+        }
+    }
+}
+
+/// Files of a finished torrent as they sit on disk. Multi-file torrents land
+/// in a folder named after the torrent, so both layouts are checked.
+fn downloaded_files(handle: &Arc<ManagedTorrent>, output_dir: &std::path::Path, name: &str) -> Vec<PathBuf> {
+    let relative: Vec<PathBuf> = handle
+        .with_metadata(|meta| meta.file_infos.iter().map(|f| f.relative_filename.clone()).collect())
+        .unwrap_or_default();
+
+    relative.into_iter()
+        .filter_map(|file| {
+            [output_dir.join(&file), output_dir.join(name).join(&file)]
+                .into_iter()
+                .find(|path| path.exists())
+        })
+        .collect()
+}
+
+/// Download everything queued together, then anything queued meanwhile,
+/// until the queue is empty. A failed entry is marked as such without
+/// holding up the rest; the run fails at the end if any entry did.
+pub async fn run_queue(config: &Config, output: Option<PathBuf>) -> Result<()> {
+    let state = StateDb::open_in(&config.state_dir())?;
+    let (mut total, mut failed) = (0, 0);
+
+    loop {
+        let items: Vec<_> = state.queue()?.into_iter().filter(|item| item.status == QueueStatus::Queued).collect();
+        if items.is_empty() {
+            info!("Download queue is empty");
+            break;
+        }
+
+        for item in &items {
+            info!("Starting queued download #{}: {}", item.id, item.name);
+            state.set_queue_status(item.id, QueueStatus::Downloading, None)?;
+        }
+
+        // Each entry gets its own root, so a round-robin selection spreads them
+        // and one that fills a disk doesn't decide where the rest go
+        let dirs: Vec<Result<PathBuf>> = items.iter()
+            .map(|_| resolve_output_dir(output.clone(), config, &state))
+            .collect();
+        let torrents: Vec<(String, PathBuf)> = items.iter().zip(&dirs)
+            .filter_map(|(item, dir)| Some((item.source.clone(), dir.as_ref().ok()?.clone())))
+            .collect();
+        let mut started = match download_all(config, &torrents).await {
+            Ok(outcomes) => outcomes,
+            // Nothing started, so every entry failed the same way
+            Err(e) => torrents.iter().map(|_| Err(anyhow::anyhow!("{}", e))).collect(),
+        }.into_iter();
+        let outcomes: Vec<Result<Vec<PathBuf>>> = dirs.into_iter()
+            .map(|dir| match dir {
+                Ok(_) => started.next().expect("one outcome per started download"),
+                Err(e) => Err(e),
+            })
+            .collect();
+
+        total += items.len();
+        for (item, outcome) in items.iter().zip(outcomes) {
+            match outcome {
+                Ok(_) => state.set_queue_status(item.id, QueueStatus::Completed, None)?,
+                Err(e) => {
+                    warn!("Queued download #{} failed: {}", item.id, e);
+                    state.set_queue_status(item.id, QueueStatus::Failed, Some(&e.to_string()))?;
+                    failed += 1;
+                }
+            }
+        }
+    }
+
+    if failed > 0 {
+        return Err(anyhow::anyhow!("{} of {} queued downloads failed", failed, total));
+    }
     Ok(())
-}
\ No newline at end of file
+}
+
+/// Live view of one torrent managed by a [`DownloadManager`].
+#[derive(Debug, Clone, Serialize)]
+pub struct DownloadProgress {
+    /// `None` until a .torrent file or URL has been read
+    pub info_hash: Option<String>,
+    pub name: String,
+    pub progress_bytes: u64,
+    pub total_bytes: u64,
+    pub finished: bool,
+    /// Rolling speeds, in MiB/s
+    pub download_mbps: f64,
+    pub upload_mbps: f64,
+    pub peers: usize,
+    /// Peers known to the swarm, connected or not
+    pub peers_seen: usize,
+    /// Seconds left at the current speed
+    pub eta_secs: Option<u64>,
+    /// Still resolving metadata
+    pub pending: bool,
+    pub error: Option<String>,
+}
+
+struct ManagedDownload {
+    label: String,
+    handle: Option<Arc<ManagedTorrent>>,
+    output_dir: PathBuf,
+    source: String,
+    selected_files: Option<Vec<usize>>,
+    error: Option<String>,
+    recorded: bool,
+    /// Uploaded in earlier sessions, read when first recorded
+    uploaded_before: u64,
+    /// Completion or failure has been announced
+    notified: bool,
+    /// Grabbed by the watchlist, a watch rule or a feed
+    unattended: bool,
+    meter: SpeedMeter,
+    /// What was handed to librqbit, kept for adding it again
+    tracked: Option<Tracked>,
+    /// A tracker was dropped since librqbit was handed the torrent
+    retrack: bool,
+}
+
+impl ManagedDownload {
+    fn new(label: &str, source: &str, output_dir: PathBuf, selected_files: Option<Vec<usize>>) -> Self {
+        Self {
+            label: label.to_string(),
+            handle: None,
+            output_dir,
+            source: source.to_string(),
+            selected_files,
+            error: None,
+            recorded: false,
+            uploaded_before: 0,
+            notified: false,
+            unattended: false,
+            meter: SpeedMeter::default(),
+            tracked: None,
+            retrack: false,
+        }
+    }
+}
+
+/// A shared session downloading several torrents in the background, for
+/// callers that keep running while downloads progress (e.g. the TUI).
+pub struct DownloadManager {
+    session: Arc<Session>,
+    config: Config,
+    notifier: Notifier,
+    /// For downloads nobody asked for just now
+    unattended_notifier: Notifier,
+    downloads: Mutex<Vec<ManagedDownload>>,
+}
+
+/// How a torrent came to be added, which decides what gets announced.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Added {
+    ByUser,
+    /// By the watchlist, a watch rule or a feed
+    Unattended,
+    /// Finished in an earlier run, so there's nothing to announce
+    Reseeding,
+    /// Left unfinished by an earlier run; only finishing is announced
+    Resumed,
+}
+
+impl Grabber for DownloadManager {
+    /// Added to the session, in a download root picked for it.
+    fn grab<'a>(&'a self, label: &'a str, torrent: &'a str) -> BoxFuture<'a, Result<()>> {
+        async move {
+            let output_dir = resolve_output_dir(None, &self.config, &self.state()?)?;
+            self.add_unattended(label, torrent, output_dir).await
+        }.boxed()
+    }
+}
+
+impl DownloadManager {
+    pub async fn new(config: Config, default_dir: PathBuf) -> Result<Self> {
+        let session = new_session(&config, default_dir).await?;
+        Ok(Self::with_session(config, session))
+    }
+
+    /// Manage downloads in a session set up by the caller, e.g. one that
+    /// only talks to local peers.
+    pub fn with_session(config: Config, session: Arc<Session>) -> Self {
+        Self {
+            session,
+            notifier: Notifier::from_config(&config.notifications),
+            unattended_notifier: Notifier::from_config(&config.notifications).unattended(),
+            config,
+            downloads: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// The state database the config points at.
+    fn state(&self) -> Result<StateDb> {
+        StateDb::open_in(&self.config.state_dir())
+    }
+
+    /// Add a torrent to the session. Returns once librqbit has accepted it,
+    /// which for magnets includes resolving metadata; progress shows the
+    /// entry as pending until then.
+    pub async fn add(&self, label: &str, torrent: &str, output_dir: PathBuf) -> Result<()> {
+        self.add_torrent(label, torrent, output_dir, None, Added::ByUser).await
+    }
+
+    /// Like [`add`](Self::add), for grabs by the watchlist, watch rules and
+    /// feeds, whose completion waits for the digest when one is configured.
+    pub async fn add_unattended(&self, label: &str, torrent: &str, output_dir: PathBuf) -> Result<()> {
+        self.add_torrent(label, torrent, output_dir, None, Added::Unattended).await
+    }
+
+    /// Re-add a torrent recorded in the state database, continuing from
+    /// whatever is already on disk.
+    pub(crate) async fn resume(&self, record: &TorrentRecord) -> Result<()> {
+        self.add_torrent(&record.name, &record.source, record.output_dir.clone(), record.selected_files.clone(), Added::Resumed).await
+    }
+
+    /// Resume every torrent left unfinished by an earlier run. Returns how
+    /// many were resumed; failures are logged and skipped.
+    pub async fn resume_all(&self) -> Result<usize> {
+        let unfinished = self.state()?.unfinished_torrents()?;
+        let resumed = futures::future::join_all(unfinished.iter().map(|record| async move {
+            match self.resume(record).await {
+                Ok(()) => true,
+                Err(e) => {
+                    warn!("Couldn't resume {}: {}", record.name, e);
+                    false
+                }
+            }
+        })).await;
+        Ok(resumed.into_iter().filter(|ok| *ok).count())
+    }
+
+    /// Seed a finished download again, once its files check out: by size,
+    /// and with `daemon.verify_hashes` by hashing every piece while paused.
+    /// Returns what's wrong with those that don't; they stay out of the
+    /// session rather than seed, or re-download over, whatever is there now.
+    pub(crate) async fn reseed(&self, record: &TorrentRecord) -> Result<Option<String>> {
+        let organized = self.state()?.organized_files(&record.info_hash)?;
+        if let Some(problem) = check_files(record, &organized) {
+            return Ok(Some(problem));
+        }
+        // librqbit looks for files where the torrent put them
+        organize::link_back(&organized)?;
+        if !self.config.daemon.verify_hashes {
+            self.add_torrent(&record.name, &record.source, record.output_dir.clone(), record.selected_files.clone(), Added::Reseeding).await?;
+            return Ok(None);
+        }
+
+        // Paused, so librqbit checks the pieces on disk without fetching any
+        let (id, handle, tracked) = self.start(&record.source, &record.output_dir, record.selected_files.clone(), true).await?;
+        handle.wait_until_initialized().await?;
+        let verified = handle.stats().progress_bytes;
+        if verified < record.progress_bytes {
+            self.session.delete(TorrentIdOrHash::Id(id), false).await?;
+            return Ok(Some(format!(
+                "{} of {} failed the hash check",
+                storage::format_bytes(record.progress_bytes - verified),
+                storage::format_bytes(record.progress_bytes)
+            )));
+        }
+        self.session.unpause(&handle).await?;
+        let mut download = ManagedDownload::new(&record.name, &record.source, record.output_dir.clone(), record.selected_files.clone());
+        download.handle = Some(handle);
+        download.tracked = Some(tracked);
+        download.notified = true;
+        self.downloads.lock().unwrap().push(download);
+        Ok(None)
+    }
+
+    /// Seed every finished download again, flagging those whose files
+    /// don't check out. Returns how many are seeding and the records
+    /// newly flagged; a problem already flagged isn't reported twice.
+    pub(crate) async fn reseed_all(&self) -> Result<(usize, Vec<(TorrentRecord, String)>)> {
+        let finished = self.state()?.finished_torrents()?;
+        let outcomes = futures::future::join_all(finished.iter().map(|record| async move {
+            (record, self.reseed(record).await)
+        })).await;
+
+        let state = self.state()?;
+        let mut seeding = 0;
+        let mut flagged = Vec::new();
+        for (record, outcome) in outcomes {
+            match outcome {
+                Ok(None) => {
+                    seeding += 1;
+                    state.set_problem(&record.info_hash, None)?;
+                }
+                Ok(Some(problem)) => {
+                    state.set_problem(&record.info_hash, Some(&problem))?;
+                    if record.problem.as_ref() != Some(&problem) {
+                        flagged.push((record.clone(), problem));
+                    }
+                }
+                Err(e) => warn!("Couldn't seed {}: {}", record.name, e),
+            }
+        }
+        Ok((seeding, flagged))
+    }
+
+    async fn add_torrent(&self, label: &str, torrent: &str, output_dir: PathBuf, selected_files: Option<Vec<usize>>, added: Added) -> Result<()> {
+        let reseeding = added == Added::Reseeding;
+        let announced = !reseeding && added != Added::Resumed;
+        let index = {
+            let mut downloads = self.downloads.lock().unwrap();
+            let mut download = ManagedDownload::new(label, torrent, output_dir.clone(), selected_files.clone());
+            download.notified = reseeding;
+            download.unattended = added == Added::Unattended;
+            downloads.push(download);
+            downloads.len() - 1
+        };
+        if announced {
+            self.notifier.notify(DownloadEvent::Started { name: display_name(torrent) });
+        }
+
+        let (handle, tracked) = match self.start(torrent, &output_dir, selected_files, false).await {
+            Ok((_, handle, tracked)) => (handle, tracked),
+            Err(e) => {
+                self.fail(index, e.to_string());
+                return Err(e);
+            }
+        };
+
+        if announced {
+            self.notifier.notify(DownloadEvent::MetadataResolved {
+                name: torrent_name(&handle).unwrap_or_else(|| label.to_string()),
+                total_bytes: handle.stats().total_bytes,
+            });
+        }
+        let mut downloads = self.downloads.lock().unwrap();
+        downloads[index].handle = Some(handle);
+        downloads[index].tracked = Some(tracked);
+        Ok(())
+    }
+
+    /// Hand a torrent to librqbit, with the trackers its schedule picks,
+    /// which picks up whatever of it is already in `output_dir`.
+    async fn start(&self, torrent: &str, output_dir: &Path, selected_files: Option<Vec<usize>>, paused: bool) -> Result<(usize, Arc<ManagedTorrent>, Tracked)> {
+        let contents = read_torrent(torrent).await?;
+        let tracked = Tracked::new(contents, &self.config, &self.state()?)?;
+        let (id, handle) = self.add_tracked(&tracked, output_dir, selected_files, paused).await?;
+        Ok((id, handle, tracked))
+    }
+
+    async fn add_tracked(&self, tracked: &Tracked, output_dir: &Path, selected_files: Option<Vec<usize>>, paused: bool) -> Result<(usize, Arc<ManagedTorrent>)> {
+        let options = AddTorrentOptions {
+            output_folder: Some(output_dir.to_string_lossy().to_string()),
+            only_files: selected_files,
+            overwrite: true,
+            paused,
+            ..Default::default()
+        };
+
+        match self.session.add_torrent(tracked.add_torrent()?, Some(options)).await? {
+            librqbit::AddTorrentResponse::Added(id, handle)
+            | librqbit::AddTorrentResponse::AlreadyManaged(id, handle) => Ok((id, handle)),
+            librqbit::AddTorrentResponse::ListOnly(_) => Err(anyhow::anyhow!("Torrent was added in list-only mode")),
+        }
+    }
+
+    /// Restart the torrents [`sync_state`](Self::sync_state) saw drop a
+    /// tracker, without it; librqbit keeps announcing to the trackers it was
+    /// handed until a torrent is added again. Their files are kept and
+    /// checked again.
+    pub(crate) async fn retrack(&self) {
+        // Their trackers are taken out of the list, so it isn't locked across the restarts
+        let due: Vec<_> = self.downloads.lock().unwrap()
+            .iter_mut()
+            .filter(|download| download.retrack)
+            .filter_map(|download| {
+                download.retrack = false;
+                Some((download.handle.clone()?, download.tracked.take()?, download.output_dir.clone(), download.selected_files.clone()))
+            })
+            .collect();
+
+        for (old, tracked, output_dir, selected_files) in due {
+            let info_hash = old.info_hash();
+            info!("Restarting {} without the dropped tracker", torrent_name(&old).unwrap_or_else(|| info_hash.as_string()));
+            let uploaded = old.stats().uploaded_bytes;
+            // Still running as it was when it can't be taken out
+            let restarted = match self.session.delete(TorrentIdOrHash::Hash(info_hash), false).await {
+                Ok(()) => Some(self.add_tracked(&tracked, &output_dir, selected_files, false).await),
+                Err(e) => {
+                    warn!("Couldn't restart {} without the dropped tracker: {}", info_hash.as_string(), e);
+                    None
+                }
+            };
+
+            let mut downloads = self.downloads.lock().unwrap();
+            let Some(download) = downloads.iter_mut().find(|d| d.handle.as_ref().is_some_and(|h| Arc::ptr_eq(h, &old))) else {
+                continue;
+            };
+            download.tracked = Some(tracked);
+            match restarted {
+                Some(Ok((_, handle))) => {
+                    download.uploaded_before += uploaded;
+                    download.meter = SpeedMeter::default();
+                    download.handle = Some(handle);
+                }
+                Some(Err(e)) => {
+                    download.handle = None;
+                    download.error = Some(format!("Couldn't restart without the dropped tracker: {}", e));
+                }
+                None => {}
+            }
+        }
+    }
+
+    /// Take every torrent out of the session, leaving their files, so
+    /// another session can pick them up.
+    pub async fn stop_all(&self) -> Result<()> {
+        let handles: Vec<Arc<ManagedTorrent>> = self.downloads.lock().unwrap()
+            .drain(..)
+            .filter_map(|download| download.handle)
+            .collect();
+        for handle in handles {
+            self.session.delete(TorrentIdOrHash::Hash(handle.info_hash()), false).await?;
+        }
+        Ok(())
+    }
+
+    fn fail(&self, index: usize, error: String) {
+        let mut downloads = self.downloads.lock().unwrap();
+        let download = &mut downloads[index];
+        download.error = Some(error.clone());
+        download.notified = true;
+        self.notifier.notify(DownloadEvent::Failed { name: download.label.clone(), error });
+    }
+
+    pub fn progress(&self) -> Vec<DownloadProgress> {
+        const MIB: f64 = 1024.0 * 1024.0;
+        self.downloads.lock().unwrap()
+            .iter_mut()
+            .map(|download| match &download.handle {
+                Some(handle) => {
+                    let stats = handle.stats();
+                    let live = stats.live.as_ref();
+                    if let Some(live) = live {
+                        download.meter.sample(live.snapshot.fetched_bytes, live.snapshot.uploaded_bytes);
+                    }
+                    let (download_rate, upload_rate) = download.meter.rates();
+                    DownloadProgress {
+                        info_hash: Some(handle.info_hash().as_string()),
+                        name: torrent_name(handle).unwrap_or_else(|| download.label.clone()),
+                        progress_bytes: stats.progress_bytes,
+                        total_bytes: stats.total_bytes,
+                        finished: stats.finished,
+                        download_mbps: if stats.finished { 0.0 } else { download_rate / MIB },
+                        upload_mbps: upload_rate / MIB,
+                        peers: live.map(|l| l.snapshot.peer_stats.live).unwrap_or(0),
+                        peers_seen: live.map(|l| l.snapshot.peer_stats.seen).unwrap_or(0),
+                        eta_secs: download.meter
+                            .eta(stats.total_bytes.saturating_sub(stats.progress_bytes))
+                            .filter(|_| !stats.finished)
+                            .map(|eta| eta.as_secs()),
+                        pending: false,
+                        error: stats.error.clone(),
+                    }
+                }
+                None => DownloadProgress {
+                    info_hash: trackers::magnet_info_hash(&download.source),
+                    name: download.label.clone(),
+                    progress_bytes: 0,
+                    total_bytes: 0,
+                    finished: false,
+                    download_mbps: 0.0,
+                    upload_mbps: 0.0,
+                    peers: 0,
+                    peers_seen: 0,
+                    eta_secs: None,
+                    pending: download.error.is_none(),
+                    error: download.error.clone(),
+                },
+            })
+            .collect()
+    }
+
+    /// Persist current progress so `status` and `list` see these downloads.
+    pub(crate) fn sync_state(&self, state: &StateDb) -> Result<()> {
+        let mut downloads = self.downloads.lock().unwrap();
+        for download in downloads.iter_mut() {
+            let Some(handle) = &download.handle else {
+                continue;
+            };
+            let stats = handle.stats();
+            let info_hash = handle.info_hash().as_string();
+            if let Some(live) = &stats.live {
+                download.meter.sample(live.snapshot.fetched_bytes, live.snapshot.uploaded_bytes);
+            }
+
+            if !download.recorded {
+                let disk_root = storage::root_for_path(&self.config.download.roots, &download.output_dir)
+                    .map(|root| root.path.clone())
+                    .unwrap_or_else(|| download.output_dir.clone());
+                let now = Utc::now().to_rfc3339();
+                state.upsert_torrent(&TorrentRecord {
+                    info_hash: info_hash.clone(),
+                    name: torrent_name(handle).unwrap_or_else(|| download.label.clone()),
+                    source: download.source.clone(),
+                    output_dir: download.output_dir.clone(),
+                    disk_root,
+                    status: TorrentStatus::Downloading,
+                    total_bytes: stats.total_bytes,
+                    progress_bytes: stats.progress_bytes,
+                    uploaded_bytes: 0,
+                    problem: None,
+                    selected_files: download.selected_files.clone(),
+                    content_type: classify_handle(handle),
+                    added_at: now.clone(),
+                    updated_at: now,
+                })?;
+                download.uploaded_before = state.uploaded_before(&info_hash)?;
+                download.recorded = true;
+            }
+
+            let status = if stats.error.is_some() {
+                TorrentStatus::Failed
+            } else if stats.finished {
+                TorrentStatus::Completed
+            } else {
+                TorrentStatus::Downloading
+            };
+            state.update_progress(&info_hash, status, stats.progress_bytes, download.uploaded_before + stats.uploaded_bytes)?;
+            if let Some(schedule) = download.tracked.as_mut().and_then(|tracked| tracked.trackers.as_mut()) {
+                download.retrack |= schedule.update(state)?;
+            }
+
+            if !download.notified && status != TorrentStatus::Downloading {
+                let name = torrent_name(handle).unwrap_or_else(|| download.label.clone());
+                let notifier = if download.unattended { &self.unattended_notifier } else { &self.notifier };
+                notifier.notify(match &stats.error {
+                    Some(error) => DownloadEvent::Failed { name, error: error.clone() },
+                    None => DownloadEvent::Completed { name, output_dir: download.output_dir.clone() },
+                });
+                download.notified = true;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// What's wrong with a finished download's files, going by size alone:
+/// gone, or smaller than what was downloaded. Files organized into the
+/// library are looked for there. Extra files, like subtitles fetched
+/// afterwards, are fine.
+fn check_files(record: &TorrentRecord, organized: &[OrganizedFile]) -> Option<String> {
+    let mut found = 0;
+    for file in organized {
+        match file.library.metadata() {
+            Ok(meta) => found += meta.len(),
+            Err(_) => return Some(format!("files moved or deleted; {} is gone", file.library.display())),
+        }
+    }
+    let path = record.output_dir.join(&record.name);
+    match organize::collect_files(&path) {
+        // Organized files linked back for seeding were counted in the library
+        Ok(files) => found += files.iter()
+            .filter(|f| !organized.iter().any(|o| &o.original == *f))
+            .filter_map(|f| f.metadata().ok())
+            .map(|m| m.len())
+            .sum::<u64>(),
+        // Everything may have been organized, leaving nothing behind
+        Err(_) if organized.is_empty() => return Some(format!("files moved or deleted; {} is gone", path.display())),
+        Err(_) => {}
+    }
+    if found < record.progress_bytes {
+        return Some(format!(
+            "files truncated or replaced; {} on disk of {}",
+            storage::format_bytes(found),
+            storage::format_bytes(record.progress_bytes)
+        ));
+    }
+    None
+}
+
+fn torrent_name(handle: &Arc<ManagedTorrent>) -> Option<String> {
+    handle.with_metadata(|meta| meta.info.name.as_ref().map(|n| n.to_string()))
+        .ok()
+        .flatten()
+}
+
+/// A heuristic guess at what the torrent holds, once its metadata is known.
+/// Searches record their intent over this; `list --classify` can ask the
+/// LLM about guesses that aren't confident.
+fn classify_handle(handle: &Arc<ManagedTorrent>) -> Option<ContentType> {
+    let files: Vec<(PathBuf, u64)> = handle.with_metadata(|meta| {
+        meta.file_infos.iter().map(|f| (f.relative_filename.clone(), f.len)).collect()
+    }).ok()?;
+    let classification = classify::classify(&torrent_name(handle)?, &files);
+    classification.confident.then_some(classification.content_type)
+}
@@ -0,0 +1,134 @@
+use anyhow::{Context, Result};
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+use crate::models::EvaluatedResult;
+use crate::pirate_bay_scraper::TorrentResult;
+use crate::trackers;
+
+/// Bumped when the file layout changes incompatibly.
+const FORMAT_VERSION: u32 = 1;
+
+/// Search results saved with `--export`, to browse or download elsewhere
+/// with `torrentai import`.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ResultSet {
+    pub version: u32,
+    pub query: String,
+    pub exported_at: String,
+    pub results: Vec<ExportedResult>,
+}
+
+/// A smart search result keeps its evaluation; plain searches have none.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum ExportedResult {
+    Evaluated(EvaluatedResult),
+    Plain(TorrentResult),
+}
+
+impl ExportedResult {
+    pub fn torrent(&self) -> &TorrentResult {
+        match self {
+            ExportedResult::Evaluated(result) => &result.torrent,
+            ExportedResult::Plain(torrent) => torrent,
+        }
+    }
+
+    pub fn relevance_score(&self) -> Option<f32> {
+        match self {
+            ExportedResult::Evaluated(result) => Some(result.relevance_score),
+            ExportedResult::Plain(_) => None,
+        }
+    }
+}
+
+impl ResultSet {
+    pub fn new(query: &str, results: Vec<ExportedResult>) -> Self {
+        Self {
+            version: FORMAT_VERSION,
+            query: query.to_string(),
+            exported_at: Utc::now().to_rfc3339(),
+            results,
+        }
+    }
+
+    pub fn plain(query: &str, results: &[TorrentResult]) -> Self {
+        Self::new(query, results.iter().cloned().map(ExportedResult::Plain).collect())
+    }
+
+    pub fn evaluated(query: &str, results: &[EvaluatedResult]) -> Self {
+        Self::new(query, results.iter().cloned().map(ExportedResult::Evaluated).collect())
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        std::fs::write(path, serde_json::to_string_pretty(self)?)
+            .with_context(|| format!("Could not write {}", path.display()))
+    }
+
+    /// Read an exported result set, or a plain list of magnet links, one per
+    /// line, as collected anywhere else.
+    pub fn load(path: &Path) -> Result<Self> {
+        let content = std::fs::read_to_string(path)
+            .with_context(|| format!("Could not read {}", path.display()))?;
+        if content.trim_start().starts_with('{') {
+            let set: ResultSet = serde_json::from_str(&content)
+                .with_context(|| format!("{} is not an exported result set", path.display()))?;
+            if set.version > FORMAT_VERSION {
+                return Err(anyhow::anyhow!(
+                    "{} was exported by a newer torrentai (format {}); upgrade to import it",
+                    path.display(), set.version
+                ));
+            }
+            return Ok(set);
+        }
+
+        let results: Vec<ExportedResult> = content.lines()
+            .map(str::trim)
+            .filter(|line| line.starts_with("magnet:"))
+            .map(|magnet| ExportedResult::Plain(TorrentResult {
+                title: trackers::magnet_display_name(magnet)
+                    .or_else(|| trackers::magnet_info_hash(magnet))
+                    .unwrap_or_else(|| magnet.to_string()),
+                magnet_link: magnet.to_string(),
+                size: None,
+                seeders: None,
+                leechers: None,
+                uploaded: None,
+                category: None,
+                uploader: None,
+                found_by: Vec::new(),
+            }))
+            .collect();
+        if results.is_empty() {
+            return Err(anyhow::anyhow!("{} has neither exported results nor magnet links", path.display()));
+        }
+        Ok(Self::new(&path.display().to_string(), results))
+    }
+}
+
+/// Parse picks like "all", "3" or "1,4-6" into zero-based indices below `len`.
+pub fn parse_picks(picks: &str, len: usize) -> Result<Vec<usize>> {
+    if picks.trim().eq_ignore_ascii_case("all") {
+        return Ok((0..len).collect());
+    }
+    let number = |n: &str| n.trim().parse::<usize>()
+        .with_context(|| format!("\"{}\" is not a result number", n.trim()));
+    let mut indices = Vec::new();
+    for part in picks.split(',').map(str::trim).filter(|p| !p.is_empty()) {
+        let (first, last) = match part.split_once('-') {
+            Some((first, last)) => (number(first)?, number(last)?),
+            None => (number(part)?, number(part)?),
+        };
+        if first == 0 || first > last || last > len {
+            return Err(anyhow::anyhow!("\"{}\" is out of range; there are {} results", part, len));
+        }
+        for index in first - 1..last {
+            if !indices.contains(&index) {
+                indices.push(index);
+            }
+        }
+    }
+    Ok(indices)
+}
@@ -0,0 +1,293 @@
+use anyhow::Result;
+use chrono::Utc;
+use std::collections::{HashMap, HashSet};
+use std::time::Duration;
+use tracing::{info, warn};
+
+use crate::pirate_bay_scraper::TorrentResult;
+use crate::safety;
+use crate::secrets::SecretStore;
+use crate::smart_search::SearchService;
+use crate::state::{Feed, StateDb};
+use crate::storage::format_bytes;
+
+const FETCH_TIMEOUT: Duration = Duration::from_secs(30);
+/// Query parameters that carry an indexer's API key or passkey.
+const KEY_PARAMS: &[&str] = &["apikey", "api_key", "passkey", "key", "token"];
+
+/// One item from a feed, keyed by whatever the feed uses to tell items apart.
+#[derive(Debug, Clone)]
+pub struct FeedItem {
+    pub guid: String,
+    pub result: TorrentResult,
+}
+
+/// Fetch the feed and return its new items worth downloading: every new
+/// item, or with a filter, those that match it. Items passed over here are
+/// remembered so each is considered once; those returned are only
+/// remembered once the caller has queued them with [`mark_handled`], so a
+/// poll that fails partway tries them again. A feed's first poll only takes
+/// note of what's there unless it was added to grab existing items.
+pub async fn poll(feed: &Feed, searcher: &impl SearchService) -> Result<Vec<FeedItem>> {
+    let items = match fetch(&fetch_url(feed)?).await {
+        Ok(items) => items,
+        Err(e) => {
+            StateDb::open()?.mark_feed_polled(feed.id, Some(&e.to_string()))?;
+            return Err(e);
+        }
+    };
+
+    let first_poll = feed.last_polled.is_none();
+    let new: Vec<FeedItem> = {
+        let state = StateDb::open()?;
+        let mut guids = HashSet::new();
+        let mut new = Vec::new();
+        for item in items {
+            if guids.insert(item.guid.clone()) && !state.feed_item_seen(feed.id, &item.guid)? {
+                new.push(item);
+            }
+        }
+        state.mark_feed_polled(feed.id, None)?;
+        if first_poll && !feed.grab_existing {
+            for item in &new {
+                mark_handled(&state, feed, item)?;
+            }
+            info!("Feed #{}: noted {} existing item(s); only new ones will be grabbed", feed.id, new.len());
+            return Ok(Vec::new());
+        }
+
+        // Feeds download unattended, so red flags rule an item out
+        let mut safe = Vec::new();
+        for item in new {
+            if safety::is_safe(&item.result) {
+                safe.push(item);
+            } else {
+                warn!("Feed #{}: skipping {}, it has red flags", feed.id, item.result.title);
+                mark_handled(&state, feed, &item)?;
+            }
+        }
+        safe
+    };
+
+    let Some(filter) = feed.filter.as_ref().filter(|_| !new.is_empty()) else {
+        return Ok(new);
+    };
+    let matched: HashSet<String> = searcher.match_results(filter, new.iter().map(|item| item.result.clone()).collect()).await?
+        .into_iter()
+        .map(|evaluated| evaluated.torrent.magnet_link)
+        .collect();
+    let (grabs, passed_over): (Vec<FeedItem>, Vec<FeedItem>) = new.into_iter()
+        .partition(|item| matched.contains(&item.result.magnet_link));
+    let state = StateDb::open()?;
+    for item in &passed_over {
+        mark_handled(&state, feed, item)?;
+    }
+    Ok(grabs)
+}
+
+/// Move the API key in a feed's URL, if there is one, into the secret
+/// store. Returns the URL without it, the parameter it goes back into and
+/// the secret's name, which are all the state database should keep.
+pub fn store_key(url: &str) -> Result<Option<(String, String, String)>> {
+    let Some((stripped, param, key)) = split_key(url) else {
+        return Ok(None);
+    };
+    let secret = format!("feed_{}_{}", param.to_lowercase(), Utc::now().timestamp_millis());
+    SecretStore::open()?.set(&secret, &key)?;
+    Ok(Some((stripped, param, secret)))
+}
+
+/// The URL to fetch, with the API key spliced back in from the secret store.
+fn fetch_url(feed: &Feed) -> Result<String> {
+    let (Some(param), Some(secret)) = (&feed.key_param, &feed.key_secret) else {
+        // Added before keys were kept as secrets, so the key is still in the URL
+        if let Some((stripped, param, secret)) = store_key(&feed.url)? {
+            StateDb::open()?.set_feed_key(feed.id, &stripped, &param, &secret)?;
+        }
+        return Ok(feed.url.clone());
+    };
+    let mut url = reqwest::Url::parse(&feed.url)?;
+    url.query_pairs_mut().append_pair(param, SecretStore::open()?.require(secret)?);
+    Ok(url.to_string())
+}
+
+/// The URL without its API key, the parameter the key was in, and the key.
+fn split_key(url: &str) -> Option<(String, String, String)> {
+    let mut parsed = reqwest::Url::parse(url).ok()?;
+    let (mut key, mut rest) = (None, Vec::new());
+    for (name, value) in parsed.query_pairs() {
+        if key.is_none() && KEY_PARAMS.contains(&name.to_lowercase().as_str()) {
+            key = Some((name.into_owned(), value.into_owned()));
+        } else {
+            rest.push((name.into_owned(), value.into_owned()));
+        }
+    }
+    let (param, key) = key?;
+    if rest.is_empty() {
+        parsed.set_query(None);
+    } else {
+        parsed.query_pairs_mut().clear().extend_pairs(rest);
+    }
+    Some((parsed.to_string(), param, key))
+}
+
+/// Remember a feed item as dealt with, so later polls skip it.
+pub fn mark_handled(state: &StateDb, feed: &Feed, item: &FeedItem) -> Result<()> {
+    state.mark_feed_seen(feed.id, &item.guid)?;
+    Ok(())
+}
+
+pub async fn fetch(url: &str) -> Result<Vec<FeedItem>> {
+    let client = reqwest::Client::builder().timeout(FETCH_TIMEOUT).build()?;
+    let body = client.get(url).send().await?.error_for_status()?.text().await?;
+    parse(&body)
+}
+
+/// Read the items of an RSS 2.0 feed, including Torznab's `torznab:attr`
+/// fields and per-site extensions such as `nyaa:seeders`.
+pub fn parse(xml: &str) -> Result<Vec<FeedItem>> {
+    let doc = roxmltree::Document::parse(xml)
+        .map_err(|e| anyhow::anyhow!("Not a valid RSS feed: {}", e))?;
+    if !doc.root_element().has_tag_name("rss") {
+        return Err(anyhow::anyhow!("Not an RSS feed (root element is <{}>)", doc.root_element().tag_name().name()));
+    }
+
+    let items = doc.descendants()
+        .filter(|node| node.has_tag_name("item"))
+        .filter_map(|item| parse_item(&item))
+        .collect();
+    Ok(items)
+}
+
+fn parse_item(item: &roxmltree::Node) -> Option<FeedItem> {
+    // Plain children and extension elements by local name, with Torznab's
+    // name/value attributes folded in; the first occurrence wins
+    let mut fields: HashMap<String, String> = HashMap::new();
+    for child in item.children().filter(|node| node.is_element()) {
+        let name = child.tag_name().name();
+        let (key, value) = match (name, child.attribute("name"), child.attribute("value")) {
+            ("attr", Some(key), Some(value)) => (key.to_lowercase(), value.to_string()),
+            ("attr", ..) => continue,
+            _ => (name.to_lowercase(), child.text().unwrap_or_default().trim().to_string()),
+        };
+        fields.entry(key).or_insert(value);
+    }
+    let enclosure = item.children().find(|node| node.has_tag_name("enclosure"));
+    let enclosure_url = enclosure.and_then(|e| e.attribute("url"));
+
+    let title = fields.get("title").filter(|t| !t.is_empty())?.clone();
+    let link = fields.get("link").map(String::as_str);
+    let info_hash = fields.get("infohash").filter(|h| !h.is_empty());
+
+    // A magnet anywhere beats a .torrent URL, which may need the feed's cookies
+    let magnet_link = fields.get("magneturl").map(String::as_str)
+        .into_iter()
+        .chain(enclosure_url)
+        .chain(link)
+        .find(|url| url.starts_with("magnet:"))
+        .map(str::to_string)
+        .or_else(|| info_hash.map(|hash| format!("magnet:?xt=urn:btih:{}&dn={}", hash, urlencoding::encode(&title))))
+        .or_else(|| enclosure_url.or(link).map(str::to_string))?;
+
+    let size = match fields.get("size").or(fields.get("contentlength")) {
+        Some(size) => Some(size.parse::<u64>().map(format_bytes).unwrap_or_else(|_| size.clone())),
+        None => enclosure.and_then(|e| e.attribute("length")?.parse::<u64>().ok())
+            .filter(|&bytes| bytes > 0)
+            .map(format_bytes),
+    };
+    let seeders = fields.get("seeders").and_then(|s| s.parse::<u32>().ok());
+    // Torznab's peers include the seeders
+    let leechers = fields.get("leechers").and_then(|s| s.parse().ok())
+        .or_else(|| Some(fields.get("peers")?.parse::<u32>().ok()?.saturating_sub(seeders.unwrap_or(0))));
+
+    let guid = fields.get("guid").filter(|g| !g.is_empty()).cloned()
+        .or_else(|| info_hash.cloned())
+        .unwrap_or_else(|| magnet_link.clone());
+
+    Some(FeedItem {
+        guid,
+        result: TorrentResult {
+            title,
+            magnet_link,
+            size,
+            seeders,
+            leechers,
+            uploaded: fields.get("pubdate").cloned(),
+            category: fields.get("category").filter(|c| c.parse::<u32>().is_err()).cloned(),
+            uploader: fields.get("author").or(fields.get("creator")).or(fields.get("uploader"))
+                .filter(|u| !u.is_empty())
+                .cloned(),
+            found_by: Vec::new(),
+        },
+    })
+}
+
+/// The feed URL with any API key hidden, for display.
+pub fn redact(url: &str) -> String {
+    let Ok(mut parsed) = reqwest::Url::parse(url) else {
+        return url.to_string();
+    };
+    let pairs: Vec<(String, String)> = parsed.query_pairs()
+        .map(|(key, value)| {
+            let hidden = KEY_PARAMS.contains(&key.to_lowercase().as_str());
+            (key.into_owned(), if hidden { "***".to_string() } else { value.into_owned() })
+        })
+        .collect();
+    if !pairs.is_empty() {
+        parsed.query_pairs_mut().clear().extend_pairs(pairs);
+    }
+    parsed.to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn split_key_takes_the_key_out_of_the_query() {
+        assert_eq!(
+            split_key("https://indexer.example/rss?t=search&apikey=abc123&cat=2000"),
+            Some((
+                "https://indexer.example/rss?t=search&cat=2000".to_string(),
+                "apikey".to_string(),
+                "abc123".to_string(),
+            ))
+        );
+        assert_eq!(
+            split_key("https://tracker.example/rss.php?passkey=0f1e2d"),
+            Some(("https://tracker.example/rss.php".to_string(), "passkey".to_string(), "0f1e2d".to_string()))
+        );
+        // The parameter keeps its case, since it's spliced back as is
+        assert_eq!(split_key("https://indexer.example/api?ApiKey=k").map(|(_, param, _)| param), Some("ApiKey".to_string()));
+    }
+
+    #[test]
+    fn split_key_leaves_urls_without_a_key_alone() {
+        assert_eq!(split_key("https://showrss.info/user/1234.rss?magnets=true"), None);
+        assert_eq!(split_key("https://showrss.info/user/1234.rss"), None);
+        assert_eq!(split_key("not a url"), None);
+    }
+
+    #[test]
+    fn split_key_decodes_the_key() {
+        let (url, _, key) = split_key("https://indexer.example/rss?token=a%2Bb%3D&q=the%20office").unwrap();
+        assert_eq!(key, "a+b=");
+        assert_eq!(url, "https://indexer.example/rss?q=the+office");
+    }
+
+    #[test]
+    fn redact_hides_every_key() {
+        assert_eq!(
+            redact("https://indexer.example/rss?t=search&apikey=abc123&passkey=0f1e2d"),
+            "https://indexer.example/rss?t=search&apikey=***&passkey=***"
+        );
+        assert_eq!(redact("https://indexer.example/rss?API_KEY=abc123"), "https://indexer.example/rss?API_KEY=***");
+    }
+
+    #[test]
+    fn redact_leaves_the_rest_as_is() {
+        assert_eq!(redact("https://showrss.info/user/1234.rss"), "https://showrss.info/user/1234.rss");
+        assert_eq!(redact("https://showrss.info/user/1234.rss?magnets=true"), "https://showrss.info/user/1234.rss?magnets=true");
+        assert_eq!(redact("not a url"), "not a url");
+    }
+}
@@ -0,0 +1,294 @@
+use chrono::Duration;
+use clap::{Args, ValueEnum};
+use std::cmp::Reverse;
+
+use crate::pirate_bay_scraper::TorrentResult;
+use crate::size;
+use crate::uploaded;
+
+/// Hard constraints on raw search results. A result missing the field a
+/// constraint looks at is excluded, since it can't be shown to satisfy it.
+#[derive(Debug, Clone, Default, Args)]
+pub struct ResultFilter {
+    /// Drop results with fewer seeders
+    #[arg(long)]
+    pub min_seeders: Option<u32>,
+
+    /// Drop smaller results, e.g. 700MB or 1.5GB
+    #[arg(long, value_parser = parse_size_arg)]
+    pub min_size: Option<u64>,
+
+    /// Drop larger results, e.g. 4GB
+    #[arg(long, value_parser = parse_size_arg)]
+    pub max_size: Option<u64>,
+
+    /// Keep results whose category contains this, e.g. "movies" or "audio"
+    #[arg(long)]
+    pub category: Option<String>,
+
+    /// Drop results uploaded longer ago than this, e.g. 12h, 30d, 2w or 1y
+    #[arg(long, value_parser = uploaded::parse_age_arg)]
+    pub uploaded_within: Option<Duration>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum SortKey {
+    /// Most seeders first
+    Seeders,
+    /// Largest first
+    Size,
+    /// Newest first
+    Date,
+}
+
+/// How to order and trim results for display.
+#[derive(Debug, Clone, Default, Args)]
+pub struct ResultOrder {
+    /// Sort results; unknown values go last
+    #[arg(long, value_enum)]
+    pub sort: Option<SortKey>,
+
+    /// Show at most this many results
+    #[arg(long)]
+    pub limit: Option<usize>,
+}
+
+impl ResultFilter {
+    pub fn is_empty(&self) -> bool {
+        self.min_seeders.is_none() && self.min_size.is_none() && self.max_size.is_none() && self.category.is_none()
+            && self.uploaded_within.is_none()
+    }
+
+    pub fn matches(&self, result: &TorrentResult) -> bool {
+        if let Some(min) = self.min_seeders {
+            if result.seeders.is_none_or(|seeders| seeders < min) {
+                return false;
+            }
+        }
+
+        if self.min_size.is_some() || self.max_size.is_some() {
+            let Some(bytes) = result.size_bytes() else {
+                return false;
+            };
+            if self.min_size.is_some_and(|min| bytes < min) || self.max_size.is_some_and(|max| bytes > max) {
+                return false;
+            }
+        }
+
+        if let Some(wanted) = &self.category {
+            let wanted = wanted.to_lowercase();
+            if result.category.as_deref().is_none_or(|category| !category.to_lowercase().contains(&wanted)) {
+                return false;
+            }
+        }
+
+        if let Some(within) = self.uploaded_within {
+            if result.uploaded_at().is_none_or(|at| uploaded::age(at) > within) {
+                return false;
+            }
+        }
+
+        true
+    }
+
+    pub fn apply(&self, results: Vec<TorrentResult>) -> Vec<TorrentResult> {
+        results.into_iter().filter(|r| self.matches(r)).collect()
+    }
+
+    /// The active constraints, for explaining why results went missing.
+    pub fn describe(&self) -> String {
+        let mut parts = Vec::new();
+        if let Some(min) = self.min_seeders {
+            parts.push(format!("at least {} seeders", min));
+        }
+        if let Some(min) = self.min_size {
+            parts.push(format!("at least {}", crate::storage::format_bytes(min)));
+        }
+        if let Some(max) = self.max_size {
+            parts.push(format!("at most {}", crate::storage::format_bytes(max)));
+        }
+        if let Some(category) = &self.category {
+            parts.push(format!("category \"{}\"", category));
+        }
+        if let Some(within) = self.uploaded_within {
+            parts.push(match within.num_days() {
+                0 => format!("uploaded in the last {} hours", within.num_hours()),
+                days => format!("uploaded in the last {} days", days),
+            });
+        }
+        parts.join(", ")
+    }
+
+    /// What to say about `hidden` results left out by these constraints or
+    /// `--limit`; nothing when none were.
+    pub fn hidden_note(&self, hidden: usize) -> Option<String> {
+        match hidden {
+            0 => None,
+            _ if self.is_empty() => Some(format!("({} hidden by --limit)", hidden)),
+            _ => Some(format!("({} hidden by filters or --limit: {})", hidden, self.describe())),
+        }
+    }
+}
+
+impl ResultOrder {
+    pub fn apply(&self, mut results: Vec<TorrentResult>) -> Vec<TorrentResult> {
+        // Largest first, with unknown values after every known one
+        match self.sort {
+            Some(SortKey::Seeders) => results.sort_by_key(|r| (r.seeders.is_none(), Reverse(r.seeders))),
+            Some(SortKey::Size) => results.sort_by_key(|r| {
+                let bytes = r.size_bytes();
+                (bytes.is_none(), Reverse(bytes))
+            }),
+            Some(SortKey::Date) => results.sort_by_key(|r| {
+                let uploaded = r.uploaded_at();
+                (uploaded.is_none(), Reverse(uploaded))
+            }),
+            None => {}
+        }
+        if let Some(limit) = self.limit {
+            results.truncate(limit);
+        }
+        results
+    }
+}
+
+fn parse_size_arg(value: &str) -> Result<u64, String> {
+    // Bare units like "4G" parse as well as "4GB" and "4 GiB"
+    size::parse_bytes(value).ok_or_else(|| format!("invalid size \"{}\" (try 700MB or 4GB)", value))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+
+    fn result(title: &str, seeders: Option<u32>, size: Option<&str>, category: Option<&str>, uploaded: Option<&str>) -> TorrentResult {
+        TorrentResult {
+            title: title.to_string(),
+            magnet_link: format!("magnet:?dn={}", title),
+            size: size.map(str::to_string),
+            seeders,
+            leechers: None,
+            uploaded: uploaded.map(str::to_string),
+            category: category.map(str::to_string),
+            uploader: None,
+            found_by: Vec::new(),
+        }
+    }
+
+    fn titles(results: &[TorrentResult]) -> Vec<&str> {
+        results.iter().map(|r| r.title.as_str()).collect()
+    }
+
+    fn days_ago(days: i64) -> String {
+        (Utc::now() - Duration::days(days)).format("%Y-%m-%d").to_string()
+    }
+
+    #[test]
+    fn min_seeders() {
+        let filter = ResultFilter { min_seeders: Some(10), ..Default::default() };
+        let results = vec![
+            result("many", Some(50), None, None, None),
+            result("exactly", Some(10), None, None, None),
+            result("few", Some(9), None, None, None),
+            result("unknown", None, None, None, None),
+        ];
+        assert_eq!(titles(&filter.apply(results)), ["many", "exactly"]);
+    }
+
+    #[test]
+    fn size_bounds() {
+        let filter = ResultFilter { min_size: Some(700 * 1024 * 1024), max_size: Some(4 * 1024 * 1024 * 1024), ..Default::default() };
+        let results = vec![
+            result("small", None, Some("350 MiB"), None, None),
+            result("fits", None, Some("1.5 GiB"), None, None),
+            result("large", None, Some("12 GiB"), None, None),
+            result("unknown", None, None, None, None),
+        ];
+        assert_eq!(titles(&filter.apply(results)), ["fits"]);
+    }
+
+    #[test]
+    fn category_ignores_case() {
+        let filter = ResultFilter { category: Some("movies".to_string()), ..Default::default() };
+        let results = vec![
+            result("hd", None, None, Some("Video > HD - Movies"), None),
+            result("music", None, None, Some("Audio > Music"), None),
+            result("unknown", None, None, None, None),
+        ];
+        assert_eq!(titles(&filter.apply(results)), ["hd"]);
+    }
+
+    #[test]
+    fn uploaded_within() {
+        let filter = ResultFilter { uploaded_within: Some(Duration::days(7)), ..Default::default() };
+        let results = vec![
+            result("recent", None, None, None, Some(&days_ago(2))),
+            result("old", None, None, None, Some("05-11 2013")),
+            result("unknown", None, None, None, None),
+        ];
+        assert_eq!(titles(&filter.apply(results)), ["recent"]);
+    }
+
+    #[test]
+    fn every_constraint_must_hold() {
+        let filter = ResultFilter { min_seeders: Some(5), category: Some("audio".to_string()), ..Default::default() };
+        let results = vec![
+            result("both", Some(8), None, Some("Audio > FLAC"), None),
+            result("seeded", Some(8), None, Some("Video"), None),
+            result("audio", Some(1), None, Some("Audio"), None),
+        ];
+        assert_eq!(titles(&filter.apply(results)), ["both"]);
+        assert!(!filter.is_empty());
+        assert!(ResultFilter::default().is_empty());
+    }
+
+    #[test]
+    fn order_puts_unknown_values_last() {
+        let results = || vec![
+            result("a", Some(5), Some("2 GiB"), None, Some("05-11 2013")),
+            result("b", None, None, None, None),
+            result("c", Some(50), Some("700 MiB"), None, Some(&days_ago(1))),
+            result("d", Some(0), Some("8 GiB"), None, Some(&days_ago(30))),
+        ];
+        let sorted = |sort| titles(&ResultOrder { sort: Some(sort), limit: None }.apply(results())).join("");
+        assert_eq!(sorted(SortKey::Seeders), "cadb");
+        assert_eq!(sorted(SortKey::Size), "dacb");
+        assert_eq!(sorted(SortKey::Date), "cdab");
+        assert_eq!(titles(&ResultOrder::default().apply(results())), ["a", "b", "c", "d"]);
+    }
+
+    #[test]
+    fn limit_applies_after_sorting() {
+        let results = vec![
+            result("few", Some(1), None, None, None),
+            result("most", Some(90), None, None, None),
+            result("some", Some(20), None, None, None),
+        ];
+        let order = ResultOrder { sort: Some(SortKey::Seeders), limit: Some(2) };
+        assert_eq!(titles(&order.apply(results.clone())), ["most", "some"]);
+        let unsorted = ResultOrder { sort: None, limit: Some(5) };
+        assert_eq!(unsorted.apply(results).len(), 3);
+    }
+
+    #[test]
+    fn hidden_results_are_counted() {
+        let found = vec![
+            result("a", Some(40), None, None, None),
+            result("b", Some(30), None, None, None),
+            result("c", Some(20), None, None, None),
+            result("d", Some(1), None, None, None),
+            result("e", None, None, None, None),
+        ];
+        let filter = ResultFilter { min_seeders: Some(10), ..Default::default() };
+        let order = ResultOrder { sort: None, limit: Some(2) };
+        let shown = order.apply(filter.apply(found.clone()));
+        let hidden = found.len() - shown.len();
+        assert_eq!(hidden, 3);
+        assert_eq!(filter.hidden_note(hidden).as_deref(), Some("(3 hidden by filters or --limit: at least 10 seeders)"));
+
+        let unfiltered = ResultFilter::default();
+        assert_eq!(unfiltered.hidden_note(2).as_deref(), Some("(2 hidden by --limit)"));
+        assert_eq!(filter.hidden_note(0), None);
+    }
+}
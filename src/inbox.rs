@@ -0,0 +1,104 @@
+use anyhow::Result;
+use std::fmt;
+use std::path::PathBuf;
+
+use crate::config::Config;
+use crate::downloader;
+use crate::models::EvaluatedResult;
+use crate::state::{RequestRecord, RequestStatus, StateDb};
+
+// Guests can't download, so they file requests instead. Each request keeps the
+// parsed intent and the best result found when it was made, so an admin can
+// review exactly what would be downloaded and approve it in one step.
+
+/// Why a request can't be approved or rejected.
+#[derive(Debug)]
+pub enum Undecidable {
+    Missing(i64),
+    AlreadyDecided(i64, RequestStatus),
+    /// Nothing matched when it was filed, so approving has nothing to download
+    NoResult(i64, String),
+}
+
+impl fmt::Display for Undecidable {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Undecidable::Missing(id) => write!(f, "No request #{}", id),
+            Undecidable::AlreadyDecided(id, status) => write!(f, "Request #{} was already {}", id, status.as_str()),
+            Undecidable::NoResult(id, query) => write!(
+                f,
+                "Request #{} has no matching result to download; search for \"{}\" and download manually",
+                id, query
+            ),
+        }
+    }
+}
+
+impl std::error::Error for Undecidable {}
+
+/// The request and the result approving it would download. Requests whose
+/// download failed can be approved again.
+pub fn approvable(state: &StateDb, id: i64) -> Result<(RequestRecord, EvaluatedResult)> {
+    let request = state.request(id)?.ok_or(Undecidable::Missing(id))?;
+    if !is_open(request.status) {
+        return Err(Undecidable::AlreadyDecided(id, request.status).into());
+    }
+
+    let result = request.top_result.clone()
+        .ok_or_else(|| Undecidable::NoResult(id, request.query.clone()))?;
+    Ok((request, result))
+}
+
+/// Mark an approvable request approved before its download starts, so two
+/// admins approving at once can't both download it.
+pub fn claim(state: &StateDb, id: i64) -> Result<()> {
+    if !state.decide_open_request(id, RequestStatus::Approved, None)? {
+        return Err(already_decided(state, id));
+    }
+    Ok(())
+}
+
+/// Mark a claimed request failed when its download didn't work out, so it
+/// can be approved again.
+pub fn record_failure(state: &StateDb, id: i64, error: &anyhow::Error) -> Result<()> {
+    state.decide_request(id, RequestStatus::Failed, Some(&error.to_string()))
+}
+
+/// Approve a pending request and download its top result.
+pub async fn approve(id: i64, output: Option<PathBuf>) -> Result<()> {
+    let config = Config::load()?;
+    let state = StateDb::open()?;
+    let (_, result) = approvable(&state, id)?;
+    let output_dir = downloader::resolve_output_dir(output, &config, &state)?;
+    claim(&state, id)?;
+
+    let download = downloader::download_torrent(&result.torrent.magnet_link, output_dir).await;
+    if let Err(e) = &download {
+        record_failure(&state, id, e)?;
+    }
+    download?;
+    Ok(())
+}
+
+/// Reject a request that's pending, or whose approved download failed.
+pub fn reject(id: i64, reason: Option<&str>) -> Result<()> {
+    let state = StateDb::open()?;
+    if !state.decide_open_request(id, RequestStatus::Rejected, reason)? {
+        return Err(already_decided(&state, id));
+    }
+    Ok(())
+}
+
+/// Pending requests, and approved ones whose download failed.
+fn is_open(status: RequestStatus) -> bool {
+    matches!(status, RequestStatus::Pending | RequestStatus::Failed)
+}
+
+/// Why a request couldn't be decided: it's gone, or no longer open.
+fn already_decided(state: &StateDb, id: i64) -> anyhow::Error {
+    match state.request(id) {
+        Ok(Some(request)) => Undecidable::AlreadyDecided(id, request.status).into(),
+        Ok(None) => Undecidable::Missing(id).into(),
+        Err(e) => e,
+    }
+}
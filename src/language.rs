@@ -0,0 +1,250 @@
+use lazy_static::lazy_static;
+use regex::Regex;
+
+use crate::models::EvaluatedResult;
+
+/// ISO 639-1 code, English name, and how release names tag the language.
+const LANGUAGES: &[(&str, &str, &str)] = &[
+    ("en", "English", "eng|english"),
+    ("fr", "French", "french|truefrench|vff|vfq|vfi|vf2?"),
+    ("it", "Italian", "ita|italian"),
+    ("de", "German", "ger|german|deutsch"),
+    ("es", "Spanish", "spa|spanish|esp|castellano|latino"),
+    ("pt", "Portuguese", "por|portuguese|pt-?br|dublado"),
+    ("ru", "Russian", "rus|russian"),
+    ("pl", "Polish", "polish|lektor"),
+    ("nl", "Dutch", "dutch|flemish"),
+    ("sv", "Swedish", "swe|swedish"),
+    ("tr", "Turkish", "tur|turkish"),
+    ("hu", "Hungarian", "hun|hungarian"),
+    ("cs", "Czech", "cze|czech"),
+    ("hi", "Hindi", "hindi"),
+    ("ta", "Tamil", "tamil"),
+    ("te", "Telugu", "telugu"),
+    ("ja", "Japanese", "jpn|japanese"),
+    ("ko", "Korean", "kor|korean"),
+    ("zh", "Chinese", "chi|chinese|mandarin|cantonese"),
+];
+
+/// Dubs in another language cost this much relevance and confidence.
+const MISMATCH_FACTOR: f32 = 0.5;
+
+lazy_static! {
+    /// Where release tags start: the year, an episode marker or a resolution
+    static ref TAGS_START_RE: Regex = Regex::new(r"(?i)\b((19|20)\d{2}|s\d{1,2}e\d{1,3}|\d{3,4}p)\b").unwrap();
+    /// Several audio tracks, usually the original among them. A lone "DL"
+    /// is German for dual language; "WEB-DL" is not.
+    static ref MULTI_RE: Regex = Regex::new(r"(?i)\b(multi|dual([\s._-]?audio)?)\b|(^|[\s._])dl\b").unwrap();
+    /// Subtitle tags: VOSTFR, SUBFRENCH, ESub, "ita sub", "subs eng"
+    static ref SUBTITLE_RE: Regex = Regex::new(&format!(
+        r"(?i)\b(vostfr|vost|subfrench|subita|esubs?|multi[\s._-]?subs?|({tags})[\s._-]?subs?|subs?[\s._-]?({tags}))\b",
+        tags = all_tags()
+    )).unwrap();
+    static ref AUDIO_RES: Vec<(&'static str, Regex)> = LANGUAGES.iter()
+        .map(|(code, _, tags)| (*code, Regex::new(&format!(r"(?i)\b({})\b", tags)).unwrap()))
+        .collect();
+    /// "in French", "dubbed in Italian", "german dub", "spanish audio"
+    static ref REQUESTED_RE: Regex = Regex::new(&format!(
+        r"(?i)\b(?:(?:dubbed\s+)?in\s+({names})|({names})\s+(?:dub|dubbed|audio|version))\b",
+        names = LANGUAGES.iter().map(|(_, name, _)| *name).collect::<Vec<_>>().join("|")
+    )).unwrap();
+}
+
+fn all_tags() -> String {
+    LANGUAGES.iter().map(|(_, _, tags)| *tags).collect::<Vec<_>>().join("|")
+}
+
+/// Languages a release name says it carries. Only the tags after the title
+/// are looked at, so a title word like "Chi" isn't taken for a language.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ReleaseLanguages {
+    /// Audio named in the title, as ISO 639-1 codes
+    pub audio: Vec<&'static str>,
+    pub subtitles: Vec<&'static str>,
+    /// MULTI or DUAL: several audio tracks
+    pub multi: bool,
+}
+
+pub fn detect(title: &str) -> ReleaseLanguages {
+    let tags = TAGS_START_RE.find(title).map_or(title, |m| &title[m.start()..]);
+    let mut found = ReleaseLanguages::default();
+
+    for caps in SUBTITLE_RE.captures_iter(tags) {
+        let tag = caps[1].to_lowercase();
+        let code = match tag.as_str() {
+            "vostfr" | "subfrench" => Some("fr"),
+            "subita" => Some("it"),
+            t if t.starts_with("esub") => Some("en"),
+            _ => caps.get(2).or_else(|| caps.get(3)).and_then(|m| tag_code(m.as_str())),
+        };
+        if let Some(code) = code.filter(|code| !found.subtitles.contains(code)) {
+            found.subtitles.push(code);
+        }
+    }
+
+    // What's left once subtitle tags are out of the way is about the audio
+    let audio_tags = SUBTITLE_RE.replace_all(tags, " ");
+    found.multi = MULTI_RE.is_match(&audio_tags);
+    found.audio = AUDIO_RES.iter()
+        .filter(|(_, re)| re.is_match(&audio_tags))
+        .map(|(code, _)| *code)
+        .collect();
+    found
+}
+
+/// The code for a language given by name, code or release tag: "English",
+/// "en" and "eng" are all "en".
+pub fn code(language: &str) -> Option<&'static str> {
+    let language = language.trim().to_lowercase();
+    LANGUAGES.iter()
+        .find(|(code, name, _)| *code == language || name.to_lowercase() == language)
+        .map(|(code, _, _)| *code)
+        .or_else(|| tag_code(&language))
+}
+
+fn tag_code(tag: &str) -> Option<&'static str> {
+    AUDIO_RES.iter().find(|(_, re)| re.is_match(tag)).map(|(code, _)| *code)
+}
+
+pub fn name(code: &str) -> &'static str {
+    LANGUAGES.iter()
+        .find(|(c, _, _)| *c == code)
+        .map_or("Unknown", |(_, name, _)| *name)
+}
+
+/// A language asked for in a query, as its English name, and the query
+/// without the words that asked for it.
+pub fn requested(query: &str) -> Option<(&'static str, String)> {
+    let caps = REQUESTED_RE.captures(query)?;
+    let asked = caps.get(1).or_else(|| caps.get(2))?.as_str();
+    let language = name(code(asked)?);
+    Some((language, REQUESTED_RE.replace(query, " ").to_string()))
+}
+
+/// Why a release doesn't suit someone wanting `wanted` audio: it's dubbed
+/// into other languages only. Untagged and multi-audio releases pass.
+pub fn mismatch(title: &str, wanted: &str) -> Option<String> {
+    let found = detect(title);
+    if found.multi || found.audio.is_empty() || found.audio.contains(&wanted) {
+        return None;
+    }
+    let audio: Vec<&str> = found.audio.iter().map(|code| name(code)).collect();
+    Some(format!("{} audio, not {}", audio.join("/"), name(wanted)))
+}
+
+/// Warn about releases in the wrong language and rank them down.
+/// Subtitles in another language are only noted, since they may be
+/// optional; dubs cost relevance and confidence alike.
+pub fn check(result: &mut EvaluatedResult, wanted: &str) {
+    if let Some(dub) = mismatch(&result.torrent.title, wanted) {
+        result.warnings.push(format!("Language: {}", dub));
+        result.relevance_score *= MISMATCH_FACTOR;
+        result.confidence *= MISMATCH_FACTOR;
+        return;
+    }
+    let subtitles = detect(&result.torrent.title).subtitles;
+    if !subtitles.is_empty() && !subtitles.contains(&wanted) {
+        let names: Vec<&str> = subtitles.iter().map(|code| name(code)).collect();
+        result.warnings.push(format!("Language: {} subtitles, possibly burned in", names.join("/")));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pirate_bay_scraper::TorrentResult;
+
+    fn evaluated(title: &str) -> EvaluatedResult {
+        EvaluatedResult {
+            torrent: TorrentResult {
+                title: title.to_string(),
+                magnet_link: String::new(),
+                size: None,
+                seeders: None,
+                leechers: None,
+                uploaded: None,
+                category: None,
+                uploader: None,
+                found_by: Vec::new(),
+            },
+            relevance_score: 0.8,
+            confidence: 0.9,
+            match_reasons: Vec::new(),
+            warnings: Vec::new(),
+            quality_score: 0.5,
+            completeness_score: 1.0,
+        }
+    }
+
+    #[test]
+    fn multi_language_tags() {
+        let found = detect("Dune.2021.MULTi.1080p.BluRay.x264-LOST");
+        assert!(found.multi);
+        assert!(found.audio.is_empty());
+
+        let found = detect("Amelie 2001 1080p BluRay DUAL Audio French English");
+        assert!(found.multi);
+        assert_eq!(found.audio, ["en", "fr"]);
+
+        // German for dual language, unlike the DL in WEB-DL
+        assert!(detect("Dune.2021.German.DL.1080p.BluRay.x264").multi);
+        assert!(!detect("Dune.2021.1080p.WEB-DL.DDP5.1.x264").multi);
+
+        assert_eq!(mismatch("Dune.2021.MULTi.TRUEFRENCH.1080p.BluRay.x264", "en"), None);
+    }
+
+    #[test]
+    fn dubbed_releases() {
+        let found = detect("Dune.2021.TRUEFRENCH.1080p.BluRay.x264-LOST");
+        assert_eq!(found.audio, ["fr"]);
+        assert!(!found.multi);
+        assert_eq!(mismatch("Dune.2021.TRUEFRENCH.1080p.BluRay.x264-LOST", "en").as_deref(), Some("French audio, not English"));
+        assert_eq!(mismatch("Dune.2021.TRUEFRENCH.1080p.BluRay.x264-LOST", "fr"), None);
+        assert_eq!(mismatch("Dune 2021 1080p iTA ENG AC3 BluRay", "en"), None);
+        assert_eq!(mismatch("Dune.2021.1080p.Dublado.WEB-DL", "en").as_deref(), Some("Portuguese audio, not English"));
+
+        let mut result = evaluated("Dune.2021.German.1080p.BluRay.x264");
+        check(&mut result, "en");
+        assert_eq!(result.warnings, ["Language: German audio, not English"]);
+        assert_eq!((result.relevance_score, result.confidence), (0.8 * MISMATCH_FACTOR, 0.9 * MISMATCH_FACTOR));
+    }
+
+    #[test]
+    fn subbed_releases() {
+        let found = detect("Dune.2021.VOSTFR.1080p.WEB-DL.x264");
+        assert_eq!(found.subtitles, ["fr"]);
+        assert!(found.audio.is_empty(), "subtitle tags aren't audio: {:?}", found.audio);
+
+        assert_eq!(detect("Dune 2021 1080p BluRay ESub").subtitles, ["en"]);
+        assert_eq!(detect("Dune 2021 1080p Hindi Eng Subs").subtitles, ["en"]);
+        assert_eq!(detect("Dune 2021 1080p Hindi Eng Subs").audio, ["hi"]);
+        assert_eq!(detect("Dune.2021.1080p.SUBITA.WEBRip").subtitles, ["it"]);
+
+        // Subtitles are noted, not held against the release
+        let mut result = evaluated("Dune.2021.VOSTFR.1080p.WEB-DL.x264");
+        check(&mut result, "en");
+        assert_eq!(result.warnings, ["Language: French subtitles, possibly burned in"]);
+        assert_eq!(result.relevance_score, 0.8);
+
+        let mut result = evaluated("Dune 2021 1080p BluRay ESub");
+        check(&mut result, "en");
+        assert!(result.warnings.is_empty());
+    }
+
+    #[test]
+    fn title_words_before_the_tags_are_ignored() {
+        assert_eq!(detect("The.Chi.S01E01.1080p.WEB.x264"), ReleaseLanguages::default());
+        assert_eq!(detect("English.Vinglish.2012.1080p.BluRay"), ReleaseLanguages::default());
+    }
+
+    #[test]
+    fn requested_languages() {
+        let (language, rest) = requested("dune in french 1080p").unwrap();
+        assert_eq!((language, rest.split_whitespace().collect::<Vec<_>>()), ("French", vec!["dune", "1080p"]));
+        assert_eq!(requested("dune german dub 1080p").map(|(language, _)| language), Some("German"));
+        assert_eq!(requested("dune 1080p"), None);
+        assert_eq!(code("English"), Some("en"));
+        assert_eq!(code("eng"), Some("en"));
+        assert_eq!(code("klingon"), None);
+    }
+}
@@ -0,0 +1,89 @@
+//! Natural-language torrent search and download.
+//!
+//! The `torrentai` binary is a thin wrapper over this crate; everything it
+//! does is available to other programs too. The main entry points are:
+//!
+//! - [`SmartSearcher`] turns a query like "the latest Severance episode in
+//!   1080p" into ranked [`EvaluatedResult`]s, using an [`LlmService`] when
+//!   one is given and a heuristic ranker otherwise.
+//! - [`SourceRegistry`] holds the sites searched, ThePirateBay and YTS by
+//!   default; implement [`TorrentSource`] to add another.
+//! - [`download_torrent`] downloads one torrent to completion, and
+//!   [`DownloadManager`] runs several in the background.
+//!
+//! ```no_run
+//! use torrentai::{download_torrent, LlmService, SmartSearcher};
+//!
+//! # async fn run() -> anyhow::Result<()> {
+//! let llm = LlmService::new("llama3.2".to_string())?;
+//! let searcher = SmartSearcher::new(Some(llm), 0.6);
+//! let results = searcher.search("dune part two 2160p", |_event| {}).await?;
+//! if let Some(best) = results.first() {
+//!     download_torrent(&best.torrent.magnet_link, "downloads".into()).await?;
+//! }
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! Settings such as mirrors, download roots and API keys come from the
+//! same `~/.torrentai/config.toml` the CLI reads.
+
+/// The `torrentai` command line, which the binary hands its arguments to.
+pub mod cli;
+pub(crate) mod access;
+/// Downloading torrents, one at a time or in a background [`DownloadManager`].
+pub mod downloader;
+pub(crate) mod pirate_bay_scraper;
+pub(crate) mod yts_scraper;
+/// Torrent sites and the [`SourceRegistry`] a search fans out to.
+pub mod scraper;
+/// Search intents and evaluated results.
+pub mod models;
+mod prompts;
+/// Query parsing, search planning and result evaluation on Ollama.
+pub mod llm_service;
+/// Natural-language search over every source, ranked by relevance.
+pub mod smart_search;
+/// `~/.torrentai/config.toml`.
+pub mod config;
+pub(crate) mod daemon;
+pub(crate) mod state;
+pub(crate) mod migrations;
+pub(crate) mod storage;
+mod ranker;
+pub(crate) mod trackers;
+pub(crate) mod secrets;
+pub(crate) mod tui;
+pub(crate) mod inbox;
+/// Canonical titles from TMDB or OMDb, for [`SmartSearcher::with_metadata`].
+pub mod metadata;
+pub(crate) mod nettest;
+pub(crate) mod filter;
+pub(crate) mod watch;
+pub(crate) mod checks;
+pub(crate) mod output;
+pub(crate) mod digest;
+pub(crate) mod feeds;
+pub(crate) mod stream;
+pub(crate) mod notifications;
+pub(crate) mod subtitles;
+pub(crate) mod organize;
+pub(crate) mod classify;
+pub(crate) mod library;
+pub(crate) mod safety;
+pub(crate) mod export;
+pub(crate) mod progress;
+pub(crate) mod size;
+pub(crate) mod trace;
+pub(crate) mod uploaded;
+pub(crate) mod stats;
+pub(crate) mod language;
+pub(crate) mod pirate_bay_parser;
+pub(crate) mod pacing;
+pub(crate) mod selftest;
+
+pub use downloader::{download_torrent, DownloadManager, DownloadProgress};
+pub use llm_service::LlmService;
+pub use models::{EvaluatedResult, SearchIntent};
+pub use scraper::{SearchHints, SourceQuery, SourceRegistry, TorrentResult, TorrentSource};
+pub use smart_search::{SmartSearchEvent, SmartSearcher};
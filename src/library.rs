@@ -0,0 +1,204 @@
+use anyhow::Result;
+use std::path::{Path, PathBuf};
+
+use crate::classify;
+use crate::models::{ContentType, SearchIntent};
+use crate::organize::{self, title_from_release};
+use crate::pirate_bay_scraper::TorrentResult;
+use crate::ranker::{episode_marker, is_season_pack};
+use crate::state::{StateDb, TorrentRecord, TorrentStatus};
+use crate::storage;
+use crate::trackers;
+
+/// A copy of something that was already downloaded.
+#[derive(Debug, Clone)]
+pub enum Owned {
+    OnDisk(PathBuf),
+    /// Downloaded, then archived with `torrentai archive`
+    Archived { name: String, archived_at: String },
+}
+
+impl Owned {
+    /// "in library at /media/Movies/Dune (2021)".
+    pub fn describe(&self) -> String {
+        match self {
+            Owned::OnDisk(path) => format!("in library at {}", path.display()),
+            Owned::Archived { name, archived_at } => format!("downloaded and archived on {} ({})", date(archived_at), name),
+        }
+    }
+}
+
+/// Where a copy of `torrent` (or of what `intent` asks for) already is, if
+/// anywhere: the same torrent downloaded before, the title in the media
+/// library, or a finished download of the same title. Copies on disk come
+/// first; archived downloads count too, but deleting files any other way
+/// lets them be downloaded again.
+pub fn find_owned(torrent: &TorrentResult, intent: Option<&SearchIntent>, library: &Path) -> Result<Option<Owned>> {
+    let downloads: Vec<_> = StateDb::open()?
+        .torrents()?
+        .into_iter()
+        .filter(|t| t.status != TorrentStatus::Failed)
+        .collect();
+    let archived = |matches: &dyn Fn(&TorrentRecord) -> bool| downloads.iter()
+        .find(|t| t.status == TorrentStatus::Archived && matches(t))
+        .map(|t| Owned::Archived { name: t.name.clone(), archived_at: t.updated_at.clone() });
+
+    let info_hash = trackers::magnet_info_hash(&torrent.magnet_link);
+    if let Some(info_hash) = &info_hash {
+        let same = downloads.iter()
+            .filter(|t| &t.info_hash == info_hash)
+            .map(|t| t.output_dir.join(&t.name))
+            .find(|path| path.exists());
+        if let Some(path) = same {
+            return Ok(Some(Owned::OnDisk(path)));
+        }
+    }
+
+    if let Some(intent) = intent {
+        if let Some(path) = find_in_library(intent, library) {
+            return Ok(Some(Owned::OnDisk(path)));
+        }
+        let finished = downloads.iter()
+            .filter(|t| t.status == TorrentStatus::Completed && release_matches(&t.name, intent))
+            .map(|t| t.output_dir.join(&t.name))
+            .find(|path| path.exists());
+        if let Some(path) = finished {
+            return Ok(Some(Owned::OnDisk(path)));
+        }
+    }
+
+    Ok(archived(&|t| info_hash.as_ref() == Some(&t.info_hash))
+        .or_else(|| intent.and_then(|intent| archived(&|t| release_matches(&t.name, intent)))))
+}
+
+/// The download `id` names: an info hash or its start, or a unique part
+/// of the name.
+pub fn find_download(id: &str) -> Result<TorrentRecord> {
+    let id = id.trim().to_lowercase();
+    if id.is_empty() {
+        return Err(anyhow::anyhow!("No download given"));
+    }
+    let downloads = StateDb::open()?.torrents()?;
+    let by_hash: Vec<_> = downloads.iter().filter(|t| t.info_hash.to_lowercase().starts_with(&id)).collect();
+    let matches = if by_hash.is_empty() {
+        downloads.iter().filter(|t| t.name.to_lowercase().contains(&id)).collect()
+    } else {
+        by_hash
+    };
+    match matches.as_slice() {
+        [only] => Ok((*only).clone()),
+        [] => Err(anyhow::anyhow!("No download matches \"{}\"; `torrentai list` shows them", id)),
+        several => Err(anyhow::anyhow!(
+            "\"{}\" matches {} downloads ({}); use more of the info hash",
+            id,
+            several.len(),
+            several.iter().map(|t| format!("{} {}", short_hash(&t.info_hash), t.name)).collect::<Vec<_>>().join(", ")
+        )),
+    }
+}
+
+/// What `archive` did or, on a dry run, would do.
+#[derive(Debug, Clone)]
+pub struct Archived {
+    /// The file or folder deleted; `None` if it was already gone
+    pub removed: Option<PathBuf>,
+    pub freed_bytes: u64,
+}
+
+/// Delete a finished download's files but keep its record, marked
+/// archived, so it still counts as owned and shows in history. With
+/// `min_ratio`, only once it has been seeded that much.
+pub fn archive(record: &TorrentRecord, min_ratio: Option<f64>, dry_run: bool) -> Result<Archived> {
+    match record.status {
+        TorrentStatus::Completed => {}
+        TorrentStatus::Archived => return Err(anyhow::anyhow!("{} is already archived", record.name)),
+        status => return Err(anyhow::anyhow!("{} is {}, not finished; only finished downloads can be archived", record.name, status.as_str())),
+    }
+    if let Some(min_ratio) = min_ratio.filter(|&min| record.ratio() < min) {
+        return Err(anyhow::anyhow!(
+            "{} has a ratio of {:.2}, under {:.2}; keep seeding it first",
+            record.name, record.ratio(), min_ratio
+        ));
+    }
+
+    // Never the download folder itself, whatever the name says
+    let path = record.output_dir.join(&record.name);
+    if record.name.trim().is_empty() || !path.starts_with(&record.output_dir) || path == record.output_dir
+        || record.name.contains("..") {
+        return Err(anyhow::anyhow!("Refusing to delete {} for {:?}", path.display(), record.name));
+    }
+
+    let removed = path.symlink_metadata().is_ok().then(|| path.clone());
+    let freed_bytes = removed.as_deref().and_then(storage::size_on_disk).unwrap_or(0);
+    if dry_run {
+        return Ok(Archived { removed, freed_bytes });
+    }
+
+    if let Some(path) = &removed {
+        if path.is_dir() {
+            std::fs::remove_dir_all(path)?;
+        } else {
+            std::fs::remove_file(path)?;
+        }
+    }
+    StateDb::open()?.archive_torrent(&record.info_hash)?;
+    Ok(Archived { removed, freed_bytes })
+}
+
+/// The first characters of an info hash, enough to tell downloads apart.
+pub fn short_hash(info_hash: &str) -> &str {
+    &info_hash[..info_hash.len().min(8)]
+}
+
+/// "2026-10-16" from an RFC 3339 timestamp.
+fn date(timestamp: &str) -> &str {
+    timestamp.split('T').next().unwrap_or(timestamp)
+}
+
+/// The movie's folder or the episode's file in the organized library.
+fn find_in_library(intent: &SearchIntent, library: &Path) -> Option<PathBuf> {
+    match (&intent.content_type, &intent.tv_details) {
+        (ContentType::TVShow, Some(tv)) => {
+            let (season, episode) = (tv.season?, tv.episode?);
+            let show = library.join("TV").read_dir().ok()?
+                .filter_map(|entry| entry.ok().map(|e| e.path()))
+                .find(|dir| dir.file_name().is_some_and(|n| same_title(&n.to_string_lossy(), &intent.title)))?;
+            organize::collect_files(&show.join(format!("Season {:02}", season))).ok()?
+                .into_iter()
+                .filter(|file| classify::is_video(file))
+                .find(|file| file.file_name().and_then(|n| episode_marker(&n.to_string_lossy())) == Some((season, episode)))
+        }
+        (ContentType::Movie, _) => library.join("Movies").read_dir().ok()?
+            .filter_map(|entry| entry.ok().map(|e| e.path()))
+            .filter(|dir| dir.file_name().is_some_and(|n| release_matches(&n.to_string_lossy(), intent)))
+            .find(|dir| organize::collect_files(dir).is_ok_and(|files| files.iter().any(|f| classify::is_video(f)))),
+        _ => None,
+    }
+}
+
+/// Whether a release or folder name is the title asked for: the same year
+/// for movies, and the episode or a pack of its season for series.
+fn release_matches(name: &str, intent: &SearchIntent) -> bool {
+    if !same_title(&title_from_release(name), &intent.title) {
+        return false;
+    }
+    match (&intent.tv_details, intent.year) {
+        (Some(tv), _) => match (tv.season, tv.episode) {
+            (Some(season), Some(episode)) => {
+                episode_marker(name) == Some((season, episode)) || is_season_pack(name, season)
+            }
+            (Some(season), None) => is_season_pack(name, season),
+            _ => false,
+        },
+        (None, Some(year)) => name.contains(&year.to_string()),
+        (None, None) => true,
+    }
+}
+
+fn same_title(a: &str, b: &str) -> bool {
+    let normalize = |s: &str| -> String {
+        s.chars().filter(|c| c.is_alphanumeric()).flat_map(char::to_lowercase).collect()
+    };
+    let (a, b) = (normalize(a), normalize(b));
+    !a.is_empty() && a == b
+}
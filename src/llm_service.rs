@@ -1,116 +1,819 @@
-use ollama_rs::{Ollama, generation::completion::request::GenerationRequest};
+use ollama_rs::{Ollama, generation::completion::request::GenerationRequest, models::ModelOptions};
 use anyhow::Result;
+use lazy_static::lazy_static;
+use regex::Regex;
+use serde::Deserialize;
 use serde::de::DeserializeOwned;
-use serde_json;
-use crate::models::{SearchIntent, EvaluatedResult, SearchStrategy};
+use std::collections::{HashSet, VecDeque};
+use std::fmt;
+use std::sync::Mutex;
+use tracing::warn;
+use crate::models::{ContentType, SearchIntent, EvaluatedResult, SearchStrategy, IntentRefinement};
 use crate::pirate_bay_scraper::TorrentResult;
-use crate::prompts::{build_parse_prompt, build_evaluation_prompt, build_query_generation_prompt};
+use crate::trace::{self, TraceEvent, Tracer};
+use crate::prompts::{build_parse_prompt, build_evaluation_prompt, build_query_generation_prompt, build_correction_prompt, build_refinement_prompt, build_classification_prompt};
 
+lazy_static! {
+    static ref REASONING_RE: Regex = Regex::new(
+        r"(?is)<think>.*?</think>|<thinking>.*?</thinking>|<reasoning>.*?</reasoning>"
+    ).unwrap();
+}
+
+/// The model never produced a response that parsed and validated.
+#[derive(Debug)]
+pub struct StructuredOutputError {
+    pub attempts: usize,
+    pub reason: String,
+    pub raw_output: String,
+}
+
+impl fmt::Display for StructuredOutputError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "LLM returned unusable output after {} attempt(s): {}\n--- raw model output ---\n{}",
+               self.attempts, self.reason, self.raw_output)
+    }
+}
+
+impl std::error::Error for StructuredOutputError {}
+
+/// One evaluation as the model is asked to return it.
+#[derive(Debug, Deserialize)]
+struct LlmEvaluation {
+    /// Echo of the result id from the prompt, so order doesn't matter
+    id: usize,
+    relevance_score: f32,
+    confidence: f32,
+    #[serde(default)]
+    match_reasons: Vec<String>,
+    #[serde(default)]
+    warnings: Vec<String>,
+    #[serde(default)]
+    quality_score: f32,
+    #[serde(default)]
+    completeness_score: f32,
+}
+
+#[derive(Debug, Deserialize)]
+struct LlmClassification {
+    content_type: String,
+}
+
+/// Models sometimes wrap the requested array in an object.
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum EvaluationResponse {
+    List(Vec<LlmEvaluation>),
+    Wrapped { evaluations: Vec<LlmEvaluation> },
+}
+
+impl EvaluationResponse {
+    fn into_vec(self) -> Vec<LlmEvaluation> {
+        match self {
+            EvaluationResponse::List(list) => list,
+            EvaluationResponse::Wrapped { evaluations } => evaluations,
+        }
+    }
+
+    fn as_slice(&self) -> &[LlmEvaluation] {
+        match self {
+            EvaluationResponse::List(list) => list,
+            EvaluationResponse::Wrapped { evaluations } => evaluations,
+        }
+    }
+}
+
+/// Talks to Ollama to parse queries into [`SearchIntent`]s, plan searches
+/// and score results against what was asked for.
 pub struct LlmService {
-    ollama: Ollama,
+    /// Ollama servers, in order of preference; evaluation is spread across all of them
+    backends: Vec<Ollama>,
     model: String,
     temperature: f32,
+    /// Generations per request before giving up on unparseable output
+    max_attempts: usize,
+    /// Results per evaluation prompt
+    chunk_size: usize,
+    /// Evaluation prompts in flight at once on each backend
+    max_concurrent_evaluations: usize,
+    /// Records every prompt and response when a trace was asked for
+    tracer: Option<Tracer>,
 }
 
 impl LlmService {
     pub fn new(model: String) -> Result<Self> {
-        let ollama = Ollama::default();
         Ok(Self {
-            ollama,
+            backends: vec![Ollama::default()],
             model,
             temperature: 0.3, // Low temperature for consistent parsing
+            max_attempts: 3,
+            chunk_size: 8,
+            max_concurrent_evaluations: 2,
+            tracer: None,
         })
     }
 
+    /// Use these Ollama servers instead of the local default. The first
+    /// reachable one answers single prompts; evaluation uses them all.
+    pub fn with_hosts(mut self, hosts: &[String]) -> Result<Self> {
+        if hosts.is_empty() {
+            return Ok(self);
+        }
+        self.backends = hosts.iter()
+            .map(|host| Ollama::try_new(host.as_str())
+                .map_err(|e| anyhow::anyhow!("Invalid Ollama host {}: {}", host, e)))
+            .collect::<Result<_>>()?;
+        Ok(self)
+    }
+
+    pub fn with_max_attempts(mut self, max_attempts: usize) -> Self {
+        self.max_attempts = max_attempts.max(1);
+        self
+    }
+
+    pub fn with_evaluation_chunking(mut self, chunk_size: usize, max_concurrent: usize) -> Self {
+        self.chunk_size = chunk_size.max(1);
+        self.max_concurrent_evaluations = max_concurrent.max(1);
+        self
+    }
+
+    pub(crate) fn with_tracer(mut self, tracer: Option<Tracer>) -> Self {
+        self.tracer = tracer;
+        self
+    }
+
     pub async fn parse_query(&self, query: &str) -> Result<SearchIntent> {
         let prompt = build_parse_prompt(query);
-        let response = self.generate(&prompt).await?;
-        self.parse_json_response(&response)
+        self.generate_structured("parse_query", None, &prompt, |intent: &SearchIntent| {
+            if intent.title.trim().is_empty() {
+                return Err("\"title\" must not be empty".to_string());
+            }
+            Ok(())
+        }).await
     }
 
+    /// Apply a follow-up like "no, the extended edition" to an existing intent.
+    pub async fn refine_intent(
+        &self,
+        intent: &SearchIntent,
+        previous_refinements: &[String],
+        refinement: &str,
+    ) -> Result<IntentRefinement> {
+        let prompt = build_refinement_prompt(intent, previous_refinements, refinement);
+        self.generate_structured("refine_intent", None, &prompt, |refined: &IntentRefinement| {
+            if refined.intent.title.trim().is_empty() {
+                return Err("\"intent.title\" must not be empty".to_string());
+            }
+            Ok(())
+        }).await
+    }
+
+    /// Evaluate results in chunks of `chunk_size`, with up to
+    /// `max_concurrent_evaluations` prompts in flight on each backend. Chunks
+    /// go to whichever backend is free, so faster machines take more of them.
+    /// A backend that can't be reached is dropped and its chunk handed to the
+    /// others. `on_chunk` is called as each chunk finishes; the returned
+    /// results keep the input order.
     pub async fn evaluate_results(
         &self, 
         intent: &SearchIntent, 
-        results: Vec<TorrentResult>
+        results: Vec<TorrentResult>,
+        on_chunk: impl Fn(&[EvaluatedResult]) + Send + Sync,
     ) -> Result<Vec<EvaluatedResult>> {
+        let mut pending: VecDeque<(usize, Vec<TorrentResult>)> = results.chunks(self.chunk_size)
+            .map(<[TorrentResult]>::to_vec)
+            .enumerate()
+            .collect();
+        let mut evaluated: Vec<Option<Vec<EvaluatedResult>>> = vec![None; pending.len()];
+        let mut alive: Vec<usize> = (0..self.backends.len()).collect();
+        let mut last_error = None;
+
+        // Each round drains the queue; chunks whose backend failed go round again
+        while !pending.is_empty() {
+            if alive.is_empty() {
+                return Err(last_error.unwrap_or_else(|| anyhow::anyhow!("No LLM backend available")));
+            }
+
+            let queue = Mutex::new(pending);
+            let retry = Mutex::new(VecDeque::new());
+            let failed = Mutex::new(Vec::new());
+            let done = Mutex::new(Vec::new());
+
+            let workers = alive.iter()
+                .flat_map(|&backend| std::iter::repeat_n(backend, self.max_concurrent_evaluations))
+                .map(|backend| {
+                    let (queue, retry, failed, done, on_chunk) = (&queue, &retry, &failed, &done, &on_chunk);
+                    async move {
+                        loop {
+                            let next = queue.lock().unwrap().pop_front();
+                            let Some((index, chunk)) = next else {
+                                return Ok(());
+                            };
+                            match self.evaluate_chunk(backend, intent, chunk.clone()).await {
+                                Ok(results) => {
+                                    on_chunk(&results);
+                                    done.lock().unwrap().push((index, results));
+                                }
+                                // Bad output would be just as bad elsewhere
+                                Err(e) if e.is::<StructuredOutputError>() => return Err(e),
+                                Err(e) => {
+                                    warn!("LLM backend {} failed, moving its work elsewhere: {}", self.backends[backend].uri(), e);
+                                    retry.lock().unwrap().push_back((index, chunk));
+                                    failed.lock().unwrap().push((backend, e));
+                                    return Ok(());
+                                }
+                            }
+                        }
+                    }
+                });
+            futures::future::try_join_all(workers).await?;
+
+            for (index, results) in done.into_inner().unwrap() {
+                evaluated[index] = Some(results);
+            }
+            for (backend, e) in failed.into_inner().unwrap() {
+                alive.retain(|&b| b != backend);
+                last_error = Some(e);
+            }
+            pending = queue.into_inner().unwrap();
+            pending.extend(retry.into_inner().unwrap());
+        }
+
+        Ok(evaluated.into_iter().flatten().flatten().collect())
+    }
+
+    async fn evaluate_chunk(&self, backend: usize, intent: &SearchIntent, results: Vec<TorrentResult>) -> Result<Vec<EvaluatedResult>> {
         let prompt = build_evaluation_prompt(intent, &results);
-        let response = self.generate(&prompt).await?;
-        self.parse_evaluation_response(&response, results)
+        let expected = results.len();
+        let response = self.generate_structured("evaluate_results", Some(backend), &prompt, |response: &EvaluationResponse| {
+            let evaluations = response.as_slice();
+            let mut seen = HashSet::new();
+            for eval in evaluations {
+                if eval.id == 0 || eval.id > expected {
+                    return Err(format!("unknown result id {} (ids are 1 to {})", eval.id, expected));
+                }
+                if !seen.insert(eval.id) {
+                    return Err(format!("result id {} evaluated more than once", eval.id));
+                }
+                for (field, value) in [
+                    ("relevance_score", eval.relevance_score),
+                    ("confidence", eval.confidence),
+                    ("quality_score", eval.quality_score),
+                    ("completeness_score", eval.completeness_score),
+                ] {
+                    if !(0.0..=1.0).contains(&value) {
+                        return Err(format!("result {}: {} must be between 0.0 and 1.0, got {}", eval.id, field, value));
+                    }
+                }
+            }
+            if seen.len() != expected {
+                let missing: Vec<String> = (1..=expected)
+                    .filter(|id| !seen.contains(id))
+                    .map(|id| id.to_string())
+                    .collect();
+                return Err(format!("missing evaluations for result ids {}", missing.join(", ")));
+            }
+            Ok(())
+        }).await?;
+
+        let mut evaluations = response.into_vec();
+        evaluations.sort_by_key(|eval| eval.id);
+
+        Ok(results.into_iter()
+            .zip(evaluations)
+            .map(|(torrent, eval)| EvaluatedResult {
+                torrent,
+                relevance_score: eval.relevance_score,
+                confidence: eval.confidence,
+                match_reasons: eval.match_reasons,
+                warnings: eval.warnings,
+                quality_score: eval.quality_score,
+                completeness_score: eval.completeness_score,
+            })
+            .collect())
     }
 
     pub async fn generate_search_queries(&self, intent: &SearchIntent) -> Result<SearchStrategy> {
         let prompt = build_query_generation_prompt(intent);
-        let response = self.generate(&prompt).await?;
-        self.parse_json_response(&response)
+        self.generate_structured("generate_search_queries", None, &prompt, |strategy: &SearchStrategy| {
+            if strategy.primary_queries.iter().all(|q| q.trim().is_empty()) {
+                return Err("\"primary_queries\" must contain at least one query".to_string());
+            }
+            Ok(())
+        }).await
+    }
+
+    /// Classify a torrent with no search intent behind it from its name and
+    /// a few of its largest files.
+    pub async fn classify_torrent(&self, name: &str, files: &[String]) -> Result<ContentType> {
+        let prompt = build_classification_prompt(name, files);
+        let classification = self.generate_structured("classify_torrent", None, &prompt, |c: &LlmClassification| {
+            if c.content_type.trim().is_empty() {
+                return Err("\"content_type\" must not be empty".to_string());
+            }
+            Ok(())
+        }).await?;
+        Ok(ContentType::from_label(&classification.content_type))
+    }
+
+    /// Generate, parse and validate a JSON response, re-prompting with the
+    /// problem when the output is unusable. `backend` of `None` uses the
+    /// first one that answers.
+    async fn generate_structured<T: DeserializeOwned>(
+        &self,
+        task: &str,
+        backend: Option<usize>,
+        prompt: &str,
+        validate: impl Fn(&T) -> Result<(), String>,
+    ) -> Result<T> {
+        let mut current_prompt = prompt.to_string();
+        let mut last_reason = String::new();
+        let mut last_output = String::new();
+
+        for attempt in 1..=self.max_attempts {
+            let raw = self.generate(backend, &current_prompt).await.inspect_err(|e| {
+                trace::record(self.tracer.as_ref(), || TraceEvent::LlmExchange {
+                    task: task.to_string(),
+                    attempt,
+                    prompt: current_prompt.clone(),
+                    response: None,
+                    rejected: None,
+                    error: Some(e.to_string()),
+                });
+            })?;
+
+            let parsed = parse_structured::<T>(&raw).and_then(|value| validate(&value).map(|_| value));
+            trace::record(self.tracer.as_ref(), || TraceEvent::LlmExchange {
+                task: task.to_string(),
+                attempt,
+                prompt: current_prompt.clone(),
+                response: Some(raw.clone()),
+                rejected: parsed.as_ref().err().cloned(),
+                error: None,
+            });
+            match parsed {
+                Ok(value) => return Ok(value),
+                Err(reason) => {
+                    warn!("LLM output rejected (attempt {}/{}): {}", attempt, self.max_attempts, reason);
+                    current_prompt = build_correction_prompt(prompt, &raw, &reason);
+                    last_reason = reason;
+                    last_output = raw;
+                }
+            }
+        }
+
+        Err(StructuredOutputError {
+            attempts: self.max_attempts,
+            reason: last_reason,
+            raw_output: last_output,
+        }.into())
     }
 
-    async fn generate(&self, prompt: &str) -> Result<String> {
-        let request = GenerationRequest::new(self.model.clone(), prompt.to_string());
-        
-        let response = self.ollama.generate(request).await?;
-        Ok(response.response)
+    async fn generate(&self, backend: Option<usize>, prompt: &str) -> Result<String> {
+        let candidates = match backend {
+            Some(index) => index..index + 1,
+            None => 0..self.backends.len(),
+        };
+
+        let mut last_error = None;
+        for index in candidates {
+            let request = GenerationRequest::new(self.model.clone(), prompt.to_string())
+                .options(ModelOptions::default().temperature(self.temperature));
+            match self.backends[index].generate(request).await {
+                Ok(response) => return Ok(response.response),
+                Err(e) => {
+                    if backend.is_none() {
+                        warn!("LLM backend {} failed: {}", self.backends[index].uri(), e);
+                    }
+                    last_error = Some(e.into());
+                }
+            }
+        }
+        Err(last_error.unwrap_or_else(|| anyhow::anyhow!("No LLM backend configured")))
     }
 
+    /// Succeeds when at least one backend is up; the others are reported.
     pub async fn health_check(&self) -> Result<bool> {
-        match self.ollama.list_local_models().await {
-            Ok(_) => Ok(true),
-            Err(_) => Err(anyhow::anyhow!("Ollama is not running. Start with: ollama serve"))
+        let mut reachable = 0;
+        for ollama in &self.backends {
+            match ollama.list_local_models().await {
+                Ok(_) => reachable += 1,
+                Err(e) => warn!("Ollama at {} is unreachable: {}", ollama.uri(), e),
+            }
         }
+        if reachable == 0 {
+            return Err(anyhow::anyhow!("Ollama is not running. Start with: ollama serve"));
+        }
+        Ok(true)
     }
 
-    pub async fn ensure_model(&self) -> Result<()> {
-        let models = self.ollama.list_local_models().await?;
-        if !models.iter().any(|m| m.name == self.model) {
+    /// Succeeds when at least one backend has the model, dropping the
+    /// backends that answered without it. Those that couldn't be asked are
+    /// kept; if they fail later, their work moves to the others.
+    pub async fn ensure_model(&mut self) -> Result<()> {
+        let mut kept = Vec::new();
+        let mut found = false;
+        for ollama in std::mem::take(&mut self.backends) {
+            match ollama.list_local_models().await {
+                Ok(models) if models.iter().any(|m| m.name == self.model) => {
+                    found = true;
+                    kept.push(ollama);
+                }
+                Ok(_) => warn!("Ollama at {} doesn't have {}; it will be skipped", ollama.uri(), self.model),
+                Err(_) => kept.push(ollama),
+            }
+        }
+        self.backends = kept;
+        if !found {
             return Err(anyhow::anyhow!("Model {} not found. Pull with: ollama pull {}", 
                                        self.model, self.model));
         }
         Ok(())
     }
+}
+
+/// Remove reasoning blocks (e.g. deepseek-r1's `<think>`) and markdown fences.
+fn strip_reasoning(response: &str) -> String {
+    let mut text = REASONING_RE.replace_all(response, "").to_string();
+
+    // A closing tag without its opening tag: everything before it is reasoning
+    for tag in ["</think>", "</thinking>", "</reasoning>"] {
+        if let Some(pos) = text.rfind(tag) {
+            text = text[pos + tag.len()..].to_string();
+        }
+    }
+    // An unterminated opening tag: the rest is reasoning
+    for tag in ["<think>", "<thinking>", "<reasoning>"] {
+        if let Some(pos) = text.find(tag) {
+            text.truncate(pos);
+        }
+    }
+
+    text.replace("```json", "").replace("```", "")
+}
+
+/// End index (exclusive) of the JSON value opening at `start`, honoring strings.
+fn balanced_end(text: &str, start: usize) -> Option<usize> {
+    let mut depth = 0usize;
+    let mut in_string = false;
+    let mut escaped = false;
+
+    for (i, b) in text.bytes().enumerate().skip(start) {
+        if in_string {
+            if escaped {
+                escaped = false;
+            } else if b == b'\\' {
+                escaped = true;
+            } else if b == b'"' {
+                in_string = false;
+            }
+            continue;
+        }
+
+        match b {
+            b'"' => in_string = true,
+            b'{' | b'[' => depth += 1,
+            b'}' | b']' => {
+                depth = depth.checked_sub(1)?;
+                if depth == 0 {
+                    return Some(i + 1);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    None
+}
+
+/// Parse the first JSON value embedded in a model response that matches `T`.
+fn parse_structured<T: DeserializeOwned>(response: &str) -> Result<T, String> {
+    let text = strip_reasoning(response);
+    let mut first_error = None;
+
+    for (start, _) in text.match_indices(['{', '[']) {
+        let Some(end) = balanced_end(&text, start) else {
+            continue;
+        };
+
+        match serde_json::from_str::<T>(&text[start..end]) {
+            Ok(value) => return Ok(value),
+            Err(e) => {
+                first_error.get_or_insert_with(|| format!("response does not match the expected JSON format: {}", e));
+            }
+        }
+    }
 
-    fn parse_json_response<T: DeserializeOwned>(&self, response: &str) -> Result<T> {
-        // Try to extract JSON from response (LLM might add explanation)
-        let json_start = response.find('{').unwrap_or(0);
-        let json_end = response.rfind('}').map(|i| i + 1).unwrap_or(response.len());
-        let json_str = &response[json_start..json_end];
-        
-        serde_json::from_str(json_str)
-            .map_err(|e| anyhow::anyhow!("Failed to parse LLM response: {}", e))
-    }
-
-    fn parse_evaluation_response(&self, response: &str, results: Vec<TorrentResult>) -> Result<Vec<EvaluatedResult>> {
-        // Extract JSON array from response
-        let json_start = response.find('[').unwrap_or(0);
-        let json_end = response.rfind(']').map(|i| i + 1).unwrap_or(response.len());
-        let json_str = &response[json_start..json_end];
-        
-        let evaluations: Vec<serde_json::Value> = serde_json::from_str(json_str)?;
-        
-        let mut evaluated_results = Vec::new();
-        for (i, eval) in evaluations.iter().enumerate() {
-            if let Some(torrent) = results.get(i) {
-                let evaluated = EvaluatedResult {
-                    torrent: torrent.clone(),
-                    relevance_score: eval["relevance_score"].as_f64().unwrap_or(0.0) as f32,
-                    confidence: eval["confidence"].as_f64().unwrap_or(0.0) as f32,
-                    match_reasons: eval["match_reasons"]
-                        .as_array()
-                        .map(|arr| arr.iter()
-                            .filter_map(|v| v.as_str().map(String::from))
-                            .collect())
-                        .unwrap_or_default(),
-                    warnings: eval["warnings"]
-                        .as_array()
-                        .map(|arr| arr.iter()
-                            .filter_map(|v| v.as_str().map(String::from))
-                            .collect())
-                        .unwrap_or_default(),
-                    quality_score: eval["quality_score"].as_f64().unwrap_or(0.0) as f32,
-                    completeness_score: eval["completeness_score"].as_f64().unwrap_or(0.0) as f32,
-                };
-                evaluated_results.push(evaluated);
-            }
-        }
-        
-        Ok(evaluated_results)
-    }
-}
\ No newline at end of file
+    Err(first_error.unwrap_or_else(|| "response contains no complete JSON value".to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{routing::post, Json, Router};
+    use std::net::Ipv4Addr;
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    #[derive(Debug, PartialEq, Deserialize)]
+    struct Answer {
+        title: String,
+    }
+
+    fn answer(title: &str) -> Result<Answer, String> {
+        Ok(Answer { title: title.to_string() })
+    }
+
+    /// An Ollama server on localhost that answers each prompt with
+    /// `respond(prompt)`, after `delay(prompt)`, and keeps the prompts.
+    struct FakeOllama {
+        url: String,
+        prompts: Arc<Mutex<Vec<String>>>,
+        server: tokio::task::JoinHandle<()>,
+    }
+
+    impl FakeOllama {
+        async fn start(respond: fn(&str) -> String) -> Self {
+            Self::start_with_delay(respond, |_| Duration::ZERO).await
+        }
+
+        async fn start_with_delay(respond: fn(&str) -> String, delay: fn(&str) -> Duration) -> Self {
+            let listener = tokio::net::TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).await.unwrap();
+            let url = format!("http://{}/", listener.local_addr().unwrap());
+            let prompts = Arc::new(Mutex::new(Vec::new()));
+            let recorded = prompts.clone();
+            let app = Router::new().route("/api/generate", post(move |Json(request): Json<serde_json::Value>| {
+                let recorded = recorded.clone();
+                async move {
+                    let prompt = request["prompt"].as_str().unwrap_or_default().to_string();
+                    tokio::time::sleep(delay(&prompt)).await;
+                    let response = respond(&prompt);
+                    recorded.lock().unwrap().push(prompt);
+                    Json(serde_json::json!({
+                        "model": "test",
+                        "created_at": "2024-01-01T00:00:00Z",
+                        "response": response,
+                        "done": true,
+                    }))
+                }
+            }));
+            let server = tokio::spawn(async move {
+                axum::serve(listener, app).await.unwrap();
+            });
+            Self { url, prompts, server }
+        }
+
+        fn service(&self) -> LlmService {
+            LlmService::new("test".to_string()).unwrap().with_hosts(std::slice::from_ref(&self.url)).unwrap()
+        }
+
+        fn prompts(&self) -> Vec<String> {
+            self.prompts.lock().unwrap().clone()
+        }
+    }
+
+    impl Drop for FakeOllama {
+        fn drop(&mut self) {
+            self.server.abort();
+        }
+    }
+
+    /// Whether the prompt is a retry after unusable output.
+    fn is_correction(prompt: &str) -> bool {
+        prompt.contains("Your previous response could not be used")
+    }
+
+    #[test]
+    fn plain_json() {
+        assert_eq!(parse_structured::<Answer>(r#"{"title": "Dune"}"#), answer("Dune"));
+        assert_eq!(parse_structured::<Vec<Answer>>(r#"[{"title": "Dune"}]"#), Ok(vec![Answer { title: "Dune".to_string() }]));
+    }
+
+    #[test]
+    fn json_after_reasoning() {
+        let response = "<think>They probably mean {\"title\": \"Dune (1984)\"}, but the newer one is likelier.</think>\n{\"title\": \"Dune\"}";
+        assert_eq!(parse_structured::<Answer>(response), answer("Dune"));
+        let response = "<thinking>hmm</thinking><reasoning>{\"title\": \"no\"}</reasoning>{\"title\": \"Dune\"}";
+        assert_eq!(parse_structured::<Answer>(response), answer("Dune"));
+        // The opening tag was cut off by the prompt template
+        assert_eq!(parse_structured::<Answer>("so {\"title\": \"no\"}</think>{\"title\": \"Dune\"}"), answer("Dune"));
+        // Reasoning that never closed hides whatever follows it
+        assert_eq!(parse_structured::<Answer>("{\"title\": \"Dune\"}<think>or {\"title\": \"Arrakis\"}"), answer("Dune"));
+    }
+
+    #[test]
+    fn fenced_json() {
+        assert_eq!(parse_structured::<Answer>("```json\n{\"title\": \"Dune\"}\n```"), answer("Dune"));
+        assert_eq!(parse_structured::<Answer>("```\n{\"title\": \"Dune\"}\n```"), answer("Dune"));
+    }
+
+    #[test]
+    fn json_among_chatter() {
+        let response = "Sure! Here is the JSON you asked for:\n{\"title\": \"Dune\"}\nLet me know if you need {anything} else.";
+        assert_eq!(parse_structured::<Answer>(response), answer("Dune"));
+        // Values of the wrong shape are passed over for the first that fits
+        assert_eq!(parse_structured::<Answer>("{\"name\": \"x\"} then {\"title\": \"Dune\"}"), answer("Dune"));
+        // Brackets inside strings don't end the value early
+        assert_eq!(parse_structured::<Answer>(r#"{"title": "Dune } [Part \"Two\"]"}"#), answer("Dune } [Part \"Two\"]"));
+    }
+
+    #[test]
+    fn truncated_or_unbalanced_json() {
+        assert_eq!(parse_structured::<Answer>("{\"title\": \"Du"), Err("response contains no complete JSON value".to_string()));
+        assert!(parse_structured::<Answer>("{\"title\": [\"Dune\"}").is_err());
+        assert_eq!(parse_structured::<Answer>("{\"title\": \"Dune\""), Err("response contains no complete JSON value".to_string()));
+        // An array cut short still holds a whole object
+        assert_eq!(parse_structured::<Answer>("[{\"title\": \"Dune\"}, {\"tit"), answer("Dune"));
+        assert_eq!(parse_structured::<Answer>("no JSON here"), Err("response contains no complete JSON value".to_string()));
+        let wrong = parse_structured::<Answer>("{\"name\": \"Dune\"}").unwrap_err();
+        assert!(wrong.starts_with("response does not match the expected JSON format"), "{}", wrong);
+    }
+
+    #[test]
+    fn balanced_ends() {
+        let text = r#"x {"a": "}", "b": [1, {"c": 2}]} y"#;
+        assert_eq!(balanced_end(text, 2), Some(text.len() - 2));
+        assert_eq!(balanced_end("[[1], [2]]", 0), Some(10));
+        assert_eq!(balanced_end("[[1], [2]", 0), None);
+        assert_eq!(balanced_end(r#"{"a": "\"}"}"#, 0), Some(12));
+        assert_eq!(balanced_end("]", 0), None);
+    }
+
+    #[tokio::test]
+    async fn corrected_output_is_used() {
+        let ollama = FakeOllama::start(|prompt| if is_correction(prompt) {
+            r#"{"content_type": "movie", "title": "Dune", "year": 2021, "tv_details": null,
+                "quality_preferences": [], "additional_context": []}"#.to_string()
+        } else {
+            "You probably want Dune (2021).".to_string()
+        }).await;
+
+        let intent = ollama.service().parse_query("dune").await.unwrap();
+        assert_eq!(intent.title, "Dune");
+        let prompts = ollama.prompts();
+        assert_eq!(prompts.len(), 2);
+        assert!(prompts[1].contains("could not be used: response contains no complete JSON value"));
+        assert!(prompts[1].contains("You probably want Dune (2021)."));
+    }
+
+    #[tokio::test]
+    async fn invalid_output_is_corrected_too() {
+        let ollama = FakeOllama::start(|prompt| {
+            let title = if is_correction(prompt) { "Dune" } else { " " };
+            format!(r#"{{"content_type": "movie", "title": "{}", "year": null, "tv_details": null,
+                         "quality_preferences": [], "additional_context": []}}"#, title)
+        }).await;
+
+        assert_eq!(ollama.service().parse_query("dune").await.unwrap().title, "Dune");
+        assert!(ollama.prompts()[1].contains("\"title\" must not be empty"));
+    }
+
+    #[tokio::test]
+    async fn gives_up_after_max_attempts() {
+        let ollama = FakeOllama::start(|_| "I can't help with that.".to_string()).await;
+        let error = ollama.service().with_max_attempts(2).parse_query("dune").await.unwrap_err();
+        let error = error.downcast::<StructuredOutputError>().unwrap();
+        assert_eq!(error.attempts, 2);
+        assert_eq!(error.raw_output, "I can't help with that.");
+        assert_eq!(ollama.prompts().len(), 2);
+    }
+
+    fn releases(count: usize) -> Vec<TorrentResult> {
+        (1..=count)
+            .map(|n| TorrentResult {
+                title: format!("Release {}", n),
+                magnet_link: format!("magnet:?xt=urn:btih:{:040}", n),
+                size: None,
+                seeders: None,
+                leechers: None,
+                uploaded: None,
+                category: None,
+                uploader: None,
+                found_by: Vec::new(),
+            })
+            .collect()
+    }
+
+    /// The ids and titles an evaluation prompt lists.
+    fn listed(prompt: &str) -> Vec<(usize, String)> {
+        prompt.lines()
+            .skip_while(|line| !line.starts_with("Results to evaluate"))
+            .skip(1)
+            .take_while(|line| !line.is_empty())
+            .filter_map(|line| {
+                let (id, title) = line.split_once(": ")?;
+                Some((id.parse().ok()?, title.to_string()))
+            })
+            .collect()
+    }
+
+    /// Evaluations for `ids` of the listed results. Each is scored by the
+    /// number in its title and names the title it was given for, so a
+    /// result matched with the wrong evaluation shows.
+    fn evaluations(prompt: &str, ids: &[usize]) -> String {
+        let listed = listed(prompt);
+        let evaluations: Vec<String> = ids.iter()
+            .map(|&id| {
+                let title = listed.iter().find(|(n, _)| *n == id).map_or("unlisted", |(_, title)| title.as_str());
+                let number: f32 = title.trim_start_matches("Release ").parse().unwrap_or(0.0);
+                format!(
+                    r#"{{"id": {}, "relevance_score": {}, "confidence": 0.9, "match_reasons": ["{}"], "warnings": [], "quality_score": 0.5, "completeness_score": 1.0}}"#,
+                    id, number / 100.0, title
+                )
+            })
+            .collect();
+        format!("[{}]", evaluations.join(", "))
+    }
+
+    /// Every listed result, in order, when retrying; `first` otherwise.
+    fn corrected(prompt: &str, first: &[usize]) -> String {
+        if is_correction(prompt) {
+            let ids: Vec<usize> = listed(prompt).iter().map(|(id, _)| *id).collect();
+            evaluations(prompt, &ids)
+        } else {
+            evaluations(prompt, first)
+        }
+    }
+
+    fn assert_matched(evaluated: &[EvaluatedResult], count: usize) {
+        let titles: Vec<&str> = evaluated.iter().map(|r| r.torrent.title.as_str()).collect();
+        let expected: Vec<String> = (1..=count).map(|n| format!("Release {}", n)).collect();
+        assert_eq!(titles, expected);
+        for (n, result) in (1..=count).zip(evaluated) {
+            assert_eq!(result.match_reasons, vec![result.torrent.title.clone()]);
+            assert_eq!(result.relevance_score, n as f32 / 100.0);
+        }
+    }
+
+    async fn evaluate(ollama: &FakeOllama, count: usize) -> Vec<EvaluatedResult> {
+        let intent = crate::ranker::parse_intent("release");
+        ollama.service().evaluate_results(&intent, releases(count), |_| {}).await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn evaluations_out_of_order() {
+        let ollama = FakeOllama::start(|prompt| evaluations(prompt, &[3, 1, 2])).await;
+        assert_matched(&evaluate(&ollama, 3).await, 3);
+        assert_eq!(ollama.prompts().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn evaluations_missing_an_id() {
+        let ollama = FakeOllama::start(|prompt| corrected(prompt, &[1, 2])).await;
+        assert_matched(&evaluate(&ollama, 3).await, 3);
+        assert!(ollama.prompts()[1].contains("missing evaluations for result ids 3"));
+    }
+
+    #[tokio::test]
+    async fn evaluations_with_a_duplicate_id() {
+        let ollama = FakeOllama::start(|prompt| corrected(prompt, &[1, 2, 1, 3])).await;
+        assert_matched(&evaluate(&ollama, 3).await, 3);
+        assert!(ollama.prompts()[1].contains("result id 1 evaluated more than once"));
+    }
+
+    #[tokio::test]
+    async fn evaluations_with_an_unknown_id() {
+        let ollama = FakeOllama::start(|prompt| corrected(prompt, &[1, 2, 3, 4])).await;
+        assert_matched(&evaluate(&ollama, 3).await, 3);
+        assert!(ollama.prompts()[1].contains("unknown result id 4 (ids are 1 to 3)"));
+    }
+
+    #[tokio::test]
+    async fn evaluations_that_never_match_fail() {
+        let ollama = FakeOllama::start(|prompt| evaluations(prompt, &[1])).await;
+        let intent = crate::ranker::parse_intent("release");
+        let error = ollama.service().with_max_attempts(2)
+            .evaluate_results(&intent, releases(3), |_| {}).await
+            .unwrap_err();
+        assert!(error.is::<StructuredOutputError>());
+    }
+
+    #[tokio::test]
+    async fn chunked_evaluations_keep_the_input_order() {
+        // Earlier chunks answer last, so chunks finish in reverse
+        let ollama = FakeOllama::start_with_delay(
+            |prompt| {
+                let ids: Vec<usize> = listed(prompt).iter().rev().map(|(id, _)| *id).collect();
+                evaluations(prompt, &ids)
+            },
+            |prompt| {
+                let first = listed(prompt).first().and_then(|(_, title)| title.trim_start_matches("Release ").parse().ok()).unwrap_or(0);
+                Duration::from_millis(200u64.saturating_sub(first * 10))
+            },
+        ).await;
+        let intent = crate::ranker::parse_intent("release");
+        let chunks = Mutex::new(Vec::new());
+        let evaluated = ollama.service()
+            .with_evaluation_chunking(3, 4)
+            .evaluate_results(&intent, releases(20), |chunk| {
+                chunks.lock().unwrap().push(chunk.first().map(|r| r.torrent.title.clone()));
+            })
+            .await
+            .unwrap();
+
+        assert_matched(&evaluated, 20);
+        let chunks = chunks.into_inner().unwrap();
+        assert_eq!(chunks.len(), 7);
+        assert_ne!(chunks.first(), Some(&Some("Release 1".to_string())));
+        assert_eq!(ollama.prompts().len(), 7);
+    }
+}
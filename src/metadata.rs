@@ -0,0 +1,299 @@
+use anyhow::Result;
+use chrono::NaiveDate;
+use lazy_static::lazy_static;
+use regex::Regex;
+use serde::Deserialize;
+
+use crate::config::{MetadataConfig, MetadataProvider};
+use crate::models::{ContentType, EvaluatedResult, SearchIntent};
+use crate::ranker::episode_marker;
+use crate::secrets::SecretStore;
+
+lazy_static! {
+    static ref YEAR_RE: Regex = Regex::new(r"\b(19[3-9]\d|20[0-4]\d)\b").unwrap();
+    static ref RUNTIME_RE: Regex = Regex::new(r"(\d+)\s*min").unwrap();
+}
+
+/// Plausible encodes, from a small x265 720p to a 4K remux.
+const MIN_MB_PER_MINUTE: f64 = 4.0;
+const MAX_MB_PER_MINUTE: f64 = 450.0;
+
+const STOP_WORDS: &[&str] = &["the", "a", "an", "of", "and"];
+
+/// Canonical facts about a title, used to catch confidently wrong matches.
+#[derive(Debug, Clone)]
+pub struct CanonicalTitle {
+    pub source: &'static str,
+    pub title: String,
+    pub year: Option<u16>,
+    /// Film length, or episode length for TV
+    pub runtime_minutes: Option<u32>,
+    /// When the film, or the requested season or episode, comes out
+    pub release_date: Option<NaiveDate>,
+    pub is_tv: bool,
+    /// As the provider names them, e.g. "Science Fiction"
+    pub genres: Vec<String>,
+    /// The most recent episode to have aired, for series
+    pub latest_episode: Option<AiredEpisode>,
+}
+
+#[derive(Debug, Clone)]
+pub struct AiredEpisode {
+    pub season: u8,
+    pub episode: u8,
+    pub air_date: Option<NaiveDate>,
+}
+
+impl CanonicalTitle {
+    /// The release date, if it is still in the future.
+    pub fn unreleased(&self) -> Option<NaiveDate> {
+        self.release_date.filter(|date| *date > chrono::Local::now().date_naive())
+    }
+}
+
+pub struct MetadataClient {
+    provider: MetadataProvider,
+    api_key: String,
+    client: reqwest::Client,
+}
+
+#[derive(Deserialize)]
+struct TmdbSearch {
+    results: Vec<TmdbSearchResult>,
+}
+
+#[derive(Deserialize)]
+struct TmdbSearchResult {
+    id: u64,
+    #[serde(alias = "name")]
+    title: String,
+    #[serde(default, alias = "first_air_date")]
+    release_date: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct TmdbDetails {
+    runtime: Option<u32>,
+    #[serde(default)]
+    episode_run_time: Vec<u32>,
+    #[serde(default)]
+    seasons: Vec<TmdbSeason>,
+    next_episode_to_air: Option<TmdbEpisode>,
+    last_episode_to_air: Option<TmdbEpisode>,
+    #[serde(default)]
+    genres: Vec<TmdbGenre>,
+}
+
+#[derive(Deserialize)]
+struct TmdbGenre {
+    name: String,
+}
+
+#[derive(Deserialize)]
+struct TmdbSeason {
+    season_number: u8,
+    air_date: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct TmdbEpisode {
+    season_number: u8,
+    episode_number: u8,
+    air_date: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct OmdbTitle {
+    response: String,
+    title: Option<String>,
+    year: Option<String>,
+    runtime: Option<String>,
+    released: Option<String>,
+    genre: Option<String>,
+}
+
+impl MetadataClient {
+    /// Build a client from config; `None` when no provider is configured.
+    pub fn from_config(config: &MetadataConfig) -> Result<Option<Self>> {
+        let Some(provider) = config.provider else {
+            return Ok(None);
+        };
+
+        let secret = config.api_key_secret.clone()
+            .unwrap_or_else(|| provider.as_str().to_string());
+        let api_key = SecretStore::open()?.require(&secret)?.to_string();
+
+        Ok(Some(Self {
+            provider,
+            api_key,
+            client: reqwest::Client::new(),
+        }))
+    }
+
+    /// Look up the title the user asked for. Only movies and TV are covered.
+    pub async fn lookup(&self, intent: &SearchIntent) -> Result<Option<CanonicalTitle>> {
+        let is_tv = match intent.content_type {
+            ContentType::Movie => false,
+            ContentType::TVShow => true,
+            _ => return Ok(None),
+        };
+
+        match self.provider {
+            MetadataProvider::Tmdb => self.lookup_tmdb(intent, is_tv).await,
+            MetadataProvider::Omdb => self.lookup_omdb(intent, is_tv).await,
+        }
+    }
+
+    async fn lookup_tmdb(&self, intent: &SearchIntent, is_tv: bool) -> Result<Option<CanonicalTitle>> {
+        let kind = if is_tv { "tv" } else { "movie" };
+        let mut url = format!(
+            "https://api.themoviedb.org/3/search/{}?api_key={}&query={}",
+            kind, self.api_key, urlencoding::encode(&intent.title)
+        );
+        if let Some(year) = intent.year {
+            let param = if is_tv { "first_air_date_year" } else { "year" };
+            url.push_str(&format!("&{}={}", param, year));
+        }
+
+        let search: TmdbSearch = self.client.get(&url).send().await?.error_for_status()?.json().await?;
+        let Some(found) = search.results.into_iter().next() else {
+            return Ok(None);
+        };
+
+        let details_url = format!("https://api.themoviedb.org/3/{}/{}?api_key={}", kind, found.id, self.api_key);
+        let details: TmdbDetails = self.client.get(&details_url).send().await?.error_for_status()?.json().await?;
+
+        // For TV, the date that matters is the requested season's or episode's
+        let mut release_date = found.release_date.as_deref().and_then(parse_date);
+        if let Some(tv) = intent.tv_details.as_ref().filter(|_| is_tv) {
+            if let Some(season) = tv.season {
+                let next = details.next_episode_to_air.as_ref()
+                    .filter(|next| next.season_number == season && tv.episode.is_some_and(|e| e >= next.episode_number));
+                let season_start = details.seasons.iter().find(|s| s.season_number == season);
+                release_date = match (next, season_start) {
+                    (Some(next), _) => next.air_date.as_deref().and_then(parse_date),
+                    (None, Some(start)) => start.air_date.as_deref().and_then(parse_date),
+                    (None, None) => None,
+                };
+            }
+        }
+
+        Ok(Some(CanonicalTitle {
+            source: "TMDB",
+            title: found.title,
+            year: found.release_date.as_deref().and_then(|d| d.get(..4)).and_then(|y| y.parse().ok()),
+            runtime_minutes: details.runtime.or_else(|| details.episode_run_time.first().copied()),
+            release_date,
+            is_tv,
+            genres: details.genres.into_iter().map(|g| g.name).collect(),
+            latest_episode: details.last_episode_to_air.filter(|_| is_tv).map(|last| AiredEpisode {
+                season: last.season_number,
+                episode: last.episode_number,
+                air_date: last.air_date.as_deref().and_then(parse_date),
+            }),
+        }))
+    }
+
+    async fn lookup_omdb(&self, intent: &SearchIntent, is_tv: bool) -> Result<Option<CanonicalTitle>> {
+        let mut url = format!(
+            "https://www.omdbapi.com/?apikey={}&t={}&type={}",
+            self.api_key,
+            urlencoding::encode(&intent.title),
+            if is_tv { "series" } else { "movie" }
+        );
+        if let Some(year) = intent.year {
+            url.push_str(&format!("&y={}", year));
+        }
+
+        let found: OmdbTitle = self.client.get(&url).send().await?.error_for_status()?.json().await?;
+        if found.response != "True" {
+            return Ok(None);
+        }
+
+        Ok(Some(CanonicalTitle {
+            source: "OMDb",
+            title: found.title.unwrap_or_else(|| intent.title.clone()),
+            // Series years look like "2008–2013"; the first year is what we want
+            year: found.year.as_deref().and_then(|y| y.get(..4)).and_then(|y| y.parse().ok()),
+            runtime_minutes: found.runtime.as_deref()
+                .and_then(|r| RUNTIME_RE.captures(r))
+                .and_then(|c| c[1].parse().ok()),
+            // OMDb only knows when a series premiered, so seasons aren't checked
+            release_date: found.released.as_deref()
+                .filter(|_| !is_tv)
+                .and_then(|d| NaiveDate::parse_from_str(d, "%d %b %Y").ok()),
+            is_tv,
+            // "Action, Sci-Fi"; "N/A" when unknown
+            genres: found.genre.as_deref()
+                .filter(|g| *g != "N/A")
+                .map(|g| g.split(',').map(|g| g.trim().to_string()).collect())
+                .unwrap_or_default(),
+            latest_episode: None,
+        }))
+    }
+}
+
+/// Cross-check a result against the canonical title, year and runtime,
+/// recording the outcome in its reasons and warnings and scaling relevance.
+pub fn verify(canonical: &CanonicalTitle, result: &mut EvaluatedResult) {
+    let release = result.torrent.title.to_lowercase();
+    let release_tokens = tokenize(&release);
+    let mut factor = 1.0;
+
+    // Title: every significant word of the canonical title should appear
+    let wanted: Vec<String> = tokenize(&canonical.title.to_lowercase())
+        .into_iter()
+        .filter(|t| !STOP_WORDS.contains(&t.as_str()))
+        .collect();
+    let matched = wanted.iter().filter(|t| release_tokens.contains(t)).count();
+    if !wanted.is_empty() && matched == wanted.len() {
+        result.match_reasons.push(format!("Matches {} title \"{}\"", canonical.source, canonical.title));
+    } else if matched * 2 < wanted.len() {
+        factor *= 0.5;
+        result.warnings.push(format!("Doesn't look like {} title \"{}\"", canonical.source, canonical.title));
+    }
+
+    // Year: TV seasons span years, so only movies are checked
+    if let (Some(year), false) = (canonical.year, canonical.is_tv) {
+        let release_year = YEAR_RE.captures(&release).and_then(|c| c[1].parse::<u16>().ok());
+        match release_year {
+            Some(found) if found.abs_diff(year) <= 1 => {
+                result.match_reasons.push(format!("Release year {} matches {}", found, canonical.source));
+            }
+            Some(found) => {
+                factor *= 0.6;
+                result.warnings.push(format!("Year {} but {} says {}", found, canonical.source, year));
+            }
+            None => {}
+        }
+    }
+
+    // Size against runtime, for a film or a single episode
+    let single_episode = episode_marker(&release).is_some();
+    if let (Some(runtime), Some(bytes)) = (canonical.runtime_minutes, result.torrent.size_bytes()) {
+        if !canonical.is_tv || single_episode {
+            let mb_per_minute = bytes as f64 / (1024.0 * 1024.0) / runtime as f64;
+            if mb_per_minute < MIN_MB_PER_MINUTE {
+                factor *= 0.7;
+                result.warnings.push(format!("Too small for {} minutes of video", runtime));
+            } else if mb_per_minute > MAX_MB_PER_MINUTE {
+                factor *= 0.8;
+                result.warnings.push(format!("Too large for {} minutes of video", runtime));
+            }
+        }
+    }
+
+    result.relevance_score = (result.relevance_score * factor).clamp(0.0, 1.0);
+}
+
+fn parse_date(date: &str) -> Option<NaiveDate> {
+    NaiveDate::parse_from_str(date, "%Y-%m-%d").ok()
+}
+
+fn tokenize(s: &str) -> Vec<String> {
+    s.split(|c: char| !c.is_alphanumeric())
+        .filter(|t| !t.is_empty())
+        .map(String::from)
+        .collect()
+}
@@ -0,0 +1,438 @@
+use anyhow::Result;
+use chrono::Utc;
+use rusqlite::{Connection, Transaction, TransactionBehavior};
+use std::path::{Path, PathBuf};
+use tracing::info;
+
+/// Backups kept in `~/.torrentai/backups`; older ones are pruned.
+const MAX_BACKUPS: usize = 5;
+
+type Migration = fn(&Transaction) -> rusqlite::Result<()>;
+
+/// Schema changes in order; a database at version N has had the first N
+/// applied. Never edit or reorder a released migration, only append.
+///
+/// Databases from before versioning report version 0 but may already have
+/// any of these tables, so every step must be safe to re-run on them.
+const MIGRATIONS: &[(&str, Migration)] = &[
+    ("initial schema", initial_schema),
+    ("persist file selections", add_selected_files),
+    ("scrape cache and search history", add_search_cache),
+    ("mirror health", add_mirrors),
+    ("watch rules", add_watch_rules),
+    ("notification digest", add_digest),
+    ("feeds", add_feeds),
+    ("content types", add_content_types),
+    ("upload totals", add_uploaded_bytes),
+    ("torrent problems", add_torrent_problems),
+    ("feed keys", add_feed_keys),
+    ("organized files", add_organized_files),
+];
+
+/// Schema version this build expects.
+pub fn latest_version() -> u32 {
+    MIGRATIONS.len() as u32
+}
+
+pub fn schema_version(conn: &Connection) -> Result<u32> {
+    Ok(conn.query_row("PRAGMA user_version", [], |row| row.get(0))?)
+}
+
+/// Bring the schema up to date, backing the database up into `backups`
+/// first. Refuses to touch a database written by a newer torrentai.
+pub fn migrate(conn: &mut Connection, backups: &Path) -> Result<()> {
+    let version = schema_version(conn)?;
+    let latest = latest_version();
+    if version > latest {
+        return Err(anyhow::anyhow!(
+            "state.db has schema version {} but this torrentai only knows up to {}; upgrade torrentai",
+            version, latest
+        ));
+    }
+    if version == latest {
+        return Ok(());
+    }
+
+    // A brand-new database has nothing worth backing up
+    let objects: i64 = conn.query_row("SELECT count(*) FROM sqlite_master", [], |row| row.get(0))?;
+    if objects > 0 {
+        let backup = backup(conn, backups)?;
+        info!("Backed up state database to {} before migrating", backup.display());
+    }
+
+    // Another process may have migrated while we were backing up
+    let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+    let version = schema_version(&tx)?;
+    for (index, (name, migration)) in MIGRATIONS.iter().enumerate().skip(version as usize) {
+        info!("Migrating state database to version {}: {}", index + 1, name);
+        migration(&tx)?;
+    }
+    tx.pragma_update(None, "user_version", latest)?;
+    tx.commit()?;
+    Ok(())
+}
+
+/// Write a consistent copy of the database to the `dir` backups folder.
+pub fn backup(conn: &Connection, dir: &Path) -> Result<PathBuf> {
+    std::fs::create_dir_all(dir)?;
+    let path = dir.join(format!(
+        "state-v{}-{}.db",
+        schema_version(conn)?,
+        Utc::now().format("%Y%m%d-%H%M%S")
+    ));
+    conn.execute("VACUUM INTO ?1", [path.to_string_lossy()])?;
+
+    for old in backups(dir)?.into_iter().skip(MAX_BACKUPS) {
+        std::fs::remove_file(old)?;
+    }
+    Ok(path)
+}
+
+/// Backups in `dir`, newest first.
+pub fn backups(dir: &Path) -> Result<Vec<PathBuf>> {
+    if !dir.exists() {
+        return Ok(Vec::new());
+    }
+
+    let mut found: Vec<PathBuf> = std::fs::read_dir(dir)?
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|path| path.extension().is_some_and(|ext| ext == "db"))
+        .collect();
+    // Names lead with the schema version, so order by age instead
+    found.sort_by_key(|path| std::fs::metadata(path).and_then(|m| m.modified()).ok());
+    found.reverse();
+    Ok(found)
+}
+
+/// Replace the database at `db` with `backup`. The current file is copied
+/// aside into `dir` first, since it may be the only copy of recent changes.
+pub fn restore(db: &Path, backup: &Path, dir: &Path) -> Result<PathBuf> {
+    Connection::open(backup)?
+        .query_row("PRAGMA quick_check", [], |row| row.get::<_, String>(0))
+        .map_err(anyhow::Error::from)
+        .and_then(|check| match check.as_str() {
+            "ok" => Ok(()),
+            problem => Err(anyhow::anyhow!("{} is damaged too: {}", backup.display(), problem)),
+        })?;
+
+    std::fs::create_dir_all(dir)?;
+    let aside = dir.join(format!("state-replaced-{}.db", Utc::now().format("%Y%m%d-%H%M%S")));
+    if db.exists() {
+        std::fs::copy(db, &aside)?;
+    }
+    std::fs::copy(backup, db)?;
+    Ok(aside)
+}
+
+/// The backups folder for the state database in `dir`.
+pub fn backups_dir(dir: &Path) -> PathBuf {
+    dir.join("backups")
+}
+
+fn initial_schema(tx: &Transaction) -> rusqlite::Result<()> {
+    tx.execute_batch(
+        "CREATE TABLE IF NOT EXISTS torrents (
+            info_hash      TEXT PRIMARY KEY,
+            name           TEXT NOT NULL,
+            source         TEXT NOT NULL,
+            output_dir     TEXT NOT NULL,
+            disk_root      TEXT NOT NULL,
+            status         TEXT NOT NULL,
+            total_bytes    INTEGER NOT NULL DEFAULT 0,
+            progress_bytes INTEGER NOT NULL DEFAULT 0,
+            added_at       TEXT NOT NULL,
+            updated_at     TEXT NOT NULL
+        );
+        CREATE TABLE IF NOT EXISTS trackers (
+            info_hash     TEXT NOT NULL,
+            url           TEXT NOT NULL,
+            tier          INTEGER NOT NULL,
+            failures      INTEGER NOT NULL DEFAULT 0,
+            last_error    TEXT,
+            last_announce TEXT,
+            next_announce TEXT NOT NULL,
+            interval_secs INTEGER NOT NULL DEFAULT 0,
+            seeders       INTEGER,
+            leechers      INTEGER,
+            dropped       INTEGER NOT NULL DEFAULT 0,
+            PRIMARY KEY (info_hash, url)
+        );
+        CREATE TABLE IF NOT EXISTS settings (
+            key   TEXT PRIMARY KEY,
+            value TEXT NOT NULL
+        );
+        CREATE TABLE IF NOT EXISTS requests (
+            id           INTEGER PRIMARY KEY AUTOINCREMENT,
+            query        TEXT NOT NULL,
+            requested_by TEXT NOT NULL,
+            status       TEXT NOT NULL,
+            intent       TEXT,
+            top_result   TEXT,
+            note         TEXT,
+            created_at   TEXT NOT NULL,
+            decided_at   TEXT
+        );
+        CREATE TABLE IF NOT EXISTS queue (
+            id       INTEGER PRIMARY KEY AUTOINCREMENT,
+            name     TEXT NOT NULL,
+            source   TEXT NOT NULL,
+            status   TEXT NOT NULL,
+            error    TEXT,
+            added_at TEXT NOT NULL
+        );
+        CREATE TABLE IF NOT EXISTS watchlist (
+            id           INTEGER PRIMARY KEY AUTOINCREMENT,
+            query        TEXT NOT NULL,
+            title        TEXT NOT NULL,
+            release_date TEXT NOT NULL,
+            status       TEXT NOT NULL,
+            added_at     TEXT NOT NULL
+        );",
+    )
+}
+
+fn add_selected_files(tx: &Transaction) -> rusqlite::Result<()> {
+    add_column_if_missing(tx, "torrents", "selected_files", "TEXT")
+}
+
+fn add_search_cache(tx: &Transaction) -> rusqlite::Result<()> {
+    tx.execute_batch(
+        "CREATE TABLE IF NOT EXISTS scrape_cache (
+            source     TEXT NOT NULL,
+            query      TEXT NOT NULL,
+            results    TEXT NOT NULL,
+            fetched_at TEXT NOT NULL,
+            PRIMARY KEY (source, query)
+        );
+        CREATE TABLE IF NOT EXISTS search_history (
+            id          INTEGER PRIMARY KEY AUTOINCREMENT,
+            query       TEXT NOT NULL,
+            results     TEXT NOT NULL,
+            searched_at TEXT NOT NULL
+        );",
+    )
+}
+
+fn add_mirrors(tx: &Transaction) -> rusqlite::Result<()> {
+    tx.execute_batch(
+        "CREATE TABLE IF NOT EXISTS mirrors (
+            url          TEXT PRIMARY KEY,
+            failures     INTEGER NOT NULL DEFAULT 0,
+            last_error   TEXT,
+            last_success TEXT,
+            retry_after  TEXT
+        );",
+    )
+}
+
+fn add_watch_rules(tx: &Transaction) -> rusqlite::Result<()> {
+    tx.execute_batch(
+        "CREATE TABLE IF NOT EXISTS watch_rules (
+            id                  INTEGER PRIMARY KEY AUTOINCREMENT,
+            query               TEXT NOT NULL,
+            interval_mins       INTEGER NOT NULL,
+            auto_download_above REAL,
+            last_checked        TEXT,
+            created_at          TEXT NOT NULL
+        );
+        CREATE TABLE IF NOT EXISTS watch_seen (
+            rule_id   INTEGER NOT NULL,
+            info_hash TEXT NOT NULL,
+            seen_at   TEXT NOT NULL,
+            PRIMARY KEY (rule_id, info_hash)
+        );",
+    )
+}
+
+fn add_digest(tx: &Transaction) -> rusqlite::Result<()> {
+    tx.execute_batch(
+        "CREATE TABLE IF NOT EXISTS digest_entries (
+            id          INTEGER PRIMARY KEY AUTOINCREMENT,
+            source      TEXT NOT NULL,
+            subject     TEXT NOT NULL,
+            outcome     TEXT NOT NULL,
+            detail      TEXT,
+            recorded_at TEXT NOT NULL
+        );",
+    )
+}
+
+fn add_feeds(tx: &Transaction) -> rusqlite::Result<()> {
+    tx.execute_batch(
+        "CREATE TABLE IF NOT EXISTS feeds (
+            id            INTEGER PRIMARY KEY AUTOINCREMENT,
+            url           TEXT NOT NULL,
+            filter        TEXT,
+            interval_mins INTEGER NOT NULL,
+            grab_existing INTEGER NOT NULL DEFAULT 0,
+            last_polled   TEXT,
+            last_error    TEXT,
+            created_at    TEXT NOT NULL
+        );
+        CREATE TABLE IF NOT EXISTS feed_seen (
+            feed_id INTEGER NOT NULL,
+            guid    TEXT NOT NULL,
+            seen_at TEXT NOT NULL,
+            PRIMARY KEY (feed_id, guid)
+        );",
+    )
+}
+
+fn add_content_types(tx: &Transaction) -> rusqlite::Result<()> {
+    add_column_if_missing(tx, "torrents", "content_type", "TEXT")
+}
+
+fn add_uploaded_bytes(tx: &Transaction) -> rusqlite::Result<()> {
+    add_column_if_missing(tx, "torrents", "uploaded_bytes", "INTEGER NOT NULL DEFAULT 0")
+}
+
+fn add_torrent_problems(tx: &Transaction) -> rusqlite::Result<()> {
+    add_column_if_missing(tx, "torrents", "problem", "TEXT")
+}
+
+fn add_feed_keys(tx: &Transaction) -> rusqlite::Result<()> {
+    add_column_if_missing(tx, "feeds", "key_param", "TEXT")?;
+    add_column_if_missing(tx, "feeds", "key_secret", "TEXT")
+}
+
+fn add_organized_files(tx: &Transaction) -> rusqlite::Result<()> {
+    tx.execute_batch(
+        "CREATE TABLE IF NOT EXISTS organized_files (
+            info_hash TEXT NOT NULL,
+            original  TEXT NOT NULL,
+            library   TEXT NOT NULL,
+            PRIMARY KEY (info_hash, original)
+        );",
+    )
+}
+
+/// `ALTER TABLE ... ADD COLUMN` that tolerates pre-versioning databases
+/// which already have the column.
+fn add_column_if_missing(tx: &Transaction, table: &str, column: &str, definition: &str) -> rusqlite::Result<()> {
+    let exists = tx
+        .prepare(&format!("SELECT 1 FROM pragma_table_info('{}') WHERE name = ?1", table))?
+        .exists([column])?;
+    if !exists {
+        tx.execute(&format!("ALTER TABLE {} ADD COLUMN {} {}", table, column, definition), [])?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::{Duration, SystemTime};
+
+    fn scratch_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("torrentai-migrations-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        dir
+    }
+
+    fn columns(conn: &Connection, table: &str) -> Vec<String> {
+        conn.prepare(&format!("SELECT name FROM pragma_table_info('{}')", table)).unwrap()
+            .query_map([], |row| row.get(0)).unwrap()
+            .collect::<rusqlite::Result<_>>().unwrap()
+    }
+
+    #[test]
+    fn migrates_an_empty_database() {
+        let dir = scratch_dir("empty");
+        let mut conn = Connection::open_in_memory().unwrap();
+        migrate(&mut conn, &dir).unwrap();
+
+        assert_eq!(schema_version(&conn).unwrap(), latest_version());
+        assert!(columns(&conn, "torrents").contains(&"problem".to_string()));
+        assert!(columns(&conn, "feeds").contains(&"key_secret".to_string()));
+        assert!(!dir.exists(), "nothing to back up");
+
+        // Already current, so a second run changes nothing
+        migrate(&mut conn, &dir).unwrap();
+        assert_eq!(schema_version(&conn).unwrap(), latest_version());
+    }
+
+    #[test]
+    fn migrates_a_pre_versioning_database() {
+        let dir = scratch_dir("unversioned");
+        let mut conn = Connection::open_in_memory().unwrap();
+        // Some columns predate versioning, so they exist at version 0
+        conn.execute_batch(
+            "CREATE TABLE torrents (
+                info_hash      TEXT PRIMARY KEY,
+                name           TEXT NOT NULL,
+                source         TEXT NOT NULL,
+                output_dir     TEXT NOT NULL,
+                disk_root      TEXT NOT NULL,
+                status         TEXT NOT NULL,
+                total_bytes    INTEGER NOT NULL DEFAULT 0,
+                progress_bytes INTEGER NOT NULL DEFAULT 0,
+                selected_files TEXT,
+                content_type   TEXT,
+                added_at       TEXT NOT NULL,
+                updated_at     TEXT NOT NULL
+            );
+            INSERT INTO torrents VALUES ('abc', 'Dune', 'magnet', '/d', '/d/Dune', 'completed', 10, 10, '[0]', 'movie', 'then', 'then');",
+        ).unwrap();
+        assert_eq!(schema_version(&conn).unwrap(), 0);
+
+        migrate(&mut conn, &dir).unwrap();
+
+        assert_eq!(schema_version(&conn).unwrap(), latest_version());
+        let added = columns(&conn, "torrents");
+        for column in ["selected_files", "content_type", "uploaded_bytes", "problem"] {
+            assert_eq!(added.iter().filter(|c| *c == column).count(), 1, "{}", column);
+        }
+        let kept: (String, String) = conn
+            .query_row("SELECT selected_files, content_type FROM torrents WHERE info_hash = 'abc'", [], |row| Ok((row.get(0)?, row.get(1)?)))
+            .unwrap();
+        assert_eq!(kept, ("[0]".to_string(), "movie".to_string()));
+
+        let backups = backups(&dir).unwrap();
+        assert_eq!(backups.len(), 1);
+        let backed_up = Connection::open(&backups[0]).unwrap();
+        assert_eq!(schema_version(&backed_up).unwrap(), 0);
+        assert!(!columns(&backed_up, "torrents").contains(&"problem".to_string()));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn refuses_a_newer_database() {
+        let dir = scratch_dir("newer");
+        let mut conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch("CREATE TABLE future (id INTEGER)").unwrap();
+        conn.pragma_update(None, "user_version", latest_version() + 1).unwrap();
+
+        let error = migrate(&mut conn, &dir).unwrap_err();
+        assert!(error.to_string().contains("upgrade torrentai"), "{}", error);
+        assert_eq!(schema_version(&conn).unwrap(), latest_version() + 1);
+        assert!(!dir.exists(), "a refused database isn't backed up");
+    }
+
+    #[test]
+    fn backups_are_pruned() {
+        let dir = scratch_dir("pruned");
+        std::fs::create_dir_all(&dir).unwrap();
+        // Older backups, the first the oldest, and a file that isn't one
+        let now = SystemTime::now();
+        let old: Vec<PathBuf> = (0..7)
+            .map(|n| {
+                let path = dir.join(format!("state-v1-old{}.db", n));
+                let file = std::fs::File::create(&path).unwrap();
+                file.set_modified(now - Duration::from_secs(3600 * (7 - n))).unwrap();
+                path
+            })
+            .collect();
+        std::fs::write(dir.join("notes.txt"), "keep").unwrap();
+
+        let conn = Connection::open_in_memory().unwrap();
+        let newest = backup(&conn, &dir).unwrap();
+
+        let kept = backups(&dir).unwrap();
+        assert_eq!(kept.len(), MAX_BACKUPS);
+        assert_eq!(kept[0], newest);
+        assert_eq!(kept[1..], [&old[6], &old[5], &old[4], &old[3]].map(PathBuf::clone));
+        assert!(old[..3].iter().all(|path| !path.exists()));
+        assert!(dir.join("notes.txt").exists());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
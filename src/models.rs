@@ -15,6 +15,34 @@ pub enum ContentType {
     Other(String),
 }
 
+impl ContentType {
+    /// The snake_case name used in prompts, the state database and `--type`.
+    pub fn label(&self) -> &str {
+        match self {
+            ContentType::Movie => "movie",
+            ContentType::TVShow => "tv_show",
+            ContentType::Music => "music",
+            ContentType::Software => "software",
+            ContentType::Book => "book",
+            ContentType::Game => "game",
+            ContentType::Other(other) => other,
+        }
+    }
+
+    pub fn from_label(label: &str) -> Self {
+        match label.trim().to_lowercase().replace([' ', '-'], "_").as_str() {
+            "movie" | "film" => ContentType::Movie,
+            "tv_show" | "tv" | "show" | "series" => ContentType::TVShow,
+            "music" | "album" => ContentType::Music,
+            "software" | "app" => ContentType::Software,
+            "book" | "ebook" => ContentType::Book,
+            "game" => ContentType::Game,
+            other => ContentType::Other(other.to_string()),
+        }
+    }
+}
+
+/// What a natural-language query asks for.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SearchIntent {
     pub content_type: ContentType,
@@ -24,6 +52,18 @@ pub struct SearchIntent {
     pub quality_preferences: Vec<String>,
     pub language: Option<String>,
     pub additional_context: Vec<String>,
+    /// Wants the newest upload, as in "the latest episode"
+    #[serde(default)]
+    pub prefer_recent: bool,
+}
+
+/// An intent updated by a follow-up like "only 1080p" during interactive search.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IntentRefinement {
+    pub intent: SearchIntent,
+    /// Whether the sources must be searched again, or the results already
+    /// found can simply be re-ranked against the new intent
+    pub needs_new_search: bool,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -35,6 +75,7 @@ pub struct TvDetails {
     pub complete_series: bool,
 }
 
+/// A [`TorrentResult`] scored against a [`SearchIntent`].
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct EvaluatedResult {
     pub torrent: TorrentResult,
@@ -46,6 +87,36 @@ pub struct EvaluatedResult {
     pub completeness_score: f32,
 }
 
+/// Per-episode downloads standing in for a season pack that couldn't be found.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EpisodePlan {
+    pub season: u8,
+    /// Every wanted episode in order, with the best torrent found for it
+    pub episodes: Vec<PlannedEpisode>,
+    /// The episode count came from what was found rather than the request,
+    /// so episodes after the last one found may also be missing
+    pub inferred_range: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PlannedEpisode {
+    pub episode: u8,
+    pub result: Option<EvaluatedResult>,
+}
+
+impl EpisodePlan {
+    pub fn matched(&self) -> impl Iterator<Item = (u8, &EvaluatedResult)> {
+        self.episodes.iter().filter_map(|e| e.result.as_ref().map(|r| (e.episode, r)))
+    }
+
+    pub fn missing(&self) -> Vec<u8> {
+        self.episodes.iter()
+            .filter(|e| e.result.is_none())
+            .map(|e| e.episode)
+            .collect()
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SearchStrategy {
     pub primary_queries: Vec<String>,
@@ -0,0 +1,227 @@
+use anyhow::Result;
+use futures::StreamExt;
+use librqbit::api::TorrentIdOrHash;
+use librqbit::{AddTorrent, AddTorrentOptions, AddTorrentResponse};
+use std::collections::HashSet;
+use std::net::{IpAddr, SocketAddr};
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+use tracing::{info, warn};
+
+use crate::config::Config;
+use crate::downloader;
+use crate::trackers::{self, TrackerProbe};
+
+/// Give up on the reference torrent if it has no metadata after this long.
+const METADATA_TIMEOUT: Duration = Duration::from_secs(60);
+/// How long to collect peers from the DHT.
+const DHT_WINDOW: Duration = Duration::from_secs(20);
+const PROBE_TIMEOUT: Duration = Duration::from_secs(5);
+/// Peers asked of each tracker.
+const TRACKER_NUMWANT: u32 = 200;
+/// Below this a well-seeded torrent points at the connection, not the torrent.
+const SLOW_MIB_PER_SEC: f64 = 0.1;
+
+/// Whether peers can open connections to our listen port.
+#[derive(Debug)]
+pub enum Reachability {
+    Open { public_ip: IpAddr, port: u16 },
+    Closed { public_ip: IpAddr, port: u16, error: String },
+    Unknown(String),
+}
+
+#[derive(Debug)]
+pub struct NettestReport {
+    pub name: String,
+    /// Time until the torrent was ready to download, if it ever was
+    pub metadata_secs: Option<f64>,
+    pub seconds: u64,
+    pub downloaded_bytes: u64,
+    pub average_mib_per_sec: f64,
+    pub peak_mib_per_sec: f64,
+    pub peers_seen: usize,
+    pub max_live_peers: usize,
+    pub trackers: Vec<TrackerProbe>,
+    /// `None` when the session has no DHT
+    pub dht_peers: Option<usize>,
+    /// Seen by the session but announced by neither trackers nor the DHT:
+    /// peer exchange and incoming connections
+    pub other_peers: usize,
+    pub reachability: Reachability,
+}
+
+impl NettestReport {
+    pub fn tracker_peers(&self) -> usize {
+        self.trackers.iter().flat_map(|t| &t.peers).collect::<HashSet<_>>().len()
+    }
+
+    pub fn trackers_ok(&self) -> usize {
+        self.trackers.iter().filter(|t| t.error.is_none()).count()
+    }
+
+    /// One-line conclusion, telling a broken setup apart from a dead torrent.
+    pub fn verdict(&self) -> String {
+        let discovered = self.tracker_peers() + self.dht_peers.unwrap_or(0) + self.other_peers;
+        let discovery_broken = self.trackers_ok() == 0 && self.dht_peers.unwrap_or(0) == 0;
+
+        if discovery_broken && self.peers_seen == 0 {
+            "❌ Your setup is broken: no tracker answered and the DHT found nobody. \
+             A firewall, VPN or ISP is likely blocking BitTorrent traffic."
+                .to_string()
+        } else if discovered == 0 && self.peers_seen == 0 {
+            "⚠️  Trackers answered but nobody is sharing the reference torrent. \
+             Try another one with --torrent before blaming your connection."
+                .to_string()
+        } else if self.average_mib_per_sec < SLOW_MIB_PER_SEC {
+            "❌ Your setup is the problem: peers were found but almost no data arrived. \
+             Look for throttling or traffic shaping between you and the swarm."
+                .to_string()
+        } else {
+            "✅ Your connection works. If a particular download is slow, that torrent is poorly seeded."
+                .to_string()
+        }
+    }
+}
+
+/// Download `torrent` into a scratch folder for `seconds`, then report what
+/// arrived, where the peers came from and whether our port is reachable.
+pub async fn run(torrent: &str, seconds: u64, config: &Config) -> Result<NettestReport> {
+    let dir = std::env::temp_dir().join(format!("torrentai-nettest-{}", std::process::id()));
+    std::fs::create_dir_all(&dir)?;
+
+    let result = measure(torrent, seconds, config, dir.clone()).await;
+    if let Err(e) = std::fs::remove_dir_all(&dir) {
+        warn!("Could not remove {}: {}", dir.display(), e);
+    }
+    result
+}
+
+async fn measure(torrent: &str, seconds: u64, config: &Config, dir: PathBuf) -> Result<NettestReport> {
+    // Keep the .torrent bytes around to read its trackers
+    let (add, tiers) = if torrent.starts_with("magnet:") {
+        (AddTorrent::from_url(torrent), trackers::tiers_from_magnet(torrent))
+    } else {
+        let bytes = if torrent.starts_with("http://") || torrent.starts_with("https://") {
+            reqwest::get(torrent).await?.error_for_status()?.bytes().await?.to_vec()
+        } else {
+            std::fs::read(torrent)?
+        };
+        let tiers = trackers::tiers_from_torrent_file(&bytes);
+        (AddTorrent::from_bytes(bytes), tiers)
+    };
+
+    let session = downloader::new_session(config, dir).await?;
+    let options = AddTorrentOptions {
+        overwrite: true,
+        ..Default::default()
+    };
+    let (id, handle) = match session.add_torrent(add, Some(options)).await? {
+        AddTorrentResponse::Added(id, handle) | AddTorrentResponse::AlreadyManaged(id, handle) => (id, handle),
+        AddTorrentResponse::ListOnly(_) => return Err(anyhow::anyhow!("Torrent was only listed")),
+    };
+
+    let started = Instant::now();
+    let metadata_secs = match tokio::time::timeout(METADATA_TIMEOUT, handle.wait_until_initialized()).await {
+        Ok(Ok(())) => Some(started.elapsed().as_secs_f64()),
+        Ok(Err(e)) => return Err(anyhow::anyhow!("Reference torrent failed to start: {}", e)),
+        Err(_) => None,
+    };
+    let name = handle.with_metadata(|meta| meta.info.name.as_ref().map(|n| n.to_string()))
+        .ok()
+        .flatten()
+        .unwrap_or_else(|| "reference torrent".to_string());
+    info!("Nettest: {} ready after {:?}", name, metadata_secs);
+
+    let info_hash = handle.info_hash();
+    let port = session.tcp_listen_port();
+
+    let dht_peers = async {
+        let dht = session.get_dht()?;
+        // The lookup never ends by itself; it keeps walking the DHT for more peers
+        let mut peers = dht.get_peers(info_hash, None);
+        let mut found = HashSet::new();
+        let _ = tokio::time::timeout(DHT_WINDOW, async {
+            while let Some(peer) = peers.next().await {
+                found.insert(peer);
+            }
+        }).await;
+        Some(found)
+    };
+
+    // Sample once a second; a torrent stuck without metadata still reports peers
+    let sampling = async {
+        let start_bytes = handle.stats().progress_bytes;
+        let start = Instant::now();
+        let (mut peak, mut peers_seen, mut max_live) = (0.0f64, 0, 0);
+        for _ in 0..seconds {
+            tokio::time::sleep(Duration::from_secs(1)).await;
+            if let Some(live) = handle.stats().live {
+                peak = peak.max(live.download_speed.mbps);
+                peers_seen = peers_seen.max(live.snapshot.peer_stats.seen);
+                max_live = max_live.max(live.snapshot.peer_stats.live);
+            }
+        }
+        let downloaded = handle.stats().progress_bytes.saturating_sub(start_bytes);
+        (downloaded, start.elapsed().as_secs_f64(), peak, peers_seen, max_live)
+    };
+
+    let info_hash_hex = info_hash.as_string();
+    let (probes, dht_found, (downloaded, elapsed, peak, peers_seen, max_live), reachability) = tokio::join!(
+        trackers::probe(&info_hash_hex, &tiers, port.unwrap_or(6881), TRACKER_NUMWANT),
+        dht_peers,
+        sampling,
+        check_reachability(port),
+    );
+
+    if let Err(e) = session.delete(TorrentIdOrHash::Id(id), true).await {
+        warn!("Could not remove the reference torrent from the session: {}", e);
+    }
+    let probes = probes?;
+
+    let announced: HashSet<SocketAddr> = probes.iter()
+        .flat_map(|probe| probe.peers.iter().copied())
+        .chain(dht_found.iter().flatten().copied())
+        .collect();
+
+    Ok(NettestReport {
+        name,
+        metadata_secs,
+        seconds,
+        downloaded_bytes: downloaded,
+        average_mib_per_sec: downloaded as f64 / (1024.0 * 1024.0) / elapsed.max(1.0),
+        peak_mib_per_sec: peak,
+        peers_seen,
+        max_live_peers: max_live,
+        trackers: probes,
+        dht_peers: dht_found.map(|found| found.len()),
+        // An estimate: the session's peers are counted, not matched by address
+        other_peers: peers_seen.saturating_sub(announced.len()),
+        reachability,
+    })
+}
+
+/// Connect to our own listen port through the public address. Routers
+/// without hairpin NAT refuse this even when the port is forwarded, so a
+/// failure is a strong hint rather than proof.
+async fn check_reachability(port: Option<u16>) -> Reachability {
+    let Some(port) = port else {
+        return Reachability::Unknown("the session is not listening for incoming peers".to_string());
+    };
+
+    let public_ip = match public_ip().await {
+        Ok(ip) => ip,
+        Err(e) => return Reachability::Unknown(format!("could not look up the public IP: {}", e)),
+    };
+
+    match tokio::time::timeout(PROBE_TIMEOUT, tokio::net::TcpStream::connect((public_ip, port))).await {
+        Ok(Ok(_)) => Reachability::Open { public_ip, port },
+        Ok(Err(e)) => Reachability::Closed { public_ip, port, error: e.to_string() },
+        Err(_) => Reachability::Closed { public_ip, port, error: "timed out".to_string() },
+    }
+}
+
+async fn public_ip() -> Result<IpAddr> {
+    let client = reqwest::Client::builder().timeout(PROBE_TIMEOUT).build()?;
+    let body = client.get("https://api.ipify.org").send().await?.error_for_status()?.text().await?;
+    Ok(body.trim().parse()?)
+}
@@ -0,0 +1,243 @@
+use serde::Serialize;
+use std::path::PathBuf;
+use tracing::warn;
+
+use crate::config::{DigestSchedule, NotificationEvent, NotificationsConfig};
+use crate::digest;
+use crate::secrets::SecretStore;
+use crate::state::DigestEntry;
+use crate::storage::format_bytes;
+use crate::trackers;
+
+/// Something worth telling the user about while a download runs unattended.
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum DownloadEvent {
+    Started {
+        name: String,
+    },
+    MetadataResolved {
+        name: String,
+        total_bytes: u64,
+    },
+    Completed {
+        name: String,
+        output_dir: PathBuf,
+    },
+    Failed {
+        name: String,
+        error: String,
+    },
+    WatchMatched {
+        query: String,
+        /// The best of the new results
+        name: String,
+        relevance_score: f32,
+        /// Further new results besides the best
+        others: usize,
+    },
+    Digest {
+        schedule: DigestSchedule,
+        entries: Vec<DigestEntry>,
+    },
+}
+
+impl DownloadEvent {
+    /// `None` for digests, which go out whenever a schedule is configured.
+    fn kind(&self) -> Option<NotificationEvent> {
+        match self {
+            DownloadEvent::Started { .. } => Some(NotificationEvent::Started),
+            DownloadEvent::MetadataResolved { .. } => Some(NotificationEvent::MetadataResolved),
+            DownloadEvent::Completed { .. } => Some(NotificationEvent::Completed),
+            DownloadEvent::Failed { .. } => Some(NotificationEvent::Failed),
+            DownloadEvent::WatchMatched { .. } => Some(NotificationEvent::WatchMatched),
+            DownloadEvent::Digest { .. } => None,
+        }
+    }
+
+    fn summary(&self) -> &'static str {
+        match self {
+            DownloadEvent::Started { .. } => "Download started",
+            DownloadEvent::MetadataResolved { .. } => "Download metadata resolved",
+            DownloadEvent::Completed { .. } => "Download complete",
+            DownloadEvent::Failed { .. } => "Download failed",
+            DownloadEvent::WatchMatched { .. } => "New match for a watch",
+            DownloadEvent::Digest { schedule: DigestSchedule::Weekly, .. } => "torrentai weekly digest",
+            DownloadEvent::Digest { .. } => "torrentai daily digest",
+        }
+    }
+
+    fn body(&self) -> String {
+        match self {
+            DownloadEvent::Started { name } => name.clone(),
+            DownloadEvent::MetadataResolved { name, total_bytes } => format!("{} ({})", name, format_bytes(*total_bytes)),
+            DownloadEvent::Completed { name, output_dir } => format!("{} → {}", name, output_dir.display()),
+            DownloadEvent::Failed { name, error } => format!("{}: {}", name, error),
+            DownloadEvent::WatchMatched { query, name, relevance_score, others } => {
+                let mut body = format!("\"{}\": {} ({:.0}%)", query, name, relevance_score * 100.0);
+                if *others > 0 {
+                    body.push_str(&format!(" and {} more", others));
+                }
+                body
+            }
+            DownloadEvent::Digest { entries, .. } => digest::render(entries),
+        }
+    }
+}
+
+/// Sends download events to every configured sink. Failures are logged and
+/// never affect the download.
+#[derive(Clone)]
+pub struct Notifier {
+    events: Vec<NotificationEvent>,
+    desktop: bool,
+    webhook_url: Option<String>,
+    discord_url: Option<String>,
+    /// Bot token and chat ID
+    telegram: Option<(String, String)>,
+    /// Watch matches are held for the digest instead of sent one by one
+    digest: bool,
+    /// Announces downloads nobody asked for just now, whose completions
+    /// are held for the digest too
+    unattended: bool,
+    client: reqwest::Client,
+}
+
+impl Notifier {
+    /// Build a notifier from config. Sinks whose secrets can't be read are
+    /// skipped with a warning rather than failing the download.
+    pub fn from_config(config: &NotificationsConfig) -> Self {
+        let store = if config.discord_webhook_secret.is_some() || config.telegram_bot_token_secret.is_some() {
+            SecretStore::open().map_err(|e| warn!("Notifications: {}", e)).ok()
+        } else {
+            None
+        };
+        let read_secret = |name: &str| -> Option<String> {
+            match store.as_ref()?.require(name) {
+                Ok(value) => Some(value.to_string()),
+                Err(e) => {
+                    warn!("Notifications: {}", e);
+                    None
+                }
+            }
+        };
+
+        let discord_url = config.discord_webhook_secret.as_deref().and_then(read_secret);
+        let telegram = match (&config.telegram_bot_token_secret, &config.telegram_chat_id) {
+            (Some(secret), Some(chat_id)) => read_secret(secret).map(|token| (token, chat_id.clone())),
+            _ => None,
+        };
+
+        Self {
+            events: config.events.clone(),
+            desktop: config.desktop,
+            webhook_url: config.webhook_url.clone(),
+            discord_url,
+            telegram,
+            digest: config.digest != DigestSchedule::Off,
+            unattended: false,
+            client: reqwest::Client::new(),
+        }
+    }
+
+    /// For downloads grabbed by the watchlist, watch rules or feeds. With a
+    /// digest on, their completions wait for it like watch matches do.
+    pub fn unattended(mut self) -> Self {
+        self.unattended = true;
+        self
+    }
+
+    fn held_for_digest(&self, event: &DownloadEvent) -> bool {
+        self.digest && match event {
+            DownloadEvent::WatchMatched { .. } => true,
+            DownloadEvent::Completed { .. } => self.unattended,
+            _ => false,
+        }
+    }
+
+    /// Deliver `event` to every sink and wait for them, so a notification
+    /// sent just before the process exits still goes out.
+    pub async fn send(&self, event: DownloadEvent) {
+        // Each failed sink has already been logged
+        let _ = self.try_send(event).await;
+    }
+
+    /// Like [`send`](Self::send), but fails when there were sinks to send
+    /// to and none of them took it. Events filtered out count as sent.
+    pub async fn try_send(&self, event: DownloadEvent) -> anyhow::Result<()> {
+        if event.kind().is_some_and(|kind| !self.events.contains(&kind)) {
+            return Ok(());
+        }
+        if self.held_for_digest(&event) {
+            return Ok(());
+        }
+
+        let text = format!("{}: {}", event.summary(), event.body());
+        let mut deliveries: Vec<futures::future::BoxFuture<'_, (&str, anyhow::Result<()>)>> = Vec::new();
+
+        if self.desktop {
+            let (summary, body) = (event.summary(), event.body());
+            deliveries.push(Box::pin(async move {
+                let shown = tokio::task::spawn_blocking(move || {
+                    notify_rust::Notification::new()
+                        .appname("torrentai")
+                        .summary(summary)
+                        .body(&body)
+                        .show()
+                        .map(|_| ())
+                }).await;
+                ("desktop", shown.map_err(anyhow::Error::from).and_then(|r| r.map_err(anyhow::Error::from)))
+            }));
+        }
+        if let Some(url) = &self.webhook_url {
+            let payload = serde_json::json!({
+                "event": &event,
+                "message": &text,
+                "timestamp": chrono::Utc::now().to_rfc3339(),
+            });
+            deliveries.push(Box::pin(async move { ("webhook", self.post(url, payload).await) }));
+        }
+        if let Some(url) = &self.discord_url {
+            let payload = serde_json::json!({ "content": &text });
+            deliveries.push(Box::pin(async move { ("Discord", self.post(url, payload).await) }));
+        }
+        if let Some((token, chat_id)) = &self.telegram {
+            let url = format!("https://api.telegram.org/bot{}/sendMessage", token);
+            let payload = serde_json::json!({ "chat_id": chat_id, "text": &text });
+            deliveries.push(Box::pin(async move { ("Telegram", self.post(&url, payload).await) }));
+        }
+
+        let attempted = deliveries.len();
+        let mut failed = 0;
+        for (sink, result) in futures::future::join_all(deliveries).await {
+            if let Err(e) = result {
+                warn!("Failed to send {} notification: {}", sink, e);
+                failed += 1;
+            }
+        }
+        if attempted > 0 && failed == attempted {
+            return Err(anyhow::anyhow!("No notification sink accepted it"));
+        }
+        Ok(())
+    }
+
+    /// Fire and forget, for callers that can't wait.
+    pub fn notify(&self, event: DownloadEvent) {
+        let notifier = self.clone();
+        tokio::spawn(async move { notifier.send(event).await });
+    }
+
+    async fn post(&self, url: &str, payload: serde_json::Value) -> anyhow::Result<()> {
+        self.client.post(url).json(&payload).send().await?.error_for_status()?;
+        Ok(())
+    }
+}
+
+/// Best name available before metadata arrives: the magnet's `dn`, or the
+/// file name of a URL or .torrent path.
+pub fn display_name(torrent: &str) -> String {
+    if torrent.starts_with("magnet:") {
+        return trackers::magnet_display_name(torrent).unwrap_or_else(|| "magnet link".to_string());
+    }
+    torrent.rsplit('/').next().unwrap_or(torrent).to_string()
+}
@@ -0,0 +1,248 @@
+use anyhow::Result;
+use lazy_static::lazy_static;
+use regex::Regex;
+use std::path::{Path, PathBuf};
+
+use crate::classify::{self, has_extension, is_video, AUDIO_EXTENSIONS, BOOK_EXTENSIONS};
+use crate::models::{ContentType, SearchIntent};
+use crate::ranker::episode_marker;
+use crate::state::{OrganizedFile, StateDb};
+
+lazy_static! {
+    static ref YEAR_RE: Regex = Regex::new(r"\b(19[3-9]\d|20[0-4]\d)\b").unwrap();
+    /// Where the title ends in a release name: year, episode marker, or quality tag
+    static ref TITLE_END_RE: Regex = Regex::new(
+        r"(?i)[\s.\-_(\[]+(19[3-9]\d|20[0-4]\d|s\d{1,2}e\d{1,3}|s\d{1,2}\b|season|2160p|1080p|720p|480p|4k|bluray|web-?dl|webrip|hdtv|dvdrip|x264|x265|hevc)"
+    ).unwrap();
+    /// Language suffix of a subtitle file, as in `Movie.en.srt`
+    static ref SUBTITLE_LANG_RE: Regex = Regex::new(r"\.([a-z]{2,3})$").unwrap();
+}
+
+const SUBTITLE_EXTENSIONS: &[&str] = &["srt", "ass", "ssa", "sub", "vtt"];
+
+/// A rename from a download into the media library.
+#[derive(Debug, Clone)]
+pub struct PlannedMove {
+    pub from: PathBuf,
+    pub to: PathBuf,
+}
+
+/// Plan where each video and subtitle in `files` belongs in a Plex/Jellyfin
+/// layout under `library`:
+///
+/// - `Movies/Title (Year)/Title (Year).mkv`
+/// - `TV/Show/Season 02/Show - S02E03.mkv`
+/// - `Music/Album/01 - Track.flac`
+/// - `Books/Title.epub`
+///
+/// The title and year come from `intent` when the download came from smart
+/// search, otherwise from the file names; without an intent the files are
+/// classified to tell music and books from video. Samples and other files
+/// stay put.
+pub fn plan(files: &[PathBuf], intent: Option<&SearchIntent>, library: &Path) -> Vec<PlannedMove> {
+    let content_type = match intent {
+        Some(intent) => intent.content_type.clone(),
+        None => classify::classify_paths(files).content_type,
+    };
+    match content_type {
+        ContentType::Music => return plan_into(files, AUDIO_EXTENSIONS, intent, &library.join("Music"), true),
+        ContentType::Book => return plan_into(files, BOOK_EXTENSIONS, intent, &library.join("Books"), false),
+        _ => {}
+    }
+
+    let videos: Vec<&PathBuf> = files.iter().filter(|f| is_video(f)).collect();
+    let movie_parts = videos.iter().filter(|v| episode_marker(&file_name(v)).is_none()).count();
+
+    let mut moves = Vec::new();
+    for file in files {
+        let (video, language) = if is_video(file) {
+            (file, None)
+        } else if has_extension(file, SUBTITLE_EXTENSIONS) {
+            // Subtitles follow the video they were saved next to
+            let stem = file.file_stem().map(|s| s.to_string_lossy().to_string()).unwrap_or_default();
+            let language = SUBTITLE_LANG_RE.captures(&stem).map(|c| c[1].to_string());
+            let video_stem = SUBTITLE_LANG_RE.replace(&stem, "").to_string();
+            match videos.iter().find(|v| v.file_stem().map(|s| s.to_string_lossy() == video_stem).unwrap_or(false)) {
+                Some(video) => (*video, language),
+                None => continue,
+            }
+        } else {
+            continue;
+        };
+
+        let name = file_name(video);
+        let extension = file.extension().map(|e| e.to_string_lossy().to_lowercase()).unwrap_or_default();
+        let suffix = match &language {
+            Some(language) => format!(".{}.{}", language, extension),
+            None => format!(".{}", extension),
+        };
+
+        let to = match episode_marker(&name) {
+            Some((season, episode)) => {
+                let show = intent
+                    .filter(|i| matches!(i.content_type, ContentType::TVShow))
+                    .map(|i| display_title(&i.title))
+                    .unwrap_or_else(|| title_from_release(&name));
+                library.join("TV")
+                    .join(sanitize(&show))
+                    .join(format!("Season {:02}", season))
+                    .join(format!("{} - S{:02}E{:02}{}", sanitize(&show), season, episode, suffix))
+            }
+            None => {
+                let title = intent
+                    .map(|i| display_title(&i.title))
+                    .unwrap_or_else(|| title_from_release(&name));
+                let year = intent.and_then(|i| i.year)
+                    .or_else(|| YEAR_RE.captures(&name).and_then(|c| c[1].parse().ok()));
+                let folder = match year {
+                    Some(year) => format!("{} ({})", sanitize(&title), year),
+                    None => sanitize(&title),
+                };
+                // Multi-part movies keep their own names so parts stay distinct
+                let target = if movie_parts > 1 {
+                    format!("{}{}", video.file_stem().unwrap_or_default().to_string_lossy(), suffix)
+                } else {
+                    format!("{}{}", folder, suffix)
+                };
+                library.join("Movies").join(&folder).join(target)
+            }
+        };
+
+        if &to != file {
+            moves.push(PlannedMove { from: file.clone(), to });
+        }
+    }
+
+    moves
+}
+
+/// Move files with one of `extensions` into `folder`, keeping their names;
+/// with `per_release` they go in a subfolder named after the release, as
+/// albums do.
+fn plan_into(files: &[PathBuf], extensions: &[&str], intent: Option<&SearchIntent>, folder: &Path, per_release: bool) -> Vec<PlannedMove> {
+    let folder = if per_release {
+        let title = intent
+            .map(|i| display_title(&i.title))
+            .or_else(|| classify::release_name(files).map(|name| title_from_release(&name)))
+            .unwrap_or_default();
+        folder.join(sanitize(&title))
+    } else {
+        folder.to_path_buf()
+    };
+
+    files.iter()
+        .filter(|file| classify::has_extension(file, extensions))
+        .map(|file| PlannedMove { from: file.clone(), to: folder.join(file_name(file)) })
+        .filter(|planned| planned.to != planned.from)
+        .collect()
+}
+
+/// Carry out planned moves, creating folders as needed. Falls back to copy
+/// and delete when the library is on a different filesystem.
+pub fn apply(moves: &[PlannedMove]) -> Result<()> {
+    for planned in moves {
+        if planned.to.exists() {
+            return Err(anyhow::anyhow!("{} already exists", planned.to.display()));
+        }
+        if let Some(parent) = planned.to.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        if std::fs::rename(&planned.from, &planned.to).is_err() {
+            std::fs::copy(&planned.from, &planned.to)?;
+            std::fs::remove_file(&planned.from)?;
+        }
+    }
+    Ok(())
+}
+
+/// Plan the moves for `files` and carry them out, returning what moved.
+/// Files moved out of a download are recorded in `state`, so it can still
+/// be seeded.
+pub fn organize(files: &[PathBuf], intent: Option<&SearchIntent>, library: &Path, state: &StateDb) -> Result<Vec<PlannedMove>> {
+    let moves = plan(files, intent, library);
+    apply(&moves)?;
+    state.record_organized(&moves)?;
+    Ok(moves)
+}
+
+/// Put organized files back where their torrent expects them, as hard
+/// links so the library keeps them. Files already in place are left alone.
+pub fn link_back(files: &[OrganizedFile]) -> Result<()> {
+    for file in files {
+        if file.original.exists() {
+            continue;
+        }
+        if let Some(parent) = file.original.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        if std::fs::hard_link(&file.library, &file.original).is_err() {
+            // The library is on a different filesystem
+            #[cfg(unix)]
+            std::os::unix::fs::symlink(&file.library, &file.original)?;
+            #[cfg(not(unix))]
+            std::fs::copy(&file.library, &file.original)?;
+        }
+    }
+    Ok(())
+}
+
+/// Every file under `path`, or `path` itself if it is a file.
+pub fn collect_files(path: &Path) -> Result<Vec<PathBuf>> {
+    if path.is_file() {
+        return Ok(vec![path.to_path_buf()]);
+    }
+
+    let mut files = Vec::new();
+    for entry in std::fs::read_dir(path)? {
+        let entry = entry?.path();
+        if entry.is_dir() {
+            files.extend(collect_files(&entry)?);
+        } else {
+            files.push(entry);
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+/// Best-effort title from a release name like `The.Matrix.1999.1080p.BluRay`.
+pub fn title_from_release(name: &str) -> String {
+    let stem = Path::new(name).file_stem().map(|s| s.to_string_lossy().to_string()).unwrap_or_default();
+    let title = match TITLE_END_RE.find(&stem) {
+        Some(m) if m.start() > 0 => &stem[..m.start()],
+        _ => stem.as_str(),
+    };
+    display_title(&title.replace(['.', '_'], " "))
+}
+
+/// Title-case names the heuristic parser lowercased; keep LLM casing as is.
+fn display_title(title: &str) -> String {
+    let title = title.trim();
+    if title.chars().any(|c| c.is_uppercase()) {
+        return title.to_string();
+    }
+    title.split_whitespace()
+        .map(|word| {
+            let mut chars = word.chars();
+            match chars.next() {
+                Some(first) => first.to_uppercase().chain(chars).collect(),
+                None => String::new(),
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Strip characters media servers or filesystems trip over.
+fn sanitize(name: &str) -> String {
+    name.chars()
+        .filter(|c| !matches!(c, '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|'))
+        .collect::<String>()
+        .trim()
+        .to_string()
+}
+
+fn file_name(path: &Path) -> String {
+    path.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default()
+}
+
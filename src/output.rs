@@ -0,0 +1,292 @@
+use anyhow::{Context, Result};
+use clap::Args;
+use serde::Serialize;
+use std::path::PathBuf;
+
+use crate::pirate_bay_scraper::TorrentResult;
+use crate::trackers;
+
+/// Placeholders `--format` understands.
+const FIELDS: &[&str] = &[
+    "title", "magnet", "size", "seeders", "leechers", "uploaded", "category", "info_hash", "source", "query", "score",
+];
+
+/// Print results through a user template instead of the usual listing.
+#[derive(Debug, Clone, Default, Args)]
+pub struct ResultOutput {
+    /// One line per result, e.g. '{title}\t{seeders}\t{magnet}'; \t and \n are expanded.
+    /// Fields: title, magnet, size, seeders, leechers, uploaded, category, info_hash, source, query, score
+    #[arg(long, conflicts_with = "template", value_parser = check_format)]
+    pub format: Option<String>,
+
+    /// Render every result at once with a Tera template file, given `query` and `results`
+    #[arg(long)]
+    pub template: Option<PathBuf>,
+}
+
+/// One result as templates see it. Missing values render as empty strings
+/// in `--format` and as null in Tera templates.
+#[derive(Debug, Serialize)]
+pub struct Row<'a> {
+    pub source: &'a str,
+    /// The search query that found it, for smart search results
+    pub query: Option<&'a str>,
+    pub title: &'a str,
+    pub magnet: &'a str,
+    pub size: Option<&'a str>,
+    pub seeders: Option<u32>,
+    pub leechers: Option<u32>,
+    pub uploaded: Option<&'a str>,
+    pub category: Option<&'a str>,
+    pub info_hash: Option<String>,
+    /// Relevance from smart search
+    pub score: Option<f32>,
+}
+
+impl<'a> Row<'a> {
+    /// A result from `source`, unless smart search recorded where it came from.
+    pub fn new(source: &'a str, torrent: &'a TorrentResult) -> Self {
+        let found_by = torrent.found_by.first();
+        Self {
+            source: found_by.map_or(source, |p| p.source.as_str()),
+            query: found_by.map(|p| p.query.as_str()),
+            title: &torrent.title,
+            magnet: &torrent.magnet_link,
+            size: torrent.size.as_deref(),
+            seeders: torrent.seeders,
+            leechers: torrent.leechers,
+            uploaded: torrent.uploaded.as_deref(),
+            category: torrent.category.as_deref(),
+            info_hash: trackers::magnet_info_hash(&torrent.magnet_link),
+            score: None,
+        }
+    }
+
+    pub fn with_score(mut self, score: f32) -> Self {
+        self.score = Some(score);
+        self
+    }
+
+    fn field(&self, name: &str) -> String {
+        let text = |value: Option<&str>| value.unwrap_or_default().to_string();
+        let number = |value: Option<u32>| value.map(|n| n.to_string()).unwrap_or_default();
+        match name {
+            "title" => self.title.to_string(),
+            "magnet" => self.magnet.to_string(),
+            "size" => text(self.size),
+            "seeders" => number(self.seeders),
+            "leechers" => number(self.leechers),
+            "uploaded" => text(self.uploaded),
+            "category" => text(self.category),
+            "info_hash" => text(self.info_hash.as_deref()),
+            "source" => self.source.to_string(),
+            "query" => text(self.query),
+            "score" => self.score.map(|s| format!("{:.2}", s)).unwrap_or_default(),
+            _ => String::new(),
+        }
+    }
+}
+
+enum Segment {
+    Text(String),
+    Field(String),
+}
+
+impl ResultOutput {
+    pub fn is_set(&self) -> bool {
+        self.format.is_some() || self.template.is_some()
+    }
+
+    /// Everything to print for `rows`, or `None` when no template was given.
+    pub fn render(&self, query: &str, rows: &[Row]) -> Result<Option<String>> {
+        if let Some(format) = &self.format {
+            let segments = parse_format(format)?;
+            let lines: Vec<String> = rows.iter()
+                .map(|row| segments.iter()
+                    .map(|segment| match segment {
+                        Segment::Text(text) => text.clone(),
+                        Segment::Field(name) => row.field(name),
+                    })
+                    .collect())
+                .collect();
+            return Ok(Some(lines.iter().map(|line| format!("{}\n", line)).collect()));
+        }
+
+        let Some(path) = &self.template else {
+            return Ok(None);
+        };
+        let template = std::fs::read_to_string(path)
+            .with_context(|| format!("Could not read template {}", path.display()))?;
+        let mut context = tera::Context::new();
+        context.insert("query", query);
+        context.insert("results", rows);
+        let rendered = tera::Tera::one_off(&template, &context, false)
+            .map_err(|e| anyhow::anyhow!("{}: {}", path.display(), tera_error(&e)))?;
+        Ok(Some(rendered))
+    }
+}
+
+/// Reject bad placeholders before searching rather than after.
+fn check_format(format: &str) -> Result<String, String> {
+    parse_format(format).map(|_| format.to_string()).map_err(|e| e.to_string())
+}
+
+/// Split `{field}` placeholders from literal text, expanding `\t` and `\n`
+/// and treating `{{` and `}}` as literal braces.
+fn parse_format(format: &str) -> Result<Vec<Segment>> {
+    let format = format.replace("\\t", "\t").replace("\\n", "\n");
+    let mut segments = Vec::new();
+    let mut text = String::new();
+    let mut chars = format.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '{' if chars.peek() == Some(&'{') => {
+                chars.next();
+                text.push('{');
+            }
+            '}' if chars.peek() == Some(&'}') => {
+                chars.next();
+                text.push('}');
+            }
+            '{' => {
+                let mut name = String::new();
+                let closed = chars.by_ref().any(|c| c == '}' || {
+                    name.push(c);
+                    false
+                });
+                if !closed {
+                    return Err(anyhow::anyhow!("unclosed {{{}; write {{{{ for a literal brace", name));
+                }
+                if !FIELDS.contains(&name.as_str()) {
+                    return Err(anyhow::anyhow!("unknown field {{{}}}; use one of: {}", name, FIELDS.join(", ")));
+                }
+                if !text.is_empty() {
+                    segments.push(Segment::Text(std::mem::take(&mut text)));
+                }
+                segments.push(Segment::Field(name));
+            }
+            c => text.push(c),
+        }
+    }
+    if !text.is_empty() {
+        segments.push(Segment::Text(text));
+    }
+    Ok(segments)
+}
+
+/// Tera puts the useful part (line, column, what was expected) in the source.
+fn tera_error(error: &tera::Error) -> String {
+    let mut message = error.to_string();
+    let mut source = std::error::Error::source(error);
+    while let Some(cause) = source {
+        message.push_str(&format!(": {}", cause));
+        source = cause.source();
+    }
+    message
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn results() -> Vec<TorrentResult> {
+        vec![
+            TorrentResult {
+                title: "Dune.2021.1080p.BluRay.x264".to_string(),
+                magnet_link: "magnet:?xt=urn:btih:0123456789abcdef0123456789abcdef01234567&dn=Dune".to_string(),
+                size: Some("8.2 GiB".to_string()),
+                seeders: Some(1520),
+                leechers: Some(88),
+                uploaded: Some("05-11 2022".to_string()),
+                category: Some("Video > HD - Movies".to_string()),
+                uploader: None,
+                found_by: Vec::new(),
+            },
+            TorrentResult {
+                title: "Dune.2021.720p.WEBRip".to_string(),
+                magnet_link: "magnet:?dn=Dune".to_string(),
+                size: None,
+                seeders: None,
+                leechers: None,
+                uploaded: None,
+                category: None,
+                uploader: None,
+                found_by: Vec::new(),
+            },
+        ]
+    }
+
+    fn rows(results: &[TorrentResult]) -> Vec<Row<'_>> {
+        results.iter().map(|r| Row::new("ThePirateBay", r)).collect()
+    }
+
+    fn format(format: &str) -> ResultOutput {
+        ResultOutput { format: Some(format.to_string()), template: None }
+    }
+
+    fn template(name: &str, contents: &str) -> (ResultOutput, PathBuf) {
+        let path = std::env::temp_dir().join(format!("torrentai-output-{}-{}.tera", name, std::process::id()));
+        std::fs::write(&path, contents).unwrap();
+        (ResultOutput { format: None, template: Some(path.clone()) }, path)
+    }
+
+    #[test]
+    fn nothing_to_render_without_a_template() {
+        let results = results();
+        assert_eq!(ResultOutput::default().render("dune", &rows(&results)).unwrap(), None);
+    }
+
+    #[test]
+    fn format_renders_a_line_per_row() {
+        let results = results();
+        let mut rows = rows(&results);
+        rows[0] = Row::new("ThePirateBay", &results[0]).with_score(0.876);
+        // As typed on the command line, with \t left for render to expand
+        let rendered = format(r"{title}\t{size}\t{seeders}/{leechers}\t{info_hash}\t{score} {{{source}}}")
+            .render("dune", &rows)
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            rendered,
+            "Dune.2021.1080p.BluRay.x264\t8.2 GiB\t1520/88\t0123456789abcdef0123456789abcdef01234567\t0.88 {ThePirateBay}\n\
+             Dune.2021.720p.WEBRip\t\t/\t\t {ThePirateBay}\n"
+        );
+    }
+
+    #[test]
+    fn custom_template_sees_every_row() {
+        let results = results();
+        let (output, path) = template(
+            "custom",
+            "{{ query }}:{% for r in results %} {{ r.title }} ({{ r.seeders | default(value=\"?\") }}){% endfor %}",
+        );
+        let rendered = output.render("dune", &rows(&results)).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(rendered.as_deref(), Some("dune: Dune.2021.1080p.BluRay.x264 (1520) Dune.2021.720p.WEBRip (?)"));
+    }
+
+    #[test]
+    fn invalid_templates_fail_without_output() {
+        let results = results();
+        for bad in ["{title", "{title}\t{nope}", "{}"] {
+            assert!(format(bad).render("dune", &rows(&results)).is_err(), "{}", bad);
+            assert!(check_format(bad).is_err(), "{}", bad);
+        }
+
+        // A syntax error, and one that only shows on the second row
+        for (name, contents) in [
+            ("syntax", "{% for r in results %}{{ r.title }"),
+            ("runtime", "{% for r in results %}{{ r.title }} {{ r.seeders + 1 }}\n{% endfor %}"),
+        ] {
+            let (output, path) = template(name, contents);
+            let rendered = output.render("dune", &rows(&results));
+            std::fs::remove_file(&path).unwrap();
+            let error = rendered.unwrap_err().to_string();
+            assert!(error.starts_with(&path.display().to_string()), "{}", error);
+        }
+
+        let missing = ResultOutput { format: None, template: Some(PathBuf::from("/nonexistent/template.tera")) };
+        assert!(missing.render("dune", &rows(&results)).is_err());
+    }
+}
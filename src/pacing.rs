@@ -0,0 +1,99 @@
+use futures::future::{BoxFuture, FutureExt};
+use std::collections::hash_map::RandomState;
+use std::hash::BuildHasher;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::Mutex;
+use tokio::time::Instant;
+
+use crate::scraper::{SourceQuery, SourceRegistry, TorrentResult, TorrentSource};
+
+/// How far a check may drift from its even slot, as a share of the gap
+/// between slots. Under half, so checks never swap places.
+const JITTER: f64 = 0.4;
+
+/// When to start each of `count` checks, as offsets from now: one right
+/// away, the rest evenly over `window`, each nudged by a random amount so
+/// batches from several machines or restarts don't line up.
+pub fn spread(count: usize, window: Duration) -> Vec<Duration> {
+    if count <= 1 || window.is_zero() {
+        return vec![Duration::ZERO; count];
+    }
+    let gap = window.as_secs_f64() / count as f64;
+    (0..count)
+        .map(|slot| {
+            if slot == 0 {
+                return Duration::ZERO;
+            }
+            let nudge = (random_fraction() * 2.0 - 1.0) * JITTER;
+            Duration::from_secs_f64(gap * (slot as f64 + nudge))
+        })
+        .collect()
+}
+
+/// A number in [0, 1), good enough for jitter.
+fn random_fraction() -> f64 {
+    (RandomState::new().hash_one(0u8) >> 11) as f64 / (1u64 << 53) as f64
+}
+
+/// Spaces out the searches one source gets, to at most `per_hour`.
+/// Waiters queue in turn, so a burst drains at that pace rather than all
+/// at once when the hour rolls over.
+pub struct Budget {
+    gap: Duration,
+    next: Mutex<Instant>,
+}
+
+impl Budget {
+    pub fn new(per_hour: u32) -> Self {
+        Self {
+            gap: Duration::from_secs(3600) / per_hour.max(1),
+            next: Mutex::new(Instant::now()),
+        }
+    }
+
+    /// Wait until this source may be searched again.
+    pub async fn wait(&self) {
+        let mut next = self.next.lock().await;
+        tokio::time::sleep_until(*next).await;
+        *next = Instant::now() + self.gap;
+    }
+}
+
+/// A source whose searches each wait their turn in a [`Budget`]. Cached
+/// results are served without waiting, since the source isn't asked.
+pub struct RateLimited {
+    inner: Arc<dyn TorrentSource>,
+    budget: Budget,
+}
+
+impl TorrentSource for RateLimited {
+    fn name(&self) -> &'static str {
+        self.inner.name()
+    }
+
+    fn search<'a>(&'a self, query: &'a SourceQuery) -> BoxFuture<'a, anyhow::Result<Vec<TorrentResult>>> {
+        async move {
+            self.budget.wait().await;
+            self.inner.search(query).await
+        }.boxed()
+    }
+
+    fn cache_key(&self, query: &SourceQuery) -> String {
+        self.inner.cache_key(query)
+    }
+}
+
+/// `registry`'s sources, each limited to `per_hour` searches; 0 leaves
+/// them unlimited.
+pub fn rate_limited(registry: SourceRegistry, per_hour: u32) -> SourceRegistry {
+    if per_hour == 0 {
+        return registry;
+    }
+    registry.sources().iter().fold(SourceRegistry::new(), |limited, source| {
+        limited.register(RateLimited {
+            inner: source.clone(),
+            budget: Budget::new(per_hour),
+        })
+    })
+}
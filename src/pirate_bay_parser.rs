@@ -0,0 +1,410 @@
+use lazy_static::lazy_static;
+use regex::Regex;
+use scraper::{ElementRef, Html, Selector};
+
+use crate::pirate_bay_scraper::TorrentResult;
+use crate::size;
+use crate::uploaded;
+
+/// A strategy whose results are at least this complete is used without
+/// trying the rest.
+const GOOD_CONFIDENCE: f32 = 0.75;
+
+lazy_static! {
+    static ref TABLE: Selector = Selector::parse("table").unwrap();
+    static ref RESULTS_TABLE: Selector = Selector::parse("table#searchResult").unwrap();
+    static ref ROW: Selector = Selector::parse("tr").unwrap();
+    static ref HEADER_CELL: Selector = Selector::parse("th, td").unwrap();
+    static ref CELL: Selector = Selector::parse("td").unwrap();
+    static ref LINK: Selector = Selector::parse("a").unwrap();
+    static ref MAGNET: Selector = Selector::parse("a[href^='magnet:']").unwrap();
+    static ref DET_NAME: Selector = Selector::parse(".detName a, a.detLink").unwrap();
+    static ref DET_DESC: Selector = Selector::parse(".detDesc").unwrap();
+    /// The classic layout's one-line summary under the title
+    static ref DESC_RE: Regex = Regex::new(r"(?i)uploaded\s+(.+?),\s*size\s+(.+?)(?:,\s*ul(?:ed)?\s+by\s+(.+))?$").unwrap();
+    static ref COUNT_RE: Regex = Regex::new(r"^\d+$").unwrap();
+}
+
+/// What a results page parsed to, and how far to trust it.
+#[derive(Debug, Clone)]
+pub struct ParsedPage {
+    pub results: Vec<TorrentResult>,
+    /// The strategy the results came from
+    pub strategy: &'static str,
+    /// Share of size, seeders, leechers and upload date found across the
+    /// rows, less rows that had a magnet link but couldn't be read
+    pub confidence: f32,
+    /// Rows with a magnet link that no result came from
+    pub dropped: usize,
+    /// Magnet links anywhere on the page
+    pub magnets: usize,
+}
+
+impl ParsedPage {
+    /// "fixed columns, 30 results, 92% confidence".
+    pub fn describe(&self) -> String {
+        let dropped = if self.dropped > 0 { format!(", {} rows unreadable", self.dropped) } else { String::new() };
+        format!("{}, {} results, {:.0}% confidence{}", self.strategy, self.results.len(), self.confidence * 100.0, dropped)
+    }
+}
+
+type Strategy = fn(&Html) -> Option<(Vec<TorrentResult>, usize)>;
+
+/// Tried in order; each returns its results and how many rows it dropped,
+/// or nothing when the page doesn't have the layout it reads.
+const STRATEGIES: &[(&str, Strategy)] = &[
+    ("header columns", header_columns),
+    ("fixed columns", fixed_columns),
+    ("classic layout", classic_layout),
+    ("generic table", generic_table),
+];
+
+/// Parse a search results page with whichever strategy reads it best.
+/// Strategies run in order until one is confident enough; failing that,
+/// the most useful partial reading wins.
+pub fn parse(html: &str) -> ParsedPage {
+    let document = Html::parse_document(html);
+    let magnets = document.select(&MAGNET).count();
+    let mut best: Option<ParsedPage> = None;
+
+    for (name, strategy) in STRATEGIES {
+        let Some((results, dropped)) = strategy(&document) else {
+            continue;
+        };
+        let page = ParsedPage {
+            confidence: confidence(&results, dropped),
+            results,
+            strategy: name,
+            dropped,
+            magnets,
+        };
+        if page.confidence >= GOOD_CONFIDENCE && !page.results.is_empty() {
+            return page;
+        }
+        let score = |p: &ParsedPage| p.results.len() as f32 * p.confidence;
+        if best.as_ref().is_none_or(|b| score(&page) > score(b)) {
+            best = Some(page);
+        }
+    }
+
+    best.unwrap_or(ParsedPage {
+        results: Vec::new(),
+        strategy: "none",
+        confidence: 0.0,
+        dropped: magnets,
+        magnets,
+    })
+}
+
+/// How complete the rows are, discounted by the rows that were dropped.
+fn confidence(results: &[TorrentResult], dropped: usize) -> f32 {
+    if results.is_empty() {
+        return 0.0;
+    }
+    let found: usize = results.iter()
+        .map(|r| {
+            [
+                r.size_bytes().is_some(),
+                r.seeders.is_some(),
+                r.leechers.is_some(),
+                r.uploaded_at().is_some(),
+            ].iter().filter(|&&found| found).count()
+        })
+        .sum();
+    let completeness = found as f32 / (results.len() * 4) as f32;
+    completeness * results.len() as f32 / (results.len() + dropped) as f32
+}
+
+fn text(element: ElementRef) -> String {
+    element.text()
+        .collect::<String>()
+        .replace('\u{a0}', " ")
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn non_empty(text: String) -> Option<String> {
+    Some(text).filter(|t| !t.is_empty())
+}
+
+fn magnet(row: ElementRef) -> Option<String> {
+    row.select(&MAGNET)
+        .next()
+        .and_then(|a| a.value().attr("href"))
+        .map(str::to_string)
+}
+
+/// The longest link text in an element that isn't the magnet link itself.
+fn link_title(element: ElementRef) -> Option<String> {
+    element.select(&LINK)
+        .filter(|a| a.value().attr("href").is_some_and(|href| !href.starts_with("magnet:")))
+        .map(text)
+        .filter(|t| !t.is_empty() && !t.contains("Details for"))
+        .max_by_key(|t| t.len())
+}
+
+fn count(text: &str) -> Option<u32> {
+    Some(text.trim()).filter(|t| COUNT_RE.is_match(t)).and_then(|t| t.parse().ok())
+}
+
+/// "Video (HD - Movies)" as "Video > HD - Movies".
+fn category(text: String) -> Option<String> {
+    non_empty(text.replace(" (", " > ").replace(['(', ')'], "").trim().to_string())
+}
+
+/// Rows of the results table with a magnet link, and how many of those
+/// rows `read` couldn't make a result of.
+fn read_rows(
+    table: ElementRef,
+    mut read: impl FnMut(ElementRef, &[ElementRef], String) -> Option<TorrentResult>,
+) -> (Vec<TorrentResult>, usize) {
+    let mut results = Vec::new();
+    let mut dropped = 0;
+    for row in table.select(&ROW) {
+        let Some(magnet_link) = magnet(row) else {
+            continue;
+        };
+        let cells: Vec<ElementRef> = row.select(&CELL).collect();
+        match read(row, &cells, magnet_link) {
+            Some(result) => results.push(result),
+            None => dropped += 1,
+        }
+    }
+    (results, dropped)
+}
+
+/// Columns found by their header text, so added, removed or reordered
+/// columns are still read correctly.
+fn header_columns(document: &Html) -> Option<(Vec<TorrentResult>, usize)> {
+    let table = document.select(&RESULTS_TABLE).next()?;
+    let header = table.select(&ROW).next()?;
+    let names: Vec<String> = header.select(&HEADER_CELL).map(|cell| text(cell).to_lowercase()).collect();
+    // Long headers hold sort links for several columns ("Name Order by: Uploaded, Size")
+    let column = |matches: &dyn Fn(&str) -> bool| names.iter().position(|name| name.len() <= 24 && matches(name));
+
+    let title = column(&|n| n.contains("name") || n.contains("title"))?;
+    let size = column(&|n| n.contains("size"));
+    let seeders = column(&|n| n == "se" || n.starts_with("seed"));
+    let leechers = column(&|n| n == "le" || n.starts_with("leech"));
+    // A name column alone could be any table's header
+    size.or(seeders)?;
+    let uploaded = column(&|n| n.contains("uploaded") || n.contains("date") || n == "added");
+    let uploader = column(&|n| n.contains("uled") || n.contains("uploader") || n == "by");
+    let kind = column(&|n| n == "type" || n.contains("category"));
+
+    let cell = |cells: &[ElementRef], index: Option<usize>| index.and_then(|i| cells.get(i)).map(|c| text(*c));
+    Some(read_rows(table, |_, cells, magnet_link| {
+        Some(TorrentResult {
+            title: cells.get(title).and_then(|c| link_title(*c))?,
+            magnet_link,
+            size: cell(cells, size).and_then(non_empty),
+            seeders: cell(cells, seeders).as_deref().and_then(count),
+            leechers: cell(cells, leechers).as_deref().and_then(count),
+            uploaded: cell(cells, uploaded).and_then(non_empty),
+            category: cell(cells, kind).and_then(category),
+            uploader: cell(cells, uploader).and_then(non_empty),
+            found_by: Vec::new(),
+        })
+    }))
+}
+
+/// The eight-column proxy layout by position: type, name, uploaded, the
+/// magnet link, size, seeders, leechers, uploader.
+fn fixed_columns(document: &Html) -> Option<(Vec<TorrentResult>, usize)> {
+    let table = document.select(&RESULTS_TABLE).next()?;
+    Some(read_rows(table, |_, cells, magnet_link| {
+        if cells.len() < 7 {
+            return None;
+        }
+        let cell = |i: usize| cells.get(i).map(|c| text(*c));
+        Some(TorrentResult {
+            title: link_title(cells[1])?,
+            magnet_link,
+            size: cell(4).and_then(non_empty),
+            seeders: cell(5).as_deref().and_then(count),
+            leechers: cell(6).as_deref().and_then(count),
+            uploaded: cell(2).and_then(non_empty),
+            category: cell(0).and_then(category),
+            uploader: cell(7).and_then(non_empty),
+            found_by: Vec::new(),
+        })
+    }))
+}
+
+/// The original four-column layout, with upload date, size and uploader
+/// in a line of text under the title.
+fn classic_layout(document: &Html) -> Option<(Vec<TorrentResult>, usize)> {
+    let table = document.select(&RESULTS_TABLE).next()?;
+    table.select(&DET_DESC).next()?;
+    Some(read_rows(table, |row, cells, magnet_link| {
+        let title = row.select(&DET_NAME).next().map(text).and_then(non_empty)?;
+        let desc = row.select(&DET_DESC).next().map(text).unwrap_or_default();
+        let caps = DESC_RE.captures(&desc);
+        let desc_part = |i: usize| caps.as_ref().and_then(|c| c.get(i)).map(|m| m.as_str().trim().to_string());
+        let counts: Vec<u32> = cells.iter().filter_map(|c| count(&text(*c))).collect();
+        Some(TorrentResult {
+            title,
+            magnet_link,
+            size: desc_part(2),
+            seeders: counts.len().checked_sub(2).map(|i| counts[i]),
+            leechers: counts.last().copied(),
+            uploaded: desc_part(1),
+            category: cells.first().map(|c| text(*c)).and_then(category),
+            uploader: desc_part(3),
+            found_by: Vec::new(),
+        })
+    }))
+}
+
+/// Any table row with a magnet link, its fields recognised by what they
+/// look like: a size, a date, and two counts for seeders and leechers.
+fn generic_table(document: &Html) -> Option<(Vec<TorrentResult>, usize)> {
+    let mut results = Vec::new();
+    let mut dropped = 0;
+    // Only the innermost tables, so nested layouts don't repeat rows
+    for table in document.select(&TABLE).filter(|t| t.select(&TABLE).nth(1).is_none()) {
+        let (found, missed) = read_rows(table, |row, cells, magnet_link| {
+            let title = row.select(&DET_NAME).next().map(text).and_then(non_empty)
+                .or_else(|| link_title(row))?;
+            let texts: Vec<String> = cells.iter()
+                .map(|c| text(*c))
+                .filter(|t| !t.contains(&title))
+                .collect();
+            let counts: Vec<u32> = texts.iter().filter_map(|t| count(t)).collect();
+            let desc = texts.iter().find_map(|t| DESC_RE.captures(t));
+            Some(TorrentResult {
+                size: texts.iter()
+                    // Short, so a size inside a line of text isn't taken for the cell
+                    .find(|t| t.len() <= 16 && size::parse_bytes(t).is_some())
+                    .cloned()
+                    .or_else(|| desc.as_ref().map(|c| c[2].trim().to_string())),
+                seeders: counts.len().checked_sub(2).map(|i| counts[i]),
+                leechers: counts.last().copied().filter(|_| counts.len() >= 2),
+                uploaded: texts.iter()
+                    .find(|t| uploaded::parse(t).is_some())
+                    .cloned()
+                    .or_else(|| desc.as_ref().map(|c| c[1].trim().to_string())),
+                category: None,
+                uploader: desc.as_ref().and_then(|c| c.get(3)).map(|m| m.as_str().trim().to_string()),
+                found_by: Vec::new(),
+                title,
+                magnet_link,
+            })
+        });
+        results.extend(found);
+        dropped += missed;
+    }
+    Some((results, dropped))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The results table as the proxies serve it today.
+    const CURRENT: &str = r#"<html><body>
+<table id="searchResult">
+  <thead>
+    <tr><th>Type</th><th>Name</th><th>Uploaded</th><th></th><th>Size</th><th>SE</th><th>LE</th><th>ULed by</th></tr>
+  </thead>
+  <tr>
+    <td><a href="/browse/207">Video (HD - Movies)</a></td>
+    <td><a href="/torrent/1" title="Details for Dune.2021.1080p.BluRay.x264">Dune.2021.1080p.BluRay.x264</a></td>
+    <td>05-11 2022</td>
+    <td><a href="magnet:?xt=urn:btih:0000000000000000000000000000000000000001">&nbsp;</a></td>
+    <td>2.5&nbsp;GiB</td>
+    <td>1520</td>
+    <td>88</td>
+    <td>rarbg</td>
+  </tr>
+  <tr>
+    <td><a href="/browse/201">Video (Movies)</a></td>
+    <td><a href="/torrent/2">Dune.2021.720p.WEBRip.x264</a></td>
+    <td>Y-day 14:32</td>
+    <td><a href="magnet:?xt=urn:btih:0000000000000000000000000000000000000002">&nbsp;</a></td>
+    <td>1.1&nbsp;GiB</td>
+    <td>340</td>
+    <td>12</td>
+    <td>yts</td>
+  </tr>
+</table>
+</body></html>"#;
+
+    /// The same page once the table lost its id.
+    const NO_TABLE_ID: &str = r#"<html><body>
+<table class="results">
+  <tr><th>Type</th><th>Name</th><th>Uploaded</th><th></th><th>Size</th><th>SE</th><th>LE</th><th>ULed by</th></tr>
+  <tr>
+    <td>Video (HD - Movies)</td>
+    <td><a href="/torrent/1">Dune.2021.1080p.BluRay.x264</a></td>
+    <td>05-11 2022</td>
+    <td><a href="magnet:?xt=urn:btih:0000000000000000000000000000000000000001">&nbsp;</a></td>
+    <td>2.5&nbsp;GiB</td>
+    <td>1520</td>
+    <td>88</td>
+    <td>rarbg</td>
+  </tr>
+  <tr>
+    <td>Video (Movies)</td>
+    <td><a href="/torrent/2">Dune.2021.720p.WEBRip.x264</a></td>
+    <td>Y-day 14:32</td>
+    <td><a href="magnet:?xt=urn:btih:0000000000000000000000000000000000000002">&nbsp;</a></td>
+    <td>1.1&nbsp;GiB</td>
+    <td>340</td>
+    <td>12</td>
+    <td>yts</td>
+  </tr>
+</table>
+</body></html>"#;
+
+    /// A challenge page in place of results, with a stray magnet link.
+    const GARBAGE: &str = r#"<html><body>
+<h1>Checking your browser before accessing</h1>
+<p>Please wait. <a href="magnet:?xt=urn:btih:0000000000000000000000000000000000000003">mirror</a></p>
+</body></html>"#;
+
+    #[test]
+    fn current_layout() {
+        let page = parse(CURRENT);
+        assert_eq!(page.strategy, "header columns");
+        assert_eq!(page.confidence, 1.0);
+        assert_eq!((page.dropped, page.magnets), (0, 2));
+
+        let titles: Vec<&str> = page.results.iter().map(|r| r.title.as_str()).collect();
+        assert_eq!(titles, ["Dune.2021.1080p.BluRay.x264", "Dune.2021.720p.WEBRip.x264"]);
+        let first = &page.results[0];
+        assert_eq!(first.magnet_link, "magnet:?xt=urn:btih:0000000000000000000000000000000000000001");
+        assert_eq!(first.size.as_deref(), Some("2.5 GiB"));
+        assert_eq!((first.seeders, first.leechers), (Some(1520), Some(88)));
+        assert_eq!(first.uploaded.as_deref(), Some("05-11 2022"));
+        assert_eq!(first.category.as_deref(), Some("Video > HD - Movies"));
+        assert_eq!(first.uploader.as_deref(), Some("rarbg"));
+        assert_eq!(page.results[1].uploaded.as_deref(), Some("Y-day 14:32"));
+    }
+
+    #[test]
+    fn layout_without_the_table_id() {
+        let page = parse(NO_TABLE_ID);
+        assert_eq!(page.strategy, "generic table");
+        assert_eq!(page.confidence, 1.0);
+        assert_eq!((page.dropped, page.magnets), (0, 2));
+
+        let titles: Vec<&str> = page.results.iter().map(|r| r.title.as_str()).collect();
+        assert_eq!(titles, ["Dune.2021.1080p.BluRay.x264", "Dune.2021.720p.WEBRip.x264"]);
+        let first = &page.results[0];
+        assert_eq!(first.size.as_deref(), Some("2.5 GiB"));
+        assert_eq!((first.seeders, first.leechers), (Some(1520), Some(88)));
+        assert_eq!(first.uploaded.as_deref(), Some("05-11 2022"));
+        assert_eq!(first.category, None);
+    }
+
+    #[test]
+    fn garbage_page() {
+        let page = parse(GARBAGE);
+        assert_eq!(page.strategy, "generic table");
+        assert_eq!(page.confidence, 0.0);
+        assert!(page.results.is_empty());
+        assert_eq!(page.magnets, 1);
+    }
+}
@@ -1,9 +1,28 @@
 use anyhow::Result;
-use reqwest;
-use scraper::{Html, Selector};
+use chrono::{DateTime, Duration, Local, NaiveDateTime, Utc};
 use serde::{Deserialize, Serialize};
-use tracing::info;
+use std::collections::HashMap;
+use tracing::{info, warn};
 
+use crate::config::{self, Config, PirateBayConfig};
+use crate::pirate_bay_parser;
+use crate::state::StateDb;
+use crate::size;
+use crate::storage::format_bytes;
+use crate::uploaded;
+
+/// Markers of a Cloudflare interstitial instead of the page we asked for.
+const CLOUDFLARE_MARKERS: &[&str] = &[
+    "Just a moment...",
+    "challenge-platform",
+    "cf-browser-verification",
+    "Attention Required! | Cloudflare",
+];
+
+/// Below this, results are used but a warning says they may be misread.
+const LOW_CONFIDENCE: f32 = 0.5;
+
+/// One torrent as a source listed it.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TorrentResult {
     pub title: String,
@@ -12,130 +31,385 @@ pub struct TorrentResult {
     pub seeders: Option<u32>,
     pub leechers: Option<u32>,
     pub uploaded: Option<String>,
+    /// As the source labels it, e.g. "Video > HD - Movies"
+    #[serde(default)]
+    pub category: Option<String>,
+    /// Account that posted it, where the source says
+    #[serde(default)]
+    pub uploader: Option<String>,
+    /// Each source and query that turned it up in a smart search, first
+    /// finder first
+    #[serde(default)]
+    pub found_by: Vec<Provenance>,
+}
+
+/// Where a result came from: a source and the query sent to it.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Provenance {
+    pub source: String,
+    pub query: String,
+}
+
+impl Provenance {
+    /// "ThePirateBay for \"dune 2021 1080p\"".
+    pub fn describe(&self) -> String {
+        format!("{} for \"{}\"", self.source, self.query)
+    }
+}
+
+impl TorrentResult {
+    /// The advertised size in bytes, whichever way the source wrote it.
+    pub fn size_bytes(&self) -> Option<u64> {
+        self.size.as_deref().and_then(size::parse_bytes)
+    }
+
+    /// When it was uploaded, in local time, whichever way the source wrote it.
+    pub fn uploaded_at(&self) -> Option<NaiveDateTime> {
+        self.uploaded.as_deref().and_then(uploaded::parse)
+    }
+}
+
+/// How a mirror has been behaving, persisted so a dead mirror is skipped
+/// across runs until its backoff expires.
+#[derive(Debug, Clone)]
+pub struct MirrorHealth {
+    pub url: String,
+    /// Consecutive failures since the last good response
+    pub failures: u32,
+    pub last_error: Option<String>,
+    pub last_success: Option<DateTime<Utc>>,
+    /// Not tried again before this, unless every mirror is backing off
+    pub retry_after: Option<DateTime<Utc>>,
+}
+
+impl MirrorHealth {
+    fn new(url: &str) -> Self {
+        Self {
+            url: url.to_string(),
+            failures: 0,
+            last_error: None,
+            last_success: None,
+            retry_after: None,
+        }
+    }
 }
 
 pub struct PirateBayScraper {
     client: reqwest::Client,
-    base_url: String,
+    config: PirateBayConfig,
+}
+
+impl Default for PirateBayScraper {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl PirateBayScraper {
     pub fn new() -> Self {
+        let config = Config::load()
+            .map(|config| config.pirate_bay)
+            .unwrap_or_else(|e| {
+                warn!("Using default ThePirateBay mirrors: {}", e);
+                PirateBayConfig::default()
+            });
         let client = reqwest::Client::builder()
-            .timeout(std::time::Duration::from_secs(30))
+            .timeout(std::time::Duration::from_secs(config.timeout_secs))
             .user_agent("Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36")
             .build()
             .expect("Failed to create HTTP client");
         
         Self {
             client,
-            base_url: "https://thepiratebay10.info".to_string(),
+            config,
         }
     }
     
+    /// Search the JSON API, or failing that the first mirror that answers
+    /// with a real results page. Backends backing off after recent failures
+    /// are skipped unless none are left.
     pub async fn search(&self, query: &str) -> Result<Vec<TorrentResult>> {
-        let search_url = format!("{}/search/{}/1/99/0", self.base_url, urlencoding::encode(query));
-        info!("Searching: {}", search_url);
+        let health = load_health();
+        let now = Utc::now();
+        let ready = |mirror: &&String| health.get(*mirror)
+            .and_then(|h| h.retry_after)
+            .is_none_or(|retry| retry <= now);
         
-        let response = self.client.get(&search_url).send().await?;
+        // The API is preferred, and its health tracked like a mirror's
+        let api = Some(&self.config.api_url).filter(|url| !url.is_empty());
+        let backends: Vec<&String> = api.into_iter().chain(&self.config.mirrors).collect();
+        let mut mirrors: Vec<&String> = backends.iter().copied().filter(ready).collect();
+        if mirrors.is_empty() {
+            let soonest = backends.iter()
+                .min_by_key(|mirror| health.get(**mirror).and_then(|h| h.retry_after));
+            mirrors.extend(soonest);
+        }
         
-        if !response.status().is_success() {
-            return Err(anyhow::anyhow!("HTTP error: {}", response.status()));
+        let mut errors = Vec::new();
+        for mirror in mirrors {
+            let mut mirror_health = health.get(mirror).cloned().unwrap_or_else(|| MirrorHealth::new(mirror));
+            let found = if Some(mirror) == api {
+                self.search_api(mirror, query).await
+            } else {
+                self.search_mirror(mirror, query).await
+            };
+            match found {
+                Ok(results) => {
+                    mirror_health.failures = 0;
+                    mirror_health.last_error = None;
+                    mirror_health.last_success = Some(Utc::now());
+                    mirror_health.retry_after = None;
+                    save_health(&mirror_health);
+                    return Ok(results);
+                }
+                Err(e) => {
+                    warn!("ThePirateBay mirror {} failed: {}", mirror, e);
+                    mirror_health.failures += 1;
+                    mirror_health.last_error = Some(e.to_string());
+                    mirror_health.retry_after = Some(Utc::now() + self.backoff(mirror_health.failures));
+                    save_health(&mirror_health);
+                    errors.push(format!("{}: {}", mirror, e));
+                }
+            }
         }
         
+        Err(anyhow::anyhow!("every mirror failed ({})", errors.join("; ")))
+    }
+    
+    /// Search an apibay-style `q.php` endpoint, which returns the same data
+    /// as the HTML pages as JSON.
+    async fn search_api(&self, api_url: &str, query: &str) -> Result<Vec<TorrentResult>> {
+        let search_url = format!("{}/q.php", api_url.trim_end_matches('/'));
+        info!("Searching: {} for \"{}\"", search_url, query);
+        
+        let response = self.client.get(&search_url)
+            .query(&[("q", query), ("cat", "0")])
+            .send()
+            .await?;
+        let status = response.status();
+        if !status.is_success() {
+            return Err(anyhow::anyhow!("HTTP error: {}", status));
+        }
+        let json_content = response.text().await?;
+        
+        if std::env::var("DEBUG_JSON").is_ok() {
+            std::fs::write("debug_apibay_results.json", &json_content)?;
+            info!("Saved JSON to debug_apibay_results.json");
+        }
+        
+        let entries: Vec<ApiTorrent> = serde_json::from_str(&json_content)
+            .map_err(|e| anyhow::anyhow!("unexpected API response: {}", e))?;
+        // No hits come back as a single placeholder with an all-zero hash
+        let results: Vec<TorrentResult> = entries.into_iter()
+            .filter(|entry| entry.info_hash.chars().any(|c| c != '0'))
+            .map(ApiTorrent::into_result)
+            .collect();
+        
+        info!("Found {} results", results.len());
+        Ok(results)
+    }
+    
+    async fn search_mirror(&self, mirror: &str, query: &str) -> Result<Vec<TorrentResult>> {
+        let search_url = format!("{}/search/{}/1/99/0", mirror.trim_end_matches('/'), urlencoding::encode(query));
+        info!("Searching: {}", search_url);
+        
+        let response = self.client.get(&search_url).send().await?;
+        let status = response.status();
+        let challenged = response.headers().get("cf-mitigated").is_some_and(|v| v == "challenge");
         let html_content = response.text().await?;
         
+        // Checked first: challenges come back as 403 or 503, and sometimes 200
+        if challenged || CLOUDFLARE_MARKERS.iter().any(|marker| html_content.contains(marker)) {
+            return Err(anyhow::anyhow!("blocked by a Cloudflare challenge"));
+        }
+        if !status.is_success() {
+            return Err(anyhow::anyhow!("HTTP error: {}", status));
+        }
+        
         // Debug: Save HTML to file for inspection
         if std::env::var("DEBUG_HTML").is_ok() {
             std::fs::write("debug_search_results.html", &html_content)?;
             info!("Saved HTML to debug_search_results.html");
         }
         
-        self.parse_search_results(&html_content)
-    }
-    
-    fn parse_search_results(&self, html: &str) -> Result<Vec<TorrentResult>> {
-        let document = Html::parse_document(html);
-        let mut results = Vec::new();
-        
-        // Find the search results table
-        let table_selector = Selector::parse("table#searchResult").unwrap();
-        let row_selector = Selector::parse("tr").unwrap();
-        let td_selector = Selector::parse("td").unwrap();
-        let link_selector = Selector::parse("a").unwrap();
-        let magnet_selector = Selector::parse("a[href^='magnet:']").unwrap();
+        let page = pirate_bay_parser::parse(&html_content);
+        info!("Parsed {}", page.describe());
         
-        if let Some(table) = document.select(&table_selector).next() {
-            for row in table.select(&row_selector) {
-                let td_elements: Vec<_> = row.select(&td_selector).collect();
-                
-                // Skip rows that don't have enough columns (header row, etc.)
-                if td_elements.len() < 4 {
-                    continue;
-                }
-                
-                // Extract title from second td element
-                let title = if let Some(title_td) = td_elements.get(1) {
-                    if let Some(link) = title_td.select(&link_selector).next() {
-                        let title_text = link.text().collect::<String>().trim().to_string();
-                        if !title_text.is_empty() && title_text.contains("Details for") == false {
-                            Some(title_text)
-                        } else {
-                            None
-                        }
-                    } else {
-                        None
-                    }
-                } else {
-                    None
-                };
-                
-                if title.is_none() {
-                    continue;
-                }
-                
-                // Extract magnet link
-                let magnet_link = if let Some(elem) = row.select(&magnet_selector).next() {
-                    Some(elem.value().attr("href").unwrap_or("").to_string())
-                } else {
-                    None
-                };
-                
-                if magnet_link.is_none() || !magnet_link.as_ref().unwrap().starts_with("magnet:") {
-                    continue;
-                }
-                
-                // Extract size from 5th column (index 4)
-                let size = td_elements.get(4)
-                    .map(|td| td.text().collect::<String>().trim().to_string())
-                    .filter(|s| !s.is_empty());
-                
-                // Extract seeders from 6th column (index 5)
-                let seeders = td_elements.get(5)
-                    .and_then(|td| td.text().collect::<String>().trim().parse::<u32>().ok());
-                
-                // Extract leechers from 7th column (index 6) 
-                let leechers = td_elements.get(6)
-                    .and_then(|td| td.text().collect::<String>().trim().parse::<u32>().ok());
-                
-                // Extract upload date from 3rd column (index 2)
-                let uploaded = td_elements.get(2)
-                    .map(|td| td.text().collect::<String>().trim().to_string())
-                    .filter(|s| !s.is_empty());
-                
-                results.push(TorrentResult {
-                    title: title.unwrap(),
-                    magnet_link: magnet_link.unwrap(),
-                    size,
-                    seeders,
-                    leechers,
-                    uploaded,
-                });
+        if page.results.is_empty() && !html_content.contains("No hits") {
+            self.dump_unparsed(mirror, &html_content);
+            // Magnet links nobody could read mean the layout moved under the parser
+            if page.magnets > 0 {
+                return Err(anyhow::anyhow!("layout changed: {} magnet links but no readable results", page.magnets));
             }
+            // A genuine empty search still has the results table or says "No hits";
+            // anything else is a parked domain, an error page or a changed layout
+            if !html_content.contains("searchResult") {
+                return Err(anyhow::anyhow!("page has no search results (mirror down or layout changed)"));
+            }
+            warn!("{} returned an empty results table without saying \"No hits\"; if \"{}\" should have results, its layout may have changed", mirror, query);
+        } else if page.confidence < LOW_CONFIDENCE {
+            warn!("Results from {} read with low confidence ({}); sizes, seeders or dates may be wrong or missing", mirror, page.describe());
         }
-        
-        info!("Found {} results", results.len());
-        Ok(results)
+        Ok(page.results)
+    }
+    
+    /// Exponential backoff after consecutive failures, capped at `max_backoff_secs`.
+    fn backoff(&self, failures: u32) -> Duration {
+        let secs = self.config.retry_base_secs
+            .saturating_mul(1u64 << failures.saturating_sub(1).min(16))
+            .min(self.config.max_backoff_secs);
+        Duration::seconds(secs as i64)
+    }
+    
+    /// Keep a page that couldn't be read, if configured to, for working
+    /// out what changed.
+    fn dump_unparsed(&self, mirror: &str, html: &str) {
+        if !self.config.dump_unparsed {
+            return;
+        }
+        let host = mirror.split("://").last().unwrap_or(mirror).trim_end_matches('/').replace(['/', ':'], "_");
+        let dir = config::config_dir().join("unparsed");
+        let path = dir.join(format!("{}-{}.html", host, Utc::now().format("%Y%m%d-%H%M%S")));
+        match std::fs::create_dir_all(&dir).and_then(|_| std::fs::write(&path, html)) {
+            Ok(()) => warn!("Saved the page from {} to {}", mirror, path.display()),
+            Err(e) => warn!("Failed to save the page from {}: {}", mirror, e),
+        }
+    }
+}
+
+/// One result from the JSON API. Numbers come back as strings.
+#[derive(Debug, Deserialize)]
+struct ApiTorrent {
+    name: String,
+    info_hash: String,
+    seeders: String,
+    leechers: String,
+    size: String,
+    /// Unix time
+    added: String,
+    category: String,
+    username: String,
+}
+
+impl ApiTorrent {
+    fn into_result(self) -> TorrentResult {
+        let magnet_link = format!(
+            "magnet:?xt=urn:btih:{}&dn={}{}",
+            self.info_hash.to_lowercase(),
+            ::urlencoding::encode(&self.name),
+            API_TRACKERS.iter().map(|tracker| format!("&tr={}", ::urlencoding::encode(tracker))).collect::<String>()
+        );
+        TorrentResult {
+            magnet_link,
+            size: self.size.parse::<u64>().ok().filter(|&bytes| bytes > 0).map(format_bytes),
+            seeders: self.seeders.parse().ok(),
+            leechers: self.leechers.parse().ok(),
+            // The HTML pages' format, in local time like every parsed date
+            uploaded: self.added.parse::<i64>().ok()
+                .and_then(|secs| DateTime::from_timestamp(secs, 0))
+                .map(|added| added.with_timezone(&Local).format("%Y-%m-%d %H:%M").to_string()),
+            category: category_name(&self.category),
+            uploader: Some(self.username).filter(|u| !u.is_empty()),
+            found_by: Vec::new(),
+            title: self.name,
+        }
+    }
+}
+
+/// Trackers ThePirateBay's own magnet links carry.
+const API_TRACKERS: &[&str] = &[
+    "udp://tracker.opentrackr.org:1337/announce",
+    "udp://open.stealth.si:80/announce",
+    "udp://tracker.torrent.eu.org:451/announce",
+    "udp://tracker.openbittorrent.com:6969/announce",
+    "udp://open.demonii.com:1337/announce",
+    "udp://exodus.desync.com:6969/announce",
+];
+
+/// "Video > HD - Movies" for the API's category code 207, as the HTML pages
+/// label it.
+fn category_name(code: &str) -> Option<String> {
+    let name = match code {
+        "100" => "Audio",
+        "101" => "Audio > Music",
+        "102" => "Audio > Audio books",
+        "103" => "Audio > Sound clips",
+        "104" => "Audio > FLAC",
+        "199" => "Audio > Other",
+        "200" => "Video",
+        "201" => "Video > Movies",
+        "202" => "Video > Movies DVDR",
+        "203" => "Video > Music videos",
+        "204" => "Video > Movie clips",
+        "205" => "Video > TV shows",
+        "206" => "Video > Handheld",
+        "207" => "Video > HD - Movies",
+        "208" => "Video > HD - TV shows",
+        "209" => "Video > 3D",
+        "211" => "Video > UHD/4k - Movies",
+        "212" => "Video > UHD/4k - TV shows",
+        "299" => "Video > Other",
+        "300" => "Applications",
+        "301" => "Applications > Windows",
+        "302" => "Applications > Mac",
+        "303" => "Applications > UNIX",
+        "304" => "Applications > Handheld",
+        "305" => "Applications > IOS (iPad/iPhone)",
+        "306" => "Applications > Android",
+        "399" => "Applications > Other OS",
+        "400" => "Games",
+        "401" => "Games > PC",
+        "402" => "Games > Mac",
+        "403" => "Games > PSx",
+        "404" => "Games > XBOX360",
+        "405" => "Games > Wii",
+        "406" => "Games > Handheld",
+        "407" => "Games > IOS (iPad/iPhone)",
+        "408" => "Games > Android",
+        "499" => "Games > Other",
+        "601" => "Other > E-books",
+        "602" => "Other > Comics",
+        "603" => "Other > Pictures",
+        "604" => "Other > Covers",
+        "605" => "Other > Physibles",
+        "600" | "699" => "Other",
+        _ if code.starts_with('5') => "Porn",
+        _ => return None,
+    };
+    Some(name.to_string())
+}
+
+/// Mirror health by URL. Without the database every mirror looks healthy.
+fn load_health() -> HashMap<String, MirrorHealth> {
+    match StateDb::open().and_then(|state| state.mirror_health()) {
+        Ok(health) => health.into_iter().map(|h| (h.url.clone(), h)).collect(),
+        Err(e) => {
+            warn!("Mirror health unavailable: {}", e);
+            HashMap::new()
+        }
+    }
+}
+
+fn save_health(health: &MirrorHealth) {
+    if let Err(e) = StateDb::open().and_then(|state| state.upsert_mirror(health)) {
+        warn!("Failed to save mirror health: {}", e);
+    }
+}
+
+/// Human readable summary of a mirror's health for `status`.
+pub fn describe(health: &MirrorHealth) -> String {
+    let now = Utc::now();
+    match (&health.last_error, health.retry_after) {
+        (Some(error), Some(retry)) if retry > now => {
+            let wait = (retry - now).num_seconds();
+            let wait = if wait >= 60 { format!("{}m", wait / 60) } else { format!("{}s", wait) };
+            format!("failing x{} ({}), retry in {}", health.failures, error, wait)
+        }
+        (Some(error), _) => format!("failing x{} ({}), will be retried", health.failures, error),
+        (None, _) => match health.last_success {
+            Some(at) => format!("ok, last worked {}", at.format("%Y-%m-%d %H:%M")),
+            None => "not tried yet".to_string(),
+        },
     }
 }
 
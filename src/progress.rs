@@ -0,0 +1,94 @@
+use std::collections::VecDeque;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use crate::storage::format_bytes;
+
+/// How far back rolling speeds look.
+const WINDOW: Duration = Duration::from_secs(10);
+
+/// Download and upload speeds over the last few seconds: steadier than a
+/// single reading, and quicker to follow a stall than an average over the
+/// whole download.
+#[derive(Debug, Default)]
+pub struct SpeedMeter {
+    /// (when, downloaded bytes, uploaded bytes)
+    samples: VecDeque<(Instant, u64, u64)>,
+}
+
+impl SpeedMeter {
+    pub fn sample(&mut self, downloaded: u64, uploaded: u64) {
+        let now = Instant::now();
+        self.samples.push_back((now, downloaded, uploaded));
+        // Keep one sample from before the window so the rate spans all of it
+        while self.samples.len() > 2 && now.duration_since(self.samples[1].0) >= WINDOW {
+            self.samples.pop_front();
+        }
+    }
+
+    /// Download and upload speeds in bytes per second.
+    pub fn rates(&self) -> (f64, f64) {
+        let (Some(first), Some(last)) = (self.samples.front(), self.samples.back()) else {
+            return (0.0, 0.0);
+        };
+        let secs = last.0.duration_since(first.0).as_secs_f64();
+        if secs <= 0.0 {
+            return (0.0, 0.0);
+        }
+        (
+            last.1.saturating_sub(first.1) as f64 / secs,
+            last.2.saturating_sub(first.2) as f64 / secs,
+        )
+    }
+
+    /// Time left for `remaining` bytes at the current download speed; none
+    /// while nothing is arriving.
+    pub fn eta(&self, remaining: u64) -> Option<Duration> {
+        let (download, _) = self.rates();
+        (remaining > 0 && download >= 1.0).then(|| Duration::from_secs_f64(remaining as f64 / download))
+    }
+}
+
+pub fn format_rate(bytes_per_sec: f64) -> String {
+    format!("{}/s", format_bytes(bytes_per_sec as u64))
+}
+
+/// "37s", "4m 12s" or "2h 05m".
+pub fn format_duration(duration: Duration) -> String {
+    let secs = duration.as_secs();
+    if secs >= 3600 {
+        format!("{}h {:02}m", secs / 3600, secs % 3600 / 60)
+    } else if secs >= 60 {
+        format!("{}m {:02}s", secs / 60, secs % 60)
+    } else {
+        format!("{}s", secs)
+    }
+}
+
+/// One line of a running download: progress, speeds, peers and ETA.
+pub fn status_line(name: &str, progress: u64, total: u64, meter: &SpeedMeter, peers: usize, seen: usize) -> String {
+    let percent = if total > 0 { progress as f64 / total as f64 * 100.0 } else { 0.0 };
+    let (download, upload) = meter.rates();
+    let eta = meter.eta(total.saturating_sub(progress))
+        .map(format_duration)
+        .unwrap_or_else(|| "--".to_string());
+    format!("{}: {:.1}% of {} | ↓ {} ↑ {} | {} peers ({} seen) | ETA {}",
+            name, percent, format_bytes(total), format_rate(download), format_rate(upload), peers, seen, eta)
+}
+
+/// What a finished download took and where it ended up.
+pub fn print_summary(name: &str, output_dir: &Path, elapsed: Duration, downloaded: u64, files: &[PathBuf]) {
+    let location = Some(output_dir.join(name))
+        .filter(|path| path.exists())
+        .unwrap_or_else(|| output_dir.to_path_buf());
+    let average = downloaded as f64 / elapsed.as_secs_f64().max(1.0);
+
+    println!("\n✅ Downloaded {}", name);
+    println!("   Took {} | {} at {} on average", format_duration(elapsed), format_bytes(downloaded), format_rate(average));
+    println!("   Saved to {}", location.display());
+    for file in files {
+        let size = std::fs::metadata(file).map(|m| m.len()).unwrap_or(0);
+        let shown = file.strip_prefix(&location).unwrap_or(file);
+        println!("   {:>10}  {}", format_bytes(size), shown.display());
+    }
+}
@@ -15,6 +15,7 @@ Extract the following information:
 5. Quality preferences (1080p, 4K, BluRay, etc.)
 6. Language preferences
 7. Any other relevant context
+8. Whether they want the most recent upload (e.g. "latest", "newest"); for "the latest episode" leave season and episode null
 
 Respond with ONLY valid JSON in this format:
 {{
@@ -30,7 +31,8 @@ Respond with ONLY valid JSON in this format:
     }},
     "quality_preferences": [],
     "language": null,
-    "additional_context": []
+    "additional_context": [],
+    "prefer_recent": false
 }}
 "#, query)
 }
@@ -39,12 +41,13 @@ pub fn build_evaluation_prompt(intent: &SearchIntent, results: &[TorrentResult])
     format!(r#"
 You are evaluating torrent search results for relevance.
 
-User wants: {} - {}{}
+User wants: {} - {}{}{}{}
 
-Results to evaluate:
+Results to evaluate (id: title):
 {}
 
 For each result, provide:
+0. The result's id, exactly as listed above
 1. Relevance score (0.0-1.0) - how well it matches the request
 2. Confidence (0.0-1.0) - how sure you are about the match
 3. Match reasons - why this is or isn't a good match
@@ -52,8 +55,9 @@ For each result, provide:
 5. Quality score (0.0-1.0) - based on resolution, encoding, source
 6. Completeness score (0.0-1.0) - does it have everything requested?
 
-Respond with a JSON array of evaluations in order. Each evaluation should have this structure:
+Respond with a JSON array containing one evaluation per result, in any order. Each evaluation should have this structure:
 {{
+    "id": 1,
     "relevance_score": 0.95,
     "confidence": 0.9,
     "match_reasons": ["Complete season 2", "High quality BluRay"],
@@ -68,21 +72,55 @@ Respond with a JSON array of evaluations in order. Each evaluation should have t
             _ => "Content",
         },
         intent.title,
-        if let Some(tv) = &intent.tv_details {
-            format!(" Season {}", tv.season.unwrap_or(0))
-        } else {
-            String::new()
-        },
+        tv_line(intent),
+        preferences_line(intent),
+        recency_line(intent),
         results.iter().enumerate()
-            .map(|(i, r)| format!("{}: {}", i + 1, r.title))
+            .map(|(i, r)| match r.uploaded_at().filter(|_| intent.prefer_recent) {
+                Some(uploaded) => format!("{}: {} (uploaded {})", i + 1, r.title, uploaded.format("%Y-%m-%d")),
+                None => format!("{}: {}", i + 1, r.title),
+            })
             .collect::<Vec<_>>()
             .join("\n")
     )
 }
 
+/// For "latest" requests, today's date so upload dates can be judged.
+fn recency_line(intent: &SearchIntent) -> String {
+    if intent.prefer_recent {
+        format!("\nThe user wants the most recent release; today is {}. Score newer uploads higher and warn about old ones.",
+                chrono::Local::now().format("%Y-%m-%d"))
+    } else {
+        String::new()
+    }
+}
+
+/// The season and episode asked for, if any.
+fn tv_line(intent: &SearchIntent) -> String {
+    match intent.tv_details.as_ref().map(|tv| (tv.season, tv.episode)) {
+        Some((Some(season), Some(episode))) => format!(" Season {} Episode {}", season, episode),
+        Some((Some(season), None)) => format!(" Season {}", season),
+        _ => String::new(),
+    }
+}
+
+/// Quality and other preferences, which refinements such as "only 1080p" add to.
+fn preferences_line(intent: &SearchIntent) -> String {
+    let preferences: Vec<&str> = intent.quality_preferences.iter()
+        .chain(&intent.additional_context)
+        .map(String::as_str)
+        .collect();
+
+    if preferences.is_empty() {
+        String::new()
+    } else {
+        format!("\nPreferences: {}", preferences.join(", "))
+    }
+}
+
 pub fn build_query_generation_prompt(intent: &SearchIntent) -> String {
     format!(r#"
-Generate optimized search queries for finding: {} - {}{}
+Generate optimized search queries for finding: {} - {}{}{}
 
 Create multiple search query variations that torrent sites would understand:
 1. Primary queries - most likely to find exact matches
@@ -111,10 +149,88 @@ Respond with JSON:
             _ => "Content",
         },
         intent.title,
-        if let Some(tv) = &intent.tv_details {
-            format!(" Season {}", tv.season.unwrap_or(0))
+        tv_line(intent),
+        if intent.prefer_recent {
+            format!("\nThe user wants the most recent release. Today is {}; favor queries that find the newest season or episode, or this year's uploads.",
+                    chrono::Local::now().format("%Y-%m-%d"))
         } else {
             String::new()
         }
     )
-}
\ No newline at end of file
+}
+
+pub fn build_correction_prompt(original_prompt: &str, previous_response: &str, problem: &str) -> String {
+    // Keep the echoed response short so retries don't blow the context window
+    let previous: String = previous_response.chars().take(2000).collect();
+
+    format!(r#"{}
+
+Your previous response could not be used: {}
+
+Previous response:
+{}
+
+Respond again with ONLY the JSON described above. Do not include explanations, reasoning, or markdown.
+"#, original_prompt.trim_end(), problem, previous)
+}
+
+pub fn build_refinement_prompt(intent: &SearchIntent, previous_refinements: &[String], refinement: &str) -> String {
+    let history = if previous_refinements.is_empty() {
+        "(none)".to_string()
+    } else {
+        previous_refinements.iter()
+            .map(|r| format!("- {}", r))
+            .collect::<Vec<_>>()
+            .join("\n")
+    };
+
+    format!(r#"
+You are a torrent search assistant refining a search the user has already made.
+
+Current request, as structured JSON:
+{}
+
+Earlier follow-ups:
+{}
+
+New follow-up from the user: "{}"
+
+Update the request to reflect the follow-up. Keep everything the follow-up does not change.
+Put edition or version wishes (e.g. "extended edition", "director's cut") in additional_context
+and resolution or source wishes in quality_preferences. Set needs_new_search to true only when
+the title, year, season or episode changed, so the existing results can't contain the answer.
+
+Respond with ONLY valid JSON in this format:
+{{
+    "intent": {{
+        "content_type": "movie",
+        "title": "The Lord of the Rings: The Fellowship of the Ring",
+        "year": 2001,
+        "tv_details": null,
+        "quality_preferences": ["1080p"],
+        "language": null,
+        "additional_context": ["extended edition"],
+        "prefer_recent": false
+    }},
+    "needs_new_search": false
+}}
+"#, serde_json::to_string_pretty(intent).unwrap_or_default(), history, refinement)
+}
+
+pub fn build_classification_prompt(name: &str, files: &[String]) -> String {
+    format!(r#"
+Classify the content of this torrent.
+
+Name: "{}"
+
+Largest files:
+{}
+
+Choose one content type: movie, tv_show, music, software, book, game, other
+
+Respond with ONLY valid JSON in this format:
+{{
+    "content_type": "movie"
+}}
+"#, name, if files.is_empty() { "(unknown)".to_string() } else { files.join("\n") })
+}
@@ -0,0 +1,601 @@
+use chrono::{Datelike, Local};
+use lazy_static::lazy_static;
+use regex::Regex;
+
+use crate::models::{ContentType, EvaluatedResult, IntentRefinement, SearchIntent, TvDetails};
+use crate::pirate_bay_scraper::TorrentResult;
+use crate::language;
+use crate::uploaded;
+
+// Deterministic fallback for when no LLM is available. Everything here is
+// keyword and regex driven so results are reproducible run to run.
+
+lazy_static! {
+    static ref EPISODE_RE: Regex = Regex::new(r"(?i)\bs(\d{1,2})\s*e(\d{1,3})\b").unwrap();
+    static ref SEASON_RE: Regex = Regex::new(r"(?i)\b(?:s(\d{1,2})\b|season\s*(\d{1,2}))").unwrap();
+    static ref EPISODE_WORD_RE: Regex = Regex::new(r"(?i)\bepisode\s*(\d{1,3})\b").unwrap();
+    static ref EPISODE_NOUN_RE: Regex = Regex::new(r"(?i)\bepisodes?\b").unwrap();
+    static ref RECENT_RE: Regex = Regex::new(r"(?i)\b(latest|newest|most\s+recent)\b").unwrap();
+    static ref YEAR_RE: Regex = Regex::new(r"\b(19[3-9]\d|20[0-4]\d)\b").unwrap();
+}
+
+const QUALITY_KEYWORDS: &[&str] = &[
+    "2160p", "4k", "uhd", "1080p", "720p", "480p", "bluray", "blu-ray", "bdrip", "brrip",
+    "web-dl", "webdl", "webrip", "hdtv", "dvdrip", "remux", "hdr", "x265", "hevc", "x264",
+    "flac", "mp3", "320kbps",
+];
+
+const LOW_QUALITY_KEYWORDS: &[&str] = &["cam", "camrip", "hdcam", "ts", "telesync", "hdts", "screener", "scr"];
+
+const FILLER_WORDS: &[&str] = &[
+    "download", "find", "get", "grab", "me", "please", "want", "i", "all", "of", "in", "the",
+    "complete", "full", "movie", "movies", "film", "show", "series", "quality",
+];
+
+const EDITION_KEYWORDS: &[&str] = &[
+    "extended", "director's cut", "directors cut", "unrated", "uncut", "theatrical", "remastered", "imax",
+];
+
+const MUSIC_KEYWORDS: &[&str] = &["album", "discography", "flac", "mp3", "soundtrack", "ost"];
+const SOFTWARE_KEYWORDS: &[&str] = &["iso", "linux", "windows", "software", "app", "crack"];
+
+/// Build a `SearchIntent` from the raw query without an LLM.
+pub fn parse_intent(query: &str) -> SearchIntent {
+    let lower = query.to_lowercase();
+    let words: Vec<&str> = lower.split_whitespace().collect();
+
+    let mut tv_details = None;
+    let mut stripped = lower.clone();
+
+    if let Some(caps) = EPISODE_RE.captures(&lower) {
+        tv_details = Some(TvDetails {
+            season: caps[1].parse().ok(),
+            episode: caps[2].parse().ok(),
+            episode_range: None,
+            complete_season: false,
+            complete_series: false,
+        });
+        stripped = EPISODE_RE.replace_all(&stripped, " ").to_string();
+    } else if let Some(caps) = SEASON_RE.captures(&lower) {
+        let season = caps.get(1).or_else(|| caps.get(2)).and_then(|m| m.as_str().parse().ok());
+        let episode = EPISODE_WORD_RE.captures(&lower).and_then(|c| c[1].parse().ok());
+        tv_details = Some(TvDetails {
+            season,
+            episode,
+            episode_range: None,
+            complete_season: episode.is_none(),
+            complete_series: false,
+        });
+        stripped = SEASON_RE.replace_all(&stripped, " ").to_string();
+        stripped = EPISODE_WORD_RE.replace_all(&stripped, " ").to_string();
+    }
+
+    let year = YEAR_RE.captures(&lower).and_then(|c| c[1].parse().ok());
+    stripped = YEAR_RE.replace_all(&stripped, " ").to_string();
+
+    let language = language::requested(&stripped).map(|(language, rest)| {
+        stripped = rest;
+        language.to_string()
+    });
+
+    let prefer_recent = RECENT_RE.is_match(&lower);
+    stripped = RECENT_RE.replace_all(&stripped, " ").to_string();
+
+    // "The latest episode of ..." is a series even without a season number
+    if prefer_recent && tv_details.is_none() && EPISODE_NOUN_RE.is_match(&lower) {
+        tv_details = Some(TvDetails {
+            season: None,
+            episode: None,
+            episode_range: None,
+            complete_season: false,
+            complete_series: false,
+        });
+        stripped = EPISODE_NOUN_RE.replace_all(&stripped, " ").to_string();
+    }
+
+    let quality_preferences: Vec<String> = QUALITY_KEYWORDS.iter()
+        .filter(|k| words.contains(k))
+        .map(|k| k.to_string())
+        .collect();
+
+    let content_type = if tv_details.is_some() {
+        ContentType::TVShow
+    } else if words.iter().any(|w| MUSIC_KEYWORDS.contains(w)) {
+        ContentType::Music
+    } else if words.iter().any(|w| SOFTWARE_KEYWORDS.contains(w)) {
+        ContentType::Software
+    } else {
+        ContentType::Movie
+    };
+
+    let title = stripped
+        .split_whitespace()
+        .filter(|w| !QUALITY_KEYWORDS.contains(w) && !FILLER_WORDS.contains(w))
+        .collect::<Vec<_>>()
+        .join(" ");
+
+    SearchIntent {
+        content_type,
+        title: if title.is_empty() { query.trim().to_string() } else { title },
+        year,
+        tv_details,
+        quality_preferences,
+        language,
+        additional_context: vec!["parsed without LLM".to_string()],
+        prefer_recent,
+    }
+}
+
+/// Apply a follow-up like "only 1080p" or "season 3 instead" to an intent.
+///
+/// Quality and edition wishes only re-rank what was already found; a new year,
+/// season or episode needs a fresh search. Anything unrecognised is taken as
+/// a new title.
+pub fn refine_intent(intent: &SearchIntent, refinement: &str) -> IntentRefinement {
+    let lower = refinement.to_lowercase();
+    let parsed = parse_intent(refinement);
+    let mut refined = intent.clone();
+    let mut recognised = false;
+    let mut needs_new_search = false;
+
+    // The newest quality wish replaces earlier ones ("only 1080p")
+    if !parsed.quality_preferences.is_empty() {
+        refined.quality_preferences = parsed.quality_preferences.clone();
+        recognised = true;
+    }
+
+    for edition in EDITION_KEYWORDS.iter().filter(|e| lower.contains(*e)) {
+        if !refined.additional_context.iter().any(|c| c == edition) {
+            refined.additional_context.push(edition.to_string());
+        }
+        recognised = true;
+    }
+
+    if parsed.prefer_recent {
+        refined.prefer_recent = true;
+        recognised = true;
+    }
+
+    if parsed.year.is_some() && parsed.year != intent.year {
+        refined.year = parsed.year;
+        recognised = true;
+        needs_new_search = true;
+    }
+
+    if let Some(tv) = parsed.tv_details {
+        refined.content_type = ContentType::TVShow;
+        refined.tv_details = Some(tv);
+        recognised = true;
+        needs_new_search = true;
+    }
+
+    if !recognised {
+        let title = parsed.title.trim_start_matches("no ").trim_start_matches("actually ").to_string();
+        if !title.is_empty() {
+            refined.title = title;
+            refined.tv_details = None;
+            refined.year = None;
+            needs_new_search = true;
+        }
+    }
+
+    IntentRefinement {
+        intent: refined,
+        needs_new_search,
+    }
+}
+
+/// Search queries derived directly from the intent.
+pub fn build_queries(intent: &SearchIntent) -> Vec<String> {
+    let mut queries = Vec::new();
+
+    if let Some(tv) = &intent.tv_details {
+        match (tv.season, tv.episode) {
+            (Some(season), Some(episode)) => {
+                queries.push(format!("{} S{:02}E{:02}", intent.title, season, episode));
+            }
+            (Some(season), None) => {
+                queries.push(format!("{} S{:02}", intent.title, season));
+                queries.push(format!("{} Season {}", intent.title, season));
+            }
+            _ => {}
+        }
+    }
+
+    if let Some(year) = intent.year {
+        queries.push(format!("{} {}", intent.title, year));
+    } else if intent.prefer_recent && intent.tv_details.as_ref().is_none_or(|tv| tv.episode.is_none()) {
+        // Daily shows are released by date, so this year's uploads come first
+        queries.push(format!("{} {}", intent.title, Local::now().year()));
+    }
+
+    queries.push(intent.title.clone());
+    queries
+}
+
+/// Score results against the intent using title tokens, seeders, size sanity,
+/// and quality keywords.
+pub fn rank(intent: &SearchIntent, results: Vec<TorrentResult>) -> Vec<EvaluatedResult> {
+    results.into_iter()
+        .map(|torrent| evaluate(intent, torrent))
+        .collect()
+}
+
+fn evaluate(intent: &SearchIntent, torrent: TorrentResult) -> EvaluatedResult {
+    let mut match_reasons = Vec::new();
+    let mut warnings = Vec::new();
+    let title_lower = torrent.title.to_lowercase();
+    let title_tokens = tokenize(&title_lower);
+
+    // Title token matching
+    let wanted = tokenize(&intent.title.to_lowercase());
+    let matched = wanted.iter().filter(|t| title_tokens.contains(t)).count();
+    let title_score = if wanted.is_empty() { 0.0 } else { matched as f32 / wanted.len() as f32 };
+    if title_score >= 1.0 {
+        match_reasons.push("Title matches".to_string());
+    } else if title_score > 0.0 {
+        warnings.push(format!("Only {}/{} title words match", matched, wanted.len()));
+    } else {
+        warnings.push("Title does not match".to_string());
+    }
+
+    // Year and TV details
+    let mut detail_score = 1.0;
+    if let Some(year) = intent.year {
+        if title_lower.contains(&year.to_string()) {
+            match_reasons.push(format!("Year {} matches", year));
+        } else if YEAR_RE.is_match(&title_lower) {
+            detail_score -= 0.5;
+            warnings.push(format!("Different year than {}", year));
+        }
+    }
+
+    let mut completeness_score = 1.0;
+    if let Some(tv) = &intent.tv_details {
+        let (score, reason) = tv_match(tv, &title_lower);
+        completeness_score = score;
+        detail_score *= score;
+        match reason {
+            Ok(reason) => match_reasons.push(reason),
+            Err(warning) => warnings.push(warning),
+        }
+    }
+
+    // Seeders on a log scale; 500+ seeders is as good as it gets
+    let seeders = torrent.seeders.unwrap_or(0);
+    let seeder_score = ((1.0 + seeders as f32).ln() / 501f32.ln()).min(1.0);
+    if seeders == 0 {
+        warnings.push("No seeders".to_string());
+    } else if seeders >= 50 {
+        match_reasons.push(format!("Well seeded ({})", seeders));
+    }
+
+    // Size sanity for the content type
+    let size_score = match torrent.size_bytes() {
+        Some(bytes) => {
+            let (min, max) = expected_size_range(intent);
+            let gb = bytes as f64 / (1024.0 * 1024.0 * 1024.0);
+            if gb < min {
+                warnings.push(format!("Suspiciously small ({:.2} GB)", gb));
+                0.2
+            } else if gb > max {
+                warnings.push(format!("Unusually large ({:.1} GB)", gb));
+                0.6
+            } else {
+                1.0
+            }
+        }
+        None => 0.5,
+    };
+
+    // Quality keywords
+    let mut quality_score: f32 = 0.4;
+    for keyword in ["2160p", "4k", "remux", "bluray", "1080p", "web-dl", "720p"] {
+        if title_tokens.iter().any(|t| t == keyword) {
+            quality_score = quality_score.max(match keyword {
+                "2160p" | "4k" | "remux" => 1.0,
+                "bluray" | "1080p" | "web-dl" => 0.85,
+                _ => 0.65,
+            });
+        }
+    }
+    if title_tokens.iter().any(|t| LOW_QUALITY_KEYWORDS.contains(&t.as_str())) {
+        quality_score = 0.1;
+        warnings.push("Low quality source (CAM/TS)".to_string());
+    }
+    // Explicit quality and edition wishes weigh more than the general signals above
+    let mut preference_factor = 1.0;
+    let mut preference_matched = intent.quality_preferences.is_empty();
+    for pref in &intent.quality_preferences {
+        if title_lower.contains(&pref.to_lowercase()) {
+            match_reasons.push(format!("Matches preferred quality {}", pref));
+            preference_matched = true;
+        }
+    }
+    if !preference_matched {
+        preference_factor *= 0.6;
+        warnings.push(format!("Not in preferred quality ({})", intent.quality_preferences.join(", ")));
+    }
+
+    for edition in intent.additional_context.iter().filter(|c| EDITION_KEYWORDS.contains(&c.as_str())) {
+        if title_lower.contains(edition.as_str()) {
+            match_reasons.push(format!("{} edition", edition));
+        } else {
+            preference_factor *= 0.6;
+            warnings.push(format!("Not the {} edition", edition));
+        }
+    }
+
+    // Asked for the latest: newer uploads win, and ones far too old to be it lose out
+    if intent.prefer_recent {
+        match torrent.uploaded_at() {
+            Some(uploaded) => {
+                let days = uploaded::age(uploaded).num_days();
+                preference_factor *= match days {
+                    ..=7 => 1.0,
+                    8..=30 => 0.9,
+                    31..=365 => 0.75,
+                    _ => 0.6,
+                };
+                if days <= 30 {
+                    match_reasons.push(format!("Recent upload ({})", uploaded::describe_age(uploaded)));
+                } else {
+                    warnings.push(format!("Uploaded {}", uploaded::describe_age(uploaded)));
+                }
+            }
+            None => preference_factor *= 0.8,
+        }
+    }
+
+    let relevance_score = ((0.5 * title_score
+        + 0.2 * detail_score
+        + 0.1 * seeder_score
+        + 0.1 * size_score
+        + 0.1 * quality_score)
+        * preference_factor)
+        .clamp(0.0, 1.0);
+    let confidence = (title_score * if seeders > 0 { 0.9 } else { 0.6 }).clamp(0.0, 1.0);
+
+    EvaluatedResult {
+        torrent,
+        relevance_score,
+        confidence,
+        match_reasons,
+        warnings,
+        quality_score,
+        completeness_score,
+    }
+}
+
+fn tv_match(tv: &TvDetails, title: &str) -> (f32, Result<String, String>) {
+    let found_episode = EPISODE_RE.captures(title)
+        .map(|c| (c[1].parse::<u8>().ok(), c[2].parse::<u8>().ok()));
+    let found_season = SEASON_RE.captures(title)
+        .and_then(|c| c.get(1).or_else(|| c.get(2)))
+        .and_then(|m| m.as_str().parse::<u8>().ok());
+
+    match (tv.season, tv.episode) {
+        (Some(season), Some(episode)) => match found_episode {
+            Some((Some(s), Some(e))) if s == season && e == episode => {
+                (1.0, Ok(format!("S{:02}E{:02} matches", season, episode)))
+            }
+            Some(_) => (0.0, Err("Different episode".to_string())),
+            None if found_season == Some(season) => {
+                (0.6, Ok(format!("Season {} pack contains the episode", season)))
+            }
+            None => (0.3, Err("No episode marker in title".to_string())),
+        },
+        (Some(season), None) => match (found_episode, found_season) {
+            (Some(_), _) => (0.3, Err("Single episode, not a full season".to_string())),
+            (None, Some(s)) if s == season => (1.0, Ok(format!("Season {} pack", season))),
+            (None, Some(_)) => (0.0, Err("Different season".to_string())),
+            (None, None) if title.contains("complete") => {
+                (0.7, Ok("Complete series pack".to_string()))
+            }
+            (None, None) => (0.4, Err("No season marker in title".to_string())),
+        },
+        _ => (1.0, Ok("TV show".to_string())),
+    }
+}
+
+/// For "the latest episode" with no air date to go on, the highest episode
+/// among the results is taken to be it and older ones are marked down.
+pub fn favor_newest_episode(intent: &SearchIntent, results: &mut [EvaluatedResult]) {
+    let Some(tv) = intent.tv_details.as_ref().filter(|tv| intent.prefer_recent && tv.episode.is_none()) else {
+        return;
+    };
+    let in_season = |marker: &(u8, u8)| tv.season.is_none_or(|season| marker.0 == season);
+    let Some(newest) = results.iter()
+        .filter_map(|r| episode_marker(&r.torrent.title))
+        .filter(in_season)
+        .max()
+    else {
+        return;
+    };
+
+    for result in results.iter_mut() {
+        match episode_marker(&result.torrent.title).filter(in_season) {
+            Some(marker) if marker == newest => {
+                result.match_reasons.push(format!("Newest episode found (S{:02}E{:02})", newest.0, newest.1));
+            }
+            Some((season, episode)) => {
+                result.relevance_score *= 0.7;
+                result.warnings.push(format!("Older episode (S{:02}E{:02})", season, episode));
+            }
+            None => {}
+        }
+    }
+}
+
+/// Season and episode from an `SxxEyy` marker in a release title.
+pub fn episode_marker(title: &str) -> Option<(u8, u8)> {
+    let caps = EPISODE_RE.captures(title)?;
+    Some((caps[1].parse().ok()?, caps[2].parse().ok()?))
+}
+
+/// Whether a release title looks like a whole-season pack for `season`.
+pub fn is_season_pack(title: &str, season: u8) -> bool {
+    if EPISODE_RE.is_match(title) {
+        return false;
+    }
+    SEASON_RE.captures(title)
+        .and_then(|c| c.get(1).or_else(|| c.get(2)))
+        .and_then(|m| m.as_str().parse::<u8>().ok())
+        == Some(season)
+}
+
+/// Plausible size bounds in GB for the requested content.
+fn expected_size_range(intent: &SearchIntent) -> (f64, f64) {
+    match &intent.content_type {
+        ContentType::Movie => (0.5, 80.0),
+        ContentType::TVShow => match &intent.tv_details {
+            Some(tv) if tv.episode.is_some() => (0.1, 15.0),
+            _ => (1.0, 300.0),
+        },
+        ContentType::Music => (0.02, 10.0),
+        _ => (0.0, f64::MAX),
+    }
+}
+
+fn tokenize(s: &str) -> Vec<String> {
+    s.split(|c: char| !c.is_alphanumeric() && c != '-')
+        .map(|t| t.trim_matches('-'))
+        .filter(|t| !t.is_empty())
+        .map(String::from)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn release(title: &str, size: &str, seeders: u32) -> TorrentResult {
+        TorrentResult {
+            title: title.to_string(),
+            magnet_link: format!("magnet:?xt=urn:btih:{}", title.len()),
+            size: Some(size.to_string()),
+            seeders: Some(seeders),
+            leechers: Some(seeders / 4),
+            uploaded: None,
+            category: None,
+            uploader: None,
+            found_by: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn intent_for_an_episode() {
+        let intent = parse_intent("Breaking Bad S05E14 1080p");
+        assert!(matches!(intent.content_type, ContentType::TVShow));
+        assert_eq!(intent.title, "breaking bad");
+        let tv = intent.tv_details.unwrap();
+        assert_eq!((tv.season, tv.episode, tv.complete_season), (Some(5), Some(14), false));
+        assert_eq!(intent.quality_preferences, vec!["1080p"]);
+    }
+
+    #[test]
+    fn intent_for_a_season() {
+        let intent = parse_intent("download the office season 3");
+        assert_eq!(intent.title, "office");
+        let tv = intent.tv_details.unwrap();
+        assert_eq!((tv.season, tv.episode, tv.complete_season), (Some(3), None, true));
+
+        let tv = parse_intent("the bear season 2 episode 7").tv_details.unwrap();
+        assert_eq!((tv.season, tv.episode, tv.complete_season), (Some(2), Some(7), false));
+    }
+
+    #[test]
+    fn intent_for_a_movie() {
+        let intent = parse_intent("The Matrix 1999 4k");
+        assert!(matches!(intent.content_type, ContentType::Movie));
+        assert_eq!(intent.title, "matrix");
+        assert_eq!(intent.year, Some(1999));
+        assert!(intent.tv_details.is_none());
+        assert_eq!(intent.quality_preferences, vec!["4k"]);
+
+        let intent = parse_intent("amelie 2001 in french");
+        assert_eq!(intent.title, "amelie");
+        assert_eq!(intent.language.as_deref(), Some("French"));
+    }
+
+    #[test]
+    fn intent_for_music_and_software() {
+        let intent = parse_intent("pink floyd discography flac");
+        assert!(matches!(intent.content_type, ContentType::Music));
+        assert_eq!(intent.title, "pink floyd discography");
+        assert!(matches!(parse_intent("ubuntu 24.04 iso").content_type, ContentType::Software));
+    }
+
+    #[test]
+    fn intent_for_the_latest_episode() {
+        let intent = parse_intent("latest episode of severance");
+        assert!(intent.prefer_recent);
+        assert_eq!(intent.title, "severance");
+        let tv = intent.tv_details.unwrap();
+        assert_eq!((tv.season, tv.episode), (None, None));
+    }
+
+    #[test]
+    fn rank_prefers_the_right_movie() {
+        let intent = parse_intent("the matrix 1999 1080p");
+        let ranked = rank(&intent, vec![
+            release("The.Matrix.1999.1080p.BluRay.x264-AMIABLE", "1.4 GiB", 812),
+            release("The Matrix Resurrections 2021 1080p WEB-DL", "2.1 GiB", 1500),
+            release("The.Matrix.1999.HDCAM.XviD", "700 MiB", 3),
+            release("Inception.2010.1080p.BluRay.x264", "1.8 GiB", 900),
+        ]);
+        let scores: Vec<f32> = ranked.iter().map(|r| r.relevance_score).collect();
+        assert!(scores[0] > scores[1], "{:?}", scores);
+        assert!(scores[0] > scores[2], "{:?}", scores);
+        assert!(scores[0] > scores[3], "{:?}", scores);
+        assert!(ranked[0].match_reasons.iter().any(|r| r == "Year 1999 matches"));
+        assert!(ranked[1].warnings.iter().any(|w| w == "Different year than 1999"));
+        assert!(ranked[2].warnings.iter().any(|w| w == "Low quality source (CAM/TS)"));
+        assert!(ranked[3].warnings.iter().any(|w| w == "Title does not match"));
+    }
+
+    #[test]
+    fn rank_prefers_the_right_episode() {
+        let intent = parse_intent("breaking bad s05e14");
+        let ranked = rank(&intent, vec![
+            release("Breaking.Bad.S05E14.Ozymandias.1080p.WEB-DL.DD5.1.H.264", "1.6 GiB", 240),
+            release("Breaking Bad Season 5 Complete 1080p BluRay x264", "22 GiB", 310),
+            release("Breaking.Bad.S05E13.720p.HDTV.x264-EVOLVE", "600 MiB", 120),
+        ]);
+        assert!(ranked[0].relevance_score > ranked[1].relevance_score);
+        assert!(ranked[1].relevance_score > ranked[2].relevance_score);
+        assert!(ranked[0].match_reasons.iter().any(|r| r == "S05E14 matches"));
+        assert!(ranked[1].match_reasons.iter().any(|r| r == "Season 5 pack contains the episode"));
+        assert!(ranked[2].warnings.iter().any(|w| w == "Different episode"));
+    }
+
+    #[test]
+    fn rank_flags_fakes_by_size() {
+        let intent = parse_intent("dune part two 2024");
+        let ranked = rank(&intent, vec![
+            release("Dune.Part.Two.2024.2160p.WEB-DL.DDP5.1.Atmos.HDR.H.265", "18.4 GiB", 640),
+            release("Dune.Part.Two.2024.2160p.WEB-DL.mkv.exe", "2.3 MiB", 640),
+        ]);
+        assert!(ranked[0].relevance_score > ranked[1].relevance_score);
+        assert!(ranked[1].warnings.iter().any(|w| w.starts_with("Suspiciously small")));
+    }
+
+    #[test]
+    fn episode_markers() {
+        assert_eq!(episode_marker("Severance.S02E10.1080p.WEB.h264-ETHEL"), Some((2, 10)));
+        assert_eq!(episode_marker("Only.Murders.in.the.Building.s04e01.720p"), Some((4, 1)));
+        assert_eq!(episode_marker("One Piece S01E1089 1080p"), None);
+        assert_eq!(episode_marker("The.Bear.S03.1080p.WEB.h264-ETHEL"), None);
+        assert_eq!(episode_marker("Dune.Part.Two.2024.2160p.WEB-DL"), None);
+    }
+
+    #[test]
+    fn season_packs() {
+        assert!(is_season_pack("The.Bear.S03.1080p.WEB.h264-ETHEL", 3));
+        assert!(is_season_pack("The Office (US) Season 3 Complete 720p", 3));
+        assert!(is_season_pack("Fargo.S05.COMPLETE.720p.AMZN.WEBRip.x264-GalaxyTV", 5));
+        assert!(!is_season_pack("The.Bear.S03.1080p.WEB.h264-ETHEL", 2));
+        assert!(!is_season_pack("The.Bear.S03E01.1080p.WEB.h264-ETHEL", 3));
+        assert!(!is_season_pack("Fargo 2014 Complete Series 1080p", 1));
+    }
+}
@@ -0,0 +1,200 @@
+use lazy_static::lazy_static;
+use regex::Regex;
+
+use crate::models::EvaluatedResult;
+use crate::pirate_bay_scraper::TorrentResult;
+use crate::uploaded;
+
+lazy_static! {
+    /// Executables and scripts, which have no business in a video release
+    static ref EXECUTABLE_RE: Regex = Regex::new(r"(?i)\.(exe|scr|bat|cmd|lnk|vbs|msi|apk)\b").unwrap();
+    /// What fakes say to get an installer or a survey site run
+    static ref LURE_RE: Regex = Regex::new(
+        r"(?i)\b(password[\s._-]*(protected|required|inside|in[\s._-]+(the[\s._-]+)?(txt|file|description))|codec[\s._-]*(pack|required|needed)|install(er)?\s+to\s+(play|watch)|(watch|download)\s+(free|now|online)|full\s+movie\s+download|free\s+download)\b"
+    ).unwrap();
+    /// Tags that say the torrent is a video
+    static ref VIDEO_RE: Regex = Regex::new(
+        r"(?i)\b(2160p|4k|uhd|1080p|720p|blu-?ray|remux|web-?dl|webrip|hdtv|hdrip|dvdrip|x26[45]|hevc)\b"
+    ).unwrap();
+    static ref UHD_RE: Regex = Regex::new(r"(?i)\b(2160p|4k|uhd|remux)\b").unwrap();
+    static ref FULL_HD_RE: Regex = Regex::new(r"(?i)\b(1080p|blu-?ray)\b").unwrap();
+    /// Name shapes of throwaway accounts that post fakes
+    static ref SCAM_UPLOADER_RES: Vec<Regex> = [
+        r"(?i)^(free|full|hd|new)[\s._-]?(movies?|films?)[\s._-]?(download|online|hd|4u)?\d*$",
+        r"(?i)^(movie|film|torrent)s?[\s._-]?(king|hub|zone|world|master)\d*$",
+    ]
+    .iter()
+    .map(|pattern| Regex::new(pattern).unwrap())
+    .collect();
+}
+
+/// Smallest believable size for a video advertised in 4K, 1080p, or at all.
+const MIN_UHD_BYTES: u64 = 500 * 1024 * 1024;
+const MIN_FULL_HD_BYTES: u64 = 150 * 1024 * 1024;
+const MIN_VIDEO_BYTES: u64 = 50 * 1024 * 1024;
+/// An upload this fresh with nobody seeding it is a common fake pattern.
+const NEW_UPLOAD_HOURS: i64 = 48;
+
+/// Deterministic signs that a torrent is a fake or carries malware. They
+/// don't depend on the LLM or the search intent, and any one of them is
+/// enough to stop an automatic download.
+pub fn red_flags(torrent: &TorrentResult) -> Vec<String> {
+    let mut flags = Vec::new();
+    let title = &torrent.title;
+    let is_video = VIDEO_RE.is_match(title)
+        || torrent.category.as_deref().is_some_and(|c| c.starts_with("Video"));
+
+    if is_video {
+        if let Some(extension) = EXECUTABLE_RE.captures(title) {
+            flags.push(format!("Video release names an executable ({})", &extension[0]));
+        }
+        if let Some(bytes) = torrent.size_bytes() {
+            let (min, advertised) = if UHD_RE.is_match(title) {
+                (MIN_UHD_BYTES, "4K")
+            } else if FULL_HD_RE.is_match(title) {
+                (MIN_FULL_HD_BYTES, "1080p")
+            } else {
+                (MIN_VIDEO_BYTES, "video")
+            };
+            if bytes < min {
+                flags.push(format!("Far too small for {} ({})", advertised, torrent.size.as_deref().unwrap_or_default()));
+            }
+        }
+    }
+
+    if let Some(lure) = LURE_RE.find(title) {
+        flags.push(format!("Title says \"{}\"", lure.as_str()));
+    }
+
+    if torrent.seeders == Some(0) {
+        let age = torrent.uploaded_at().map(uploaded::age);
+        if age.is_some_and(|age| age.num_hours() < NEW_UPLOAD_HOURS) {
+            flags.push("Brand-new upload with no seeders".to_string());
+        }
+    }
+
+    if let Some(uploader) = torrent.uploader.as_deref() {
+        if SCAM_UPLOADER_RES.iter().any(|re| re.is_match(uploader)) {
+            flags.push(format!("Uploader \"{}\" looks like a spam account", uploader));
+        }
+    }
+
+    flags
+}
+
+/// Add a torrent's red flags to an evaluation's warnings, whatever the
+/// evaluator made of it.
+pub fn flag(result: &mut EvaluatedResult) {
+    for flag in red_flags(&result.torrent) {
+        result.warnings.push(format!("Red flag: {}", flag));
+    }
+}
+
+/// Safe to download without anyone looking at it first.
+pub fn is_safe(torrent: &TorrentResult) -> bool {
+    red_flags(torrent).is_empty()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn torrent(title: &str, size: &str, seeders: u32) -> TorrentResult {
+        TorrentResult {
+            title: title.to_string(),
+            magnet_link: String::new(),
+            size: Some(size.to_string()),
+            seeders: Some(seeders),
+            leechers: Some(3),
+            uploaded: Some("05-11 2022".to_string()),
+            category: None,
+            uploader: Some("rarbg".to_string()),
+            found_by: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn real_releases_are_safe() {
+        for title in [
+            "Dune.Part.Two.2024.2160p.UHD.BluRay.REMUX.HDR.HEVC-FGT",
+            "Oppenheimer.2023.1080p.WEB-DL.DDP5.1.x264",
+            "The.Bear.S02E01.720p.HDTV.x264",
+        ] {
+            let result = torrent(title, "12.4 GiB", 150);
+            assert!(is_safe(&result), "{}: {:?}", title, red_flags(&result));
+        }
+    }
+
+    #[test]
+    fn executables_in_video_releases() {
+        for title in ["Dune.2021.1080p.BluRay.x264.exe", "Dune 2021 1080p WEBRip Player.scr", "Dune.2021.720p.HDRip.Codec.msi"] {
+            let flags = red_flags(&torrent(title, "1.5 GiB", 40));
+            assert!(flags.iter().any(|f| f.starts_with("Video release names an executable")), "{}: {:?}", title, flags);
+        }
+        // Named by the category rather than the title
+        let mut result = torrent("Dune Full Movie.exe", "1.5 GiB", 40);
+        result.category = Some("Video > Movies".to_string());
+        assert!(!is_safe(&result));
+    }
+
+    #[test]
+    fn executables_outside_video_are_expected() {
+        let mut result = torrent("VLC.Media.Player.3.0.20.Setup.exe", "40 MiB", 200);
+        result.category = Some("Applications > Windows".to_string());
+        assert!(is_safe(&result), "{:?}", red_flags(&result));
+        // ".exe" inside a word isn't an extension
+        assert!(is_safe(&torrent("Exec.Summary.2020.1080p.WEB-DL.x264", "2 GiB", 20)));
+    }
+
+    #[test]
+    fn password_protected_archives() {
+        for title in [
+            "Dune 2021 1080p BluRay RAR Password Protected",
+            "Dune.2021.1080p.WEBRip.password.in.txt",
+            "Dune 2021 720p (password required)",
+        ] {
+            let flags = red_flags(&torrent(title, "1.5 GiB", 40));
+            assert!(flags.iter().any(|f| f.starts_with("Title says")), "{}: {:?}", title, flags);
+        }
+        // A password manager is software, not a lure
+        assert!(is_safe(&torrent("1Password.8.10.Password.Manager", "120 MiB", 20)));
+    }
+
+    #[test]
+    fn other_lures() {
+        for title in ["Dune 2021 Full Movie Download HD", "Dune 2021 1080p Codec Pack Required", "Dune 2021 Watch Free 1080p"] {
+            assert!(!is_safe(&torrent(title, "1.5 GiB", 40)), "{}", title);
+        }
+    }
+
+    #[test]
+    fn too_small_for_the_advertised_quality() {
+        assert!(!is_safe(&torrent("Dune.2021.2160p.UHD.BluRay.x265", "300 MiB", 40)));
+        assert!(!is_safe(&torrent("Dune.2021.1080p.WEB-DL.x264", "90 MiB", 40)));
+        assert!(!is_safe(&torrent("Dune.2021.720p.WEBRip.x264", "20 MiB", 40)));
+        assert!(is_safe(&torrent("Dune.2021.720p.WEBRip.x264", "700 MiB", 40)));
+        // Unknown sizes aren't held against a release
+        let mut unknown = torrent("Dune.2021.2160p.UHD.BluRay.x265", "", 40);
+        unknown.size = None;
+        assert!(is_safe(&unknown));
+    }
+
+    #[test]
+    fn new_uploads_without_seeders() {
+        let mut result = torrent("Dune.2021.1080p.BluRay.x264", "8 GiB", 0);
+        result.uploaded = Some("5 mins ago".to_string());
+        assert_eq!(red_flags(&result), ["Brand-new upload with no seeders"]);
+        // Old and unseeded is merely dead, not suspicious
+        result.uploaded = Some("05-11 2013".to_string());
+        assert!(is_safe(&result));
+    }
+
+    #[test]
+    fn spam_uploaders() {
+        for uploader in ["FreeMoviesDownload", "hd_films_4u", "MovieKing2024"] {
+            let mut result = torrent("Dune.2021.1080p.BluRay.x264", "8 GiB", 40);
+            result.uploader = Some(uploader.to_string());
+            assert!(!is_safe(&result), "{}", uploader);
+        }
+    }
+}
@@ -1,3 +1,143 @@
+use anyhow::Result;
+use futures::future::{join_all, BoxFuture, FutureExt};
+use std::sync::Arc;
+
+use crate::metadata::CanonicalTitle;
+use crate::models::{ContentType, SearchIntent};
+
 // Re-export the scrapers
-pub use crate::pirate_bay_scraper::{PirateBayScraper, TorrentResult};
-pub use crate::yts_scraper::YtsScraper;
\ No newline at end of file
+pub use crate::pirate_bay_scraper::{PirateBayScraper, Provenance, TorrentResult};
+pub use crate::yts_scraper::{YtsFilter, YtsScraper};
+
+/// What to search a source for.
+#[derive(Debug, Clone, Default)]
+pub struct SourceQuery {
+    pub text: String,
+    /// For sources that can narrow results on their side; others ignore them
+    pub hints: SearchHints,
+}
+
+impl SourceQuery {
+    pub fn new(text: &str) -> Self {
+        Self {
+            text: text.to_string(),
+            hints: SearchHints::default(),
+        }
+    }
+
+    pub fn with_hints(mut self, hints: SearchHints) -> Self {
+        self.hints = hints;
+        self
+    }
+}
+
+/// What a search is after, beyond its text. Each source turns whatever it
+/// can filter on into its own parameters and ignores the rest.
+#[derive(Debug, Clone, Default)]
+pub struct SearchHints {
+    pub content_type: Option<ContentType>,
+    /// Most preferred first, as releases name them, e.g. "2160p" or "x265"
+    pub qualities: Vec<String>,
+    /// As TMDB or OMDb name them, e.g. "Science Fiction"
+    pub genres: Vec<String>,
+    /// Lowest IMDb rating wanted, out of 10
+    pub min_rating: Option<u8>,
+}
+
+impl SearchHints {
+    /// Hints for a smart search: the kind of content and preferred quality,
+    /// and the genres from TMDB or OMDb.
+    pub fn for_intent(intent: &SearchIntent, canonical: Option<&CanonicalTitle>) -> Self {
+        Self {
+            content_type: Some(intent.content_type.clone()),
+            qualities: intent.quality_preferences.clone(),
+            genres: canonical.map(|c| c.genres.clone()).unwrap_or_default(),
+            min_rating: None,
+        }
+    }
+}
+
+/// A site torrents can be searched on. Implement it to plug another site
+/// into [`SourceRegistry`] and, through it, the smart search.
+pub trait TorrentSource: Send + Sync {
+    /// Shown next to results, and part of the scrape cache key.
+    fn name(&self) -> &'static str;
+
+    fn search<'a>(&'a self, query: &'a SourceQuery) -> BoxFuture<'a, Result<Vec<TorrentResult>>>;
+
+    /// What results are cached under; queries the source answers
+    /// differently need different keys.
+    fn cache_key(&self, query: &SourceQuery) -> String {
+        query.text.clone()
+    }
+}
+
+impl TorrentSource for PirateBayScraper {
+    fn name(&self) -> &'static str {
+        "ThePirateBay"
+    }
+
+    fn search<'a>(&'a self, query: &'a SourceQuery) -> BoxFuture<'a, Result<Vec<TorrentResult>>> {
+        PirateBayScraper::search(self, &query.text).boxed()
+    }
+}
+
+impl TorrentSource for YtsScraper {
+    fn name(&self) -> &'static str {
+        "YTS"
+    }
+
+    fn search<'a>(&'a self, query: &'a SourceQuery) -> BoxFuture<'a, Result<Vec<TorrentResult>>> {
+        async move { self.search_filtered(&query.text, &YtsFilter::from_hints(&query.hints)).await }.boxed()
+    }
+
+    fn cache_key(&self, query: &SourceQuery) -> String {
+        // Filtered results are cached apart from unfiltered ones
+        let filter = YtsFilter::from_hints(&query.hints);
+        if filter.is_empty() {
+            query.text.clone()
+        } else {
+            format!("{} [{}]", query.text, filter.describe())
+        }
+    }
+}
+
+/// The sources a search fans out to.
+#[derive(Clone)]
+pub struct SourceRegistry {
+    sources: Vec<Arc<dyn TorrentSource>>,
+}
+
+impl SourceRegistry {
+    /// A registry with no sources; see [`SourceRegistry::default`] for the
+    /// built-in ones.
+    pub fn new() -> Self {
+        Self { sources: Vec::new() }
+    }
+
+    pub fn register(mut self, source: impl TorrentSource + 'static) -> Self {
+        self.sources.push(Arc::new(source));
+        self
+    }
+
+    pub fn sources(&self) -> &[Arc<dyn TorrentSource>] {
+        &self.sources
+    }
+
+    /// Search every source concurrently. One failing doesn't fail the
+    /// others, so each source's outcome comes back separately.
+    pub async fn search_all(&self, query: &SourceQuery) -> Vec<(&'static str, Result<Vec<TorrentResult>>)> {
+        join_all(self.sources.iter().map(|source| async move {
+            (source.name(), source.search(query).await)
+        })).await
+    }
+}
+
+impl Default for SourceRegistry {
+    /// ThePirateBay and YTS, configured from the user's config file.
+    fn default() -> Self {
+        Self::new()
+            .register(PirateBayScraper::new())
+            .register(YtsScraper::new())
+    }
+}
@@ -0,0 +1,110 @@
+use age::secrecy::ExposeSecret;
+use anyhow::Result;
+use std::collections::BTreeMap;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use crate::config::config_dir;
+
+/// Encrypted store for tracker passwords and API keys.
+///
+/// Secrets live in `~/.torrentai/secrets.age`, encrypted to an age identity kept
+/// in `~/.torrentai/identity.txt` (created on first use, readable only by the
+/// owner). Config files reference secrets by name instead of holding values.
+pub struct SecretStore {
+    identity: age::x25519::Identity,
+    path: PathBuf,
+    secrets: BTreeMap<String, String>,
+}
+
+impl SecretStore {
+    pub fn open() -> Result<Self> {
+        let dir = config_dir();
+        std::fs::create_dir_all(&dir)?;
+
+        let identity = load_or_create_identity(&dir.join("identity.txt"))?;
+        let path = dir.join("secrets.age");
+
+        let secrets = if path.exists() {
+            let ciphertext = std::fs::read(&path)?;
+            let plaintext = age::decrypt(&identity, &ciphertext)
+                .map_err(|e| anyhow::anyhow!("Failed to decrypt {}: {}", path.display(), e))?;
+            serde_json::from_slice(&plaintext)?
+        } else {
+            BTreeMap::new()
+        };
+
+        Ok(Self { identity, path, secrets })
+    }
+
+    fn get(&self, name: &str) -> Option<&str> {
+        self.secrets.get(name).map(String::as_str)
+    }
+
+    /// Look up a secret referenced from config, with a hint when it is missing.
+    pub fn require(&self, name: &str) -> Result<&str> {
+        self.get(name).ok_or_else(|| anyhow::anyhow!(
+            "Secret '{}' is not set. Add it with: torrentai secret set {}", name, name
+        ))
+    }
+
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.secrets.keys().map(String::as_str)
+    }
+
+    pub fn set(&mut self, name: &str, value: &str) -> Result<()> {
+        self.secrets.insert(name.to_string(), value.to_string());
+        self.save()
+    }
+
+    pub fn remove(&mut self, name: &str) -> Result<bool> {
+        let removed = self.secrets.remove(name).is_some();
+        if removed {
+            self.save()?;
+        }
+        Ok(removed)
+    }
+
+    fn save(&self) -> Result<()> {
+        let plaintext = serde_json::to_vec(&self.secrets)?;
+        let ciphertext = age::encrypt(&self.identity.to_public(), &plaintext)
+            .map_err(|e| anyhow::anyhow!("Failed to encrypt secrets: {}", e))?;
+        write_private(&self.path, &ciphertext)
+    }
+}
+
+fn load_or_create_identity(path: &Path) -> Result<age::x25519::Identity> {
+    if path.exists() {
+        let content = std::fs::read_to_string(path)?;
+        let key = content.lines()
+            .find(|line| line.starts_with("AGE-SECRET-KEY-"))
+            .ok_or_else(|| anyhow::anyhow!("No age secret key found in {}", path.display()))?;
+        return key.parse()
+            .map_err(|e| anyhow::anyhow!("Invalid identity in {}: {}", path.display(), e));
+    }
+
+    let identity = age::x25519::Identity::generate();
+    let content = format!(
+        "# torrentai secrets key, created {}\n# public key: {}\n{}\n",
+        chrono::Utc::now().to_rfc3339(),
+        identity.to_public(),
+        identity.to_string().expose_secret()
+    );
+    write_private(path, content.as_bytes())?;
+    Ok(identity)
+}
+
+/// Write a file only the current user can read.
+fn write_private(path: &Path, contents: &[u8]) -> Result<()> {
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+
+    let mut file = options.open(path)?;
+    file.write_all(contents)?;
+    Ok(())
+}
@@ -0,0 +1,574 @@
+use anyhow::Result;
+use axum::{
+    extract::{RawQuery, State},
+    routing::get,
+    Router,
+};
+use chrono::Utc;
+use futures::future::{BoxFuture, FutureExt};
+use librqbit::{AddTorrent, AddTorrentOptions, AddTorrentResponse, Session, SessionOptions};
+use serde_bencode::value::Value;
+use sha1::{Digest, Sha1};
+use std::collections::HashMap;
+use std::net::Ipv4Addr;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tracing::warn;
+
+use crate::config::Config;
+use crate::downloader::{self, DownloadManager};
+use crate::organize;
+use crate::state::{QueueStatus, StateDb, TorrentRecord, TorrentStatus};
+use crate::storage::format_bytes;
+
+/// The fixture is cut into pieces this size.
+const PIECE_LENGTH: usize = 256 * 1024;
+/// Not a whole number of pieces, so the last one is short.
+const FIXTURE_LEN: usize = 3 * 1024 * 1024 + 4321;
+const FIXTURE_NAME: &str = "Selftest.Movie.2024.1080p.mkv";
+/// How often peers announce again to the local tracker.
+const ANNOUNCE_INTERVAL_SECS: i64 = 5;
+/// A step that hasn't finished after this long has failed.
+const STEP_TIMEOUT: Duration = Duration::from_secs(90);
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Generated content and a private torrent for it.
+pub struct Fixture {
+    pub name: String,
+    /// The generated file, which the seeder shares
+    pub path: PathBuf,
+    pub torrent: Vec<u8>,
+    pub torrent_path: PathBuf,
+    /// Hex, as librqbit and the state database write it
+    pub info_hash: String,
+    pub len: u64,
+    pub pieces: usize,
+}
+
+impl Fixture {
+    /// Write the fixture file and its torrent, announcing to `announce`,
+    /// under `dir`.
+    pub fn create(dir: &Path, announce: &str) -> Result<Self> {
+        std::fs::create_dir_all(dir)?;
+        let content = content();
+        let path = dir.join(FIXTURE_NAME);
+        std::fs::write(&path, &content)?;
+
+        let pieces: Vec<u8> = content.chunks(PIECE_LENGTH).flat_map(|piece| Sha1::digest(piece).to_vec()).collect();
+        let info = dict([
+            ("length", Value::Int(content.len() as i64)),
+            ("name", Value::Bytes(FIXTURE_NAME.into())),
+            ("piece length", Value::Int(PIECE_LENGTH as i64)),
+            ("pieces", Value::Bytes(pieces)),
+            // Kept off the DHT and peer exchange even by clients that use them
+            ("private", Value::Int(1)),
+        ]);
+        let info_hash = hex(&Sha1::digest(serde_bencode::to_bytes(&info)?));
+        let torrent = serde_bencode::to_bytes(&dict([
+            ("announce", Value::Bytes(announce.into())),
+            ("info", info),
+        ]))?;
+        let torrent_path = dir.join(format!("{}.torrent", FIXTURE_NAME));
+        std::fs::write(&torrent_path, &torrent)?;
+
+        Ok(Self {
+            name: FIXTURE_NAME.to_string(),
+            path,
+            torrent,
+            torrent_path,
+            info_hash,
+            len: content.len() as u64,
+            pieces: content.len().div_ceil(PIECE_LENGTH),
+        })
+    }
+
+    /// Whether the copy under `output_dir` is byte for byte the fixture.
+    pub fn check_copy(&self, output_dir: &Path) -> Result<()> {
+        let path = output_dir.join(&self.name);
+        let copy = std::fs::read(&path)
+            .map_err(|e| anyhow::anyhow!("Could not read {}: {}", path.display(), e))?;
+        let content = content();
+        if copy.len() != content.len() {
+            return Err(anyhow::anyhow!("{} is {} bytes, not {}", path.display(), copy.len(), content.len()));
+        }
+        if let Some(offset) = copy.iter().zip(&content).position(|(a, b)| a != b) {
+            return Err(anyhow::anyhow!("{} differs from the fixture at byte {}", path.display(), offset));
+        }
+        Ok(())
+    }
+
+    /// The torrent file, as it's passed to the download manager.
+    fn source(&self) -> String {
+        self.torrent_path.to_string_lossy().to_string()
+    }
+
+    /// How a download of the fixture into `output_dir` is recorded once finished.
+    fn record(&self, output_dir: &Path) -> TorrentRecord {
+        let now = Utc::now().to_rfc3339();
+        TorrentRecord {
+            info_hash: self.info_hash.clone(),
+            name: self.name.clone(),
+            source: self.source(),
+            output_dir: output_dir.to_path_buf(),
+            disk_root: output_dir.to_path_buf(),
+            status: TorrentStatus::Completed,
+            total_bytes: self.len,
+            progress_bytes: self.len,
+            uploaded_bytes: 0,
+            selected_files: None,
+            content_type: None,
+            problem: None,
+            added_at: now.clone(),
+            updated_at: now,
+        }
+    }
+}
+
+/// Noise, so no piece repeats another, but the same noise every time.
+fn content() -> Vec<u8> {
+    let mut state: u64 = 0x9e37_79b9_7f4a_7c15;
+    (0..FIXTURE_LEN)
+        .map(|_| {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            (state >> 32) as u8
+        })
+        .collect()
+}
+
+fn dict<const N: usize>(entries: [(&str, Value); N]) -> Value {
+    Value::Dict(entries.into_iter().map(|(key, value)| (key.as_bytes().to_vec(), value)).collect())
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// The peers in each info hash's swarm, by peer ID.
+type Swarms = Arc<Mutex<HashMap<String, HashMap<Vec<u8>, SwarmPeer>>>>;
+
+/// What a peer said in its last announce.
+struct SwarmPeer {
+    port: u16,
+    /// Announced with nothing left to download
+    seeding: bool,
+}
+
+/// A minimal HTTP tracker on localhost that introduces whoever announces
+/// the same torrent to each other. Every peer is taken to be on this
+/// machine. Stops when dropped.
+pub struct Tracker {
+    pub announce_url: String,
+    swarms: Swarms,
+    server: tokio::task::JoinHandle<()>,
+}
+
+impl Tracker {
+    pub async fn start() -> Result<Self> {
+        let listener = tokio::net::TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).await?;
+        let announce_url = format!("http://{}/announce", listener.local_addr()?);
+        let swarms = Swarms::default();
+        let app = Router::new()
+            .route("/announce", get(announce))
+            .with_state(swarms.clone());
+        let server = tokio::spawn(async move {
+            if let Err(e) = axum::serve(listener, app).await {
+                warn!("Selftest tracker stopped: {}", e);
+            }
+        });
+        Ok(Self { announce_url, swarms, server })
+    }
+
+    /// Peers that have announced `info_hash` and not stopped since.
+    pub fn peers(&self, info_hash: &str) -> usize {
+        self.swarms.lock().unwrap().get(info_hash).map_or(0, |swarm| swarm.len())
+    }
+}
+
+impl Drop for Tracker {
+    fn drop(&mut self) {
+        self.server.abort();
+    }
+}
+
+/// Record the announcing peer and answer with the rest of its swarm, in
+/// the compact format.
+async fn announce(State(swarms): State<Swarms>, RawQuery(query): RawQuery) -> Vec<u8> {
+    // info_hash and peer_id are raw bytes, so the query can't be decoded as text
+    let params: HashMap<&str, Vec<u8>> = query.as_deref().unwrap_or_default()
+        .split('&')
+        .filter_map(|pair| pair.split_once('='))
+        .map(|(key, value)| (key, urlencoding::decode_binary(value.as_bytes()).into_owned()))
+        .collect();
+    let (Some(info_hash), Some(peer_id)) = (params.get("info_hash"), params.get("peer_id")) else {
+        let failure = dict([("failure reason", Value::Bytes(b"info_hash and peer_id are required".to_vec()))]);
+        return serde_bencode::to_bytes(&failure).unwrap_or_default();
+    };
+    let port = params.get("port")
+        .and_then(|port| std::str::from_utf8(port).ok()?.parse::<u16>().ok())
+        .unwrap_or(0);
+    let seeding = params.get("left").is_some_and(|left| left == b"0");
+
+    let mut swarms = swarms.lock().unwrap();
+    let swarm = swarms.entry(hex(info_hash)).or_default();
+    if params.get("event").is_some_and(|event| event == b"stopped") {
+        swarm.remove(peer_id);
+    } else {
+        swarm.insert(peer_id.clone(), SwarmPeer { port, seeding });
+    }
+    let peers: Vec<u8> = swarm.iter()
+        .filter(|(id, peer)| *id != peer_id && peer.port != 0)
+        .flat_map(|(_, peer)| Ipv4Addr::LOCALHOST.octets().into_iter().chain(peer.port.to_be_bytes()))
+        .collect();
+    // Clients insist on the swarm's size alongside its peers
+    let seeders = swarm.values().filter(|peer| peer.seeding).count() as i64;
+    serde_bencode::to_bytes(&dict([
+        ("interval", Value::Int(ANNOUNCE_INTERVAL_SECS)),
+        ("complete", Value::Int(seeders)),
+        ("incomplete", Value::Int(swarm.len() as i64 - seeders)),
+        ("peers", Value::Bytes(peers)),
+    ])).unwrap_or_default()
+}
+
+/// A session that only finds peers through the torrent's tracker: no
+/// DHT, no port forwarding, listening on a free port.
+async fn local_session(dir: PathBuf) -> Result<Arc<Session>> {
+    let port = std::net::TcpListener::bind((Ipv4Addr::LOCALHOST, 0))?.local_addr()?.port();
+    std::fs::create_dir_all(&dir)?;
+    Session::new_with_opts(dir, SessionOptions {
+        disable_dht: true,
+        disable_dht_persistence: true,
+        listen_port_range: Some(port..port + 1),
+        enable_upnp_port_forwarding: false,
+        ..Default::default()
+    }).await
+}
+
+/// How one step of the self-test went.
+#[derive(Debug)]
+pub struct Step {
+    pub name: &'static str,
+    pub elapsed: Duration,
+    /// What was shown to work, or why it didn't
+    pub outcome: Result<String, String>,
+}
+
+#[derive(Debug, Default)]
+pub struct SelftestReport {
+    pub steps: Vec<Step>,
+}
+
+impl SelftestReport {
+    pub fn passed(&self) -> bool {
+        !self.steps.is_empty() && self.steps.iter().all(|step| step.outcome.is_ok())
+    }
+}
+
+type StepFn = for<'a> fn(&'a Harness) -> BoxFuture<'a, Result<String>>;
+
+/// Run in order; each picks up where the last left off.
+const STEPS: &[(&str, StepFn)] = &[
+    ("seed", |h| h.seed().boxed()),
+    ("download", |h| h.download().boxed()),
+    ("queue", |h| h.queue().boxed()),
+    ("download command", |h| h.download_command().boxed()),
+    ("resume", |h| h.resume().boxed()),
+    ("reseed", |h| h.reseed().boxed()),
+    ("flag damaged files", |h| h.flag_damaged().boxed()),
+    ("flag missing files", |h| h.flag_missing().boxed()),
+    ("reseed organized files", |h| h.reseed_organized().boxed()),
+];
+
+/// Share generated content from a local tracker and seeder, then put the
+/// download manager through it: download, work the queue, resume after
+/// losing half the data, and seed again with the files intact, damaged,
+/// gone and organized into a library. Stops at the first step that fails.
+/// Nothing leaves this machine, and the user's config and state database
+/// aren't touched.
+pub async fn run(config: &Config) -> Result<SelftestReport> {
+    let dir = std::env::temp_dir().join(format!("torrentai-selftest-{}", std::process::id()));
+    let result = run_in(config, dir.clone()).await;
+    if let Err(e) = std::fs::remove_dir_all(&dir) {
+        warn!("Could not remove {}: {}", dir.display(), e);
+    }
+    result
+}
+
+async fn run_in(config: &Config, dir: PathBuf) -> Result<SelftestReport> {
+    // A throwaway state database, so the user's isn't touched
+    let mut config = config.clone().with_state_dir(dir.join("state"));
+    config.notifications.events.clear();
+    // Reseeding is checked the thorough way
+    config.daemon.verify_hashes = true;
+    // Peers only come from the local tracker
+    config.download.dht = false;
+
+    let tracker = Tracker::start().await?;
+    let harness = Harness {
+        fixture: Fixture::create(&dir.join("fixture"), &tracker.announce_url)?,
+        tracker,
+        config,
+        dir,
+        seeder: Mutex::new(None),
+    };
+
+    let mut report = SelftestReport::default();
+    for (name, step) in STEPS {
+        let started = Instant::now();
+        let outcome = match tokio::time::timeout(STEP_TIMEOUT, step(&harness)).await {
+            Ok(result) => result.map_err(|e| e.to_string()),
+            Err(_) => Err(format!("timed out after {}s", STEP_TIMEOUT.as_secs())),
+        };
+        let failed = outcome.is_err();
+        report.steps.push(Step { name, elapsed: started.elapsed(), outcome });
+        if failed {
+            break;
+        }
+    }
+    Ok(report)
+}
+
+struct Harness {
+    fixture: Fixture,
+    tracker: Tracker,
+    config: Config,
+    dir: PathBuf,
+    /// Kept alive for the whole run, since dropping it stops seeding
+    seeder: Mutex<Option<Arc<Session>>>,
+}
+
+impl Harness {
+    fn output_dir(&self) -> PathBuf {
+        self.dir.join("downloads")
+    }
+
+    fn state(&self) -> Result<StateDb> {
+        StateDb::open_in(&self.config.state_dir())
+    }
+
+    /// A download manager in a fresh local session, as after a restart.
+    async fn manager(&self, name: &str) -> Result<DownloadManager> {
+        let session = local_session(self.dir.join("sessions").join(name)).await?;
+        Ok(DownloadManager::with_session(self.config.clone(), session))
+    }
+
+    /// The fixture's own file, checked by librqbit and announced.
+    async fn seed(&self) -> Result<String> {
+        let session = local_session(self.dir.join("sessions").join("seeder")).await?;
+        let options = AddTorrentOptions {
+            output_folder: Some(self.fixture.path.parent().unwrap_or(&self.dir).to_string_lossy().to_string()),
+            overwrite: true,
+            ..Default::default()
+        };
+        let handle = match session.add_torrent(AddTorrent::from_bytes(self.fixture.torrent.clone()), Some(options)).await? {
+            AddTorrentResponse::Added(_, handle) | AddTorrentResponse::AlreadyManaged(_, handle) => handle,
+            AddTorrentResponse::ListOnly(_) => return Err(anyhow::anyhow!("The seeder only listed the fixture")),
+        };
+        handle.wait_until_initialized().await?;
+        let stats = handle.stats();
+        if !stats.finished {
+            return Err(anyhow::anyhow!(
+                "Only {} of the {} fixture matched its own torrent",
+                format_bytes(stats.progress_bytes),
+                format_bytes(self.fixture.len)
+            ));
+        }
+        *self.seeder.lock().unwrap() = Some(session);
+
+        while self.tracker.peers(&self.fixture.info_hash) == 0 {
+            tokio::time::sleep(POLL_INTERVAL).await;
+        }
+        Ok(format!("{} in {} pieces, announced to {}", format_bytes(self.fixture.len), self.fixture.pieces, self.tracker.announce_url))
+    }
+
+    async fn download(&self) -> Result<String> {
+        let downloads = self.manager("download").await?;
+        let started = Instant::now();
+        downloads.add(&self.fixture.name, &self.fixture.source(), self.output_dir()).await?;
+        wait_until_finished(&downloads).await?;
+        let took = started.elapsed();
+        downloads.stop_all().await?;
+        self.fixture.check_copy(&self.output_dir())?;
+        Ok(format!("{} in {:.1}s, identical to the fixture", format_bytes(self.fixture.len), took.as_secs_f64()))
+    }
+
+    /// Queue the fixture and work the queue as `queue run` does, in a
+    /// session of its own.
+    async fn queue(&self) -> Result<String> {
+        let output_dir = self.dir.join("queue");
+        let state = self.state()?;
+        let id = state.enqueue(&self.fixture.name, &self.fixture.source())?;
+        downloader::run_queue(&self.config, Some(output_dir.clone())).await?;
+        let status = state.queue()?.into_iter().find(|item| item.id == id).map(|item| item.status);
+        if status != Some(QueueStatus::Completed) {
+            return Err(anyhow::anyhow!("Queue entry #{} ended up {:?}", id, status));
+        }
+        self.fixture.check_copy(&output_dir)?;
+        Ok(format!("entry #{} completed, identical to the fixture", id))
+    }
+
+    /// The same download the `download` command makes.
+    async fn download_command(&self) -> Result<String> {
+        let output_dir = self.dir.join("download-command");
+        let files = downloader::download_all(&self.config, &[(self.fixture.source(), output_dir.clone())]).await?
+            .pop()
+            .expect("one outcome per torrent")?;
+        self.fixture.check_copy(&output_dir)?;
+        Ok(format!("{} file(s), identical to the fixture", files.len()))
+    }
+
+    /// Cut the copy short, as if the download had been interrupted, and
+    /// pick it up in a new session.
+    async fn resume(&self) -> Result<String> {
+        let kept = self.fixture.len / 2;
+        std::fs::OpenOptions::new()
+            .write(true)
+            .open(self.output_dir().join(&self.fixture.name))?
+            .set_len(kept)?;
+
+        let downloads = self.manager("resume").await?;
+        downloads.resume(&self.fixture.record(&self.output_dir())).await?;
+        wait_until_finished(&downloads).await?;
+        downloads.stop_all().await?;
+        self.fixture.check_copy(&self.output_dir())?;
+        Ok(format!("finished from {} already on disk", format_bytes(kept)))
+    }
+
+    async fn reseed(&self) -> Result<String> {
+        let downloads = self.manager("reseed").await?;
+        let problem = downloads.reseed(&self.fixture.record(&self.output_dir())).await?;
+        downloads.stop_all().await?;
+        match problem {
+            None => Ok("seeding again after every piece was hashed".to_string()),
+            Some(problem) => Err(anyhow::anyhow!("The intact copy was flagged: {}", problem)),
+        }
+    }
+
+    /// Overwrite part of the copy without changing its size, which only
+    /// hashing notices.
+    async fn flag_damaged(&self) -> Result<String> {
+        let path = self.output_dir().join(&self.fixture.name);
+        let mut copy = std::fs::read(&path)?;
+        let damaged = PIECE_LENGTH + 100..PIECE_LENGTH + 4196;
+        copy[damaged.clone()].iter_mut().for_each(|byte| *byte = !*byte);
+        std::fs::write(&path, &copy)?;
+        self.expect_flagged("damaged").await
+    }
+
+    async fn flag_missing(&self) -> Result<String> {
+        std::fs::remove_file(self.output_dir().join(&self.fixture.name))?;
+        self.expect_flagged("missing").await
+    }
+
+    /// Organize the download command's copy into a library, as
+    /// `--organize` does, and seed it from there.
+    async fn reseed_organized(&self) -> Result<String> {
+        let output_dir = self.dir.join("download-command");
+        let record = self.fixture.record(&output_dir);
+        let state = self.state()?;
+        state.upsert_torrent(&record)?;
+        let files = organize::collect_files(&output_dir.join(&self.fixture.name))?;
+        let moves = organize::organize(&files, None, &self.dir.join("library"), &state)?;
+        let [organized] = moves.as_slice() else {
+            return Err(anyhow::anyhow!("Organized {} files, not the fixture alone", moves.len()));
+        };
+
+        let downloads = self.manager("organized").await?;
+        let problem = downloads.reseed(&record).await?;
+        let seeding = downloads.progress().len();
+        downloads.stop_all().await?;
+        match problem {
+            None if seeding == 1 => Ok(format!("seeding {} after every piece was hashed", organized.to.display())),
+            None => Err(anyhow::anyhow!("Not flagged, but not seeding either")),
+            Some(problem) => Err(anyhow::anyhow!("The organized copy was flagged: {}", problem)),
+        }
+    }
+
+    /// Reseeding the copy should leave it out of the session, saying why.
+    async fn expect_flagged(&self, what: &str) -> Result<String> {
+        let downloads = self.manager(what).await?;
+        let problem = downloads.reseed(&self.fixture.record(&self.output_dir())).await?;
+        let seeding = downloads.progress().len();
+        downloads.stop_all().await?;
+        match problem {
+            Some(problem) if seeding == 0 => Ok(format!("not seeded: {}", problem)),
+            Some(_) => Err(anyhow::anyhow!("Flagged, but seeding the {} copy anyway", what)),
+            None => Err(anyhow::anyhow!("Seeding the {} copy without complaint", what)),
+        }
+    }
+}
+
+/// Wait for every download in `downloads` to finish, failing on the first error.
+async fn wait_until_finished(downloads: &DownloadManager) -> Result<()> {
+    loop {
+        let progress = downloads.progress();
+        if let Some((name, error)) = progress.iter().find_map(|p| Some((&p.name, p.error.as_ref()?))) {
+            return Err(anyhow::anyhow!("{} failed: {}", name, error));
+        }
+        if !progress.is_empty() && progress.iter().all(|p| p.finished) {
+            return Ok(());
+        }
+        tokio::time::sleep(POLL_INTERVAL).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fixture_torrent_describes_the_content() {
+        let dir = std::env::temp_dir().join(format!("torrentai-selftest-fixture-{}", std::process::id()));
+        let fixture = Fixture::create(&dir, "http://127.0.0.1:1/announce").unwrap();
+
+        let metainfo: Value = serde_bencode::from_bytes(&fixture.torrent).unwrap();
+        let Value::Dict(metainfo) = metainfo else { panic!("metainfo isn't a dictionary") };
+        let info = serde_bencode::to_bytes(&metainfo[&b"info".to_vec()]).unwrap();
+        assert_eq!(hex(&Sha1::digest(info)), fixture.info_hash);
+        assert_eq!(crate::trackers::tiers_from_torrent_file(&fixture.torrent), vec![vec!["http://127.0.0.1:1/announce".to_string()]]);
+        assert_eq!(fixture.pieces, 13);
+        fixture.check_copy(&dir).unwrap();
+
+        std::fs::write(dir.join(FIXTURE_NAME), b"not the fixture").unwrap();
+        assert!(fixture.check_copy(&dir).is_err());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn tracker_introduces_peers_to_each_other() {
+        let tracker = Tracker::start().await.unwrap();
+        let info_hash = "%01".repeat(20);
+        let announce = |peer: &'static str, port: u16, event: &'static str| {
+            let url = format!("{}?info_hash={}&peer_id={}&port={}&compact=1&event={}", tracker.announce_url, info_hash, peer.repeat(20), port, event);
+            async move { reqwest::get(url).await.unwrap().bytes().await.unwrap().to_vec() }
+        };
+        let peers = |response: &[u8]| match serde_bencode::from_bytes::<Value>(response).unwrap() {
+            Value::Dict(fields) => match &fields[&b"peers".to_vec()] {
+                Value::Bytes(peers) => peers.clone(),
+                _ => panic!("peers aren't compact"),
+            },
+            _ => panic!("response isn't a dictionary"),
+        };
+
+        assert!(peers(&announce("a", 6881, "started").await).is_empty());
+        assert_eq!(peers(&announce("b", 6882, "started").await), vec![127, 0, 0, 1, 0x1a, 0xe1]);
+        assert_eq!(tracker.peers(&"01".repeat(20)), 2);
+        announce("a", 6881, "stopped").await;
+        assert!(peers(&announce("b", 6882, "").await).is_empty());
+        assert_eq!(tracker.peers(&"01".repeat(20)), 1);
+    }
+
+    /// The whole run, with real librqbit sessions swapping data over
+    /// loopback; slow enough to leave to `cargo test --features e2e`.
+    #[cfg(feature = "e2e")]
+    #[tokio::test]
+    async fn selftest_passes() {
+        let report = run(&Config::default()).await.unwrap();
+        for step in &report.steps {
+            assert!(step.outcome.is_ok(), "{}: {:?}", step.name, step.outcome);
+        }
+        assert_eq!(report.steps.len(), STEPS.len());
+    }
+}
@@ -0,0 +1,111 @@
+use lazy_static::lazy_static;
+use regex::Regex;
+
+lazy_static! {
+    /// A number and its unit. Digits may be grouped with commas, dots or
+    /// apostrophes, or with spaces before groups of exactly three digits.
+    static ref SIZE_RE: Regex = Regex::new(
+        r"(?i)(\d+(?:[.,']\d+|[ \x{a0}\x{202f}]\d{3}\b)*)\s*(bytes?|octets?|[kmgtp]i?[bo]|[kmgt])(?:[^a-z]|$)"
+    ).unwrap();
+}
+
+/// Sizes as sources print them, in bytes: "1.4 GiB" and "700.2 MiB" from
+/// ThePirateBay, "1.96 GB" from YTS, "4,37 Go" or "1.234,5 MB" from
+/// European sites, and bare units like "4G" typed on the command line.
+/// Decimal and binary units alike are read as powers of 1024, the way the
+/// sites mean them and the way sizes are shown back.
+pub fn parse_bytes(text: &str) -> Option<u64> {
+    let caps = SIZE_RE.captures(text)?;
+    let value = parse_number(&caps[1])?;
+    let unit = caps[2].to_ascii_lowercase();
+    let exponent = match unit.chars().next()? {
+        'b' | 'o' => 0,
+        'k' => 1,
+        'm' => 2,
+        'g' => 3,
+        't' => 4,
+        'p' => 5,
+        _ => return None,
+    };
+    Some((value * 1024f64.powi(exponent)) as u64)
+}
+
+/// A number in either convention: whichever of '.' and ',' comes last is
+/// the decimal point when both appear, a separator repeated is grouping,
+/// and a lone comma before exactly three digits groups thousands.
+fn parse_number(number: &str) -> Option<f64> {
+    let digits: String = number.chars().filter(|c| !matches!(c, ' ' | '\u{a0}' | '\u{202f}' | '\'')).collect();
+    let normalized = match (digits.rfind('.'), digits.rfind(',')) {
+        (Some(dot), Some(comma)) if dot > comma => digits.replace(',', ""),
+        (Some(_), Some(_)) => digits.replace('.', "").replace(',', "."),
+        (Some(_), None) if digits.matches('.').count() > 1 => digits.replace('.', ""),
+        (None, Some(comma)) if digits.matches(',').count() > 1 || digits.len() - comma == 4 => digits.replace(',', ""),
+        (None, Some(_)) => digits.replace(',', "."),
+        _ => digits,
+    };
+    normalized.parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const KIB: f64 = 1024.0;
+    const MIB: f64 = KIB * 1024.0;
+    const GIB: f64 = MIB * 1024.0;
+
+    fn bytes(value: f64) -> Option<u64> {
+        Some(value as u64)
+    }
+
+    #[test]
+    fn pirate_bay_sizes() {
+        assert_eq!(parse_bytes("1.4\u{a0}GiB"), bytes(1.4 * GIB));
+        assert_eq!(parse_bytes("700.2\u{a0}MiB"), bytes(700.2 * MIB));
+        assert_eq!(parse_bytes("Uploaded 03-14\u{a0}2019, Size 1.37\u{a0}GiB, ULed by yify"), bytes(1.37 * GIB));
+        assert_eq!(parse_bytes("Uploaded Y-day\u{a0}14:32, Size 356.98\u{a0}MiB, ULed by eztv"), bytes(356.98 * MIB));
+        assert_eq!(parse_bytes("512 KiB"), bytes(512.0 * KIB));
+    }
+
+    #[test]
+    fn yts_and_formatted_sizes() {
+        assert_eq!(parse_bytes("1.96 GB"), bytes(1.96 * GIB));
+        assert_eq!(parse_bytes("798.4 MB"), bytes(798.4 * MIB));
+        assert_eq!(parse_bytes("4.37 GB"), bytes(4.37 * GIB));
+        assert_eq!(parse_bytes("12.45 GB"), bytes(12.45 * GIB));
+        assert_eq!(parse_bytes("700MB"), bytes(700.0 * MIB));
+    }
+
+    #[test]
+    fn european_sizes() {
+        assert_eq!(parse_bytes("1,4 GB"), bytes(1.4 * GIB));
+        assert_eq!(parse_bytes("4,37 Go"), bytes(4.37 * GIB));
+        assert_eq!(parse_bytes("1.234,5 MB"), bytes(1234.5 * MIB));
+        assert_eq!(parse_bytes("1 234,5 Mo"), bytes(1234.5 * MIB));
+        assert_eq!(parse_bytes("1\u{202f}024 MB"), bytes(1024.0 * MIB));
+    }
+
+    #[test]
+    fn grouped_thousands() {
+        assert_eq!(parse_bytes("1,024.5 MB"), bytes(1024.5 * MIB));
+        assert_eq!(parse_bytes("2,048 MB"), bytes(2048.0 * MIB));
+        assert_eq!(parse_bytes("1'536 MB"), bytes(1536.0 * MIB));
+        assert_eq!(parse_bytes("1,503,238,553 bytes"), Some(1_503_238_553));
+    }
+
+    #[test]
+    fn bare_units() {
+        assert_eq!(parse_bytes("4G"), bytes(4.0 * GIB));
+        assert_eq!(parse_bytes("700m"), bytes(700.0 * MIB));
+        assert_eq!(parse_bytes("1.5T"), bytes(1.5 * GIB * 1024.0));
+    }
+
+    #[test]
+    fn not_sizes() {
+        assert_eq!(parse_bytes(""), None);
+        assert_eq!(parse_bytes("Unknown"), None);
+        assert_eq!(parse_bytes("1080p"), None);
+        assert_eq!(parse_bytes("12"), None);
+        assert_eq!(parse_bytes("Uploaded 03-14\u{a0}2019"), None);
+    }
+}
@@ -1,101 +1,1227 @@
-use crate::{llm_service::LlmService, models::*, scraper::*};
+use crate::{filter::ResultFilter, llm_service::LlmService, metadata::{self, CanonicalTitle, MetadataClient}, models::*, ranker, safety, scraper::*, uploaded};
+use crate::config::LanguageConfig;
+use crate::language;
 use crate::pirate_bay_scraper::TorrentResult;
+use crate::state::StateDb;
+use crate::trace::{self, TraceEvent, Tracer};
 use anyhow::Result;
-use std::collections::HashSet;
+use chrono::{DateTime, Local, NaiveDate, Utc};
+use futures::future::{BoxFuture, FutureExt};
+use futures::Stream;
+use std::collections::{HashMap, HashSet};
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use tokio::sync::mpsc;
+use tracing::{debug, warn};
 
+/// Stop probing for further episodes of a season past this number.
+const MAX_PLANNED_EPISODE: u8 = 40;
+
+// Adaptive thresholding: how far ahead the top result must be to pass alone
+// when everything is under the threshold, and the lowest it may then go
+const DOMINANCE_MARGIN: f32 = 0.2;
+const RELAXED_FLOOR: f32 = 0.4;
+// Results this close to the threshold count as clustered around it
+const CLUSTER_BAND: f32 = 0.1;
+const CLUSTER_SIZE: usize = 3;
+const TIGHTEN_STEP: f32 = 0.1;
+
+/// Progress of a smart search, in pipeline order.
+#[derive(Debug, Clone)]
+pub enum SmartSearchEvent {
+    IntentParsed(SearchIntent),
+    /// A follow-up changed the intent; `new_search` is false when only re-ranking
+    Refined {
+        intent: SearchIntent,
+        new_search: bool,
+    },
+    SourceResults {
+        source: String,
+        query: String,
+        results: Vec<TorrentResult>,
+    },
+    /// Adaptive mode moved the confidence threshold for this result set
+    ThresholdAdjusted {
+        from: f32,
+        to: f32,
+        reason: String,
+    },
+    /// Canonical title found for cross-checking results
+    MetadataFound(CanonicalTitle),
+    /// "The latest episode" pinned to the newest one that has aired
+    LatestResolved {
+        intent: SearchIntent,
+        air_date: Option<NaiveDate>,
+    },
+    /// The title isn't out yet, so the sources weren't searched
+    NotYetReleased {
+        title: String,
+        release_date: NaiveDate,
+    },
+    /// A step failed and a lesser fallback stood in for it
+    Degraded(Degradation),
+    EvaluationStarted {
+        total: usize,
+    },
+    EvaluationChunk(Vec<EvaluatedResult>),
+    /// Counts from each pipeline stage, sent just before `Finished`
+    Diagnostics(SearchDiagnostics),
+    /// A season was requested but no pack was found, so episodes were matched individually
+    EpisodesPlanned(EpisodePlan),
+    /// Final results above the confidence threshold, best first
+    Finished(Vec<EvaluatedResult>),
+}
+
+/// How a search falls back instead of failing, in order: the LLM to the
+/// heuristic ranker, a failed source to the ones still answering, and
+/// every source failing to whatever was cached for the query.
+#[derive(Debug, Clone)]
+pub enum Degradation {
+    /// The LLM failed at `task`, so heuristics run the rest of the search
+    Heuristics {
+        task: String,
+        error: String,
+    },
+    /// A source failed; the others are still searched
+    SourceDown {
+        source: String,
+        error: String,
+    },
+    /// Every source failed, so `source`'s cached results were used
+    CacheOnly {
+        source: String,
+        query: String,
+        fetched_at: DateTime<Utc>,
+    },
+    /// Every source failed and nothing was cached for the query
+    NothingCached {
+        query: String,
+    },
+}
+
+impl Degradation {
+    pub fn describe(&self) -> String {
+        match self {
+            Degradation::Heuristics { task, error } => {
+                format!("The LLM couldn't {} ({}); using heuristics for the rest of this search", task, error)
+            }
+            Degradation::SourceDown { source, error } => {
+                format!("{} is unavailable ({}); continuing with the other sources", source, error)
+            }
+            Degradation::CacheOnly { source, query, fetched_at } => {
+                let age = uploaded::describe_age(fetched_at.with_timezone(&Local).naive_local());
+                format!("Every source is down; using {} results for \"{}\" cached {}", source, query, age)
+            }
+            Degradation::NothingCached { query } => {
+                format!("Every source is down and nothing is cached for \"{}\"", query)
+            }
+        }
+    }
+}
+
+/// Async stream of [`SmartSearchEvent`]s for callers that render progress
+/// themselves (GUI wrappers, bots) instead of reading stdout. An error ends
+/// the stream after being yielded once.
+pub struct SmartSearchStream {
+    rx: mpsc::UnboundedReceiver<Result<SmartSearchEvent>>,
+}
+
+impl Stream for SmartSearchStream {
+    type Item = Result<SmartSearchEvent>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.rx.poll_recv(cx)
+    }
+}
+
+/// Where results were lost along the pipeline, so an empty result set can be
+/// explained instead of just reported.
+#[derive(Debug, Clone, Default)]
+pub struct SearchDiagnostics {
+    pub content_type: Option<ContentType>,
+    pub queries: Vec<String>,
+    /// Raw results per source, summed over every query
+    pub source_counts: Vec<(String, usize)>,
+    /// Sources that failed, with the error
+    pub source_errors: Vec<(String, String)>,
+    pub duplicates_removed: usize,
+    /// Active hard constraints, if any, and how many results they removed
+    pub filters: Option<String>,
+    pub filtered_out: usize,
+    pub evaluated: usize,
+    pub ranked_by_llm: bool,
+    pub min_confidence: f32,
+    /// Results evaluated but dropped for falling under `min_confidence`
+    pub below_confidence: usize,
+    pub best_rejected_confidence: Option<f32>,
+    /// Set when the search was skipped because the title isn't out yet
+    pub unreleased: Option<NaiveDate>,
+}
+
+impl SearchDiagnostics {
+    pub fn raw_results(&self) -> usize {
+        self.source_counts.iter().map(|(_, count)| count).sum()
+    }
+
+    fn record_source(&mut self, source: &str, result: &Result<Vec<TorrentResult>>) {
+        match result {
+            Ok(results) => match self.source_counts.iter_mut().find(|(name, _)| name == source) {
+                Some((_, count)) => *count += results.len(),
+                None => self.source_counts.push((source.to_string(), results.len())),
+            },
+            Err(e) => self.source_errors.push((source.to_string(), e.to_string())),
+        }
+    }
+
+    /// One-line explanation for compact displays.
+    pub fn summary(&self) -> String {
+        if let Some(date) = self.unreleased {
+            return format!("not released until {}", date.format("%B %-d, %Y"));
+        }
+        if self.raw_results() == 0 {
+            return format!("no source returned results for {} queries", self.queries.len());
+        }
+        format!(
+            "{} found, {} duplicates, {} filtered out, {} below confidence {:.2}",
+            self.raw_results(), self.duplicates_removed, self.filtered_out, self.below_confidence, self.min_confidence
+        )
+    }
+
+    /// What to try next when nothing made it through.
+    pub fn suggestions(&self) -> Vec<String> {
+        let mut suggestions = Vec::new();
+
+        if !self.source_errors.is_empty() {
+            let failed: Vec<&str> = self.source_errors.iter().map(|(source, _)| source.as_str()).collect();
+            suggestions.push(format!("{} failed; check your connection or try again later", failed.join(", ")));
+        }
+
+        if self.raw_results() == 0 {
+            suggestions.push("Rephrase the request: use the release's usual title, or drop quality and edition terms".to_string());
+        }
+
+        if let (Some(filters), true) = (&self.filters, self.filtered_out > 0) {
+            suggestions.push(format!("Loosen the filters ({}); they removed {} result(s)", filters, self.filtered_out));
+        }
+
+        if let Some(best) = self.best_rejected_confidence {
+            let suggested = ((best * 10.0).floor() / 10.0).max(0.1);
+            suggestions.push(format!(
+                "Lower the threshold: the best rejected result had {:.0}% confidence (try --min-confidence {:.1})",
+                best * 100.0, suggested
+            ));
+        }
+
+        if self.ranked_by_llm && self.below_confidence > 0 {
+            suggestions.push("Try --no-llm to compare against the heuristic ranking".to_string());
+        }
+
+        // YTS only carries movies, so anything else effectively has a single source
+        if !matches!(self.content_type, Some(ContentType::Movie) | None) {
+            suggestions.push("Only ThePirateBay carries this kind of content; try `torrentai search` with a shorter query".to_string());
+        }
+
+        suggestions
+    }
+}
+
+/// State carried between follow-ups in an interactive search.
+pub struct SearchSession {
+    /// The query the session started from
+    pub query: String,
+    pub intent: SearchIntent,
+    /// Follow-ups applied so far, oldest first
+    pub refinements: Vec<String>,
+    /// Every unique result found, before evaluation
+    candidates: Vec<TorrentResult>,
+    /// Current ranked results above the confidence threshold
+    pub results: Vec<EvaluatedResult>,
+    /// Per-episode fallback when a season was asked for and no pack was found
+    pub episode_plan: Option<EpisodePlan>,
+    /// Stage counts from the latest search or refinement
+    pub diagnostics: SearchDiagnostics,
+    /// Metadata for the current intent, when a provider is configured
+    pub canonical: Option<CanonicalTitle>,
+}
+
+/// Runs searches for unattended checks such as watch rules and feeds: a
+/// [`SmartSearcher`] itself, or the daemon's coalescer in front of one.
+pub trait SearchService: Send + Sync {
+    fn search<'a>(&'a self, query: &'a str) -> BoxFuture<'a, Result<Vec<EvaluatedResult>>>;
+
+    /// Score `results` against a description, as [`SmartSearcher::match_results`] does.
+    fn match_results<'a>(&'a self, description: &'a str, results: Vec<TorrentResult>) -> BoxFuture<'a, Result<Vec<EvaluatedResult>>>;
+}
+
+impl SearchService for SmartSearcher {
+    fn search<'a>(&'a self, query: &'a str) -> BoxFuture<'a, Result<Vec<EvaluatedResult>>> {
+        SmartSearcher::search(self, query, |_| {}).boxed()
+    }
+
+    fn match_results<'a>(&'a self, description: &'a str, results: Vec<TorrentResult>) -> BoxFuture<'a, Result<Vec<EvaluatedResult>>> {
+        SmartSearcher::match_results(self, description, results).boxed()
+    }
+}
+
+/// The natural-language search pipeline: parse the query, search every
+/// source in its [`SourceRegistry`], then score and filter what comes back.
 pub struct SmartSearcher {
-    llm: LlmService,
+    /// `None` runs the deterministic heuristic ranker instead of the LLM
+    llm: Option<LlmService>,
+    /// Verifies results against TMDB/OMDb when configured
+    metadata: Option<MetadataClient>,
     min_confidence: f32,
+    /// Move the threshold based on how confidences are distributed
+    adaptive_threshold: bool,
+    /// Reuse scraper results younger than this; `None` always scrapes
+    cache_ttl: Option<chrono::Duration>,
+    /// Applied to raw results before anything is evaluated
+    filter: ResultFilter,
+    /// Audio language wanted when the request doesn't name one
+    language: LanguageConfig,
+    /// Records each pipeline step for `--trace-file`
+    tracer: Option<Tracer>,
+    /// Where results come from
+    sources: SourceRegistry,
+    /// Record searches for `torrentai history`
+    history: bool,
+}
+
+/// What one search has learned about its backends so far. Kept per call
+/// rather than on the searcher, which concurrent searches share.
+#[derive(Default)]
+struct SearchRun {
+    /// The LLM failed; skip it for the rest of this search
+    llm_failed: AtomicBool,
+    /// Sources already reported down
+    sources_down: Mutex<HashSet<&'static str>>,
 }
 
 impl SmartSearcher {
-    pub fn new(llm: LlmService, min_confidence: f32) -> Self {
+    pub fn new(llm: Option<LlmService>, min_confidence: f32) -> Self {
         Self {
             llm,
+            metadata: None,
             min_confidence,
+            adaptive_threshold: false,
+            cache_ttl: None,
+            filter: ResultFilter::default(),
+            language: LanguageConfig::default(),
+            tracer: None,
+            sources: SourceRegistry::default(),
+            history: false,
         }
     }
 
-    pub async fn search(&self, query: &str) -> Result<Vec<EvaluatedResult>> {
+    pub fn with_adaptive_threshold(mut self, adaptive: bool) -> Self {
+        self.adaptive_threshold = adaptive;
+        self
+    }
+
+    pub fn with_metadata(mut self, metadata: Option<MetadataClient>) -> Self {
+        self.metadata = metadata;
+        self
+    }
+
+    pub fn with_cache(mut self, ttl: Option<chrono::Duration>) -> Self {
+        self.cache_ttl = ttl;
+        self
+    }
+
+    pub(crate) fn with_filter(mut self, filter: ResultFilter) -> Self {
+        self.filter = filter;
+        self
+    }
+
+    pub fn with_language(mut self, language: LanguageConfig) -> Self {
+        self.language = language;
+        self
+    }
+
+    /// Record each search and refinement in the history. Off by default, so
+    /// only searches a person ran show up there.
+    pub fn with_history(mut self, history: bool) -> Self {
+        self.history = history;
+        self
+    }
+
+    /// Search these sources instead of the built-in ThePirateBay and YTS.
+    pub fn with_sources(mut self, sources: SourceRegistry) -> Self {
+        self.sources = sources;
+        self
+    }
+
+    /// Record every step, including the LLM's prompts and responses.
+    pub(crate) fn with_tracer(mut self, tracer: Option<Tracer>) -> Self {
+        self.llm = self.llm.map(|llm| llm.with_tracer(tracer.clone()));
+        self.tracer = tracer;
+        self
+    }
+
+    /// Run the search in the background, yielding events as each stage completes.
+    pub fn search_stream(self: Arc<Self>, query: impl Into<String>) -> SmartSearchStream {
+        let query = query.into();
+        let (tx, rx) = mpsc::unbounded_channel();
+
+        tokio::spawn(async move {
+            let events = tx.clone();
+            let result = self.search(&query, move |event| {
+                let _ = events.send(Ok(event.clone()));
+            }).await;
+
+            if let Err(e) = result {
+                let _ = tx.send(Err(e));
+            }
+        });
+
+        SmartSearchStream { rx }
+    }
+
+    /// Run the full pipeline, reporting each stage to `on_event`.
+    pub async fn search(
+        &self,
+        query: &str,
+        on_event: impl Fn(&SmartSearchEvent) + Send + Sync,
+    ) -> Result<Vec<EvaluatedResult>> {
+        Ok(self.start_session(query, on_event).await?.results)
+    }
+
+    /// Score results found elsewhere, such as feed items, against a
+    /// natural-language description without searching any source. Only
+    /// results above the confidence threshold are returned.
+    pub async fn match_results(&self, description: &str, results: Vec<TorrentResult>) -> Result<Vec<EvaluatedResult>> {
+        let run = &SearchRun::default();
+        let intent = self.parse_intent(run, description, &|_: &SmartSearchEvent| {}).await;
+        let mut diagnostics = self.new_diagnostics(run, &intent);
+        let candidates = self.filter.apply(results);
+        self.evaluate(run, &intent, None, candidates, &mut diagnostics, &|_: &SmartSearchEvent| {}).await
+    }
+
+    /// Like [`search`](Self::search), but keeps the parsed intent and every
+    /// result found so follow-ups can be applied with [`refine`](Self::refine).
+    pub async fn start_session(
+        &self,
+        query: &str,
+        on_event: impl Fn(&SmartSearchEvent) + Send + Sync,
+    ) -> Result<SearchSession> {
         // 1. Parse query into intent
-        println!("🤖 Understanding your request...");
-        let intent = self.llm.parse_query(query).await?;
-        self.display_intent(&intent);
+        let run = &SearchRun::default();
+        let intent = self.parse_intent(run, query, &on_event).await;
+        on_event(&SmartSearchEvent::IntentParsed(intent.clone()));
+
+        let mut session = SearchSession {
+            query: query.to_string(),
+            intent,
+            refinements: Vec::new(),
+            candidates: Vec::new(),
+            results: Vec::new(),
+            episode_plan: None,
+            diagnostics: SearchDiagnostics::default(),
+            canonical: None,
+        };
+
+        session.canonical = self.lookup_metadata(&session.intent, &on_event).await;
+        self.resolve_latest(&mut session, &on_event);
+        let mut diagnostics = self.new_diagnostics(run, &session.intent);
+        if !self.announce_unreleased(&session, &mut diagnostics, &on_event) {
+            session.candidates = self.gather(run, &session.intent, session.canonical.as_ref(), &mut diagnostics, &on_event).await?;
+            session.results = self.evaluate(run, &session.intent, session.canonical.as_ref(), session.candidates.clone(), &mut diagnostics, &on_event).await?;
+            session.episode_plan = self.plan_episodes(run, &session.intent, &mut session.candidates, &session.results, &on_event).await?;
+        }
+        trace::record(self.tracer.as_ref(), || TraceEvent::Finished {
+            results: session.results.clone(),
+            episode_plan: session.episode_plan.clone(),
+        });
+        on_event(&SmartSearchEvent::Diagnostics(diagnostics.clone()));
+        on_event(&SmartSearchEvent::Finished(session.results.clone()));
+        session.diagnostics = diagnostics;
+        if self.history {
+            record_history(query, &session.results);
+        }
+
+        Ok(session)
+    }
+
+    /// Apply a natural-language follow-up such as "only 1080p". The session's
+    /// existing results are re-ranked, searching the sources again only when
+    /// the follow-up changes what to search for.
+    pub async fn refine(
+        &self,
+        session: &mut SearchSession,
+        refinement: &str,
+        on_event: impl Fn(&SmartSearchEvent) + Send + Sync,
+    ) -> Result<()> {
+        let run = &SearchRun::default();
+        let by_llm = match self.live_llm(run) {
+            Some(llm) => llm.refine_intent(&session.intent, &session.refinements, refinement).await
+                .map_err(|e| self.fall_back_to_heuristics(run, "understand the follow-up", e, &on_event))
+                .ok(),
+            None => None,
+        };
+        let refined = by_llm.unwrap_or_else(|| ranker::refine_intent(&session.intent, refinement));
+        trace::record(self.tracer.as_ref(), || TraceEvent::IntentRefined {
+            refinement: refinement.to_string(),
+            intent: refined.intent.clone(),
+            new_search: refined.needs_new_search,
+        });
+        on_event(&SmartSearchEvent::Refined {
+            intent: refined.intent.clone(),
+            new_search: refined.needs_new_search,
+        });
+
+        session.intent = refined.intent;
+        session.refinements.push(refinement.to_string());
+
+        let mut diagnostics = self.new_diagnostics(run, &session.intent);
+        if refined.needs_new_search {
+            session.canonical = self.lookup_metadata(&session.intent, &on_event).await;
+            self.resolve_latest(session, &on_event);
+        }
+        if self.announce_unreleased(session, &mut diagnostics, &on_event) {
+            session.results.clear();
+            session.episode_plan = None;
+        } else {
+            if refined.needs_new_search {
+                let found = self.gather(run, &session.intent, session.canonical.as_ref(), &mut diagnostics, &on_event).await?;
+                session.candidates = self.deduplicate_results(found.into_iter().chain(session.candidates.drain(..)).collect());
+            }
+            session.results = self.evaluate(run, &session.intent, session.canonical.as_ref(), session.candidates.clone(), &mut diagnostics, &on_event).await?;
+            session.episode_plan = self.plan_episodes(run, &session.intent, &mut session.candidates, &session.results, &on_event).await?;
+        }
+        trace::record(self.tracer.as_ref(), || TraceEvent::Finished {
+            results: session.results.clone(),
+            episode_plan: session.episode_plan.clone(),
+        });
+        on_event(&SmartSearchEvent::Diagnostics(diagnostics.clone()));
+        on_event(&SmartSearchEvent::Finished(session.results.clone()));
+        session.diagnostics = diagnostics;
+        if self.history {
+            record_history(&format!("{} → {}", session.query, session.refinements.join(" → ")), &session.results);
+        }
+        Ok(())
+    }
+
+    /// The audio language to hold results to: the one asked for, else the
+    /// configured default. A language asked for but not recognised turns
+    /// the check off rather than falling back to the default.
+    fn wanted_language(&self, intent: &SearchIntent) -> Option<&'static str> {
+        match intent.language.as_deref().filter(|l| !l.trim().is_empty()) {
+            Some(asked) => language::code(asked),
+            None => self.language.preferred.as_deref().and_then(language::code),
+        }
+    }
+
+    /// The LLM, unless there is none or it already failed during this search.
+    fn live_llm(&self, run: &SearchRun) -> Option<&LlmService> {
+        self.llm.as_ref().filter(|_| !run.llm_failed.load(Ordering::Relaxed))
+    }
+
+    /// Note that the LLM failed at `task`, leaving the rest of the search
+    /// to heuristics rather than retrying a backend that is likely down.
+    fn fall_back_to_heuristics(
+        &self,
+        run: &SearchRun,
+        task: &str,
+        error: anyhow::Error,
+        on_event: &(impl Fn(&SmartSearchEvent) + Send + Sync),
+    ) {
+        warn!("LLM failed to {}: {:#}", task, error);
+        run.llm_failed.store(true, Ordering::Relaxed);
+        self.degrade(Degradation::Heuristics {
+            task: task.to_string(),
+            error: error.to_string(),
+        }, on_event);
+    }
+
+    fn degrade(&self, degradation: Degradation, on_event: &(impl Fn(&SmartSearchEvent) + Send + Sync)) {
+        trace::record(self.tracer.as_ref(), || TraceEvent::Degraded {
+            message: degradation.describe(),
+        });
+        on_event(&SmartSearchEvent::Degraded(degradation));
+    }
+
+    async fn parse_intent(&self, run: &SearchRun, query: &str, on_event: &(impl Fn(&SmartSearchEvent) + Send + Sync)) -> SearchIntent {
+        let by_llm = match self.live_llm(run) {
+            Some(llm) => llm.parse_query(query).await
+                .map_err(|e| self.fall_back_to_heuristics(run, "understand the request", e, on_event))
+                .ok(),
+            None => None,
+        };
+        let parsed_by_llm = by_llm.is_some();
+        let intent = by_llm.unwrap_or_else(|| ranker::parse_intent(query));
+        trace::record(self.tracer.as_ref(), || TraceEvent::IntentParsed {
+            intent: intent.clone(),
+            by_llm: parsed_by_llm,
+        });
+        intent
+    }
+
+    /// Look up canonical metadata; a failed lookup only loses the extra signal.
+    async fn lookup_metadata(
+        &self,
+        intent: &SearchIntent,
+        on_event: &(impl Fn(&SmartSearchEvent) + Send + Sync),
+    ) -> Option<CanonicalTitle> {
+        let client = self.metadata.as_ref()?;
+        match client.lookup(intent).await {
+            Ok(Some(canonical)) => {
+                on_event(&SmartSearchEvent::MetadataFound(canonical.clone()));
+                Some(canonical)
+            }
+            Ok(None) => None,
+            Err(e) => {
+                warn!("Metadata lookup failed: {}", e);
+                None
+            }
+        }
+    }
+
+    /// Pin "the latest episode" of a series to the newest one that has
+    /// aired, when the metadata provider knows it, so the search asks for
+    /// that episode rather than whatever happens to be newest on the sites.
+    fn resolve_latest(
+        &self,
+        session: &mut SearchSession,
+        on_event: &(impl Fn(&SmartSearchEvent) + Send + Sync),
+    ) {
+        let intent = &session.intent;
+        if !intent.prefer_recent || !matches!(intent.content_type, ContentType::TVShow) {
+            return;
+        }
+        let Some(latest) = session.canonical.as_ref().and_then(|c| c.latest_episode.clone()) else {
+            return;
+        };
+        // "The latest episode of season 2" can't be answered from the series' last episode
+        let asked_season = intent.tv_details.as_ref().and_then(|tv| tv.season);
+        if intent.tv_details.as_ref().is_some_and(|tv| tv.episode.is_some())
+            || asked_season.is_some_and(|season| season != latest.season)
+        {
+            return;
+        }
+
+        session.intent.tv_details = Some(TvDetails {
+            season: Some(latest.season),
+            episode: Some(latest.episode),
+            episode_range: None,
+            complete_season: false,
+            complete_series: false,
+        });
+        trace::record(self.tracer.as_ref(), || TraceEvent::LatestResolved {
+            intent: session.intent.clone(),
+        });
+        on_event(&SmartSearchEvent::LatestResolved {
+            intent: session.intent.clone(),
+            air_date: latest.air_date,
+        });
+    }
+
+    /// Report a title that isn't out yet. Returns true when searching would be futile.
+    fn announce_unreleased(
+        &self,
+        session: &SearchSession,
+        diagnostics: &mut SearchDiagnostics,
+        on_event: &(impl Fn(&SmartSearchEvent) + Send + Sync),
+    ) -> bool {
+        let Some((canonical, release_date)) = session.canonical.as_ref().and_then(|c| Some((c, c.unreleased()?))) else {
+            return false;
+        };
+        diagnostics.unreleased = Some(release_date);
+        on_event(&SmartSearchEvent::NotYetReleased {
+            title: canonical.title.clone(),
+            release_date,
+        });
+        true
+    }
 
+    fn new_diagnostics(&self, run: &SearchRun, intent: &SearchIntent) -> SearchDiagnostics {
+        SearchDiagnostics {
+            content_type: Some(intent.content_type.clone()),
+            ranked_by_llm: self.live_llm(run).is_some(),
+            min_confidence: self.min_confidence,
+            filters: (!self.filter.is_empty()).then(|| self.filter.describe()),
+            ..Default::default()
+        }
+    }
+
+    /// Generate queries for the intent and collect unique results from every source.
+    async fn gather(
+        &self,
+        run: &SearchRun,
+        intent: &SearchIntent,
+        canonical: Option<&CanonicalTitle>,
+        diagnostics: &mut SearchDiagnostics,
+        on_event: &(impl Fn(&SmartSearchEvent) + Send + Sync),
+    ) -> Result<Vec<TorrentResult>> {
         // 2. Generate search queries
-        let strategy = self.llm.generate_search_queries(&intent).await?;
+        let by_llm = match self.live_llm(run) {
+            Some(llm) => llm.generate_search_queries(intent).await
+                .map_err(|e| self.fall_back_to_heuristics(run, "plan the search", e, on_event))
+                .ok(),
+            None => None,
+        };
+        let planned_by_llm = by_llm.is_some();
+        let strategy = by_llm.unwrap_or_else(|| SearchStrategy {
+            primary_queries: ranker::build_queries(intent),
+            fallback_queries: Vec::new(),
+            scraper_hints: HashMap::new(),
+        });
+        trace::record(self.tracer.as_ref(), || TraceEvent::StrategyGenerated {
+            strategy: strategy.clone(),
+            by_llm: planned_by_llm,
+        });
         
-        // 3. Search across all scrapers
-        println!("\n🔍 Searching across sources...");
+        // 3. Search across all scrapers, letting those that can filter on their side
+        let hints = SearchHints::for_intent(intent, canonical);
         let mut all_results = Vec::new();
         
         for query in &strategy.primary_queries {
-            let results = self.search_all_sources(query).await?;
-            all_results.extend(results);
+            diagnostics.queries.push(query.clone());
+            for (source, results) in self.search_all_sources(run, query, &hints, on_event).await {
+                diagnostics.record_source(source, &results);
+                let Ok(results) = results else {
+                    continue;
+                };
+                on_event(&SmartSearchEvent::SourceResults {
+                    source: source.to_string(),
+                    query: query.clone(),
+                    results: results.clone(),
+                });
+                all_results.extend(results);
+            }
             
             if all_results.len() >= 20 {
                 break; // Enough results to evaluate
             }
         }
 
-        // 4. Deduplicate results
-        let unique_results = self.deduplicate_results(all_results);
+        // 4. Deduplicate results, then drop anything the hard constraints rule out
+        let found = all_results.len();
+        let unique = self.deduplicate_results(all_results);
+        diagnostics.duplicates_removed = found - unique.len();
+        let wanted = self.filter.apply(unique.clone());
+        diagnostics.filtered_out = unique.len() - wanted.len();
+        Ok(wanted)
+    }
 
+    /// Score results against the intent, keeping those above the confidence threshold.
+    async fn evaluate(
+        &self,
+        run: &SearchRun,
+        intent: &SearchIntent,
+        canonical: Option<&CanonicalTitle>,
+        candidates: Vec<TorrentResult>,
+        diagnostics: &mut SearchDiagnostics,
+        on_event: &(impl Fn(&SmartSearchEvent) + Send + Sync),
+    ) -> Result<Vec<EvaluatedResult>> {
+        // Strict about language: dubs aren't worth evaluating
+        let wanted = self.wanted_language(intent);
+        let mut candidates = candidates;
+        if let Some(wanted) = wanted.filter(|_| self.language.strict) {
+            let before = candidates.len();
+            candidates.retain(|c| language::mismatch(&c.title, wanted).is_none());
+            diagnostics.filtered_out += before - candidates.len();
+        }
+        diagnostics.evaluated = candidates.len();
         // 5. Evaluate and rank results
-        println!("\n📊 Evaluating {} results...", unique_results.len());
-        let evaluated = self.llm.evaluate_results(&intent, unique_results).await?;
+        on_event(&SmartSearchEvent::EvaluationStarted { total: candidates.len() });
+        let by_llm = match self.live_llm(run) {
+            Some(llm) => {
+                llm.evaluate_results(intent, candidates.clone(), |chunk| {
+                    on_event(&SmartSearchEvent::EvaluationChunk(chunk.to_vec()));
+                }).await
+                    .map_err(|e| self.fall_back_to_heuristics(run, "evaluate the results", e, on_event))
+                    .ok()
+            }
+            None => None,
+        };
+        diagnostics.ranked_by_llm = by_llm.is_some();
+        let mut evaluated = by_llm.unwrap_or_else(|| {
+            let ranked = ranker::rank(intent, candidates);
+            on_event(&SmartSearchEvent::EvaluationChunk(ranked.clone()));
+            ranked
+        });
         
-        // 6. Filter by confidence and sort by relevance
-        let mut filtered: Vec<_> = evaluated.into_iter()
-            .filter(|r| r.confidence >= self.min_confidence)
-            .collect();
+        // Cross-check against canonical metadata
+        if let Some(canonical) = canonical {
+            for result in &mut evaluated {
+                metadata::verify(canonical, result);
+            }
+        }
+        
+        // Non-English dubs often top seeder-sorted results; rank them under the rest
+        if let Some(wanted) = wanted {
+            for result in &mut evaluated {
+                language::check(result, wanted);
+            }
+        }
         
-        filtered.sort_by(|a, b| {
-            b.relevance_score.partial_cmp(&a.relevance_score).unwrap()
+        // "The latest" with no aired episode to go on: the newest episode found wins
+        ranker::favor_newest_episode(intent, &mut evaluated);
+        
+        // Red flags stand whatever the evaluator thought of the result
+        for result in &mut evaluated {
+            safety::flag(result);
+        }
+        
+        // 6. Filter by confidence and sort by relevance
+        let mut threshold = self.min_confidence;
+        let mut adjustment = None;
+        if self.adaptive_threshold {
+            let confidences: Vec<f32> = evaluated.iter().map(|r| r.confidence).collect();
+            if let Some((adjusted, reason)) = adapt_threshold(self.min_confidence, &confidences) {
+                on_event(&SmartSearchEvent::ThresholdAdjusted {
+                    from: self.min_confidence,
+                    to: adjusted,
+                    reason: reason.clone(),
+                });
+                threshold = adjusted;
+                adjustment = Some(reason);
+            }
+        }
+        diagnostics.min_confidence = threshold;
+        trace::record(self.tracer.as_ref(), || TraceEvent::Scored {
+            threshold,
+            adjustment,
+            results: evaluated.clone(),
         });
 
+        let (mut filtered, rejected): (Vec<_>, Vec<_>) = evaluated.into_iter()
+            .partition(|r| r.confidence >= threshold);
+        diagnostics.below_confidence = rejected.len();
+        diagnostics.best_rejected_confidence = rejected.iter()
+            .map(|r| r.confidence)
+            .reduce(f32::max);
+        
+        filtered.sort_by(|a, b| b.relevance_score.total_cmp(&a.relevance_score));
+
         Ok(filtered)
     }
 
-    async fn search_all_sources(&self, query: &str) -> Result<Vec<TorrentResult>> {
-        let tpb = PirateBayScraper::new();
-        let yts = YtsScraper::new();
-        
-        let (tpb_results, yts_results) = tokio::try_join!(
-            tpb.search(query),
-            yts.search(query)
-        )?;
-
-        let mut results = Vec::new();
-        results.extend(tpb_results);
-        results.extend(yts_results);
-        
-        Ok(results)
+    /// When a whole season was asked for and no season pack made the cut,
+    /// find the best torrent for each episode instead. Episodes missing from
+    /// what was already found are searched for individually; without an
+    /// explicit episode range, probing continues past the last episode found
+    /// until a search turns up nothing.
+    async fn plan_episodes(
+        &self,
+        run: &SearchRun,
+        intent: &SearchIntent,
+        candidates: &mut Vec<TorrentResult>,
+        results: &[EvaluatedResult],
+        on_event: &(impl Fn(&SmartSearchEvent) + Send + Sync),
+    ) -> Result<Option<EpisodePlan>> {
+        let Some(tv) = &intent.tv_details else {
+            return Ok(None);
+        };
+        let (Some(season), None) = (tv.season, tv.episode) else {
+            return Ok(None);
+        };
+        if tv.complete_series || results.iter().any(|r| ranker::is_season_pack(&r.torrent.title, season)) {
+            return Ok(None);
+        }
+
+        let has_episode = |candidates: &[TorrentResult], episode: u8| {
+            candidates.iter().any(|c| ranker::episode_marker(&c.title) == Some((season, episode)))
+        };
+
+        let (first, mut last, inferred_range) = match tv.episode_range {
+            // Episodes past the cap are never searched, so they aren't planned either
+            Some((first, last)) => {
+                if last > MAX_PLANNED_EPISODE {
+                    warn!("Only planning up to episode {} of the requested {}", MAX_PLANNED_EPISODE, last);
+                }
+                (first, last.min(MAX_PLANNED_EPISODE), false)
+            }
+            None => {
+                let highest = candidates.iter()
+                    .filter_map(|c| ranker::episode_marker(&c.title))
+                    .filter(|(s, _)| *s == season)
+                    .map(|(_, e)| e)
+                    .max()
+                    .unwrap_or(0);
+                (1, highest, true)
+            }
+        };
+
+        let mut episode = first;
+        while episode <= MAX_PLANNED_EPISODE {
+            if episode > last && !inferred_range {
+                break;
+            }
+            if !has_episode(candidates, episode) {
+                let query = format!("{} S{:02}E{:02}", intent.title, season, episode);
+                for (source, found) in self.search_all_sources(run, &query, &SearchHints::default(), on_event).await {
+                    let found = match found {
+                        Ok(found) => found,
+                        Err(e) => {
+                            warn!("{} search for {} failed: {}", source, query, e);
+                            continue;
+                        }
+                    };
+                    on_event(&SmartSearchEvent::SourceResults {
+                        source: source.to_string(),
+                        query: query.clone(),
+                        results: found.clone(),
+                    });
+                    candidates.extend(self.filter.apply(found));
+                }
+            }
+            if episode > last {
+                if !has_episode(candidates, episode) {
+                    break;
+                }
+                last = episode;
+            }
+            episode += 1;
+        }
+        *candidates = self.deduplicate_results(std::mem::take(candidates));
+
+        // Episode markers are exact, so the heuristic ranker is enough to pick
+        // between releases of the same episode without another LLM round trip
+        let episodes: Vec<PlannedEpisode> = (first..=last)
+            .map(|episode| {
+                let mut episode_intent = intent.clone();
+                episode_intent.tv_details = Some(TvDetails {
+                    season: Some(season),
+                    episode: Some(episode),
+                    episode_range: None,
+                    complete_season: false,
+                    complete_series: false,
+                });
+                let releases: Vec<TorrentResult> = candidates.iter()
+                    .filter(|c| ranker::episode_marker(&c.title) == Some((season, episode)))
+                    .cloned()
+                    .collect();
+                let result = ranker::rank(&episode_intent, releases)
+                    .into_iter()
+                    .filter(|r| r.confidence >= self.min_confidence)
+                    .max_by(|a, b| a.relevance_score.total_cmp(&b.relevance_score));
+                PlannedEpisode { episode, result }
+            })
+            .collect();
+
+        if episodes.iter().all(|e| e.result.is_none()) {
+            return Ok(None);
+        }
+
+        let plan = EpisodePlan {
+            season,
+            episodes,
+            inferred_range,
+        };
+        on_event(&SmartSearchEvent::EpisodesPlanned(plan.clone()));
+        Ok(Some(plan))
     }
 
-    fn display_intent(&self, intent: &SearchIntent) {
-        println!("   Content Type: {:?}", intent.content_type);
-        println!("   Title: {}", intent.title);
-        
-        if let Some(tv) = &intent.tv_details {
-            if let Some(season) = tv.season {
-                println!("   Season: {} {}", 
-                    season, 
-                    if tv.complete_season { "(Complete)" } else { "" }
-                );
+    /// Search every source for `query`. A source that fails is reported
+    /// once per search and left out; when every source fails, whatever was
+    /// cached for the query stands in, however old.
+    async fn search_all_sources(
+        &self,
+        run: &SearchRun,
+        query: &str,
+        hints: &SearchHints,
+        on_event: &(impl Fn(&SmartSearchEvent) + Send + Sync),
+    ) -> Vec<(&'static str, Result<Vec<TorrentResult>>)> {
+        let query = &SourceQuery::new(query).with_hints(hints.clone());
+        let mut outcomes = futures::future::join_all(self.sources.sources().iter().map(|source| async move {
+            let results = self.scrape_cached(source.name(), &source.cache_key(query), source.search(query)).await;
+            (source.name(), results)
+        })).await;
+
+        for (source, results) in outcomes.iter_mut() {
+            if let Ok(results) = results {
+                for result in results.iter_mut() {
+                    result.found_by = vec![Provenance {
+                        source: source.to_string(),
+                        query: query.text.clone(),
+                    }];
+                }
             }
         }
-        
-        if !intent.quality_preferences.is_empty() {
-            println!("   Quality: {}", intent.quality_preferences.join(", "));
+
+        for (source, results) in &outcomes {
+            trace::record(self.tracer.as_ref(), || TraceEvent::SourceResults {
+                source: source.to_string(),
+                query: query.text.clone(),
+                results: results.as_ref().cloned().unwrap_or_default(),
+                error: results.as_ref().err().map(|e| e.to_string()),
+            });
+        }
+
+        let all_down = !outcomes.is_empty() && outcomes.iter().all(|(_, results)| results.is_err());
+        if !all_down {
+            for (source, results) in &outcomes {
+                let Err(e) = results else {
+                    continue;
+                };
+                if run.sources_down.lock().unwrap().insert(source) {
+                    self.degrade(Degradation::SourceDown {
+                        source: source.to_string(),
+                        error: e.to_string(),
+                    }, on_event);
+                }
+            }
+            return outcomes;
         }
+
+        let mut cached_any = false;
+        for ((name, results), source) in outcomes.iter_mut().zip(self.sources.sources()) {
+            let key = source.cache_key(query);
+            match StateDb::open().and_then(|state| state.stale_scrape(name, &key)) {
+                Ok(Some((cached, fetched_at))) => {
+                    self.degrade(Degradation::CacheOnly {
+                        source: name.to_string(),
+                        query: query.text.clone(),
+                        fetched_at,
+                    }, on_event);
+                    *results = Ok(cached.into_iter()
+                        .map(|mut result| {
+                            result.found_by = vec![Provenance {
+                                source: name.to_string(),
+                                query: query.text.clone(),
+                            }];
+                            result
+                        })
+                        .collect());
+                    cached_any = true;
+                }
+                Ok(None) => {}
+                Err(e) => warn!("Scrape cache unavailable: {}", e),
+            }
+        }
+        if !cached_any {
+            self.degrade(Degradation::NothingCached { query: query.text.clone() }, on_event);
+        }
+        outcomes
+    }
+
+    /// Serve `source`'s results for `query` from the cache when fresh, else
+    /// run `scrape` and cache what it finds. Failures are never cached.
+    async fn scrape_cached(
+        &self,
+        source: &str,
+        query: &str,
+        scrape: impl Future<Output = Result<Vec<TorrentResult>>>,
+    ) -> Result<Vec<TorrentResult>> {
+        let Some(ttl) = self.cache_ttl else {
+            return scrape.await;
+        };
+
+        match StateDb::open().and_then(|state| state.cached_scrape(source, query, ttl)) {
+            Ok(Some(results)) => {
+                debug!("Using cached {} results for \"{}\"", source, query);
+                return Ok(results);
+            }
+            Ok(None) => {}
+            Err(e) => warn!("Scrape cache unavailable: {}", e),
+        }
+
+        let results = scrape.await?;
+        if let Err(e) = StateDb::open().and_then(|state| state.cache_scrape(source, query, &results, ttl)) {
+            warn!("Failed to cache {} results: {}", source, e);
+        }
+        Ok(results)
     }
 
+    /// Keep the first of each magnet link, crediting every source and
+    /// query that found it.
     fn deduplicate_results(&self, results: Vec<TorrentResult>) -> Vec<TorrentResult> {
-        let mut seen = HashSet::new();
-        results.into_iter()
-            .filter(|r| seen.insert(r.magnet_link.clone()))
-            .collect()
+        let mut seen: HashMap<String, usize> = HashMap::new();
+        let mut unique: Vec<TorrentResult> = Vec::new();
+        for result in results {
+            match seen.get(&result.magnet_link) {
+                Some(&index) => {
+                    let found_by = &mut unique[index].found_by;
+                    for provenance in result.found_by {
+                        if !found_by.contains(&provenance) {
+                            found_by.push(provenance);
+                        }
+                    }
+                }
+                None => {
+                    seen.insert(result.magnet_link.clone(), unique.len());
+                    unique.push(result);
+                }
+            }
+        }
+        unique
+    }
+}
+
+/// Remember a search for `history`; losing the entry isn't worth failing over.
+fn record_history(query: &str, results: &[EvaluatedResult]) {
+    if let Err(e) = StateDb::open().and_then(|state| state.record_search(query, results)) {
+        warn!("Failed to record search history: {}", e);
+    }
+}
+
+/// Pick a threshold suited to this result set, or `None` to keep `base`.
+///
+/// When every result is under `base` but the best is well clear of the rest,
+/// the threshold drops to let that one result through rather than reporting
+/// nothing. When several results sit just around `base`, small scoring noise
+/// decides what passes, so the threshold rises if something still clears it.
+fn adapt_threshold(base: f32, confidences: &[f32]) -> Option<(f32, String)> {
+    let mut sorted = confidences.to_vec();
+    sorted.sort_by(|a, b| b.total_cmp(a));
+    let best = *sorted.first()?;
+    let runner_up = sorted.get(1).copied().unwrap_or(0.0);
+
+    if best < base {
+        if best >= RELAXED_FLOOR && best - runner_up >= DOMINANCE_MARGIN {
+            return Some((best, format!(
+                "all results are below {:.0}% but the best ({:.0}%) clearly leads the next ({:.0}%)",
+                base * 100.0, best * 100.0, runner_up * 100.0
+            )));
+        }
+        return None;
+    }
+
+    let clustered = sorted.iter().filter(|c| (**c - base).abs() <= CLUSTER_BAND).count();
+    let tightened = (base + TIGHTEN_STEP).min(1.0);
+    if clustered >= CLUSTER_SIZE && best >= tightened {
+        return Some((tightened, format!(
+            "{} results sit within {:.0} points of {:.0}%", clustered, CLUSTER_BAND * 100.0, base * 100.0
+        )));
+    }
+
+    None
+}
+
+/// Print CLI progress for a smart search event.
+pub fn print_progress(event: &SmartSearchEvent) {
+    match event {
+        SmartSearchEvent::IntentParsed(intent) => {
+            display_intent(intent);
+            println!("\n🔍 Searching across sources...");
+        }
+        SmartSearchEvent::Refined { intent, new_search } => {
+            println!("\n🔁 Updated request:");
+            display_intent(intent);
+            if *new_search {
+                println!("\n🔍 Searching again...");
+            }
+        }
+        SmartSearchEvent::SourceResults { source, query, results } => {
+            println!("   {}: {} results for \"{}\"", source, results.len(), query);
+        }
+        SmartSearchEvent::ThresholdAdjusted { from, to, reason } => {
+            println!("   🎚️  Confidence threshold {:.0}% → {:.0}%: {}", from * 100.0, to * 100.0, reason);
+        }
+        SmartSearchEvent::MetadataFound(canonical) => {
+            let year = canonical.year.map(|y| format!(" ({})", y)).unwrap_or_default();
+            let runtime = canonical.runtime_minutes.map(|m| format!(", {} min", m)).unwrap_or_default();
+            println!("   🎬 {}: {}{}{}", canonical.source, canonical.title, year, runtime);
+        }
+        SmartSearchEvent::LatestResolved { intent, air_date } => {
+            if let Some((season, episode)) = intent.tv_details.as_ref().and_then(|tv| Some((tv.season?, tv.episode?))) {
+                let aired = air_date.map(|d| format!(", aired {}", d.format("%B %-d, %Y"))).unwrap_or_default();
+                println!("   📺 Latest episode is S{:02}E{:02}{}", season, episode, aired);
+            }
+        }
+        SmartSearchEvent::NotYetReleased { title, release_date } => {
+            println!("\n📅 {} isn't out until {}; skipping the search", title, release_date.format("%B %-d, %Y"));
+        }
+        SmartSearchEvent::Degraded(degradation) => {
+            println!("   ⚠️  {}", degradation.describe());
+        }
+        SmartSearchEvent::EvaluationStarted { total } => {
+            println!("\n📊 Evaluating {} results...", total);
+        }
+        SmartSearchEvent::EvaluationChunk(chunk) => {
+            println!("   Evaluated {} results", chunk.len());
+        }
+        SmartSearchEvent::Diagnostics(_) => {}
+        SmartSearchEvent::EpisodesPlanned(plan) => display_episode_plan(plan),
+        SmartSearchEvent::Finished(_) => {}
+    }
+}
+
+/// Explain an empty result set: what each source returned, where results
+/// were dropped, and what to try next.
+pub fn print_diagnostics(diagnostics: &SearchDiagnostics) {
+    println!("\n🩺 Why nothing matched:");
+    println!("   Queries tried: {}", diagnostics.queries.iter()
+        .map(|q| format!("\"{}\"", q))
+        .collect::<Vec<_>>()
+        .join(", "));
+    
+    for (source, count) in &diagnostics.source_counts {
+        println!("   {}: {} raw results", source, count);
+    }
+    for (source, error) in &diagnostics.source_errors {
+        println!("   {}: failed ({})", source, error);
+    }
+    
+    let raw = diagnostics.raw_results();
+    println!("   {} raw → {} after removing duplicates → {} evaluated by {}",
+             raw, raw - diagnostics.duplicates_removed, diagnostics.evaluated,
+             if diagnostics.ranked_by_llm { "the LLM" } else { "heuristics" });
+    if let Some(filters) = &diagnostics.filters {
+        println!("   {} removed by filters ({})", diagnostics.filtered_out, filters);
+    }
+    println!("   {} dropped below the {:.0}% confidence threshold",
+             diagnostics.below_confidence, diagnostics.min_confidence * 100.0);
+    
+    let suggestions = diagnostics.suggestions();
+    if !suggestions.is_empty() {
+        println!("\n💡 Next steps:");
+        for suggestion in suggestions {
+            println!("   • {}", suggestion);
+        }
+    }
+}
+
+fn display_episode_plan(plan: &EpisodePlan) {
+    let matched = plan.matched().count();
+    println!("\n📺 No season {} pack found; matched {}/{} episodes individually", 
+             plan.season, matched, plan.episodes.len());
+    
+    for episode in &plan.episodes {
+        match &episode.result {
+            Some(result) => println!("   E{:02} ✓ {} ({})", episode.episode, result.torrent.title,
+                                     result.torrent.size.as_deref().unwrap_or("Unknown")),
+            None => println!("   E{:02} ✗ not found", episode.episode),
+        }
+    }
+    
+    let missing = plan.missing();
+    if !missing.is_empty() {
+        let missing: Vec<String> = missing.iter().map(|e| format!("E{:02}", e)).collect();
+        println!("   ⚠ Coverage gaps: {}", missing.join(", "));
+    }
+    if plan.inferred_range {
+        println!("   ⚠ Season length guessed from the episodes found; later episodes may be missing");
+    }
+}
+
+fn display_intent(intent: &SearchIntent) {
+    println!("   Content Type: {:?}", intent.content_type);
+    println!("   Title: {}", intent.title);
+    
+    if let Some(tv) = &intent.tv_details {
+        if let Some(season) = tv.season {
+            println!("   Season: {} {}", 
+                season, 
+                if tv.complete_season { "(Complete)" } else { "" }
+            );
+        }
+    }
+    
+    if !intent.quality_preferences.is_empty() {
+        println!("   Quality: {}", intent.quality_preferences.join(", "));
+    }
+    
+    if let Some(language) = &intent.language {
+        println!("   Language: {}", language);
+    }
+    
+    if intent.prefer_recent {
+        println!("   Wants: the most recent upload");
     }
 }
 
@@ -117,16 +1243,67 @@ pub fn display_evaluated_result(index: usize, result: &EvaluatedResult, verbose:
     }
     
     // Display torrent info
-    let size_str = result.torrent.size.as_ref()
-        .map(|s| s.as_str())
-        .unwrap_or("Unknown");
+    let size_str = result.torrent.size.as_deref().unwrap_or("Unknown");
     let seeders = result.torrent.seeders.unwrap_or(0);
     let leechers = result.torrent.leechers.unwrap_or(0);
     println!("   📦 {} | 👥 {}/{} seeders/leechers", size_str, seeders, leechers);
     
+    if let Some((first, others)) = result.torrent.found_by.split_first() {
+        let others = if others.is_empty() { String::new() } else { format!(" (+{} more)", others.len()) };
+        println!("   🔎 Found by {}{}", first.describe(), others);
+    }
+    
     if verbose {
+        for provenance in result.torrent.found_by.iter().skip(1) {
+            println!("   Also found by {}", provenance.describe());
+        }
         println!("   Confidence: {:.0}%", result.confidence * 100.0);
         println!("   Quality Score: {:.0}%", result.quality_score * 100.0);
         println!("   Completeness: {:.0}%", result.completeness_score * 100.0);
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn threshold_adapts_to_the_confidences() {
+        // (base, confidences, adapted threshold)
+        let cases: &[(f32, &[f32], Option<f32>)] = &[
+            (0.6, &[], None),
+            // Everything below the threshold: the best passes alone if it
+            // leads by DOMINANCE_MARGIN and clears RELAXED_FLOOR
+            (0.75, &[0.25, 0.5], Some(0.5)),
+            (0.75, &[0.5, 0.35], None),
+            (0.6, &[0.45], Some(0.45)),
+            (0.6, &[0.4, 0.1], Some(0.4)),
+            (0.6, &[0.39, 0.1], None),
+            (0.6, &[0.0, 0.0], None),
+            // CLUSTER_SIZE results around the threshold tighten it, as long
+            // as something still clears the tightened one
+            (0.6, &[0.55, 0.72, 0.6, 0.65], Some(0.6 + TIGHTEN_STEP)),
+            (0.6, &[0.68, 0.62, 0.58], None),
+            (0.6, &[0.9, 0.65, 0.6], None),
+            (0.6, &[0.9, 0.3, 0.2], None),
+            (0.95, &[0.88, 1.0, 0.95, 0.9], Some(1.0)),
+        ];
+        for (base, confidences, expected) in cases {
+            let adapted = adapt_threshold(*base, confidences).map(|(threshold, _)| threshold);
+            assert_eq!(adapted, *expected, "{} over {:?}", base, confidences);
+        }
+    }
+
+    #[test]
+    fn threshold_survives_nan() {
+        assert_eq!(adapt_threshold(0.6, &[0.5, f32::NAN, 0.25]), None);
+    }
+
+    #[test]
+    fn adapted_threshold_says_why() {
+        let (_, reason) = adapt_threshold(0.75, &[0.5, 0.25]).unwrap();
+        assert_eq!(reason, "all results are below 75% but the best (50%) clearly leads the next (25%)");
+        let (_, reason) = adapt_threshold(0.6, &[0.72, 0.65, 0.6, 0.55]).unwrap();
+        assert_eq!(reason, "3 results sit within 10 points of 60%");
+    }
+}
@@ -0,0 +1,1176 @@
+use anyhow::Result;
+use chrono::{DateTime, Duration, NaiveDate, Utc};
+use lazy_static::lazy_static;
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use crate::config::config_dir;
+use crate::migrations;
+use crate::models::{ContentType, EvaluatedResult, SearchIntent};
+use crate::organize::PlannedMove;
+use crate::pirate_bay_scraper::{MirrorHealth, TorrentResult};
+use crate::trackers::{self, TrackerState};
+
+/// Ranked results kept per history entry.
+const HISTORY_RESULTS: usize = 10;
+/// Searches kept in history; older ones are forgotten as new ones arrive.
+const HISTORY_ENTRIES: usize = 500;
+/// Expired scrape results are kept this long to fall back on when every
+/// source is down.
+const STALE_SCRAPE_DAYS: i64 = 7;
+
+lazy_static! {
+    /// Databases already migrated by this process. Opening is frequent, so
+    /// each is checked once rather than on every open.
+    static ref MIGRATED: Mutex<HashSet<PathBuf>> = Mutex::new(HashSet::new());
+}
+
+/// Persistent record of a torrent this client has downloaded or is downloading.
+#[derive(Debug, Clone)]
+pub struct TorrentRecord {
+    pub info_hash: String,
+    pub name: String,
+    pub source: String,
+    pub output_dir: PathBuf,
+    pub disk_root: PathBuf,
+    pub status: TorrentStatus,
+    pub total_bytes: u64,
+    pub progress_bytes: u64,
+    /// Uploaded across every session that seeded it
+    pub uploaded_bytes: u64,
+    /// Indices of the files chosen for download; `None` means all of them
+    pub selected_files: Option<Vec<usize>>,
+    /// What the torrent holds, from the search intent or classification
+    pub content_type: Option<ContentType>,
+    /// Why a finished download isn't seeding, found when the daemon last
+    /// checked its files
+    pub problem: Option<String>,
+    pub added_at: String,
+    pub updated_at: String,
+}
+
+impl TorrentRecord {
+    /// Uploaded as a multiple of its size.
+    pub fn ratio(&self) -> f64 {
+        if self.total_bytes == 0 {
+            return 0.0;
+        }
+        self.uploaded_bytes as f64 / self.total_bytes as f64
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TorrentStatus {
+    Downloading,
+    Completed,
+    Failed,
+    /// Completed, then its files deleted to free space; the record stays
+    /// so it still counts as owned
+    Archived,
+}
+
+impl TorrentStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TorrentStatus::Downloading => "downloading",
+            TorrentStatus::Completed => "completed",
+            TorrentStatus::Failed => "failed",
+            TorrentStatus::Archived => "archived",
+        }
+    }
+
+    fn from_str(s: &str) -> Self {
+        match s {
+            "completed" => TorrentStatus::Completed,
+            "failed" => TorrentStatus::Failed,
+            "archived" => TorrentStatus::Archived,
+            _ => TorrentStatus::Downloading,
+        }
+    }
+}
+
+/// A downloaded file that was organized into the media library.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OrganizedFile {
+    /// Where the torrent put it
+    pub original: PathBuf,
+    /// Where it lives now
+    pub library: PathBuf,
+}
+
+/// A guest's request waiting for (or decided by) an admin.
+#[derive(Debug, Clone, Serialize)]
+pub struct RequestRecord {
+    pub id: i64,
+    pub query: String,
+    pub requested_by: String,
+    pub status: RequestStatus,
+    pub intent: Option<SearchIntent>,
+    /// Best evaluated result at submission time, downloaded on approval
+    pub top_result: Option<EvaluatedResult>,
+    pub note: Option<String>,
+    pub created_at: String,
+    pub decided_at: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RequestStatus {
+    Pending,
+    Approved,
+    Rejected,
+    /// Approved, but its download couldn't be started; the note says why
+    Failed,
+}
+
+impl RequestStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RequestStatus::Pending => "pending",
+            RequestStatus::Approved => "approved",
+            RequestStatus::Rejected => "rejected",
+            RequestStatus::Failed => "failed",
+        }
+    }
+
+    fn from_str(s: &str) -> Self {
+        match s {
+            "approved" => RequestStatus::Approved,
+            "rejected" => RequestStatus::Rejected,
+            "failed" => RequestStatus::Failed,
+            _ => RequestStatus::Pending,
+        }
+    }
+}
+
+/// A torrent waiting its turn in the download queue.
+#[derive(Debug, Clone)]
+pub struct QueueItem {
+    pub id: i64,
+    pub name: String,
+    pub source: String,
+    pub status: QueueStatus,
+    pub error: Option<String>,
+    pub added_at: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QueueStatus {
+    Queued,
+    Downloading,
+    Completed,
+    Failed,
+}
+
+impl QueueStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            QueueStatus::Queued => "queued",
+            QueueStatus::Downloading => "downloading",
+            QueueStatus::Completed => "completed",
+            QueueStatus::Failed => "failed",
+        }
+    }
+
+    fn from_str(s: &str) -> Self {
+        match s {
+            "downloading" => QueueStatus::Downloading,
+            "completed" => QueueStatus::Completed,
+            "failed" => QueueStatus::Failed,
+            _ => QueueStatus::Queued,
+        }
+    }
+}
+
+/// An unreleased title to grab once it comes out.
+#[derive(Debug, Clone)]
+pub struct WatchItem {
+    pub id: i64,
+    /// The smart-search query to run on release
+    pub query: String,
+    pub title: String,
+    pub release_date: NaiveDate,
+    pub status: WatchStatus,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WatchStatus {
+    Waiting,
+    Grabbed,
+}
+
+impl WatchStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            WatchStatus::Waiting => "waiting",
+            WatchStatus::Grabbed => "grabbed",
+        }
+    }
+
+    fn from_str(s: &str) -> Self {
+        match s {
+            "grabbed" => WatchStatus::Grabbed,
+            _ => WatchStatus::Waiting,
+        }
+    }
+}
+
+/// A smart search re-run on a schedule, reporting results it hasn't seen.
+#[derive(Debug, Clone)]
+pub struct WatchRule {
+    pub id: i64,
+    pub query: String,
+    pub interval_mins: u64,
+    /// Download the best new result when it scores at least this
+    pub auto_download_above: Option<f32>,
+    pub last_checked: Option<DateTime<Utc>>,
+    /// How many distinct results it has reported so far
+    pub seen: usize,
+}
+
+impl WatchRule {
+    pub fn next_check(&self) -> Option<DateTime<Utc>> {
+        self.last_checked.map(|t| t + Duration::minutes(self.interval_mins as i64))
+    }
+
+    pub fn is_due(&self) -> bool {
+        self.next_check().is_none_or(|next| next <= Utc::now())
+    }
+}
+
+/// An RSS or Torznab feed polled for new torrents.
+#[derive(Debug, Clone)]
+pub struct Feed {
+    pub id: i64,
+    /// Without its API key, which is kept in the secret store
+    pub url: String,
+    /// Query parameter the API key goes in, e.g. `apikey`
+    pub key_param: Option<String>,
+    /// Name of the secret holding the API key
+    pub key_secret: Option<String>,
+    /// Natural-language description new items must match; `None` takes everything
+    pub filter: Option<String>,
+    pub interval_mins: u64,
+    /// Grab what's already in the feed on the first poll instead of only
+    /// what appears afterwards
+    pub grab_existing: bool,
+    pub last_polled: Option<DateTime<Utc>>,
+    pub last_error: Option<String>,
+    pub seen: usize,
+}
+
+impl Feed {
+    pub fn next_poll(&self) -> Option<DateTime<Utc>> {
+        self.last_polled.map(|t| t + Duration::minutes(self.interval_mins as i64))
+    }
+
+    pub fn is_due(&self) -> bool {
+        self.next_poll().is_none_or(|next| next <= Utc::now())
+    }
+}
+
+/// What an unattended search did, as reported in the notification digest.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DigestOutcome {
+    Grabbed,
+    /// New results that weren't downloaded
+    Found,
+    /// Searched, but nothing was good enough
+    Skipped,
+    Failed,
+}
+
+impl DigestOutcome {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DigestOutcome::Grabbed => "grabbed",
+            DigestOutcome::Found => "found",
+            DigestOutcome::Skipped => "skipped",
+            DigestOutcome::Failed => "failed",
+        }
+    }
+
+    fn from_str(s: &str) -> Self {
+        match s {
+            "grabbed" => DigestOutcome::Grabbed,
+            "found" => DigestOutcome::Found,
+            "failed" => DigestOutcome::Failed,
+            _ => DigestOutcome::Skipped,
+        }
+    }
+}
+
+/// Repeated outcomes for the same subject, collapsed into one line.
+#[derive(Debug, Clone, Serialize)]
+pub struct DigestEntry {
+    /// "watchlist" or "watch"
+    pub source: String,
+    pub subject: String,
+    pub outcome: DigestOutcome,
+    /// From the most recent occurrence
+    pub detail: Option<String>,
+    pub count: usize,
+}
+
+/// A result as remembered in the search history.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HistoryResult {
+    pub title: String,
+    pub magnet_link: String,
+    pub relevance_score: f32,
+}
+
+/// A past smart search and what became of it.
+#[derive(Debug, Clone)]
+pub struct HistoryEntry {
+    pub id: i64,
+    pub query: String,
+    pub searched_at: String,
+    pub results: Vec<HistoryResult>,
+    /// The result that was downloaded, with how that download went
+    pub chosen: Option<(HistoryResult, TorrentStatus)>,
+}
+
+/// Findings from [`StateDb::doctor`].
+#[derive(Debug, Clone)]
+pub struct DoctorReport {
+    pub schema_version: u32,
+    /// Integrity check failures; empty when the database is healthy
+    pub problems: Vec<String>,
+    /// Tracker rows left behind by torrents that no longer exist
+    pub orphaned_trackers: usize,
+    pub size_before: u64,
+    pub size_after: u64,
+}
+
+/// SQLite-backed local state (`~/.torrentai/state.db`).
+pub struct StateDb {
+    conn: Connection,
+    /// Holds the database and its backups
+    dir: PathBuf,
+}
+
+impl StateDb {
+    pub fn open() -> Result<Self> {
+        Self::open_in(&config_dir())
+    }
+
+    /// The database in `dir` rather than `~/.torrentai`.
+    pub fn open_in(dir: &Path) -> Result<Self> {
+        std::fs::create_dir_all(dir)?;
+        let path = db_path(dir);
+        let mut conn = Connection::open(&path)?;
+        // The downloader and its tracker announcer write from separate connections
+        conn.busy_timeout(std::time::Duration::from_secs(5))?;
+
+        let mut migrated = MIGRATED.lock().unwrap();
+        if !migrated.contains(&path) {
+            migrations::migrate(&mut conn, &migrations::backups_dir(dir))?;
+            migrated.insert(path);
+        }
+
+        Ok(Self { conn, dir: dir.to_path_buf() })
+    }
+
+    /// Check integrity, clear orphaned rows and compact the database. Cleanup
+    /// is skipped when the integrity check fails, leaving restore as the fix.
+    pub fn doctor(&self) -> Result<DoctorReport> {
+        let size_before = std::fs::metadata(db_path(&self.dir)).map(|m| m.len()).unwrap_or(0);
+        let problems: Vec<String> = self.conn
+            .prepare("PRAGMA integrity_check")?
+            .query_map([], |row| row.get::<_, String>(0))?
+            .collect::<rusqlite::Result<Vec<_>>>()?
+            .into_iter()
+            .filter(|line| line != "ok")
+            .collect();
+
+        let mut orphaned_trackers = 0;
+        if problems.is_empty() {
+            orphaned_trackers = self.conn.execute(
+                "DELETE FROM trackers WHERE info_hash NOT IN (SELECT info_hash FROM torrents)",
+                [],
+            )?;
+            self.conn.execute_batch("REINDEX; VACUUM;")?;
+        }
+
+        Ok(DoctorReport {
+            schema_version: migrations::schema_version(&self.conn)?,
+            problems,
+            orphaned_trackers,
+            size_before,
+            size_after: std::fs::metadata(db_path(&self.dir)).map(|m| m.len()).unwrap_or(0),
+        })
+    }
+
+    /// Write a backup to `~/.torrentai/backups`, returning its path.
+    pub fn backup(&self) -> Result<PathBuf> {
+        migrations::backup(&self.conn, &migrations::backups_dir(&self.dir))
+    }
+
+    pub fn upsert_torrent(&self, record: &TorrentRecord) -> Result<()> {
+        self.conn.execute(
+            "INSERT INTO torrents (info_hash, name, source, output_dir, disk_root, status,
+                                   total_bytes, progress_bytes, selected_files, content_type, added_at, updated_at,
+                                   uploaded_bytes, problem)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14)
+             ON CONFLICT(info_hash) DO UPDATE SET
+                name = excluded.name,
+                output_dir = excluded.output_dir,
+                disk_root = excluded.disk_root,
+                status = excluded.status,
+                total_bytes = excluded.total_bytes,
+                progress_bytes = excluded.progress_bytes,
+                selected_files = excluded.selected_files,
+                content_type = COALESCE(content_type, excluded.content_type),
+                problem = excluded.problem,
+                updated_at = excluded.updated_at",
+            params![
+                record.info_hash,
+                record.name,
+                record.source,
+                record.output_dir.to_string_lossy(),
+                record.disk_root.to_string_lossy(),
+                record.status.as_str(),
+                record.total_bytes as i64,
+                record.progress_bytes as i64,
+                record.selected_files.as_ref().map(serde_json::to_string).transpose()?,
+                record.content_type.as_ref().map(ContentType::label),
+                record.added_at,
+                record.updated_at,
+                record.uploaded_bytes as i64,
+                record.problem,
+            ],
+        )?;
+        Ok(())
+    }
+
+    /// `uploaded_bytes` is the total across sessions, not this session's.
+    pub fn update_progress(&self, info_hash: &str, status: TorrentStatus, progress_bytes: u64, uploaded_bytes: u64) -> Result<()> {
+        self.conn.execute(
+            "UPDATE torrents SET status = ?2, progress_bytes = ?3, uploaded_bytes = ?4, updated_at = ?5 WHERE info_hash = ?1",
+            params![info_hash, status.as_str(), progress_bytes as i64, uploaded_bytes as i64, Utc::now().to_rfc3339()],
+        )?;
+        Ok(())
+    }
+
+    /// Uploaded in earlier sessions, which this session's count adds to.
+    pub fn uploaded_before(&self, info_hash: &str) -> Result<u64> {
+        Ok(self.conn
+            .query_row("SELECT uploaded_bytes FROM torrents WHERE info_hash = ?1", [info_hash], |row| row.get::<_, i64>(0))
+            .optional()?
+            .unwrap_or(0) as u64)
+    }
+
+    /// Mark a torrent archived once its files are gone.
+    pub fn archive_torrent(&self, info_hash: &str) -> Result<()> {
+        self.conn.execute(
+            "UPDATE torrents SET status = ?2, updated_at = ?3 WHERE info_hash = ?1",
+            params![info_hash, TorrentStatus::Archived.as_str(), Utc::now().to_rfc3339()],
+        )?;
+        Ok(())
+    }
+
+    /// Flag a finished download whose files don't check out, or clear the
+    /// flag once they do.
+    pub fn set_problem(&self, info_hash: &str, problem: Option<&str>) -> Result<()> {
+        self.conn.execute(
+            "UPDATE torrents SET problem = ?2 WHERE info_hash = ?1",
+            params![info_hash, problem],
+        )?;
+        Ok(())
+    }
+
+    /// Record what a torrent holds, replacing any earlier classification.
+    pub fn set_content_type(&self, info_hash: &str, content_type: &ContentType) -> Result<()> {
+        self.conn.execute(
+            "UPDATE torrents SET content_type = ?2 WHERE info_hash = ?1",
+            params![info_hash, content_type.label()],
+        )?;
+        Ok(())
+    }
+
+    pub fn torrents(&self) -> Result<Vec<TorrentRecord>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT info_hash, name, source, output_dir, disk_root, status,
+                    total_bytes, progress_bytes, selected_files, content_type, added_at, updated_at,
+                    uploaded_bytes, problem
+             FROM torrents ORDER BY added_at",
+        )?;
+
+        let records = stmt
+            .query_map([], |row| {
+                Ok(TorrentRecord {
+                    info_hash: row.get(0)?,
+                    name: row.get(1)?,
+                    source: row.get(2)?,
+                    output_dir: PathBuf::from(row.get::<_, String>(3)?),
+                    disk_root: PathBuf::from(row.get::<_, String>(4)?),
+                    status: TorrentStatus::from_str(&row.get::<_, String>(5)?),
+                    total_bytes: row.get::<_, i64>(6)? as u64,
+                    progress_bytes: row.get::<_, i64>(7)? as u64,
+                    uploaded_bytes: row.get::<_, i64>(12)? as u64,
+                    selected_files: row.get::<_, Option<String>>(8)?
+                        .and_then(|json| serde_json::from_str(&json).ok()),
+                    content_type: row.get::<_, Option<String>>(9)?.map(|t| ContentType::from_label(&t)),
+                    problem: row.get(13)?,
+                    added_at: row.get(10)?,
+                    updated_at: row.get(11)?,
+                })
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+
+        Ok(records)
+    }
+
+    /// Torrents that were still downloading when the process last stopped.
+    pub fn unfinished_torrents(&self) -> Result<Vec<TorrentRecord>> {
+        Ok(self.torrents()?
+            .into_iter()
+            .filter(|t| t.status == TorrentStatus::Downloading)
+            .collect())
+    }
+
+    /// Torrents that finished downloading and haven't been archived since.
+    pub fn finished_torrents(&self) -> Result<Vec<TorrentRecord>> {
+        Ok(self.torrents()?
+            .into_iter()
+            .filter(|t| t.status == TorrentStatus::Completed)
+            .collect())
+    }
+
+    pub fn upsert_tracker(&self, info_hash: &str, tracker: &TrackerState) -> Result<()> {
+        self.conn.execute(
+            "INSERT INTO trackers (info_hash, url, tier, failures, last_error, last_announce,
+                                   next_announce, interval_secs, seeders, leechers, dropped)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)
+             ON CONFLICT(info_hash, url) DO UPDATE SET
+                tier = excluded.tier,
+                failures = excluded.failures,
+                last_error = excluded.last_error,
+                last_announce = excluded.last_announce,
+                next_announce = excluded.next_announce,
+                interval_secs = excluded.interval_secs,
+                seeders = excluded.seeders,
+                leechers = excluded.leechers,
+                dropped = excluded.dropped",
+            params![
+                info_hash,
+                tracker.url,
+                tracker.tier as i64,
+                tracker.failures,
+                tracker.last_error,
+                tracker.last_announce.map(|t| t.to_rfc3339()),
+                tracker.next_announce.to_rfc3339(),
+                tracker.interval_secs as i64,
+                tracker.seeders,
+                tracker.leechers,
+                tracker.dropped,
+            ],
+        )?;
+        Ok(())
+    }
+
+    pub fn trackers(&self, info_hash: &str) -> Result<Vec<TrackerState>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT url, tier, failures, last_error, last_announce, next_announce,
+                    interval_secs, seeders, leechers, dropped
+             FROM trackers WHERE info_hash = ?1 ORDER BY tier, url",
+        )?;
+
+        let trackers = stmt
+            .query_map([info_hash], |row| {
+                Ok(TrackerState {
+                    url: row.get(0)?,
+                    tier: row.get::<_, i64>(1)? as usize,
+                    failures: row.get(2)?,
+                    last_error: row.get(3)?,
+                    last_announce: row.get::<_, Option<String>>(4)?
+                        .and_then(|t| parse_timestamp(&t)),
+                    next_announce: parse_timestamp(&row.get::<_, String>(5)?)
+                        .unwrap_or_else(Utc::now),
+                    interval_secs: row.get::<_, i64>(6)? as u64,
+                    seeders: row.get(7)?,
+                    leechers: row.get(8)?,
+                    dropped: row.get(9)?,
+                })
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+
+        Ok(trackers)
+    }
+
+    /// Remember where organized files went, so their torrents can still be
+    /// checked and seeded. Files no torrent downloaded are left out.
+    pub fn record_organized(&self, moves: &[PlannedMove]) -> Result<()> {
+        let torrents = self.torrents()?;
+        for planned in moves {
+            // Organizing the library again moves files already recorded
+            let moved_again = self.conn.execute(
+                "UPDATE organized_files SET library = ?2 WHERE library = ?1",
+                params![planned.from.to_string_lossy(), planned.to.to_string_lossy()],
+            )?;
+            if moved_again > 0 {
+                continue;
+            }
+            let Some(torrent) = torrents.iter().find(|t| planned.from.starts_with(t.output_dir.join(&t.name))) else {
+                continue;
+            };
+            self.conn.execute(
+                "INSERT INTO organized_files (info_hash, original, library) VALUES (?1, ?2, ?3)
+                 ON CONFLICT(info_hash, original) DO UPDATE SET library = excluded.library",
+                params![torrent.info_hash, planned.from.to_string_lossy(), planned.to.to_string_lossy()],
+            )?;
+        }
+        Ok(())
+    }
+
+    pub fn organized_files(&self, info_hash: &str) -> Result<Vec<OrganizedFile>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT original, library FROM organized_files WHERE info_hash = ?1 ORDER BY original",
+        )?;
+
+        let files = stmt
+            .query_map([info_hash], |row| {
+                Ok(OrganizedFile {
+                    original: PathBuf::from(row.get::<_, String>(0)?),
+                    library: PathBuf::from(row.get::<_, String>(1)?),
+                })
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+
+        Ok(files)
+    }
+
+    pub fn get_setting(&self, key: &str) -> Result<Option<String>> {
+        Ok(self
+            .conn
+            .query_row("SELECT value FROM settings WHERE key = ?1", [key], |row| row.get(0))
+            .optional()?)
+    }
+
+    pub fn set_setting(&self, key: &str, value: &str) -> Result<()> {
+        self.conn.execute(
+            "INSERT INTO settings (key, value) VALUES (?1, ?2)
+             ON CONFLICT(key) DO UPDATE SET value = excluded.value",
+            params![key, value],
+        )?;
+        Ok(())
+    }
+
+    pub fn enqueue(&self, name: &str, source: &str) -> Result<i64> {
+        self.conn.execute(
+            "INSERT INTO queue (name, source, status, added_at) VALUES (?1, ?2, ?3, ?4)",
+            params![name, source, QueueStatus::Queued.as_str(), Utc::now().to_rfc3339()],
+        )?;
+        Ok(self.conn.last_insert_rowid())
+    }
+
+    /// Queue entries in the order they were added.
+    pub fn queue(&self) -> Result<Vec<QueueItem>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT id, name, source, status, error, added_at FROM queue ORDER BY id",
+        )?;
+
+        let items = stmt
+            .query_map([], |row| {
+                Ok(QueueItem {
+                    id: row.get(0)?,
+                    name: row.get(1)?,
+                    source: row.get(2)?,
+                    status: QueueStatus::from_str(&row.get::<_, String>(3)?),
+                    error: row.get(4)?,
+                    added_at: row.get(5)?,
+                })
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+
+        Ok(items)
+    }
+
+    pub fn set_queue_status(&self, id: i64, status: QueueStatus, error: Option<&str>) -> Result<()> {
+        self.conn.execute(
+            "UPDATE queue SET status = ?2, error = ?3 WHERE id = ?1",
+            params![id, status.as_str(), error],
+        )?;
+        Ok(())
+    }
+
+    pub fn add_request(
+        &self,
+        query: &str,
+        requested_by: &str,
+        intent: Option<&SearchIntent>,
+        top_result: Option<&EvaluatedResult>,
+    ) -> Result<i64> {
+        self.conn.execute(
+            "INSERT INTO requests (query, requested_by, status, intent, top_result, created_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            params![
+                query,
+                requested_by,
+                RequestStatus::Pending.as_str(),
+                intent.map(serde_json::to_string).transpose()?,
+                top_result.map(serde_json::to_string).transpose()?,
+                Utc::now().to_rfc3339(),
+            ],
+        )?;
+        Ok(self.conn.last_insert_rowid())
+    }
+
+    /// Requests, oldest first; `status` of `None` returns all of them.
+    pub fn requests(&self, status: Option<RequestStatus>) -> Result<Vec<RequestRecord>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT id, query, requested_by, status, intent, top_result, note, created_at, decided_at
+             FROM requests WHERE ?1 IS NULL OR status = ?1 ORDER BY id",
+        )?;
+
+        let records = stmt
+            .query_map([status.map(|s| s.as_str())], request_from_row)?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+
+        Ok(records)
+    }
+
+    pub fn request(&self, id: i64) -> Result<Option<RequestRecord>> {
+        Ok(self
+            .conn
+            .query_row(
+                "SELECT id, query, requested_by, status, intent, top_result, note, created_at, decided_at
+                 FROM requests WHERE id = ?1",
+                [id],
+                request_from_row,
+            )
+            .optional()?)
+    }
+
+    pub fn decide_request(&self, id: i64, status: RequestStatus, note: Option<&str>) -> Result<()> {
+        self.conn.execute(
+            "UPDATE requests SET status = ?2, note = ?3, decided_at = ?4 WHERE id = ?1",
+            params![id, status.as_str(), note, Utc::now().to_rfc3339()],
+        )?;
+        Ok(())
+    }
+
+    /// Decide a request that's still open, i.e. pending or failed. False if
+    /// it isn't open any more, e.g. because another approval got there first.
+    pub fn decide_open_request(&self, id: i64, status: RequestStatus, note: Option<&str>) -> Result<bool> {
+        let decided = self.conn.execute(
+            "UPDATE requests SET status = ?2, note = ?3, decided_at = ?4
+             WHERE id = ?1 AND status IN (?5, ?6)",
+            params![
+                id,
+                status.as_str(),
+                note,
+                Utc::now().to_rfc3339(),
+                RequestStatus::Pending.as_str(),
+                RequestStatus::Failed.as_str(),
+            ],
+        )?;
+        Ok(decided > 0)
+    }
+
+    pub fn add_watch(&self, query: &str, title: &str, release_date: NaiveDate) -> Result<i64> {
+        self.conn.execute(
+            "INSERT INTO watchlist (query, title, release_date, status, added_at) VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![query, title, release_date.to_string(), WatchStatus::Waiting.as_str(), Utc::now().to_rfc3339()],
+        )?;
+        Ok(self.conn.last_insert_rowid())
+    }
+
+    /// Watchlist entries, soonest release first.
+    pub fn watchlist(&self) -> Result<Vec<WatchItem>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT id, query, title, release_date, status FROM watchlist ORDER BY release_date, id",
+        )?;
+
+        let items = stmt
+            .query_map([], |row| {
+                Ok(WatchItem {
+                    id: row.get(0)?,
+                    query: row.get(1)?,
+                    title: row.get(2)?,
+                    release_date: row.get::<_, String>(3)?.parse().unwrap_or_default(),
+                    status: WatchStatus::from_str(&row.get::<_, String>(4)?),
+                })
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+
+        Ok(items)
+    }
+
+    /// Waiting entries whose release date has arrived.
+    pub fn due_watchlist(&self) -> Result<Vec<WatchItem>> {
+        let today = chrono::Local::now().date_naive();
+        Ok(self.watchlist()?
+            .into_iter()
+            .filter(|item| item.status == WatchStatus::Waiting && item.release_date <= today)
+            .collect())
+    }
+
+    pub fn set_watch_status(&self, id: i64, status: WatchStatus) -> Result<()> {
+        self.conn.execute(
+            "UPDATE watchlist SET status = ?2 WHERE id = ?1",
+            params![id, status.as_str()],
+        )?;
+        Ok(())
+    }
+
+    /// Returns false when there was no such entry.
+    pub fn remove_watch(&self, id: i64) -> Result<bool> {
+        Ok(self.conn.execute("DELETE FROM watchlist WHERE id = ?1", [id])? > 0)
+    }
+
+    pub fn add_watch_rule(&self, query: &str, interval_mins: u64, auto_download_above: Option<f32>) -> Result<i64> {
+        self.conn.execute(
+            "INSERT INTO watch_rules (query, interval_mins, auto_download_above, created_at) VALUES (?1, ?2, ?3, ?4)",
+            params![query, interval_mins as i64, auto_download_above, Utc::now().to_rfc3339()],
+        )?;
+        Ok(self.conn.last_insert_rowid())
+    }
+
+    pub fn watch_rules(&self) -> Result<Vec<WatchRule>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT r.id, r.query, r.interval_mins, r.auto_download_above, r.last_checked,
+                    (SELECT count(*) FROM watch_seen s WHERE s.rule_id = r.id)
+             FROM watch_rules r ORDER BY r.id",
+        )?;
+
+        let rules = stmt
+            .query_map([], |row| {
+                Ok(WatchRule {
+                    id: row.get(0)?,
+                    query: row.get(1)?,
+                    interval_mins: row.get::<_, i64>(2)?.max(1) as u64,
+                    auto_download_above: row.get(3)?,
+                    last_checked: row.get::<_, Option<String>>(4)?.and_then(|t| parse_timestamp(&t)),
+                    seen: row.get::<_, i64>(5)? as usize,
+                })
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+
+        Ok(rules)
+    }
+
+    /// Rules that have never run or whose interval has passed.
+    pub fn due_watch_rules(&self) -> Result<Vec<WatchRule>> {
+        Ok(self.watch_rules()?.into_iter().filter(|rule| rule.is_due()).collect())
+    }
+
+    pub fn mark_watch_checked(&self, id: i64) -> Result<()> {
+        self.conn.execute(
+            "UPDATE watch_rules SET last_checked = ?2 WHERE id = ?1",
+            params![id, Utc::now().to_rfc3339()],
+        )?;
+        Ok(())
+    }
+
+    /// Remember a result for a rule; returns true the first time it is seen.
+    pub fn mark_watch_seen(&self, id: i64, info_hash: &str) -> Result<bool> {
+        Ok(self.conn.execute(
+            "INSERT OR IGNORE INTO watch_seen (rule_id, info_hash, seen_at) VALUES (?1, ?2, ?3)",
+            params![id, info_hash, Utc::now().to_rfc3339()],
+        )? > 0)
+    }
+
+    /// Returns false when there was no such rule.
+    pub fn remove_watch_rule(&self, id: i64) -> Result<bool> {
+        self.conn.execute("DELETE FROM watch_seen WHERE rule_id = ?1", [id])?;
+        Ok(self.conn.execute("DELETE FROM watch_rules WHERE id = ?1", [id])? > 0)
+    }
+
+    /// `url` comes without its API key, which `key_param` and `key_secret`
+    /// say how to put back.
+    pub fn add_feed(
+        &self,
+        url: &str,
+        key_param: Option<&str>,
+        key_secret: Option<&str>,
+        filter: Option<&str>,
+        interval_mins: u64,
+        grab_existing: bool,
+    ) -> Result<i64> {
+        self.conn.execute(
+            "INSERT INTO feeds (url, key_param, key_secret, filter, interval_mins, grab_existing, created_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+            params![url, key_param, key_secret, filter, interval_mins as i64, grab_existing, Utc::now().to_rfc3339()],
+        )?;
+        Ok(self.conn.last_insert_rowid())
+    }
+
+    pub fn feeds(&self) -> Result<Vec<Feed>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT f.id, f.url, f.filter, f.interval_mins, f.grab_existing, f.last_polled, f.last_error,
+                    (SELECT count(*) FROM feed_seen s WHERE s.feed_id = f.id), f.key_param, f.key_secret
+             FROM feeds f ORDER BY f.id",
+        )?;
+
+        let feeds = stmt
+            .query_map([], |row| {
+                Ok(Feed {
+                    id: row.get(0)?,
+                    url: row.get(1)?,
+                    filter: row.get(2)?,
+                    interval_mins: row.get::<_, i64>(3)?.max(1) as u64,
+                    grab_existing: row.get(4)?,
+                    last_polled: row.get::<_, Option<String>>(5)?.and_then(|t| parse_timestamp(&t)),
+                    last_error: row.get(6)?,
+                    seen: row.get::<_, i64>(7)? as usize,
+                    key_param: row.get(8)?,
+                    key_secret: row.get(9)?,
+                })
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+
+        Ok(feeds)
+    }
+
+    pub fn due_feeds(&self) -> Result<Vec<Feed>> {
+        Ok(self.feeds()?.into_iter().filter(|feed| feed.is_due()).collect())
+    }
+
+    /// Replace a feed's URL with one stripped of its API key, recording
+    /// where the key goes and the secret it's kept in.
+    pub fn set_feed_key(&self, id: i64, url: &str, param: &str, secret: &str) -> Result<()> {
+        self.conn.execute(
+            "UPDATE feeds SET url = ?2, key_param = ?3, key_secret = ?4 WHERE id = ?1",
+            params![id, url, param, secret],
+        )?;
+        Ok(())
+    }
+
+    /// Record a poll; `error` replaces the previous one, so a good poll clears it.
+    pub fn mark_feed_polled(&self, id: i64, error: Option<&str>) -> Result<()> {
+        self.conn.execute(
+            "UPDATE feeds SET last_polled = ?2, last_error = ?3 WHERE id = ?1",
+            params![id, Utc::now().to_rfc3339(), error],
+        )?;
+        Ok(())
+    }
+
+    pub fn feed_item_seen(&self, id: i64, guid: &str) -> Result<bool> {
+        Ok(self.conn.query_row(
+            "SELECT EXISTS(SELECT 1 FROM feed_seen WHERE feed_id = ?1 AND guid = ?2)",
+            params![id, guid],
+            |row| row.get(0),
+        )?)
+    }
+
+    /// Remember a feed item; returns true the first time it is seen.
+    pub fn mark_feed_seen(&self, id: i64, guid: &str) -> Result<bool> {
+        Ok(self.conn.execute(
+            "INSERT OR IGNORE INTO feed_seen (feed_id, guid, seen_at) VALUES (?1, ?2, ?3)",
+            params![id, guid, Utc::now().to_rfc3339()],
+        )? > 0)
+    }
+
+    /// Returns false when there was no such feed.
+    pub fn remove_feed(&self, id: i64) -> Result<bool> {
+        self.conn.execute("DELETE FROM feed_seen WHERE feed_id = ?1", [id])?;
+        Ok(self.conn.execute("DELETE FROM feeds WHERE id = ?1", [id])? > 0)
+    }
+
+    pub fn record_digest(&self, source: &str, subject: &str, outcome: DigestOutcome, detail: Option<&str>) -> Result<()> {
+        self.conn.execute(
+            "INSERT INTO digest_entries (source, subject, outcome, detail, recorded_at) VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![source, subject, outcome.as_str(), detail, Utc::now().to_rfc3339()],
+        )?;
+        Ok(())
+    }
+
+    /// Everything recorded since the last digest, with the last row's ID
+    /// for [`StateDb::clear_digest`].
+    pub fn pending_digest(&self) -> Result<(Vec<DigestEntry>, Option<i64>)> {
+        let mut stmt = self.conn.prepare(
+            "SELECT id, source, subject, outcome, detail FROM digest_entries ORDER BY id",
+        )?;
+        let rows = stmt
+            .query_map([], |row| {
+                Ok((
+                    row.get::<_, i64>(0)?,
+                    row.get::<_, String>(1)?,
+                    row.get::<_, String>(2)?,
+                    DigestOutcome::from_str(&row.get::<_, String>(3)?),
+                    row.get::<_, Option<String>>(4)?,
+                ))
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+
+        let last_id = rows.last().map(|(id, ..)| *id);
+        let mut entries: Vec<DigestEntry> = Vec::new();
+        for (_, source, subject, outcome, detail) in rows {
+            match entries.iter_mut().find(|e| e.source == source && e.subject == subject && e.outcome == outcome) {
+                Some(entry) => {
+                    entry.count += 1;
+                    entry.detail = detail.or(entry.detail.take());
+                }
+                None => entries.push(DigestEntry { source, subject, outcome, detail, count: 1 }),
+            }
+        }
+        Ok((entries, last_id))
+    }
+
+    /// Forget entries up to and including `last_id` once they've been sent.
+    pub fn clear_digest(&self, last_id: i64) -> Result<()> {
+        self.conn.execute("DELETE FROM digest_entries WHERE id <= ?1", [last_id])?;
+        Ok(())
+    }
+
+    pub fn upsert_mirror(&self, health: &MirrorHealth) -> Result<()> {
+        self.conn.execute(
+            "INSERT OR REPLACE INTO mirrors (url, failures, last_error, last_success, retry_after)
+             VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![
+                health.url,
+                health.failures,
+                health.last_error,
+                health.last_success.map(|t| t.to_rfc3339()),
+                health.retry_after.map(|t| t.to_rfc3339()),
+            ],
+        )?;
+        Ok(())
+    }
+
+    pub fn mirror_health(&self) -> Result<Vec<MirrorHealth>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT url, failures, last_error, last_success, retry_after FROM mirrors ORDER BY url",
+        )?;
+
+        let mirrors = stmt
+            .query_map([], |row| {
+                Ok(MirrorHealth {
+                    url: row.get(0)?,
+                    failures: row.get(1)?,
+                    last_error: row.get(2)?,
+                    last_success: row.get::<_, Option<String>>(3)?.and_then(|t| parse_timestamp(&t)),
+                    retry_after: row.get::<_, Option<String>>(4)?.and_then(|t| parse_timestamp(&t)),
+                })
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+
+        Ok(mirrors)
+    }
+
+    /// Results scraped from `source` for `query` within the last `ttl`.
+    pub fn cached_scrape(&self, source: &str, query: &str, ttl: Duration) -> Result<Option<Vec<TorrentResult>>> {
+        Ok(self.stale_scrape(source, query)?
+            .filter(|(_, fetched_at)| Utc::now() - *fetched_at < ttl)
+            .map(|(results, _)| results))
+    }
+
+    /// Cached results for a query however old, with when they were fetched,
+    /// for when every source is down.
+    pub fn stale_scrape(&self, source: &str, query: &str) -> Result<Option<(Vec<TorrentResult>, DateTime<Utc>)>> {
+        let cached: Option<(String, String)> = self.conn
+            .query_row(
+                "SELECT results, fetched_at FROM scrape_cache WHERE source = ?1 AND query = ?2",
+                params![source, query],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .optional()?;
+
+        Ok(cached.and_then(|(results, fetched_at)| {
+            Some((serde_json::from_str(&results).ok()?, parse_timestamp(&fetched_at)?))
+        }))
+    }
+
+    /// Store fresh results, clearing out anything older than `ttl` while at
+    /// it, though never younger than a week so there's something to fall
+    /// back on.
+    pub fn cache_scrape(&self, source: &str, query: &str, results: &[TorrentResult], ttl: Duration) -> Result<()> {
+        let now = Utc::now();
+        self.conn.execute(
+            "DELETE FROM scrape_cache WHERE fetched_at < ?1",
+            [(now - ttl.max(Duration::days(STALE_SCRAPE_DAYS))).to_rfc3339()],
+        )?;
+        self.conn.execute(
+            "INSERT OR REPLACE INTO scrape_cache (source, query, results, fetched_at) VALUES (?1, ?2, ?3, ?4)",
+            params![source, query, serde_json::to_string(results)?, now.to_rfc3339()],
+        )?;
+        Ok(())
+    }
+
+    pub fn record_search(&self, query: &str, results: &[EvaluatedResult]) -> Result<i64> {
+        let kept: Vec<HistoryResult> = results.iter()
+            .take(HISTORY_RESULTS)
+            .map(|r| HistoryResult {
+                title: r.torrent.title.clone(),
+                magnet_link: r.torrent.magnet_link.clone(),
+                relevance_score: r.relevance_score,
+            })
+            .collect();
+        self.conn.execute(
+            "INSERT INTO search_history (query, results, searched_at) VALUES (?1, ?2, ?3)",
+            params![query, serde_json::to_string(&kept)?, Utc::now().to_rfc3339()],
+        )?;
+        let id = self.conn.last_insert_rowid();
+        self.conn.execute(
+            "DELETE FROM search_history WHERE id <= (SELECT id FROM search_history ORDER BY id DESC LIMIT 1 OFFSET ?1)",
+            params![HISTORY_ENTRIES as i64],
+        )?;
+        Ok(id)
+    }
+
+    /// The most recent searches, newest first. A result counts as chosen when
+    /// a torrent with its info hash was added, however that happened.
+    pub fn history(&self, limit: usize) -> Result<Vec<HistoryEntry>> {
+        let downloads: HashMap<String, TorrentStatus> = self.torrents()?
+            .into_iter()
+            .map(|t| (t.info_hash, t.status))
+            .collect();
+
+        let mut stmt = self.conn.prepare(
+            "SELECT id, query, results, searched_at FROM search_history ORDER BY id DESC LIMIT ?1",
+        )?;
+        let entries = stmt
+            .query_map([limit as i64], |row| {
+                let results: Vec<HistoryResult> = serde_json::from_str(&row.get::<_, String>(2)?).unwrap_or_default();
+                let chosen = results.iter().find_map(|r| {
+                    let status = downloads.get(&trackers::magnet_info_hash(&r.magnet_link)?)?;
+                    Some((r.clone(), *status))
+                });
+                Ok(HistoryEntry {
+                    id: row.get(0)?,
+                    query: row.get(1)?,
+                    searched_at: row.get(3)?,
+                    results,
+                    chosen,
+                })
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+
+        Ok(entries)
+    }
+
+    pub fn clear_history(&self) -> Result<usize> {
+        Ok(self.conn.execute("DELETE FROM search_history", [])?)
+    }
+}
+
+fn request_from_row(row: &rusqlite::Row) -> rusqlite::Result<RequestRecord> {
+    Ok(RequestRecord {
+        id: row.get(0)?,
+        query: row.get(1)?,
+        requested_by: row.get(2)?,
+        status: RequestStatus::from_str(&row.get::<_, String>(3)?),
+        // Unreadable JSON only loses the preview; the query is still there
+        intent: row.get::<_, Option<String>>(4)?
+            .and_then(|json| serde_json::from_str(&json).ok()),
+        top_result: row.get::<_, Option<String>>(5)?
+            .and_then(|json| serde_json::from_str(&json).ok()),
+        note: row.get(6)?,
+        created_at: row.get(7)?,
+        decided_at: row.get(8)?,
+    })
+}
+
+pub fn db_path(dir: &Path) -> PathBuf {
+    dir.join("state.db")
+}
+
+fn parse_timestamp(s: &str) -> Option<DateTime<Utc>> {
+    DateTime::parse_from_rfc3339(s).ok().map(|t| t.with_timezone(&Utc))
+}
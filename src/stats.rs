@@ -0,0 +1,189 @@
+use anyhow::Result;
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::sync::Mutex;
+
+use crate::downloader::DownloadProgress;
+use crate::progress::{format_duration, format_rate};
+
+/// Snapshots kept per torrent: ten minutes at the daemon's sync interval.
+const CAPACITY: usize = 120;
+/// Levels of the speed sparkline, slowest first.
+const SPARKS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+
+/// What a torrent is doing, as far as time-in-state goes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TorrentState {
+    /// Resolving metadata
+    Pending,
+    Downloading,
+    /// Not finished, but nothing arriving
+    Stalled,
+    Finished,
+    Failed,
+}
+
+impl TorrentState {
+    fn of(progress: &DownloadProgress) -> Self {
+        if progress.error.is_some() {
+            TorrentState::Failed
+        } else if progress.pending {
+            TorrentState::Pending
+        } else if progress.finished {
+            TorrentState::Finished
+        } else if progress.download_mbps <= 0.0 {
+            TorrentState::Stalled
+        } else {
+            TorrentState::Downloading
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TorrentState::Pending => "pending",
+            TorrentState::Downloading => "downloading",
+            TorrentState::Stalled => "stalled",
+            TorrentState::Finished => "finished",
+            TorrentState::Failed => "failed",
+        }
+    }
+}
+
+/// One reading of a torrent, taken by the daemon.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Sample {
+    /// Unix timestamp
+    pub at: i64,
+    pub progress_bytes: u64,
+    /// Rolling speeds, in MiB/s
+    pub download_mbps: f64,
+    pub upload_mbps: f64,
+    pub peers: usize,
+}
+
+/// Recent history of one torrent, oldest sample first.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TorrentStats {
+    pub info_hash: Option<String>,
+    pub name: String,
+    pub total_bytes: u64,
+    pub state: TorrentState,
+    /// Unix timestamp of when it entered `state`
+    pub state_since: i64,
+    pub samples: VecDeque<Sample>,
+}
+
+impl TorrentStats {
+    /// Average download speed over the samples kept, in bytes per second.
+    pub fn average_rate(&self) -> f64 {
+        if self.samples.is_empty() {
+            return 0.0;
+        }
+        let total: f64 = self.samples.iter().map(|s| s.download_mbps).sum();
+        total / self.samples.len() as f64 * 1024.0 * 1024.0
+    }
+
+    /// The download speed over time as block characters, scaled to the fastest sample.
+    pub fn sparkline(&self) -> String {
+        let fastest = self.samples.iter().map(|s| s.download_mbps).fold(0.0, f64::max);
+        self.samples.iter()
+            .map(|s| {
+                let level = if fastest > 0.0 { s.download_mbps / fastest * (SPARKS.len() - 1) as f64 } else { 0.0 };
+                SPARKS[level.round() as usize]
+            })
+            .collect()
+    }
+
+    /// "stalled for 4m 12s".
+    pub fn describe_state(&self) -> String {
+        let secs = (Utc::now().timestamp() - self.state_since).max(0) as u64;
+        format!("{} for {}", self.state.as_str(), format_duration(std::time::Duration::from_secs(secs)))
+    }
+
+    pub fn print(&self) {
+        let window = self.samples.front()
+            .zip(self.samples.back())
+            .map(|(first, last)| format_duration(std::time::Duration::from_secs((last.at - first.at).max(0) as u64)))
+            .unwrap_or_else(|| "0s".to_string());
+        println!("{}", self.name);
+        println!("   {} | averaging {} over the last {}", self.describe_state(), format_rate(self.average_rate()), window);
+        if self.samples.len() > 1 {
+            println!("   ↓ {}", self.sparkline());
+        }
+    }
+}
+
+/// Snapshots of every torrent in the daemon's session, so a status check
+/// from another process can show trends rather than one instantaneous reading.
+#[derive(Default)]
+pub struct StatsHistory {
+    torrents: Mutex<Vec<TorrentStats>>,
+}
+
+impl StatsHistory {
+    /// Add a sample for each torrent, forgetting those no longer in the session.
+    /// Torrents are told apart by info hash, since two can share a name;
+    /// only those whose hash isn't known yet go by name.
+    pub fn record(&self, progress: &[DownloadProgress]) {
+        let now = Utc::now().timestamp();
+        let mut torrents = self.torrents.lock().unwrap();
+        let mut previous = std::mem::take(&mut *torrents);
+
+        for current in progress {
+            let state = TorrentState::of(current);
+            let same = |t: &TorrentStats| match (&t.info_hash, &current.info_hash) {
+                (Some(known), Some(hash)) => known == hash,
+                (Some(_), None) => false,
+                (None, _) => t.name == current.name,
+            };
+            let mut stats = match previous.iter().position(same) {
+                Some(index) => previous.swap_remove(index),
+                None => TorrentStats {
+                    info_hash: current.info_hash.clone(),
+                    name: current.name.clone(),
+                    total_bytes: current.total_bytes,
+                    state,
+                    state_since: now,
+                    samples: VecDeque::with_capacity(CAPACITY),
+                },
+            };
+            if stats.state != state {
+                stats.state = state;
+                stats.state_since = now;
+            }
+            stats.info_hash = current.info_hash.clone();
+            stats.name = current.name.clone();
+            stats.total_bytes = current.total_bytes;
+            if stats.samples.len() == CAPACITY {
+                stats.samples.pop_front();
+            }
+            stats.samples.push_back(Sample {
+                at: now,
+                progress_bytes: current.progress_bytes,
+                download_mbps: current.download_mbps,
+                upload_mbps: current.upload_mbps,
+                peers: current.peers,
+            });
+            torrents.push(stats);
+        }
+    }
+
+    pub fn snapshot(&self) -> Vec<TorrentStats> {
+        self.torrents.lock().unwrap().clone()
+    }
+}
+
+/// Ask a running daemon for its recent stats.
+pub async fn fetch(listen: &str) -> Result<Vec<TorrentStats>> {
+    let client = reqwest::Client::builder()
+        .timeout(std::time::Duration::from_secs(2))
+        .build()?;
+    Ok(client.get(format!("http://{}/stats", listen))
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?)
+}
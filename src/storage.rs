@@ -0,0 +1,95 @@
+use anyhow::Result;
+use std::path::{Path, PathBuf};
+use tracing::info;
+
+use crate::config::{DownloadConfig, DownloadRoot, RootSelection};
+use crate::organize;
+use crate::state::StateDb;
+
+const ROUND_ROBIN_KEY: &str = "download_root_cursor";
+const GB: f64 = 1024.0 * 1024.0 * 1024.0;
+
+/// Free space for a root, creating the directory first so it can be queried.
+pub fn free_space(path: &Path) -> Result<u64> {
+    std::fs::create_dir_all(path)?;
+    Ok(fs2::available_space(path)?)
+}
+
+/// Whether `bytes` fit in the space free under `path`. Space that can't be
+/// queried doesn't stop anything; the download itself will fail soon enough.
+pub fn fits(path: &Path, bytes: u64) -> bool {
+    free_space(path).map_or(true, |free| free >= bytes)
+}
+
+/// Bytes a downloaded file or folder takes up, or `None` if it's gone.
+pub fn size_on_disk(path: &Path) -> Option<u64> {
+    let files = organize::collect_files(path).ok()?;
+    Some(files.iter().filter_map(|file| file.metadata().ok()).map(|m| m.len()).sum())
+}
+
+/// The configured root a given output directory lives under, if any.
+pub fn root_for_path<'a>(roots: &'a [DownloadRoot], path: &Path) -> Option<&'a DownloadRoot> {
+    roots.iter()
+        .filter(|root| path.starts_with(&root.path))
+        .max_by_key(|root| root.path.components().count())
+}
+
+/// Pick a download root with enough free space according to the configured strategy.
+pub fn select_download_root(config: &DownloadConfig, state: &StateDb) -> Result<PathBuf> {
+    let mut candidates = Vec::new();
+
+    for (index, root) in config.roots.iter().enumerate() {
+        match free_space(&root.path) {
+            Ok(free) if free as f64 >= root.min_free_gb * GB => {
+                candidates.push((index, root, free));
+            }
+            Ok(free) => {
+                info!("Skipping {}: {:.1} GB free, need {:.1} GB",
+                      root.path.display(), free as f64 / GB, root.min_free_gb);
+            }
+            Err(e) => {
+                info!("Skipping {}: {}", root.path.display(), e);
+            }
+        }
+    }
+
+    if candidates.is_empty() {
+        return Err(anyhow::anyhow!(
+            "No download root has enough free space (checked {} root(s))",
+            config.roots.len()
+        ));
+    }
+
+    let chosen = match config.selection {
+        RootSelection::MostFree => candidates.iter()
+            .max_by_key(|(_, _, free)| *free)
+            .map(|(_, root, _)| *root)
+            .unwrap(),
+        RootSelection::RoundRobin => {
+            let last = state.get_setting(ROUND_ROBIN_KEY)?
+                .and_then(|v| v.parse::<usize>().ok());
+
+            // Next eligible root after the one used last time, wrapping around
+            let (index, root, _) = candidates.iter()
+                .find(|(i, _, _)| last.is_none_or(|last| *i > last))
+                .unwrap_or(&candidates[0]);
+
+            state.set_setting(ROUND_ROBIN_KEY, &index.to_string())?;
+            *root
+        }
+    };
+
+    info!("Selected download root: {}", chosen.path.display());
+    Ok(chosen.path.clone())
+}
+
+pub fn format_bytes(bytes: u64) -> String {
+    let bytes = bytes as f64;
+    if bytes >= GB {
+        format!("{:.2} GB", bytes / GB)
+    } else if bytes >= 1024.0 * 1024.0 {
+        format!("{:.1} MB", bytes / (1024.0 * 1024.0))
+    } else {
+        format!("{:.0} KB", bytes / 1024.0)
+    }
+}
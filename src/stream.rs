@@ -0,0 +1,258 @@
+use anyhow::Result;
+use axum::{
+    body::{Body, Bytes},
+    extract::State,
+    http::{header, HeaderMap, HeaderValue, StatusCode},
+    response::{IntoResponse, Response},
+    routing::get,
+    Router,
+};
+use librqbit::{AddTorrent, AddTorrentOptions, AddTorrentResponse, ManagedTorrent, Session};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncSeekExt};
+use tracing::{info, warn};
+
+use crate::classify::is_video;
+use crate::storage::format_bytes;
+
+/// Bytes read from the torrent per response chunk.
+const CHUNK_SIZE: usize = 256 * 1024;
+const PROGRESS_INTERVAL: Duration = Duration::from_secs(5);
+
+/// The file being served.
+#[derive(Clone)]
+struct Served {
+    handle: Arc<ManagedTorrent>,
+    file_id: usize,
+    name: String,
+    len: u64,
+}
+
+/// Start `torrent` and serve its largest video over HTTP on `port` until
+/// interrupted. librqbit fetches the pieces a stream is reading first, so
+/// playback can start long before the download finishes; the rest of the
+/// torrent keeps downloading behind it and stays in `output_dir`.
+pub async fn run(torrent: &str, output_dir: PathBuf, port: u16) -> Result<()> {
+    let session = Session::new(output_dir.clone()).await?;
+    let add = if torrent.starts_with("magnet:") || torrent.starts_with("http://") || torrent.starts_with("https://") {
+        AddTorrent::from_url(torrent)
+    } else {
+        AddTorrent::from_local_filename(torrent)?
+    };
+    let options = AddTorrentOptions {
+        overwrite: true,
+        ..Default::default()
+    };
+    let handle = match session.add_torrent(add, Some(options)).await? {
+        AddTorrentResponse::Added(_, handle) | AddTorrentResponse::AlreadyManaged(_, handle) => handle,
+        AddTorrentResponse::ListOnly(_) => return Err(anyhow::anyhow!("Torrent was only listed")),
+    };
+
+    println!("⏳ Waiting for metadata...");
+    handle.wait_until_initialized().await?;
+    let files: Vec<(PathBuf, u64)> = handle.with_metadata(|meta| {
+        meta.file_infos.iter().map(|f| (f.relative_filename.clone(), f.len)).collect()
+    })?;
+    let (file_id, (path, len)) = pick_file(&files)
+        .ok_or_else(|| anyhow::anyhow!("The torrent has no files"))?;
+    if !is_video(path) {
+        warn!("No video file in the torrent; streaming the largest file instead");
+    }
+
+    let name = path.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_else(|| "stream".to_string());
+    let served = Served {
+        handle: handle.clone(),
+        file_id,
+        name: name.clone(),
+        len: *len,
+    };
+    let app = Router::new()
+        .route("/", get(serve))
+        .route("/:name", get(serve))
+        .with_state(served);
+
+    let listener = tokio::net::TcpListener::bind(("127.0.0.1", port)).await?;
+    let url = format!("http://{}/{}", listener.local_addr()?, urlencoding::encode(&name));
+    println!("🎬 Streaming {} ({})", name, format_bytes(*len));
+    println!("   {}", url);
+    println!("   Open it in a player, e.g. `mpv \"{}\"` or VLC's Open Network Stream", url);
+    println!("   Press Ctrl-C to stop; the download is kept in {}", output_dir.display());
+
+    tokio::spawn(report_progress(handle));
+    axum::serve(listener, app)
+        .with_graceful_shutdown(async {
+            let _ = tokio::signal::ctrl_c().await;
+        })
+        .await?;
+    Ok(())
+}
+
+/// The largest video file, or the largest file when there's no video.
+fn pick_file(files: &[(PathBuf, u64)]) -> Option<(usize, &(PathBuf, u64))> {
+    let largest = |video: bool| {
+        files.iter()
+            .enumerate()
+            .filter(|(_, (path, _))| !video || is_video(path))
+            .max_by_key(|(_, (_, len))| *len)
+    };
+    largest(true).or_else(|| largest(false))
+}
+
+fn content_type(name: &str) -> &'static str {
+    match Path::new(name).extension().and_then(|ext| ext.to_str()).map(|ext| ext.to_lowercase()).as_deref() {
+        Some("mp4" | "m4v") => "video/mp4",
+        Some("mkv") => "video/x-matroska",
+        Some("webm") => "video/webm",
+        Some("avi") => "video/x-msvideo",
+        Some("mov") => "video/quicktime",
+        Some("ts") => "video/mp2t",
+        Some("mpg" | "mpeg") => "video/mpeg",
+        _ => "application/octet-stream",
+    }
+}
+
+/// Serve the file, honouring a single `Range: bytes=` request so players
+/// can seek.
+async fn serve(State(served): State<Served>, headers: HeaderMap) -> Response {
+    let range = match requested_range(&headers, served.len) {
+        Ok(range) => range,
+        Err(response) => return *response,
+    };
+    let (start, end) = range.unwrap_or((0, served.len.saturating_sub(1)));
+    let length = if served.len == 0 { 0 } else { end - start + 1 };
+
+    let mut stream = match served.handle.clone().stream(served.file_id) {
+        Ok(stream) => stream,
+        Err(e) => return (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
+    };
+    if let Err(e) = stream.seek(std::io::SeekFrom::Start(start)).await {
+        return (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response();
+    }
+
+    // Reads block until the pieces arrive, which is what paces playback
+    let body = futures::stream::unfold((stream, length), |(mut stream, remaining)| async move {
+        if remaining == 0 {
+            return None;
+        }
+        let mut buf = vec![0; CHUNK_SIZE.min(remaining as usize)];
+        match stream.read(&mut buf).await {
+            Ok(0) => None,
+            Ok(read) => {
+                buf.truncate(read);
+                Some((Ok::<_, std::io::Error>(Bytes::from(buf)), (stream, remaining - read as u64)))
+            }
+            Err(e) => Some((Err(e), (stream, 0))),
+        }
+    });
+
+    let mut response = Response::new(Body::from_stream(body));
+    *response.status_mut() = if range.is_some() { StatusCode::PARTIAL_CONTENT } else { StatusCode::OK };
+    let headers = response.headers_mut();
+    headers.insert(header::ACCEPT_RANGES, HeaderValue::from_static("bytes"));
+    headers.insert(header::CONTENT_TYPE, HeaderValue::from_static(content_type(&served.name)));
+    headers.insert(header::CONTENT_LENGTH, HeaderValue::from(length));
+    if range.is_some() {
+        if let Ok(value) = HeaderValue::from_str(&format!("bytes {}-{}/{}", start, end, served.len)) {
+            headers.insert(header::CONTENT_RANGE, value);
+        }
+    }
+    response
+}
+
+/// The range asked for, `None` for the whole file, or the 416 response
+/// for a range that can't be served.
+fn requested_range(headers: &HeaderMap, len: u64) -> Result<Option<(u64, u64)>, Box<Response>> {
+    let Some(value) = headers.get(header::RANGE).and_then(|v| v.to_str().ok()) else {
+        return Ok(None);
+    };
+    parse_range(value, len).map(Some).ok_or_else(|| {
+        let mut response = StatusCode::RANGE_NOT_SATISFIABLE.into_response();
+        if let Ok(value) = HeaderValue::from_str(&format!("bytes */{}", len)) {
+            response.headers_mut().insert(header::CONTENT_RANGE, value);
+        }
+        Box::new(response)
+    })
+}
+
+/// Parse `bytes=start-end`, `bytes=start-` or `bytes=-suffix` into an
+/// inclusive range within `len`. Multiple ranges aren't supported; players
+/// don't ask for them.
+fn parse_range(value: &str, len: u64) -> Option<(u64, u64)> {
+    let spec = value.trim().strip_prefix("bytes=")?;
+    if spec.contains(',') || len == 0 {
+        return None;
+    }
+    let (start, end) = spec.split_once('-')?;
+    let (start, end) = match (start.trim(), end.trim()) {
+        ("", suffix) => {
+            let suffix: u64 = suffix.parse().ok()?;
+            (len.saturating_sub(suffix), len - 1)
+        }
+        (start, "") => (start.parse().ok()?, len - 1),
+        (start, end) => (start.parse().ok()?, end.parse::<u64>().ok()?.min(len - 1)),
+    };
+    (start <= end && start < len).then_some((start, end))
+}
+
+async fn report_progress(handle: Arc<ManagedTorrent>) {
+    loop {
+        tokio::time::sleep(PROGRESS_INTERVAL).await;
+        let stats = handle.stats();
+        let speed = stats.live.as_ref().map(|live| live.download_speed.mbps).unwrap_or(0.0);
+        info!("Stream download: {} of {} at {:.1} MiB/s",
+              format_bytes(stats.progress_bytes), format_bytes(stats.total_bytes), speed);
+        if stats.finished {
+            println!("✅ Download complete; the stream now reads from disk");
+            return;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_single_ranges() {
+        assert_eq!(parse_range("bytes=0-99", 1000), Some((0, 99)));
+        assert_eq!(parse_range("bytes=500-", 1000), Some((500, 999)));
+        assert_eq!(parse_range(" bytes=500 - 600 ", 1000), Some((500, 600)));
+        // An end past the file is cut to its last byte
+        assert_eq!(parse_range("bytes=900-5000", 1000), Some((900, 999)));
+    }
+
+    #[test]
+    fn parses_suffix_ranges() {
+        assert_eq!(parse_range("bytes=-100", 1000), Some((900, 999)));
+        // A suffix longer than the file is the whole file
+        assert_eq!(parse_range("bytes=-5000", 1000), Some((0, 999)));
+        assert_eq!(parse_range("bytes=-0", 1000), None);
+    }
+
+    #[test]
+    fn rejects_ranges_past_the_end() {
+        assert_eq!(parse_range("bytes=1000-", 1000), None);
+        assert_eq!(parse_range("bytes=2000-3000", 1000), None);
+        assert_eq!(parse_range("bytes=0-", 0), None);
+    }
+
+    #[test]
+    fn start_past_the_end_is_416() {
+        let mut headers = HeaderMap::new();
+        assert_eq!(requested_range(&headers, 1000).ok(), Some(None));
+        headers.insert(header::RANGE, HeaderValue::from_static("bytes=1000-"));
+        let response = requested_range(&headers, 1000).expect_err("range past the end");
+        assert_eq!(response.status(), StatusCode::RANGE_NOT_SATISFIABLE);
+        assert_eq!(response.headers()[header::CONTENT_RANGE], "bytes */1000");
+    }
+
+    #[test]
+    fn rejects_malformed_and_multiple_ranges() {
+        assert_eq!(parse_range("bytes=0-99,200-299", 1000), None);
+        assert_eq!(parse_range("bytes=600-500", 1000), None);
+        assert_eq!(parse_range("items=0-99", 1000), None);
+        assert_eq!(parse_range("bytes=a-b", 1000), None);
+    }
+}
@@ -0,0 +1,186 @@
+use anyhow::Result;
+use serde::Deserialize;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use tracing::{info, warn};
+
+use crate::classify::is_video;
+use crate::config::SubtitlesConfig;
+use crate::secrets::SecretStore;
+
+const API_URL: &str = "https://api.opensubtitles.com/api/v1";
+const USER_AGENT: &str = concat!("torrentai v", env!("CARGO_PKG_VERSION"));
+
+/// The OpenSubtitles hash reads this much from each end of the file.
+const HASH_CHUNK: u64 = 64 * 1024;
+
+#[derive(Deserialize)]
+struct SearchResponse {
+    data: Vec<SubtitleEntry>,
+}
+
+#[derive(Deserialize)]
+struct SubtitleEntry {
+    attributes: SubtitleAttributes,
+}
+
+#[derive(Deserialize)]
+struct SubtitleAttributes {
+    language: Option<String>,
+    #[serde(default)]
+    download_count: u64,
+    #[serde(default)]
+    moviehash_match: bool,
+    files: Vec<SubtitleFile>,
+}
+
+#[derive(Deserialize)]
+struct SubtitleFile {
+    file_id: u64,
+}
+
+#[derive(Deserialize)]
+struct DownloadResponse {
+    link: String,
+}
+
+#[derive(Deserialize)]
+struct LoginResponse {
+    token: String,
+}
+
+/// OpenSubtitles.com REST client. Logging in is optional but raises the
+/// daily download quota.
+pub struct SubtitleClient {
+    client: reqwest::Client,
+    api_key: String,
+    token: Option<String>,
+}
+
+impl SubtitleClient {
+    pub async fn from_config(config: &SubtitlesConfig) -> Result<Self> {
+        let secrets = SecretStore::open()?;
+        let api_key = secrets.require(&config.api_key_secret)?.to_string();
+        let client = reqwest::Client::builder().user_agent(USER_AGENT).build()?;
+
+        let token = match &config.username {
+            Some(username) => {
+                let password = secrets.require(&config.password_secret)?;
+                let response: LoginResponse = client.post(format!("{}/login", API_URL))
+                    .header("Api-Key", &api_key)
+                    .json(&serde_json::json!({ "username": username, "password": password }))
+                    .send().await?
+                    .error_for_status()?
+                    .json().await?;
+                Some(response.token)
+            }
+            None => None,
+        };
+
+        Ok(Self { client, api_key, token })
+    }
+
+    /// Best subtitle file per language, preferring exact hash matches, then popularity.
+    async fn search(&self, params: &[(&str, String)], languages: &[String]) -> Result<Vec<(String, u64)>> {
+        let mut params = params.to_vec();
+        params.push(("languages", languages.join(",")));
+
+        let response: SearchResponse = self.client.get(format!("{}/subtitles", API_URL))
+            .header("Api-Key", &self.api_key)
+            .query(&params)
+            .send().await?
+            .error_for_status()?
+            .json().await?;
+
+        let mut best = Vec::new();
+        for language in languages {
+            let pick = response.data.iter()
+                .map(|entry| &entry.attributes)
+                .filter(|attrs| attrs.language.as_deref() == Some(language.as_str()))
+                .max_by_key(|attrs| (attrs.moviehash_match, attrs.download_count))
+                .and_then(|attrs| attrs.files.first());
+            if let Some(file) = pick {
+                best.push((language.clone(), file.file_id));
+            }
+        }
+        Ok(best)
+    }
+
+    async fn download(&self, file_id: u64) -> Result<Vec<u8>> {
+        let mut request = self.client.post(format!("{}/download", API_URL))
+            .header("Api-Key", &self.api_key)
+            .json(&serde_json::json!({ "file_id": file_id }));
+        if let Some(token) = &self.token {
+            request = request.bearer_auth(token);
+        }
+
+        let response: DownloadResponse = request.send().await?.error_for_status()?.json().await?;
+        Ok(self.client.get(&response.link).send().await?.error_for_status()?.bytes().await?.to_vec())
+    }
+}
+
+/// Fetch subtitles in `languages` for every video among `files`, saving them
+/// next to the video as `<name>.<lang>.srt`. Returns the files written.
+pub async fn fetch_subtitles(files: &[PathBuf], languages: &[String], config: &SubtitlesConfig) -> Result<Vec<PathBuf>> {
+    let client = SubtitleClient::from_config(config).await?;
+    let mut written = Vec::new();
+
+    for video in files.iter().filter(|f| is_video(f)) {
+        let hash = match movie_hash(video) {
+            Ok(hash) => hash,
+            Err(e) => {
+                warn!("Can't hash {}: {}", video.display(), e);
+                continue;
+            }
+        };
+
+        let mut found = client.search(&[("moviehash", hash)], languages).await?;
+
+        // Fall back to the file name for languages with no hash match
+        let missing: Vec<String> = languages.iter()
+            .filter(|lang| !found.iter().any(|(l, _)| l == *lang))
+            .cloned()
+            .collect();
+        if !missing.is_empty() {
+            let stem = video.file_stem().map(|s| s.to_string_lossy().to_string()).unwrap_or_default();
+            found.extend(client.search(&[("query", stem)], &missing).await?);
+        }
+
+        for language in languages {
+            let Some((_, file_id)) = found.iter().find(|(l, _)| l == language) else {
+                info!("No {} subtitles found for {}", language, video.display());
+                continue;
+            };
+
+            let contents = client.download(*file_id).await?;
+            let path = video.with_extension(format!("{}.srt", language));
+            std::fs::write(&path, contents)?;
+            info!("Saved subtitles: {}", path.display());
+            written.push(path);
+        }
+    }
+
+    Ok(written)
+}
+
+/// OpenSubtitles movie hash: the file size plus the 64-bit little-endian words
+/// of the first and last 64 KiB, all wrapping.
+pub fn movie_hash(path: &Path) -> Result<String> {
+    let mut file = std::fs::File::open(path)?;
+    let size = file.metadata()?.len();
+    if size < HASH_CHUNK * 2 {
+        return Err(anyhow::anyhow!("file too small to hash ({} bytes)", size));
+    }
+
+    let mut hash = size;
+    let mut buffer = vec![0u8; HASH_CHUNK as usize];
+    for offset in [0, size - HASH_CHUNK] {
+        file.seek(SeekFrom::Start(offset))?;
+        file.read_exact(&mut buffer)?;
+        for word in buffer.chunks_exact(8) {
+            hash = hash.wrapping_add(u64::from_le_bytes(word.try_into()?));
+        }
+    }
+
+    Ok(format!("{:016x}", hash))
+}
@@ -0,0 +1,127 @@
+use anyhow::{Context, Result};
+use chrono::Utc;
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+use crate::models::{EpisodePlan, EvaluatedResult, SearchIntent, SearchStrategy};
+use crate::pirate_bay_scraper::TorrentResult;
+
+/// A step of the smart search pipeline, as written to `--trace-file`.
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum TraceEvent {
+    IntentParsed {
+        intent: SearchIntent,
+        by_llm: bool,
+    },
+    IntentRefined {
+        refinement: String,
+        intent: SearchIntent,
+        new_search: bool,
+    },
+    /// "Latest" pinned to the newest aired episode
+    LatestResolved {
+        intent: SearchIntent,
+    },
+    /// A step failed and a fallback stood in for it
+    Degraded {
+        message: String,
+    },
+    StrategyGenerated {
+        strategy: SearchStrategy,
+        by_llm: bool,
+    },
+    /// What one source returned for one query, before deduplication or filters
+    SourceResults {
+        source: String,
+        query: String,
+        results: Vec<TorrentResult>,
+        error: Option<String>,
+    },
+    /// One prompt sent to the model and what came back
+    LlmExchange {
+        task: String,
+        attempt: usize,
+        prompt: String,
+        response: Option<String>,
+        /// Why the response couldn't be used, prompting another attempt
+        rejected: Option<String>,
+        error: Option<String>,
+    },
+    /// Every candidate's scores, including those under the threshold
+    Scored {
+        threshold: f32,
+        /// Why adaptive mode moved the threshold, if it did
+        adjustment: Option<String>,
+        results: Vec<EvaluatedResult>,
+    },
+    Finished {
+        results: Vec<EvaluatedResult>,
+        episode_plan: Option<EpisodePlan>,
+    },
+}
+
+#[derive(Debug, Serialize)]
+struct TimedEvent {
+    elapsed_ms: u128,
+    #[serde(flatten)]
+    event: TraceEvent,
+}
+
+#[derive(Serialize)]
+struct TraceReport<'a> {
+    query: &'a str,
+    started_at: &'a str,
+    events: &'a [TimedEvent],
+}
+
+/// Collects [`TraceEvent`]s from the searcher and the LLM service for one
+/// run, to explain afterwards why a result was or wasn't picked. Clones
+/// share the same record.
+#[derive(Clone)]
+pub struct Tracer {
+    path: PathBuf,
+    query: String,
+    started_at: String,
+    started: Instant,
+    events: Arc<Mutex<Vec<TimedEvent>>>,
+}
+
+impl Tracer {
+    pub fn new(path: PathBuf, query: &str) -> Self {
+        Self {
+            path,
+            query: query.to_string(),
+            started_at: Utc::now().to_rfc3339(),
+            started: Instant::now(),
+            events: Arc::new(Mutex::new(Vec::new())),
+        }
+    }
+
+    pub fn record(&self, event: TraceEvent) {
+        let elapsed_ms = self.started.elapsed().as_millis();
+        self.events.lock().unwrap().push(TimedEvent { elapsed_ms, event });
+    }
+
+    /// Write everything recorded so far as JSON.
+    pub fn save(&self) -> Result<&Path> {
+        let events = self.events.lock().unwrap();
+        let report = TraceReport {
+            query: &self.query,
+            started_at: &self.started_at,
+            events: &events,
+        };
+        std::fs::write(&self.path, serde_json::to_string_pretty(&report)?)
+            .with_context(|| format!("Could not write {}", self.path.display()))?;
+        Ok(&self.path)
+    }
+}
+
+/// Record `event` if tracing is on; the event is only built when it is.
+pub fn record(tracer: Option<&Tracer>, event: impl FnOnce() -> TraceEvent) {
+    if let Some(tracer) = tracer {
+        tracer.record(event());
+    }
+}
@@ -0,0 +1,965 @@
+use anyhow::Result;
+use chrono::{DateTime, Duration, Utc};
+use lazy_static::lazy_static;
+use serde::Deserialize;
+use serde_bencode::value::Value;
+use sha1::{Digest, Sha1};
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::Mutex;
+use tokio::net::UdpSocket;
+use tracing::field::{Field, Visit};
+use tracing::span::{Attributes, Id};
+use tracing::{info, warn, Event, Metadata, Subscriber};
+use tracing_subscriber::layer::{Context, Layer};
+use tracing_subscriber::registry::LookupSpan;
+
+use crate::config::TrackerConfig;
+use crate::state::StateDb;
+
+// librqbit does the announcing but reports nothing about how each tracker
+// fares. Its tracker tasks log every announce inside a span naming the
+// tracker and torrent, so `TrackerLog` reads the outcomes from there, and a
+// `TrackerSchedule` per torrent turns them into tiers (BEP 12), backoff and
+// dropped trackers. The schedule decides which trackers librqbit is handed
+// whenever the torrent is added, so `status` shows what librqbit is actually
+// announcing to, and a dropped tracker stays out of the session.
+
+const ANNOUNCE_TIMEOUT_SECS: u64 = 15;
+const UDP_PROTOCOL_ID: u64 = 0x41727101980;
+/// Assumed when librqbit doesn't log the interval a tracker asked for
+const DEFAULT_INTERVAL_SECS: u64 = 1800;
+
+lazy_static! {
+    /// Torrents whose announces are collected, by info hash.
+    static ref WATCHED: Mutex<HashMap<String, Watched>> = Mutex::new(HashMap::new());
+}
+
+/// Announces read off librqbit's tracker spans for one torrent and not
+/// applied yet.
+#[derive(Default)]
+struct Watched {
+    /// Schedules loaded for the torrent; collecting stops when none are left
+    schedules: usize,
+    announces: Vec<(String, Announced)>,
+}
+
+#[derive(Debug, Clone)]
+pub struct TrackerState {
+    pub url: String,
+    pub tier: usize,
+    /// Consecutive failures since the last successful announce
+    pub failures: u32,
+    pub last_error: Option<String>,
+    pub last_announce: Option<DateTime<Utc>>,
+    pub next_announce: DateTime<Utc>,
+    pub interval_secs: u64,
+    pub seeders: Option<u32>,
+    pub leechers: Option<u32>,
+    pub dropped: bool,
+}
+
+impl TrackerState {
+    fn new(url: String, tier: usize) -> Self {
+        Self {
+            url,
+            tier,
+            failures: 0,
+            last_error: None,
+            last_announce: None,
+            next_announce: Utc::now(),
+            interval_secs: 0,
+            seeders: None,
+            leechers: None,
+            dropped: false,
+        }
+    }
+}
+
+#[derive(Debug)]
+struct AnnounceResponse {
+    seeders: Option<u32>,
+    leechers: Option<u32>,
+    /// Only filled in when peers were asked for
+    peers: Vec<SocketAddr>,
+}
+
+/// One tracker's answer to a single probing announce.
+#[derive(Debug)]
+pub struct TrackerProbe {
+    pub url: String,
+    pub seeders: Option<u32>,
+    pub leechers: Option<u32>,
+    pub peers: Vec<SocketAddr>,
+    pub error: Option<String>,
+}
+
+/// Tracker tiers from a magnet link. Magnets carry no tier information, so
+/// every tracker gets its own tier, in the order listed.
+pub fn tiers_from_magnet(magnet: &str) -> Vec<Vec<String>> {
+    let query = magnet.split_once('?').map(|(_, q)| q).unwrap_or("");
+    query.split('&')
+        .filter_map(|pair| pair.split_once('='))
+        .filter(|(key, _)| *key == "tr" || key.starts_with("tr."))
+        .filter_map(|(_, value)| urlencoding::decode(value).ok())
+        .map(|url| vec![url.into_owned()])
+        .collect()
+}
+
+/// Tracker tiers from a .torrent file (`announce-list`, falling back to `announce`).
+pub fn tiers_from_torrent_file(bytes: &[u8]) -> Vec<Vec<String>> {
+    #[derive(Deserialize)]
+    struct AnnounceFields {
+        announce: Option<String>,
+        #[serde(rename = "announce-list")]
+        announce_list: Option<Vec<Vec<String>>>,
+    }
+
+    match serde_bencode::from_bytes::<AnnounceFields>(bytes) {
+        Ok(fields) => match (fields.announce_list, fields.announce) {
+            (Some(list), _) if !list.is_empty() => list,
+            (_, Some(announce)) => vec![vec![announce]],
+            _ => Vec::new(),
+        },
+        Err(e) => {
+            warn!("Could not read trackers from torrent file: {}", e);
+            Vec::new()
+        }
+    }
+}
+
+/// Hex info hash of a .torrent file: the SHA-1 of its bencoded `info` dict.
+pub fn torrent_info_hash(bytes: &[u8]) -> Option<String> {
+    let Value::Dict(fields) = serde_bencode::from_bytes::<Value>(bytes).ok()? else {
+        return None;
+    };
+    let info = serde_bencode::to_bytes(fields.get(b"info".as_slice())?).ok()?;
+    Some(Sha1::digest(info).iter().map(|b| format!("{:02x}", b)).collect())
+}
+
+/// A .torrent file announcing to `tiers` and nothing else.
+pub fn with_trackers(bytes: &[u8], tiers: &[Vec<String>]) -> Result<Vec<u8>> {
+    let Value::Dict(mut fields) = serde_bencode::from_bytes::<Value>(bytes)? else {
+        return Err(anyhow::anyhow!("torrent file is not a dictionary"));
+    };
+    fields.remove(b"announce".as_slice());
+    fields.remove(b"announce-list".as_slice());
+    if let Some(first) = tiers.iter().flatten().next() {
+        fields.insert(b"announce".to_vec(), Value::Bytes(first.as_bytes().to_vec()));
+        fields.insert(b"announce-list".to_vec(), Value::List(
+            tiers.iter()
+                .map(|tier| Value::List(tier.iter().map(|url| Value::Bytes(url.as_bytes().to_vec())).collect()))
+                .collect(),
+        ));
+    }
+    Ok(serde_bencode::to_bytes(&Value::Dict(fields))?)
+}
+
+/// Hex info hash from a magnet's `xt=urn:btih:` parameter, if it is in hex form.
+pub fn magnet_info_hash(magnet: &str) -> Option<String> {
+    let query = magnet.split_once('?')?.1;
+    query.split('&')
+        .filter_map(|pair| pair.strip_prefix("xt=urn:btih:"))
+        .find(|hash| hash.len() == 40 && hash.chars().all(|c| c.is_ascii_hexdigit()))
+        .map(|hash| hash.to_lowercase())
+}
+
+/// Display name from a magnet's `dn` parameter.
+pub fn magnet_display_name(magnet: &str) -> Option<String> {
+    let query = magnet.split_once('?')?.1;
+    query.split('&')
+        .filter_map(|pair| pair.strip_prefix("dn="))
+        .find_map(|name| urlencoding::decode(&name.replace('+', " ")).ok().map(|n| n.into_owned()))
+}
+
+/// Remove the given trackers from a magnet link, keeping every other parameter.
+pub fn strip_trackers(magnet: &str, dropped: &[String]) -> String {
+    let Some((base, query)) = magnet.split_once('?') else {
+        return magnet.to_string();
+    };
+
+    let kept: Vec<&str> = query.split('&')
+        .filter(|pair| match pair.split_once('=') {
+            Some((key, value)) if key == "tr" || key.starts_with("tr.") => {
+                let url = urlencoding::decode(value).map(|u| u.into_owned()).unwrap_or_default();
+                !dropped.contains(&url)
+            }
+            _ => true,
+        })
+        .collect();
+
+    format!("{}?{}", base, kept.join("&"))
+}
+
+/// How one of librqbit's announces went.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Announced {
+    /// With the interval the tracker asked for, when librqbit logged it
+    Ok(Option<u64>),
+    Failed(String),
+}
+
+/// Tiers, health and backoff of one torrent's trackers. Decides which of
+/// them librqbit is handed when the torrent is added, and learns how they
+/// fare from librqbit's own announces.
+pub struct TrackerSchedule {
+    info_hash: String,
+    /// Trackers grouped by tier; within a tier the working tracker moves to the front
+    tiers: Vec<Vec<TrackerState>>,
+    config: TrackerConfig,
+    /// Registered with [`TrackerLog`], which collects announces for it
+    watching: bool,
+}
+
+impl TrackerSchedule {
+    /// A schedule over `tiers`, carrying over what `persisted` knows about
+    /// each tracker so dropped trackers stay dropped.
+    pub fn new(info_hash: &str, tiers: Vec<Vec<String>>, persisted: &[TrackerState], config: TrackerConfig) -> Self {
+        let mut seen = Vec::new();
+        let tiers = tiers.into_iter()
+            .filter_map(|urls| {
+                let urls: Vec<String> = urls.into_iter()
+                    .filter(|url| {
+                        let new = !seen.contains(url);
+                        seen.push(url.clone());
+                        new
+                    })
+                    .collect();
+                (!urls.is_empty()).then_some(urls)
+            })
+            .enumerate()
+            .map(|(tier, urls)| {
+                let mut trackers: Vec<TrackerState> = urls.into_iter()
+                    .map(|url| {
+                        persisted.iter()
+                            .find(|t| t.url == url)
+                            .cloned()
+                            .map(|mut t| {
+                                t.tier = tier;
+                                t
+                            })
+                            .unwrap_or_else(|| TrackerState::new(url, tier))
+                    })
+                    .collect();
+                // The tracker that last worked leads its tier again
+                trackers.sort_by_key(|t| !(t.failures == 0 && t.last_announce.is_some()));
+                trackers
+            })
+            .collect();
+
+        Self {
+            info_hash: info_hash.to_string(),
+            tiers,
+            config,
+            watching: false,
+        }
+    }
+
+    /// The schedule for `info_hash`, from its persisted tracker state, with
+    /// librqbit's announces for it collected from now on.
+    pub fn load(info_hash: &str, tiers: Vec<Vec<String>>, config: TrackerConfig, state: &StateDb) -> Result<Self> {
+        let mut schedule = Self::new(info_hash, tiers, &state.trackers(info_hash)?, config);
+        schedule.persist(state)?;
+        WATCHED.lock().unwrap().entry(info_hash.to_string()).or_default().schedules += 1;
+        schedule.watching = true;
+        Ok(schedule)
+    }
+
+    /// The trackers to hand librqbit, tier by tier: those neither dropped
+    /// nor backing off from failures. A tier whose trackers are all backing
+    /// off keeps the one due back soonest, so no tier goes quiet.
+    pub fn active(&self, now: DateTime<Utc>) -> Vec<Vec<String>> {
+        self.tiers.iter()
+            .filter_map(|tier| {
+                let live: Vec<&TrackerState> = tier.iter().filter(|t| !t.dropped).collect();
+                let ready: Vec<String> = live.iter()
+                    .filter(|t| t.failures == 0 || t.next_announce <= now)
+                    .map(|t| t.url.clone())
+                    .collect();
+                if !ready.is_empty() {
+                    return Some(ready);
+                }
+                live.iter().min_by_key(|t| t.next_announce).map(|t| vec![t.url.clone()])
+            })
+            .collect()
+    }
+
+    /// Every tracker [`active`](Self::active) leaves out.
+    pub fn inactive(&self, now: DateTime<Utc>) -> Vec<String> {
+        let active: Vec<String> = self.active(now).into_iter().flatten().collect();
+        self.tiers.iter()
+            .flatten()
+            .map(|t| t.url.clone())
+            .filter(|url| !active.contains(url))
+            .collect()
+    }
+
+    /// Apply one announce to the tracker at `url`. A success moves it to the
+    /// front of its tier; a failure backs it off, and drops it once it has
+    /// failed `drop_after_failures` times in a row. Returns whether this
+    /// dropped it.
+    pub fn record(&mut self, url: &str, announced: &Announced, now: DateTime<Utc>) -> bool {
+        let Some((tier_index, position)) = self.position(url) else {
+            return false;
+        };
+        let tier = &mut self.tiers[tier_index];
+        let tracker = &mut tier[position];
+        if tracker.dropped {
+            return false;
+        }
+        tracker.last_announce = Some(now);
+
+        match announced {
+            Announced::Ok(interval) => {
+                let interval = interval
+                    .or((tracker.interval_secs > 0).then_some(tracker.interval_secs))
+                    .unwrap_or(DEFAULT_INTERVAL_SECS)
+                    .max(self.config.min_interval_secs);
+                tracker.failures = 0;
+                tracker.last_error = None;
+                tracker.interval_secs = interval;
+                tracker.next_announce = now + Duration::seconds(interval as i64);
+
+                // BEP 12: the responsive tracker moves to the front of its tier
+                let tracker = tier.remove(position);
+                tier.insert(0, tracker);
+                false
+            }
+            Announced::Failed(error) => {
+                tracker.failures += 1;
+                tracker.last_error = Some(error.clone());
+                tracker.next_announce = now + backoff(&self.config, tracker.failures);
+
+                if self.config.drop_after_failures > 0 && tracker.failures >= self.config.drop_after_failures {
+                    tracker.dropped = true;
+                    warn!("Dropping tracker {} after {} consecutive failures", tracker.url, tracker.failures);
+                    true
+                } else {
+                    info!("Tracker {} failed ({}), backing off for {}s",
+                          tracker.url, error, (tracker.next_announce - now).num_seconds());
+                    false
+                }
+            }
+        }
+    }
+
+    /// Apply the announces librqbit made since the last update and save the
+    /// result. Returns whether a tracker was dropped, in which case the
+    /// torrent should be added again without it.
+    pub fn update(&mut self, state: &StateDb) -> Result<bool> {
+        let announces = WATCHED.lock().unwrap()
+            .get_mut(&self.info_hash)
+            .map(|watched| std::mem::take(&mut watched.announces))
+            .unwrap_or_default();
+        if announces.is_empty() {
+            return Ok(false);
+        }
+
+        let now = Utc::now();
+        let mut dropped = false;
+        for (url, announced) in &announces {
+            dropped |= self.record(url, announced, now);
+        }
+        self.persist(state)?;
+        Ok(dropped)
+    }
+
+    fn position(&self, url: &str) -> Option<(usize, usize)> {
+        let url = normalize_url(url);
+        self.tiers.iter().enumerate().find_map(|(tier_index, tier)| {
+            tier.iter()
+                .position(|t| normalize_url(&t.url) == url)
+                .map(|position| (tier_index, position))
+        })
+    }
+
+    fn persist(&self, state: &StateDb) -> Result<()> {
+        for tracker in self.tiers.iter().flatten() {
+            state.upsert_tracker(&self.info_hash, tracker)?;
+        }
+        Ok(())
+    }
+}
+
+impl Drop for TrackerSchedule {
+    fn drop(&mut self) {
+        if !self.watching {
+            return;
+        }
+        let mut watched = WATCHED.lock().unwrap();
+        if let Some(torrent) = watched.get_mut(&self.info_hash) {
+            torrent.schedules -= 1;
+            if torrent.schedules == 0 {
+                watched.remove(&self.info_hash);
+            }
+        }
+    }
+}
+
+/// Tracker URLs as librqbit prints them, so "HTTP://Host:80/announce" and
+/// "http://host/announce" match.
+fn normalize_url(url: &str) -> String {
+    reqwest::Url::parse(url).map(|u| u.to_string()).unwrap_or_else(|_| url.to_string())
+}
+
+/// The tracker and torrent one of librqbit's tracker tasks announces for.
+struct TrackerSpan {
+    url: String,
+    info_hash: String,
+}
+
+/// A tracing layer reading how librqbit's announces go. Each of its tracker
+/// tasks runs in an `http_tracker` or `udp_tracker` span naming the tracker
+/// and torrent, and logs the outcome of every announce from inside it.
+pub struct TrackerLog;
+
+impl TrackerLog {
+    /// Whether a span or event comes from librqbit's tracker tasks, which
+    /// log successes at debug and trace level, below what gets printed.
+    pub fn wants(metadata: &Metadata<'_>) -> bool {
+        metadata.target().starts_with("librqbit_tracker_comms")
+    }
+}
+
+impl<S> Layer<S> for TrackerLog
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+        if !matches!(attrs.metadata().name(), "http_tracker" | "udp_tracker") {
+            return;
+        }
+        let mut fields = Fields::default();
+        attrs.record(&mut fields);
+        if let (Some(url), Some(info_hash), Some(span)) = (fields.0.remove("tracker"), fields.0.remove("info_hash"), ctx.span(id)) {
+            span.extensions_mut().insert(TrackerSpan { url, info_hash });
+        }
+    }
+
+    fn on_event(&self, event: &Event<'_>, ctx: Context<'_, S>) {
+        let Some(span) = ctx.event_span(event) else {
+            return;
+        };
+        let extensions = span.extensions();
+        let Some(tracker) = extensions.get::<TrackerSpan>() else {
+            return;
+        };
+        let mut fields = Fields::default();
+        event.record(&mut fields);
+        let Some(announced) = fields.0.get("message").and_then(|message| announce_outcome(message)) else {
+            return;
+        };
+        if let Some(watched) = WATCHED.lock().unwrap().get_mut(&tracker.info_hash) {
+            watched.announces.push((tracker.url.clone(), announced));
+        }
+    }
+}
+
+/// Span or event fields, formatted.
+#[derive(Default)]
+struct Fields(HashMap<&'static str, String>);
+
+impl Visit for Fields {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.0.insert(field.name(), value.to_string());
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+        self.0.insert(field.name(), format!("{:?}", value));
+    }
+}
+
+/// What a message from one of librqbit's tracker tasks says about the
+/// announce it just made, if anything.
+fn announce_outcome(message: &str) -> Option<Announced> {
+    if let Some(rest) = message.strip_prefix("sleeping for ") {
+        // "sleeping for 1800s after calling tracker <host>", after an HTTP announce
+        let interval = rest.split_once("s ")
+            .and_then(|(secs, _)| secs.parse::<f64>().ok())
+            .map(|secs| secs as u64);
+        Some(Announced::Ok(interval))
+    } else if message == "received announce response" {
+        Some(Announced::Ok(None))
+    } else if let Some(rest) = message.strip_prefix("error calling the tracker ") {
+        // "<url>: <error>"
+        Some(Announced::Failed(rest.split_once(": ").map_or(rest, |(_, error)| error).to_string()))
+    } else {
+        message.strip_prefix("error reading announce response: ")
+            .map(|error| Announced::Failed(error.to_string()))
+    }
+}
+
+/// Ask every tracker in `tiers`, regardless of tier, for peers once, to see
+/// which peer sources work. This is for `nettest` only; nothing is persisted.
+pub async fn probe(info_hash_hex: &str, tiers: &[Vec<String>], port: u16, numwant: u32) -> Result<Vec<TrackerProbe>> {
+    let prober = Prober {
+        info_hash: decode_info_hash(info_hash_hex)?,
+        peer_id: generate_peer_id(),
+        port,
+        client: reqwest::Client::builder()
+            .timeout(std::time::Duration::from_secs(ANNOUNCE_TIMEOUT_SECS))
+            .build()?,
+    };
+
+    let probes = tiers.iter().flatten().map(|url| {
+        let prober = &prober;
+        async move {
+            match prober.announce(url, numwant).await {
+                Ok(response) => TrackerProbe {
+                    url: url.clone(),
+                    seeders: response.seeders,
+                    leechers: response.leechers,
+                    peers: response.peers,
+                    error: None,
+                },
+                Err(e) => TrackerProbe {
+                    url: url.clone(),
+                    seeders: None,
+                    leechers: None,
+                    peers: Vec::new(),
+                    error: Some(e.to_string()),
+                },
+            }
+        }
+    });
+    Ok(futures::future::join_all(probes).await)
+}
+
+/// Announces once to a tracker on behalf of `nettest`.
+struct Prober {
+    info_hash: [u8; 20],
+    peer_id: [u8; 20],
+    port: u16,
+    client: reqwest::Client,
+}
+
+impl Prober {
+    async fn announce(&self, url: &str, numwant: u32) -> Result<AnnounceResponse> {
+        let timeout = std::time::Duration::from_secs(ANNOUNCE_TIMEOUT_SECS);
+        if url.starts_with("udp://") {
+            tokio::time::timeout(timeout, self.announce_udp(url, numwant)).await
+                .map_err(|_| anyhow::anyhow!("timed out"))?
+        } else if url.starts_with("http://") || url.starts_with("https://") {
+            self.announce_http(url, numwant).await
+        } else {
+            Err(anyhow::anyhow!("unsupported tracker scheme"))
+        }
+    }
+
+    async fn announce_http(&self, url: &str, numwant: u32) -> Result<AnnounceResponse> {
+        #[derive(Deserialize)]
+        struct HttpAnnounce {
+            #[serde(rename = "failure reason")]
+            failure_reason: Option<String>,
+            complete: Option<i64>,
+            incomplete: Option<i64>,
+            peers: Option<serde_bencode::value::Value>,
+        }
+
+        let announce_url = format!(
+            "{}{}info_hash={}&peer_id={}&port={}&uploaded=0&downloaded=0&left=0&compact=1&numwant={}&event=started",
+            url,
+            if url.contains('?') { '&' } else { '?' },
+            percent_encode_bytes(&self.info_hash),
+            percent_encode_bytes(&self.peer_id),
+            self.port,
+            numwant,
+        );
+
+        let response = self.client.get(&announce_url).send().await?;
+        if !response.status().is_success() {
+            return Err(anyhow::anyhow!("HTTP error: {}", response.status()));
+        }
+
+        let body = response.bytes().await?;
+        let parsed: HttpAnnounce = serde_bencode::from_bytes(&body)
+            .map_err(|e| anyhow::anyhow!("invalid announce response: {}", e))?;
+
+        if let Some(reason) = parsed.failure_reason {
+            return Err(anyhow::anyhow!("tracker error: {}", reason));
+        }
+
+        Ok(AnnounceResponse {
+            seeders: parsed.complete.map(|n| n as u32),
+            leechers: parsed.incomplete.map(|n| n as u32),
+            peers: parsed.peers.map(|peers| http_peers(&peers)).unwrap_or_default(),
+        })
+    }
+
+    async fn announce_udp(&self, url: &str, numwant: u32) -> Result<AnnounceResponse> {
+        let parsed = reqwest::Url::parse(url)?;
+        let host = parsed.host_str().ok_or_else(|| anyhow::anyhow!("missing host"))?;
+        let port = parsed.port().ok_or_else(|| anyhow::anyhow!("missing port"))?;
+        let addr: SocketAddr = tokio::net::lookup_host((host, port)).await?
+            .next()
+            .ok_or_else(|| anyhow::anyhow!("could not resolve {}", host))?;
+
+        let bind = if addr.is_ipv4() { "0.0.0.0:0" } else { "[::]:0" };
+        let socket = UdpSocket::bind(bind).await?;
+        socket.connect(addr).await?;
+        let mut buf = [0u8; 1500];
+
+        // BEP 15 connect
+        let transaction_id = transaction_id();
+        let mut connect = Vec::with_capacity(16);
+        connect.extend_from_slice(&UDP_PROTOCOL_ID.to_be_bytes());
+        connect.extend_from_slice(&0u32.to_be_bytes());
+        connect.extend_from_slice(&transaction_id.to_be_bytes());
+        socket.send(&connect).await?;
+
+        let len = socket.recv(&mut buf).await?;
+        if len < 16 || read_u32(&buf, 0) != 0 || read_u32(&buf, 4) != transaction_id {
+            return Err(anyhow::anyhow!("invalid connect response"));
+        }
+        let connection_id = &buf[8..16].to_vec();
+
+        // BEP 15 announce
+        let transaction_id = transaction_id.wrapping_add(1);
+        let mut announce = Vec::with_capacity(98);
+        announce.extend_from_slice(connection_id);
+        announce.extend_from_slice(&1u32.to_be_bytes());
+        announce.extend_from_slice(&transaction_id.to_be_bytes());
+        announce.extend_from_slice(&self.info_hash);
+        announce.extend_from_slice(&self.peer_id);
+        announce.extend_from_slice(&0u64.to_be_bytes()); // downloaded
+        announce.extend_from_slice(&0u64.to_be_bytes()); // left
+        announce.extend_from_slice(&0u64.to_be_bytes()); // uploaded
+        announce.extend_from_slice(&2u32.to_be_bytes()); // event: started
+        announce.extend_from_slice(&0u32.to_be_bytes()); // ip: use sender address
+        announce.extend_from_slice(&transaction_id.to_be_bytes()); // key
+        announce.extend_from_slice(&numwant.to_be_bytes());
+        announce.extend_from_slice(&self.port.to_be_bytes());
+        socket.send(&announce).await?;
+
+        let len = socket.recv(&mut buf).await?;
+        if len >= 8 && read_u32(&buf, 0) == 3 {
+            let message = String::from_utf8_lossy(&buf[8..len]).to_string();
+            return Err(anyhow::anyhow!("tracker error: {}", message));
+        }
+        if len < 20 || read_u32(&buf, 0) != 1 || read_u32(&buf, 4) != transaction_id {
+            return Err(anyhow::anyhow!("invalid announce response"));
+        }
+
+        Ok(AnnounceResponse {
+            leechers: Some(read_u32(&buf, 12)),
+            seeders: Some(read_u32(&buf, 16)),
+            peers: compact_peers(&buf[20..len]),
+        })
+    }
+}
+
+
+/// Exponential backoff after consecutive failures, capped at `max_backoff_secs`.
+fn backoff(config: &TrackerConfig, failures: u32) -> Duration {
+    let secs = config.retry_base_secs
+        .saturating_mul(1u64 << failures.saturating_sub(1).min(16))
+        .min(config.max_backoff_secs);
+    Duration::seconds(secs as i64)
+}
+
+/// Peers from an HTTP announce: BEP 23 compact form, or the original list of dicts.
+fn http_peers(peers: &serde_bencode::value::Value) -> Vec<SocketAddr> {
+    use serde_bencode::value::Value;
+
+    match peers {
+        Value::Bytes(bytes) => compact_peers(bytes),
+        Value::List(list) => list.iter()
+            .filter_map(|peer| match peer {
+                Value::Dict(fields) => {
+                    let ip = match fields.get(b"ip".as_slice())? {
+                        Value::Bytes(ip) => String::from_utf8_lossy(ip).parse().ok()?,
+                        _ => return None,
+                    };
+                    let port = match fields.get(b"port".as_slice())? {
+                        Value::Int(port) => u16::try_from(*port).ok()?,
+                        _ => return None,
+                    };
+                    Some(SocketAddr::new(ip, port))
+                }
+                _ => None,
+            })
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// IPv4 peers packed as 4 address bytes and 2 port bytes each.
+fn compact_peers(bytes: &[u8]) -> Vec<SocketAddr> {
+    bytes.chunks_exact(6)
+        .map(|peer| SocketAddr::from((
+            [peer[0], peer[1], peer[2], peer[3]],
+            u16::from_be_bytes([peer[4], peer[5]]),
+        )))
+        .collect()
+}
+
+fn read_u32(buf: &[u8], offset: usize) -> u32 {
+    u32::from_be_bytes([buf[offset], buf[offset + 1], buf[offset + 2], buf[offset + 3]])
+}
+
+fn transaction_id() -> u32 {
+    Utc::now().timestamp_subsec_nanos() ^ std::process::id()
+}
+
+fn generate_peer_id() -> [u8; 20] {
+    let mut peer_id = *b"-TA0100-000000000000";
+    let seed = format!("{:012}", (Utc::now().timestamp_nanos_opt().unwrap_or(0) as u64) % 1_000_000_000_000);
+    peer_id[8..].copy_from_slice(seed.as_bytes());
+    peer_id
+}
+
+fn decode_info_hash(hex: &str) -> Result<[u8; 20]> {
+    if hex.len() != 40 {
+        return Err(anyhow::anyhow!("Invalid info hash: {}", hex));
+    }
+    let mut hash = [0u8; 20];
+    for (i, byte) in hash.iter_mut().enumerate() {
+        *byte = u8::from_str_radix(&hex[i * 2..i * 2 + 2], 16)
+            .map_err(|_| anyhow::anyhow!("Invalid info hash: {}", hex))?;
+    }
+    Ok(hash)
+}
+
+fn percent_encode_bytes(bytes: &[u8]) -> String {
+    bytes.iter()
+        .map(|&b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => (b as char).to_string(),
+            _ => format!("%{:02X}", b),
+        })
+        .collect()
+}
+
+/// Human readable summary of a tracker's schedule for `status`.
+pub fn describe(tracker: &TrackerState) -> String {
+    if tracker.dropped {
+        return format!("dropped after {} failures ({})",
+                       tracker.failures,
+                       tracker.last_error.as_deref().unwrap_or("unknown error"));
+    }
+
+    let next = (tracker.next_announce - Utc::now()).num_seconds().max(0);
+    let next = if next >= 60 { format!("{}m", next / 60) } else { format!("{}s", next) };
+
+    match (&tracker.last_error, tracker.last_announce) {
+        (Some(error), _) => format!("failing x{} ({}), backing off for {}", tracker.failures, error, next),
+        (None, Some(_)) => match (tracker.seeders, tracker.leechers) {
+            (Some(seeders), Some(leechers)) => format!("ok, {} seeders / {} leechers, next announce in {}", seeders, leechers, next),
+            _ => format!("ok, next announce in {}", next),
+        },
+        (None, None) => "not contacted yet".to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const A: &str = "udp://tracker.opentrackr.org:1337/announce";
+    const B: &str = "udp://open.stealth.si:80/announce";
+    const C: &str = "http://tracker.example.org:6969/announce";
+    const HASH: &str = "08ada5a7a6183aae1e09d831df6748d566095a10";
+
+    fn config(drop_after_failures: u32) -> TrackerConfig {
+        TrackerConfig {
+            drop_after_failures,
+            min_interval_secs: 60,
+            retry_base_secs: 30,
+            max_backoff_secs: 3600,
+        }
+    }
+
+    fn schedule(drop_after_failures: u32) -> TrackerSchedule {
+        let tiers = vec![vec![A.to_string(), B.to_string()], vec![C.to_string()]];
+        TrackerSchedule::new(HASH, tiers, &[], config(drop_after_failures))
+    }
+
+    fn urls(schedule: &TrackerSchedule) -> Vec<Vec<&str>> {
+        schedule.tiers.iter().map(|tier| tier.iter().map(|t| t.url.as_str()).collect()).collect()
+    }
+
+    fn failed() -> Announced {
+        Announced::Failed("timed out".to_string())
+    }
+
+    #[test]
+    fn backoff_doubles_up_to_the_cap() {
+        let config = config(0);
+        assert_eq!(backoff(&config, 1), Duration::seconds(30));
+        assert_eq!(backoff(&config, 2), Duration::seconds(60));
+        assert_eq!(backoff(&config, 3), Duration::seconds(120));
+        assert_eq!(backoff(&config, 7), Duration::seconds(1920));
+        assert_eq!(backoff(&config, 8), Duration::seconds(3600));
+        assert_eq!(backoff(&config, 40), Duration::seconds(3600));
+    }
+
+    #[test]
+    fn failing_trackers_back_off_and_leave_the_list() {
+        let mut schedule = schedule(0);
+        let now = Utc::now();
+        assert!(!schedule.record(A, &failed(), now));
+        assert!(!schedule.record(A, &failed(), now));
+
+        let tracker = &schedule.tiers[0][0];
+        assert_eq!(tracker.failures, 2);
+        assert_eq!(tracker.next_announce, now + Duration::seconds(60));
+        assert_eq!(schedule.active(now), vec![vec![B.to_string()], vec![C.to_string()]]);
+        assert_eq!(schedule.inactive(now), vec![A.to_string()]);
+
+        // Back in once its backoff is over
+        let later = now + Duration::seconds(61);
+        assert_eq!(schedule.active(later), vec![vec![A.to_string(), B.to_string()], vec![C.to_string()]]);
+    }
+
+    #[test]
+    fn tier_keeps_the_tracker_due_back_soonest() {
+        let mut schedule = schedule(0);
+        let now = Utc::now();
+        schedule.record(C, &failed(), now);
+        schedule.record(C, &failed(), now);
+        assert_eq!(schedule.active(now), vec![vec![A.to_string(), B.to_string()], vec![C.to_string()]]);
+
+        schedule.record(A, &failed(), now);
+        schedule.record(A, &failed(), now);
+        schedule.record(B, &failed(), now);
+        assert_eq!(schedule.active(now)[0], vec![B.to_string()]);
+    }
+
+    #[test]
+    fn success_promotes_to_the_front_of_its_tier() {
+        let mut schedule = schedule(0);
+        let now = Utc::now();
+        schedule.record(A, &failed(), now);
+        schedule.record(B, &Announced::Ok(Some(1800)), now);
+        assert_eq!(urls(&schedule), vec![vec![B, A], vec![C]]);
+
+        let tracker = &schedule.tiers[0][0];
+        assert_eq!(tracker.failures, 0);
+        assert_eq!(tracker.next_announce, now + Duration::seconds(1800));
+
+        // Whichever answered last leads, with its failures cleared
+        schedule.record(A, &Announced::Ok(None), now);
+        assert_eq!(urls(&schedule), vec![vec![A, B], vec![C]]);
+        assert_eq!(schedule.tiers[0][0].failures, 0);
+        assert_eq!(schedule.tiers[0][0].interval_secs, DEFAULT_INTERVAL_SECS);
+    }
+
+    #[test]
+    fn intervals_have_a_floor() {
+        let mut schedule = schedule(0);
+        let now = Utc::now();
+        schedule.record(C, &Announced::Ok(Some(5)), now);
+        assert_eq!(schedule.tiers[1][0].interval_secs, 60);
+    }
+
+    #[test]
+    fn promotion_survives_a_reload() {
+        let mut schedule = schedule(0);
+        let now = Utc::now();
+        schedule.record(B, &Announced::Ok(Some(1800)), now);
+        let persisted: Vec<TrackerState> = schedule.tiers.iter().flatten().cloned().collect();
+
+        let tiers = vec![vec![A.to_string(), B.to_string()], vec![C.to_string()]];
+        let reloaded = TrackerSchedule::new(HASH, tiers, &persisted, config(0));
+        assert_eq!(urls(&reloaded), vec![vec![B, A], vec![C]]);
+    }
+
+    #[test]
+    fn drops_after_n_consecutive_failures() {
+        let mut schedule = schedule(3);
+        let now = Utc::now();
+        assert!(!schedule.record(A, &failed(), now));
+        assert!(!schedule.record(A, &failed(), now));
+        // A success starts the count over
+        schedule.record(A, &Announced::Ok(None), now);
+        assert!(!schedule.record(A, &failed(), now));
+        assert!(!schedule.record(A, &failed(), now));
+        assert!(schedule.record(A, &failed(), now));
+        assert!(schedule.tiers[0].iter().find(|t| t.url == A).unwrap().dropped);
+
+        // Dropped for good: later announces change nothing and it's never handed out
+        assert!(!schedule.record(A, &failed(), now));
+        assert!(!schedule.record(A, &Announced::Ok(None), now));
+        let much_later = now + Duration::days(1);
+        assert_eq!(schedule.active(much_later), vec![vec![B.to_string()], vec![C.to_string()]]);
+        assert_eq!(schedule.inactive(much_later), vec![A.to_string()]);
+    }
+
+    #[test]
+    fn never_drops_when_disabled() {
+        let mut schedule = schedule(0);
+        let now = Utc::now();
+        for _ in 0..50 {
+            assert!(!schedule.record(A, &failed(), now));
+        }
+        assert!(!schedule.tiers[0][0].dropped);
+    }
+
+    #[test]
+    fn dropped_trackers_stay_dropped_on_reload() {
+        let mut schedule = schedule(1);
+        schedule.record(C, &failed(), Utc::now());
+        let persisted: Vec<TrackerState> = schedule.tiers.iter().flatten().cloned().collect();
+
+        let tiers = vec![vec![A.to_string(), B.to_string()], vec![C.to_string()]];
+        let reloaded = TrackerSchedule::new(HASH, tiers, &persisted, config(1));
+        assert_eq!(reloaded.active(Utc::now()), vec![vec![A.to_string(), B.to_string()]]);
+    }
+
+    #[test]
+    fn announces_match_normalized_urls() {
+        let mut schedule = schedule(0);
+        schedule.record("HTTP://Tracker.Example.org:6969/announce", &failed(), Utc::now());
+        assert_eq!(schedule.tiers[1][0].failures, 1);
+    }
+
+    #[test]
+    fn reads_librqbit_announce_messages() {
+        assert_eq!(announce_outcome("sleeping for 1800s after calling tracker tracker.example.org"), Some(Announced::Ok(Some(1800))));
+        assert_eq!(announce_outcome("received announce response"), Some(Announced::Ok(None)));
+        assert_eq!(
+            announce_outcome("error calling the tracker http://tracker.example.org:6969/announce?info_hash=x: tracker responded with 502"),
+            Some(Announced::Failed("tracker responded with 502".to_string()))
+        );
+        assert_eq!(
+            announce_outcome("error reading announce response: timeout"),
+            Some(Announced::Failed("timeout".to_string()))
+        );
+        assert_eq!(announce_outcome("calling tracker over http"), None);
+    }
+
+    #[test]
+    fn magnet_trackers_and_stripping() {
+        let magnet = format!(
+            "magnet:?xt=urn:btih:{}&dn=Big+Buck+Bunny&tr={}&tr={}",
+            HASH, urlencoding::encode(A), urlencoding::encode(B)
+        );
+        assert_eq!(tiers_from_magnet(&magnet), vec![vec![A.to_string()], vec![B.to_string()]]);
+        assert_eq!(magnet_info_hash(&magnet).as_deref(), Some(HASH));
+
+        let stripped = strip_trackers(&magnet, &[A.to_string()]);
+        assert_eq!(tiers_from_magnet(&stripped), vec![vec![B.to_string()]]);
+        assert_eq!(magnet_display_name(&stripped).as_deref(), Some("Big Buck Bunny"));
+    }
+
+    #[test]
+    fn rewrites_torrent_trackers_without_changing_the_info_hash() {
+        let mut info = HashMap::new();
+        info.insert(b"name".to_vec(), Value::Bytes(b"file.bin".to_vec()));
+        info.insert(b"length".to_vec(), Value::Int(3));
+        info.insert(b"piece length".to_vec(), Value::Int(16384));
+        info.insert(b"pieces".to_vec(), Value::Bytes(Sha1::digest(b"abc").to_vec()));
+        let mut torrent = HashMap::new();
+        torrent.insert(b"announce".to_vec(), Value::Bytes(A.as_bytes().to_vec()));
+        torrent.insert(b"info".to_vec(), Value::Dict(info));
+        let bytes = serde_bencode::to_bytes(&Value::Dict(torrent)).unwrap();
+        assert_eq!(tiers_from_torrent_file(&bytes), vec![vec![A.to_string()]]);
+
+        let tiers = vec![vec![B.to_string()], vec![C.to_string()]];
+        let rewritten = with_trackers(&bytes, &tiers).unwrap();
+        assert_eq!(tiers_from_torrent_file(&rewritten), tiers);
+        assert_eq!(torrent_info_hash(&rewritten), torrent_info_hash(&bytes));
+
+        let bare = with_trackers(&bytes, &[]).unwrap();
+        assert!(tiers_from_torrent_file(&bare).is_empty());
+    }
+}
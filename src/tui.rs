@@ -0,0 +1,599 @@
+use anyhow::Result;
+use chrono::NaiveDate;
+use futures::StreamExt;
+use ratatui::{
+    crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers},
+    layout::{Constraint, Direction, Layout, Rect},
+    style::{Color, Modifier, Style},
+    text::{Line, Span},
+    widgets::{Block, Borders, List, ListItem, ListState, Paragraph, Wrap},
+    DefaultTerminal, Frame,
+};
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc;
+
+use crate::access::{self, Role};
+use crate::config::Config;
+use crate::downloader::{self, DownloadManager, DownloadProgress};
+use crate::models::{EvaluatedResult, SearchIntent};
+use crate::progress;
+use crate::smart_search::{SearchDiagnostics, SmartSearchEvent, SmartSearcher};
+use crate::state::StateDb;
+use crate::storage::format_bytes;
+
+/// How often download progress is written back to the state database.
+const STATE_SYNC_INTERVAL: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SortKey {
+    Score,
+    Seeders,
+    Size,
+}
+
+impl SortKey {
+    fn next(self) -> Self {
+        match self {
+            SortKey::Score => SortKey::Seeders,
+            SortKey::Seeders => SortKey::Size,
+            SortKey::Size => SortKey::Score,
+        }
+    }
+
+    fn label(self) -> &'static str {
+        match self {
+            SortKey::Score => "score",
+            SortKey::Seeders => "seeders",
+            SortKey::Size => "size",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Focus {
+    Input,
+    Results,
+}
+
+/// Work finished in a background task, applied on the next loop iteration.
+enum AppMessage {
+    Search(Result<SmartSearchEvent>),
+    DownloadStarted(String),
+    DownloadFailed(String, String),
+}
+
+struct App {
+    role: Role,
+    searcher: Arc<SmartSearcher>,
+    downloads: Arc<DownloadManager>,
+    config: Config,
+    output: Option<PathBuf>,
+    input: String,
+    /// Query and parsed intent of the latest search, for guest requests
+    query: String,
+    intent: Option<SearchIntent>,
+    diagnostics: Option<SearchDiagnostics>,
+    /// Title and release date when the latest search was for something not out yet
+    unreleased: Option<(String, NaiveDate)>,
+    focus: Focus,
+    results: Vec<EvaluatedResult>,
+    selected: ListState,
+    sort: SortKey,
+    status: String,
+    searching: bool,
+    evaluated: usize,
+    evaluation_total: usize,
+    tx: mpsc::UnboundedSender<AppMessage>,
+    rx: mpsc::UnboundedReceiver<AppMessage>,
+    /// Set after one `q` while downloads are still running
+    confirm_quit: bool,
+}
+
+/// Run the interactive terminal UI until the user quits.
+pub async fn run(
+    role: Role,
+    searcher: Arc<SmartSearcher>,
+    config: Config,
+    output: Option<PathBuf>,
+    initial_query: Option<String>,
+) -> Result<()> {
+    let default_dir = match &output {
+        Some(dir) => dir.clone(),
+        None => config.download.roots.first()
+            .map(|root| root.path.clone())
+            .unwrap_or_else(|| PathBuf::from("./downloads")),
+    };
+    let downloads = Arc::new(DownloadManager::new(config.clone(), default_dir).await?);
+    let state = StateDb::open()?;
+
+    let mut app = App::new(role, searcher, downloads, config, output);
+    if let Some(query) = initial_query {
+        app.input = query;
+        app.start_search();
+    }
+
+    let mut terminal = ratatui::init();
+    let result = app.run(&mut terminal, &state).await;
+    ratatui::restore();
+
+    // Leave the database with the final progress of anything left running
+    app.downloads.sync_state(&state)?;
+    result
+}
+
+impl App {
+    fn new(
+        role: Role,
+        searcher: Arc<SmartSearcher>,
+        downloads: Arc<DownloadManager>,
+        config: Config,
+        output: Option<PathBuf>,
+    ) -> Self {
+        let (tx, rx) = mpsc::unbounded_channel();
+        Self {
+            role,
+            searcher,
+            downloads,
+            config,
+            output,
+            input: String::new(),
+            query: String::new(),
+            intent: None,
+            unreleased: None,
+            diagnostics: None,
+            focus: Focus::Input,
+            results: Vec::new(),
+            selected: ListState::default(),
+            sort: SortKey::Score,
+            status: "Type a request and press Enter. Esc switches to results, q quits from there.".to_string(),
+            searching: false,
+            evaluated: 0,
+            evaluation_total: 0,
+            tx,
+            rx,
+            confirm_quit: false,
+        }
+    }
+
+    async fn run(&mut self, terminal: &mut DefaultTerminal, state: &StateDb) -> Result<()> {
+        let mut last_sync = Instant::now();
+
+        loop {
+            while let Ok(message) = self.rx.try_recv() {
+                self.handle_message(message);
+            }
+
+            let downloads = self.downloads.progress();
+            terminal.draw(|frame| self.draw(frame, &downloads))?;
+
+            // Polling keeps the download panel and search progress live without input
+            if event::poll(Duration::from_millis(200))? {
+                if let Event::Key(key) = event::read()? {
+                    if key.kind == KeyEventKind::Press && self.handle_key(key, &downloads) {
+                        return Ok(());
+                    }
+                }
+            }
+
+            if last_sync.elapsed() >= STATE_SYNC_INTERVAL {
+                self.downloads.sync_state(state)?;
+                self.downloads.retrack().await;
+                last_sync = Instant::now();
+            }
+        }
+    }
+
+    /// Returns true when the app should exit.
+    fn handle_key(&mut self, key: KeyEvent, downloads: &[DownloadProgress]) -> bool {
+        let code = key.code;
+        // Raw mode swallows SIGINT, so honour Ctrl-C directly
+        if key.modifiers.contains(KeyModifiers::CONTROL) && code == KeyCode::Char('c') {
+            return true;
+        }
+        if code != KeyCode::Char('q') {
+            self.confirm_quit = false;
+        }
+
+        match self.focus {
+            Focus::Input => match code {
+                KeyCode::Enter => self.start_search(),
+                KeyCode::Char(c) => self.input.push(c),
+                KeyCode::Backspace => {
+                    self.input.pop();
+                }
+                KeyCode::Tab | KeyCode::Down if !self.results.is_empty() => self.focus = Focus::Results,
+                KeyCode::Esc => self.focus = Focus::Results,
+                _ => {}
+            },
+            Focus::Results => match code {
+                KeyCode::Char('q') => {
+                    let active = downloads.iter().any(|d| !d.finished && d.error.is_none());
+                    if !active || self.confirm_quit {
+                        return true;
+                    }
+                    self.confirm_quit = true;
+                    self.status = "Downloads are still running. Press q again to quit.".to_string();
+                }
+                KeyCode::Char('/') | KeyCode::Char('i') | KeyCode::Tab => self.focus = Focus::Input,
+                KeyCode::Char('j') | KeyCode::Down => self.move_selection(1),
+                KeyCode::Char('k') | KeyCode::Up => self.move_selection(-1),
+                KeyCode::Char('s') => {
+                    self.sort = self.sort.next();
+                    self.sort_results();
+                    self.status = format!("Sorted by {}", self.sort.label());
+                }
+                KeyCode::Char('d') | KeyCode::Enter if self.role == Role::Guest => self.submit_request(),
+                KeyCode::Char('d') | KeyCode::Enter => self.start_download(),
+                KeyCode::Char('w') => self.add_to_watchlist(),
+                _ => {}
+            },
+        }
+
+        false
+    }
+
+    fn move_selection(&mut self, delta: isize) {
+        if self.results.is_empty() {
+            return;
+        }
+        let current = self.selected.selected().unwrap_or(0) as isize;
+        let next = (current + delta).clamp(0, self.results.len() as isize - 1);
+        self.selected.select(Some(next as usize));
+    }
+
+    fn start_search(&mut self) {
+        let query = self.input.trim().to_string();
+        if query.is_empty() || self.searching {
+            return;
+        }
+
+        self.searching = true;
+        self.query = query.clone();
+        self.intent = None;
+        self.diagnostics = None;
+        self.unreleased = None;
+        self.results.clear();
+        self.selected.select(None);
+        self.evaluated = 0;
+        self.evaluation_total = 0;
+        self.status = format!("Understanding \"{}\"...", query);
+
+        let mut stream = self.searcher.clone().search_stream(query);
+        let tx = self.tx.clone();
+        tokio::spawn(async move {
+            while let Some(event) = stream.next().await {
+                if tx.send(AppMessage::Search(event)).is_err() {
+                    break;
+                }
+            }
+        });
+    }
+
+    fn start_download(&mut self) {
+        if let Err(e) = self.role.require_manage("start downloads") {
+            self.status = e.to_string();
+            return;
+        }
+        let Some(result) = self.selected.selected().and_then(|i| self.results.get(i)) else {
+            return;
+        };
+
+        let title = result.torrent.title.clone();
+        let magnet = result.torrent.magnet_link.clone();
+        let downloads = self.downloads.clone();
+        let config = self.config.clone();
+        let output = self.output.clone();
+        let tx = self.tx.clone();
+        self.status = format!("Adding {}...", title);
+
+        tokio::spawn(async move {
+            let added = async {
+                let state = StateDb::open()?;
+                let output_dir = downloader::resolve_output_dir(output, &config, &state)?;
+                downloads.add(&title, &magnet, output_dir).await
+            }.await;
+
+            let message = match added {
+                Ok(()) => AppMessage::DownloadStarted(title),
+                Err(e) => AppMessage::DownloadFailed(title, e.to_string()),
+            };
+            let _ = tx.send(message);
+        });
+    }
+
+    /// Guests can't download, so file the selected result for an admin to approve.
+    fn submit_request(&mut self) {
+        let Some(result) = self.selected.selected().and_then(|i| self.results.get(i)) else {
+            return;
+        };
+
+        let submitted = StateDb::open().and_then(|state| {
+            state.add_request(&self.query, &access::current_user(), self.intent.as_ref(), Some(result))
+        });
+        self.status = match submitted {
+            Ok(id) => format!("Request #{} sent to an admin for approval", id),
+            Err(e) => format!("Failed to submit request: {}", e),
+        };
+    }
+
+    fn add_to_watchlist(&mut self) {
+        if let Err(e) = self.role.require_manage("edit the watchlist") {
+            self.status = e.to_string();
+            return;
+        }
+        let Some((title, release_date)) = &self.unreleased else {
+            return;
+        };
+
+        let added = StateDb::open().and_then(|state| state.add_watch(&self.query, title, *release_date));
+        self.status = match added {
+            Ok(id) => format!("Watching #{}: {} comes out {}", id, title, release_date),
+            Err(e) => format!("Failed to add to watchlist: {}", e),
+        };
+        self.unreleased = None;
+    }
+
+    fn handle_message(&mut self, message: AppMessage) {
+        match message {
+            AppMessage::Search(Ok(event)) => match event {
+                SmartSearchEvent::IntentParsed(intent) => {
+                    self.status = format!("Searching sources for {}...", intent.title);
+                    self.intent = Some(intent);
+                }
+                SmartSearchEvent::Refined { intent, .. } => {
+                    self.status = format!("Refined search for {}...", intent.title);
+                }
+                SmartSearchEvent::SourceResults { source, query, results } => {
+                    self.status = format!("{}: {} results for \"{}\"", source, results.len(), query);
+                }
+                SmartSearchEvent::ThresholdAdjusted { to, .. } => {
+                    self.status = format!("Confidence threshold adjusted to {:.0}%", to * 100.0);
+                }
+                SmartSearchEvent::MetadataFound(canonical) => {
+                    self.status = format!("Checking results against {} ({})", canonical.source, canonical.title);
+                }
+                SmartSearchEvent::LatestResolved { intent, .. } => {
+                    self.status = format!("Looking for the latest episode of {}...", intent.title);
+                    self.intent = Some(intent);
+                }
+                SmartSearchEvent::NotYetReleased { title, release_date } => {
+                    self.unreleased = Some((title, release_date));
+                }
+                SmartSearchEvent::Degraded(degradation) => {
+                    self.status = degradation.describe();
+                }
+                SmartSearchEvent::EvaluationStarted { total } => {
+                    self.evaluation_total = total;
+                    self.status = format!("Evaluating {} results...", total);
+                }
+                SmartSearchEvent::EvaluationChunk(chunk) => {
+                    self.evaluated += chunk.len();
+                    self.status = format!("Evaluated {}/{} results", self.evaluated, self.evaluation_total);
+                }
+                SmartSearchEvent::Diagnostics(diagnostics) => {
+                    self.diagnostics = Some(diagnostics);
+                }
+                SmartSearchEvent::EpisodesPlanned(plan) => {
+                    self.status = format!("No season pack; {}/{} episodes found individually",
+                                          plan.matched().count(), plan.episodes.len());
+                }
+                SmartSearchEvent::Finished(results) => {
+                    self.searching = false;
+                    self.status = match (&self.unreleased, &self.diagnostics, results.is_empty()) {
+                        (Some((title, _)), Some(diagnostics), _) if self.role == Role::Admin => {
+                            format!("{} is {}; press w to grab it when it comes out", title, diagnostics.summary())
+                        }
+                        (_, Some(diagnostics), true) => format!("No results: {}", diagnostics.summary()),
+                        _ => format!("{} results", results.len()),
+                    };
+                    self.results = results;
+                    self.sort_results();
+                    if !self.results.is_empty() || self.unreleased.is_some() {
+                        self.selected.select(Some(0));
+                        self.focus = Focus::Results;
+                    }
+                }
+            },
+            AppMessage::Search(Err(e)) => {
+                self.searching = false;
+                self.status = format!("Search failed: {}", e);
+            }
+            AppMessage::DownloadStarted(title) => {
+                self.status = format!("Downloading {}", title);
+            }
+            AppMessage::DownloadFailed(title, error) => {
+                self.status = format!("Failed to add {}: {}", title, error);
+            }
+        }
+    }
+
+    fn sort_results(&mut self) {
+        match self.sort {
+            SortKey::Score => self.results.sort_by(|a, b| {
+                b.relevance_score.partial_cmp(&a.relevance_score).unwrap_or(std::cmp::Ordering::Equal)
+            }),
+            SortKey::Seeders => self.results.sort_by_key(|r| std::cmp::Reverse(r.torrent.seeders.unwrap_or(0))),
+            SortKey::Size => self.results.sort_by_key(|r| {
+                std::cmp::Reverse(r.torrent.size_bytes().unwrap_or(0))
+            }),
+        }
+    }
+
+    fn draw(&mut self, frame: &mut Frame, downloads: &[DownloadProgress]) {
+        let rows = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([
+                Constraint::Length(3),
+                Constraint::Min(8),
+                Constraint::Length(downloads.len().clamp(1, 6) as u16 + 2),
+                Constraint::Length(1),
+            ])
+            .split(frame.area());
+
+        let middle = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints([Constraint::Percentage(55), Constraint::Percentage(45)])
+            .split(rows[1]);
+
+        self.draw_input(frame, rows[0]);
+        self.draw_results(frame, middle[0]);
+        self.draw_detail(frame, middle[1]);
+        draw_downloads(frame, rows[2], downloads);
+
+        let help = match (self.focus, self.role) {
+            (Focus::Input, _) => "Enter search · Tab/Esc results",
+            (Focus::Results, Role::Admin) => "j/k move · s sort · d download · / search · q quit",
+            (Focus::Results, Role::Guest) => "j/k move · s sort · d request · / search · q quit",
+        };
+        let mut status = Vec::new();
+        if self.role == Role::Guest {
+            status.push(Span::styled(" GUEST ", Style::default().fg(Color::Black).bg(Color::Magenta)));
+        }
+        status.extend([
+            Span::styled(format!(" {} ", self.status), Style::default().fg(Color::Yellow)),
+            Span::styled(format!("  {}", help), Style::default().fg(Color::DarkGray)),
+        ]);
+        frame.render_widget(Paragraph::new(Line::from(status)), rows[3]);
+    }
+
+    fn draw_input(&self, frame: &mut Frame, area: Rect) {
+        let input = Paragraph::new(self.input.as_str())
+            .block(pane("Search", self.focus == Focus::Input));
+        frame.render_widget(input, area);
+
+        if self.focus == Focus::Input {
+            frame.set_cursor_position((area.x + 1 + self.input.chars().count() as u16, area.y + 1));
+        }
+    }
+
+    fn draw_results(&mut self, frame: &mut Frame, area: Rect) {
+        let items: Vec<ListItem> = self.results.iter()
+            .map(|result| {
+                ListItem::new(Line::from(vec![
+                    Span::styled(
+                        format!("{:>3}% ", (result.relevance_score * 100.0) as u8),
+                        Style::default().fg(score_color(result.relevance_score)),
+                    ),
+                    Span::raw(format!("{:>9} ", result.torrent.size.as_deref().unwrap_or("?"))),
+                    Span::styled(
+                        format!("{:>5}S ", result.torrent.seeders.unwrap_or(0)),
+                        Style::default().fg(Color::Green),
+                    ),
+                    Span::raw(result.torrent.title.clone()),
+                ]))
+            })
+            .collect();
+
+        let title = format!("Results ({}, by {})", self.results.len(), self.sort.label());
+        let list = List::new(items)
+            .block(pane(&title, self.focus == Focus::Results))
+            .highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+        frame.render_stateful_widget(list, area, &mut self.selected);
+    }
+
+    fn draw_detail(&self, frame: &mut Frame, area: Rect) {
+        let Some(result) = self.selected.selected().and_then(|i| self.results.get(i)) else {
+            frame.render_widget(Paragraph::new("").block(pane("Details", false)), area);
+            return;
+        };
+
+        let torrent = &result.torrent;
+        let mut lines = vec![
+            Line::styled(torrent.title.clone(), Style::default().add_modifier(Modifier::BOLD)),
+            Line::raw(""),
+            Line::raw(format!("Size:      {}", torrent.size.as_deref().unwrap_or("Unknown"))),
+            Line::raw(format!("Peers:     {} seeders / {} leechers",
+                              torrent.seeders.unwrap_or(0), torrent.leechers.unwrap_or(0))),
+            Line::raw(format!("Uploaded:  {}", torrent.uploaded.as_deref().unwrap_or("Unknown"))),
+            Line::raw(format!("Relevance: {:.0}%  Confidence: {:.0}%",
+                              result.relevance_score * 100.0, result.confidence * 100.0)),
+            Line::raw(format!("Quality:   {:.0}%  Completeness: {:.0}%",
+                              result.quality_score * 100.0, result.completeness_score * 100.0)),
+            Line::raw(""),
+        ];
+
+        for provenance in &torrent.found_by {
+            lines.push(Line::styled(format!("Found by {}", provenance.describe()), Style::default().fg(Color::DarkGray)));
+        }
+        for reason in &result.match_reasons {
+            lines.push(Line::styled(format!("✓ {}", reason), Style::default().fg(Color::Green)));
+        }
+        for warning in &result.warnings {
+            lines.push(Line::styled(format!("⚠ {}", warning), Style::default().fg(Color::Red)));
+        }
+
+        let detail = Paragraph::new(lines)
+            .block(pane("Details", false))
+            .wrap(Wrap { trim: false });
+        frame.render_widget(detail, area);
+    }
+}
+
+fn draw_downloads(frame: &mut Frame, area: Rect, downloads: &[DownloadProgress]) {
+    let items: Vec<ListItem> = if downloads.is_empty() {
+        vec![ListItem::new(Line::styled("No active downloads", Style::default().fg(Color::DarkGray)))]
+    } else {
+        downloads.iter().map(|d| ListItem::new(download_line(d))).collect()
+    };
+
+    frame.render_widget(List::new(items).block(pane("Downloads", false)), area);
+}
+
+fn download_line(download: &DownloadProgress) -> Line<'static> {
+    if let Some(error) = &download.error {
+        return Line::styled(format!("✗ {} — {}", download.name, error), Style::default().fg(Color::Red));
+    }
+    if download.pending {
+        return Line::styled(format!("… {} — fetching metadata", download.name), Style::default().fg(Color::DarkGray));
+    }
+
+    let fraction = if download.total_bytes > 0 {
+        download.progress_bytes as f64 / download.total_bytes as f64
+    } else {
+        0.0
+    };
+    let filled = (fraction * 20.0).round() as usize;
+    let bar = format!("[{}{}]", "█".repeat(filled), "░".repeat(20 - filled.min(20)));
+
+    let detail = if download.finished {
+        format!("done, {}", format_bytes(download.total_bytes))
+    } else {
+        let eta = download.eta_secs
+            .map(|secs| format!(" ETA {}", progress::format_duration(Duration::from_secs(secs))))
+            .unwrap_or_default();
+        format!("{:.1}% of {} ↓{:.1} MiB/s ↑{:.1} MiB/s {} peers{}",
+                fraction * 100.0, format_bytes(download.total_bytes),
+                download.download_mbps, download.upload_mbps, download.peers, eta)
+    };
+
+    let color = if download.finished { Color::Green } else { Color::Cyan };
+    Line::from(vec![
+        Span::styled(bar, Style::default().fg(color)),
+        Span::raw(format!(" {} ", detail)),
+        Span::raw(download.name.clone()),
+    ])
+}
+
+fn pane(title: &str, focused: bool) -> Block<'static> {
+    let style = if focused {
+        Style::default().fg(Color::Cyan)
+    } else {
+        Style::default()
+    };
+    Block::default()
+        .borders(Borders::ALL)
+        .border_style(style)
+        .title(format!(" {} ", title))
+}
+
+fn score_color(score: f32) -> Color {
+    if score >= 0.8 {
+        Color::Green
+    } else if score >= 0.5 {
+        Color::Yellow
+    } else {
+        Color::Red
+    }
+}
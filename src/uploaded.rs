@@ -0,0 +1,185 @@
+use chrono::{DateTime, Datelike, Duration, Local, NaiveDate, NaiveDateTime};
+use lazy_static::lazy_static;
+use regex::Regex;
+
+lazy_static! {
+    /// ThePirateBay's "5 mins ago" for the last hour
+    static ref AGO_RE: Regex = Regex::new(r"(?i)^(\d+)\s*(min|mins|minutes?|hours?|days?)\s+ago$").unwrap();
+    /// "30d", "12h", "2 weeks", "6mo", "1y"
+    static ref AGE_RE: Regex = Regex::new(r"(?i)^(\d+)\s*(h|hours?|d|days?|w|weeks?|mo|months?|y|years?)$").unwrap();
+}
+
+/// Upload dates as the sources print them, in local time: ISO dates from
+/// YTS and the apibay API, RFC 2822 and RFC 3339 dates from feeds, and
+/// ThePirateBay's "MM-DD YYYY" for older uploads and "MM-DD HH:MM",
+/// "Today HH:MM", "Y-day HH:MM" or "5 mins ago" for recent ones.
+pub fn parse(uploaded: &str) -> Option<NaiveDateTime> {
+    let text = uploaded.replace('\u{a0}', " ");
+    let text = text.trim();
+    let now = Local::now().naive_local();
+    let today = now.date();
+
+    for format in ["%Y-%m-%d %H:%M:%S", "%Y-%m-%d %H:%M"] {
+        if let Ok(parsed) = NaiveDateTime::parse_from_str(text, format) {
+            return Some(parsed);
+        }
+    }
+    if let Ok(date) = NaiveDate::parse_from_str(text, "%Y-%m-%d") {
+        return date.and_hms_opt(0, 0, 0);
+    }
+    if let Ok(parsed) = DateTime::parse_from_rfc3339(text).or_else(|_| DateTime::parse_from_rfc2822(text)) {
+        return Some(parsed.with_timezone(&Local).naive_local());
+    }
+    if let Some(caps) = AGO_RE.captures(text) {
+        let amount: i64 = caps[1].parse().ok()?;
+        let unit = caps[2].to_lowercase();
+        let ago = match unit.chars().next()? {
+            'm' => Duration::try_minutes(amount),
+            'h' => Duration::try_hours(amount),
+            _ => Duration::try_days(amount),
+        }?;
+        return now.checked_sub_signed(ago);
+    }
+
+    let (day, rest) = text.split_once(' ')?;
+    let date = match day {
+        "Today" => today,
+        "Y-day" => today - Duration::days(1),
+        _ => {
+            let (month, day) = day.split_once('-')?;
+            let (month, day) = (month.parse().ok()?, day.parse().ok()?);
+            if !rest.contains(':') {
+                return NaiveDate::from_ymd_opt(rest.parse().ok()?, month, day)?.and_hms_opt(0, 0, 0);
+            }
+            // No year means this year, unless that would be in the future
+            let date = NaiveDate::from_ymd_opt(today.year(), month, day)?;
+            if date > today {
+                NaiveDate::from_ymd_opt(today.year() - 1, month, day)?
+            } else {
+                date
+            }
+        }
+    };
+    let (hour, minute) = rest.split_once(':')?;
+    date.and_hms_opt(hour.parse().ok()?, minute.parse().ok()?, 0)
+}
+
+/// How long ago something was uploaded.
+pub fn age(uploaded: NaiveDateTime) -> Duration {
+    Local::now().naive_local() - uploaded
+}
+
+/// "3 hours ago", "12 days ago" or "2 years ago".
+pub fn describe_age(uploaded: NaiveDateTime) -> String {
+    let age = age(uploaded);
+    let (amount, unit) = if age.num_days() >= 730 {
+        (age.num_days() / 365, "years")
+    } else if age.num_days() >= 60 {
+        (age.num_days() / 30, "months")
+    } else if age.num_days() >= 2 {
+        (age.num_days(), "days")
+    } else {
+        (age.num_hours().max(0), "hours")
+    };
+    format!("{} {} ago", amount, unit)
+}
+
+/// Parse `--uploaded-within` values like "30d", "12h", "2w", "6mo" or "1y".
+pub fn parse_age_arg(value: &str) -> Result<Duration, String> {
+    let invalid = || format!("invalid age \"{}\" (try 12h, 30d, 2w, 6mo or 1y)", value);
+    let caps = AGE_RE.captures(value.trim()).ok_or_else(invalid)?;
+    let amount: i64 = caps[1].parse().map_err(|_| invalid())?;
+    let unit = caps[2].to_lowercase();
+    match unit.as_str() {
+        u if u.starts_with('h') => Duration::try_hours(amount),
+        u if u.starts_with('d') => Duration::try_days(amount),
+        u if u.starts_with('w') => Duration::try_weeks(amount),
+        u if u.starts_with("mo") => amount.checked_mul(30).and_then(Duration::try_days),
+        _ => amount.checked_mul(365).and_then(Duration::try_days),
+    }
+    .ok_or_else(invalid)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(year: i32, month: u32, day: u32, hour: u32, minute: u32, second: u32) -> Option<NaiveDateTime> {
+        NaiveDate::from_ymd_opt(year, month, day)?.and_hms_opt(hour, minute, second)
+    }
+
+    #[test]
+    fn yts_and_apibay_dates() {
+        assert_eq!(parse("2015-11-01 03:31:48"), at(2015, 11, 1, 3, 31, 48));
+        assert_eq!(parse("2024-02-29 23:05"), at(2024, 2, 29, 23, 5, 0));
+        assert_eq!(parse("2019-03-14"), at(2019, 3, 14, 0, 0, 0));
+    }
+
+    #[test]
+    fn pirate_bay_older_uploads() {
+        assert_eq!(parse("03-14\u{a0}2019"), at(2019, 3, 14, 0, 0, 0));
+        assert_eq!(parse("12-31 2008"), at(2008, 12, 31, 0, 0, 0));
+    }
+
+    #[test]
+    fn pirate_bay_recent_uploads() {
+        let today = Local::now().date_naive();
+        assert_eq!(parse("Today\u{a0}08:15"), today.and_hms_opt(8, 15, 0));
+        assert_eq!(parse("Y-day\u{a0}14:32"), (today - Duration::days(1)).and_hms_opt(14, 32, 0));
+        // New Year's Day is never in the future, so it's this year's
+        assert_eq!(parse("01-01 12:00"), at(today.year(), 1, 1, 12, 0, 0));
+
+        let parsed = parse("5 mins ago").unwrap();
+        let expected = Local::now().naive_local() - Duration::minutes(5);
+        assert!((parsed - expected).num_seconds().abs() < 60);
+    }
+
+    #[test]
+    fn pirate_bay_dates_without_a_year_are_never_in_the_future() {
+        let tomorrow = Local::now().date_naive() + Duration::days(1);
+        let parsed = parse(&tomorrow.format("%m-%d 09:30").to_string()).unwrap();
+        assert!(parsed < Local::now().naive_local());
+        assert_eq!((parsed.month(), parsed.day()), (tomorrow.month(), tomorrow.day()));
+    }
+
+    #[test]
+    fn feed_dates() {
+        let parsed = parse("2024-05-01T12:00:00Z").unwrap();
+        assert_eq!(parsed, DateTime::parse_from_rfc3339("2024-05-01T12:00:00Z").unwrap().with_timezone(&Local).naive_local());
+        assert!(parse("Wed, 01 May 2024 12:00:00 +0000").is_some());
+    }
+
+    #[test]
+    fn out_of_range_dates() {
+        assert_eq!(parse("99999999999999 days ago"), None);
+        assert_eq!(parse("9223372036854775807 mins ago"), None);
+        assert_eq!(parse("02-30 2020"), None);
+        assert_eq!(parse("13-01 12:00"), None);
+        assert_eq!(parse("Y-day 25:61"), None);
+        assert_eq!(parse("03-14 99999999999"), None);
+    }
+
+    #[test]
+    fn not_dates() {
+        assert_eq!(parse(""), None);
+        assert_eq!(parse("Unknown"), None);
+        assert_eq!(parse("1080p"), None);
+    }
+
+    #[test]
+    fn age_args() {
+        assert_eq!(parse_age_arg("12h"), Ok(Duration::hours(12)));
+        assert_eq!(parse_age_arg("30d"), Ok(Duration::days(30)));
+        assert_eq!(parse_age_arg("2 weeks"), Ok(Duration::weeks(2)));
+        assert_eq!(parse_age_arg("6mo"), Ok(Duration::days(180)));
+        assert_eq!(parse_age_arg("1y"), Ok(Duration::days(365)));
+        assert!(parse_age_arg("soon").is_err());
+    }
+
+    #[test]
+    fn out_of_range_age_args() {
+        assert!(parse_age_arg("99999999999999y").is_err());
+        assert!(parse_age_arg("9999999999999999999h").is_err());
+        assert!(parse_age_arg("999999999999999w").is_err());
+    }
+}
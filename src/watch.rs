@@ -0,0 +1,98 @@
+use anyhow::Result;
+use tracing::warn;
+
+use crate::models::EvaluatedResult;
+use crate::notifications::{DownloadEvent, Notifier};
+use crate::safety;
+use crate::smart_search::SearchService;
+use crate::state::{StateDb, WatchRule};
+use crate::trackers;
+
+/// What one run of a watch rule turned up.
+pub struct WatchCheck {
+    /// Results this rule had never reported, best first
+    pub new: Vec<EvaluatedResult>,
+    /// The best new result, when it clears the rule's auto-download threshold
+    /// and has no red flags
+    pub download: Option<EvaluatedResult>,
+}
+
+/// Run `rule`'s search once and notify about results it hasn't reported
+/// before. The rule counts as checked even when the search fails, so a broken
+/// source is retried on the rule's interval rather than straight away.
+pub async fn check(rule: &WatchRule, searcher: &impl SearchService, notifier: &Notifier) -> Result<WatchCheck> {
+    let results = searcher.search(&rule.query).await;
+
+    let state = StateDb::open()?;
+    state.mark_watch_checked(rule.id)?;
+    let mut new = Vec::new();
+    for result in results? {
+        // Magnets for the same torrent differ in trackers and names
+        let key = trackers::magnet_info_hash(&result.torrent.magnet_link)
+            .unwrap_or_else(|| result.torrent.magnet_link.clone());
+        if state.mark_watch_seen(rule.id, &key)? {
+            new.push(result);
+        }
+    }
+    drop(state);
+
+    if let Some(best) = new.first() {
+        notifier.send(DownloadEvent::WatchMatched {
+            query: rule.query.clone(),
+            name: best.torrent.title.clone(),
+            relevance_score: best.relevance_score,
+            others: new.len() - 1,
+        }).await;
+    }
+
+    let download = rule.auto_download_above
+        .and_then(|min| new.first().filter(|best| best.relevance_score >= min))
+        .filter(|best| {
+            let safe = safety::is_safe(&best.torrent);
+            if !safe {
+                warn!("Watch #{}: not downloading {}, it has red flags", rule.id, best.torrent.title);
+            }
+            safe
+        })
+        .cloned();
+    Ok(WatchCheck { new, download })
+}
+
+/// "3 new results, best 82%: <title>", for logs and the digest.
+pub fn describe_new(new: &[EvaluatedResult]) -> String {
+    match new.first() {
+        Some(best) => format!("{} new result(s), best {:.0}%: {}", new.len(), best.relevance_score * 100.0, best.torrent.title),
+        None => "nothing new".to_string(),
+    }
+}
+
+/// Parse an interval such as "30m", "6h", "2d" or "1w" into minutes.
+pub fn parse_interval(value: &str) -> Result<u64, String> {
+    let value = value.trim();
+    let split = value.find(|c: char| !c.is_ascii_digit()).unwrap_or(value.len());
+    let (amount, unit) = value.split_at(split);
+    let invalid = || format!("invalid interval \"{}\" (try 30m, 6h or 1d)", value);
+    let amount: u64 = amount.parse().map_err(|_| invalid())?;
+    let unit_minutes = match unit.trim() {
+        "m" | "min" | "mins" => 1,
+        "" | "h" | "hr" | "hrs" => 60,
+        "d" | "day" | "days" => 60 * 24,
+        "w" | "week" | "weeks" => 60 * 24 * 7,
+        _ => return Err(invalid()),
+    };
+    let minutes = amount.checked_mul(unit_minutes).ok_or_else(invalid)?;
+    if minutes == 0 {
+        return Err("the interval must be at least a minute".to_string());
+    }
+    Ok(minutes)
+}
+
+/// The largest whole unit that fits, as accepted by [`parse_interval`].
+pub fn format_interval(minutes: u64) -> String {
+    match minutes {
+        m if m % (60 * 24 * 7) == 0 => format!("{}w", m / (60 * 24 * 7)),
+        m if m % (60 * 24) == 0 => format!("{}d", m / (60 * 24)),
+        m if m % 60 == 0 => format!("{}h", m / 60),
+        m => format!("{}m", m),
+    }
+}
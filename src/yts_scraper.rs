@@ -1,9 +1,106 @@
 use anyhow::Result;
-use reqwest;
+use clap::Args;
+use futures::StreamExt;
 use serde::Deserialize;
-use tracing::info;
+use std::collections::HashSet;
+use tracing::{info, warn};
 
+use crate::config::{Config, YtsConfig};
+use crate::models::ContentType;
 use crate::pirate_bay_scraper::TorrentResult;
+use crate::scraper::SearchHints;
+
+/// The most the API returns per page.
+const PAGE_SIZE: u32 = 50;
+/// Qualities the API can filter on.
+const QUALITIES: &[&str] = &["480p", "720p", "1080p", "1080p.x265", "2160p", "3D"];
+/// Genres the API can filter on, which follow IMDb's names.
+const GENRES: &[&str] = &[
+    "Action", "Adventure", "Animation", "Biography", "Comedy", "Crime", "Documentary", "Drama",
+    "Family", "Fantasy", "Film-Noir", "History", "Horror", "Music", "Musical", "Mystery",
+    "Romance", "Sci-Fi", "Sport", "Thriller", "War", "Western",
+];
+
+/// Filters YTS applies itself, so results that don't fit never come back.
+#[derive(Debug, Clone, Default, Args)]
+pub struct YtsFilter {
+    /// Only this quality: 480p, 720p, 1080p, 1080p.x265, 2160p or 3D
+    #[arg(long, value_parser = check_quality)]
+    pub quality: Option<String>,
+
+    /// Only movies rated at least this on IMDb (0-9)
+    #[arg(long = "min-rating", value_parser = clap::value_parser!(u8).range(0..=9))]
+    pub minimum_rating: Option<u8>,
+
+    /// Only this genre, e.g. Comedy or Sci-Fi
+    #[arg(long, value_parser = check_genre)]
+    pub genre: Option<String>,
+}
+
+impl YtsFilter {
+    /// The hints YTS can apply: the preferred quality, a genre it knows and
+    /// the minimum rating. Only movies are on YTS, so hints for anything
+    /// else get no filter.
+    pub fn from_hints(hints: &SearchHints) -> Self {
+        if hints.content_type.as_ref().is_some_and(|t| !matches!(t, ContentType::Movie)) {
+            return Self::default();
+        }
+        let preferences = hints.qualities.join(" ").to_lowercase();
+        let quality = hints.qualities.iter()
+            .map(|p| p.to_lowercase())
+            .find_map(|p| match p.as_str() {
+                p if p.contains("2160") || p.contains("4k") || p.contains("uhd") => Some("2160p"),
+                p if p.contains("1080") && (preferences.contains("x265") || preferences.contains("hevc")) => Some("1080p.x265"),
+                p if p.contains("1080") => Some("1080p"),
+                p if p.contains("720") => Some("720p"),
+                p if p.contains("480") => Some("480p"),
+                "3d" => Some("3D"),
+                _ => None,
+            })
+            .map(str::to_string);
+        let genre = hints.genres.iter()
+            .find_map(|g| yts_genre(g))
+            .map(str::to_string);
+        let minimum_rating = hints.min_rating.map(|r| r.min(9));
+        Self { quality, minimum_rating, genre }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.quality.is_none() && self.minimum_rating.is_none() && self.genre.is_none()
+    }
+
+    /// "1080p, Sci-Fi, rated 7+", for logs and cache keys.
+    pub fn describe(&self) -> String {
+        let mut parts: Vec<String> = Vec::new();
+        parts.extend(self.quality.clone());
+        parts.extend(self.genre.clone());
+        parts.extend(self.minimum_rating.map(|r| format!("rated {}+", r)));
+        parts.join(", ")
+    }
+}
+
+fn check_quality(quality: &str) -> Result<String, String> {
+    QUALITIES.iter()
+        .find(|q| q.eq_ignore_ascii_case(quality))
+        .map(|q| q.to_string())
+        .ok_or_else(|| format!("use one of: {}", QUALITIES.join(", ")))
+}
+
+fn check_genre(genre: &str) -> Result<String, String> {
+    yts_genre(genre)
+        .map(str::to_string)
+        .ok_or_else(|| format!("use one of: {}", GENRES.join(", ")))
+}
+
+/// YTS's name for a genre, including TMDB's names for the same thing.
+fn yts_genre(genre: &str) -> Option<&'static str> {
+    let genre = match genre.to_lowercase().as_str() {
+        "science fiction" | "sci fi" | "scifi" => "sci-fi",
+        "film noir" => "film-noir",
+        other => return GENRES.iter().find(|g| g.eq_ignore_ascii_case(other)).copied(),
+    };
+    GENRES.iter().find(|g| g.eq_ignore_ascii_case(genre)).copied()
+}
 
 #[derive(Debug, Deserialize)]
 struct YtsResponse {
@@ -13,16 +110,17 @@ struct YtsResponse {
 
 #[derive(Debug, Deserialize)]
 struct YtsData {
+    /// Matches across all pages
+    #[serde(default)]
+    movie_count: u32,
     movies: Option<Vec<YtsMovie>>,
 }
 
 #[derive(Debug, Deserialize)]
 struct YtsMovie {
+    id: u64,
     title: String,
     year: u32,
-    rating: f32,
-    runtime: Option<u32>,
-    genres: Option<Vec<String>>,
     torrents: Option<Vec<YtsTorrent>>,
     date_uploaded: Option<String>,
 }
@@ -31,8 +129,6 @@ struct YtsMovie {
 struct YtsTorrent {
     hash: String,
     quality: String,
-    #[serde(rename = "type")]
-    torrent_type: Option<String>,
     seeds: Option<u32>,
     peers: Option<u32>,
     size: Option<String>,
@@ -42,10 +138,23 @@ struct YtsTorrent {
 pub struct YtsScraper {
     client: reqwest::Client,
     base_url: String,
+    config: YtsConfig,
+}
+
+impl Default for YtsScraper {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl YtsScraper {
     pub fn new() -> Self {
+        let config = Config::load()
+            .map(|config| config.yts)
+            .unwrap_or_else(|e| {
+                warn!("Using default YTS settings: {}", e);
+                YtsConfig::default()
+            });
         let client = reqwest::Client::builder()
             .timeout(std::time::Duration::from_secs(30))
             .user_agent("Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36")
@@ -55,21 +164,68 @@ impl YtsScraper {
         Self {
             client,
             base_url: "https://yts.mx/api/v2".to_string(),
+            config,
         }
     }
     
     pub async fn search(&self, query: &str) -> Result<Vec<TorrentResult>> {
+        self.search_filtered(query, &YtsFilter::default()).await
+    }
+    
+    /// Search every page of matches, up to `max_results` movies. The first
+    /// page says how many there are; the rest are fetched concurrently.
+    pub async fn search_filtered(&self, query: &str, filter: &YtsFilter) -> Result<Vec<TorrentResult>> {
+        let (movie_count, mut movies) = self.fetch_page(query, filter, 1).await?;
+        let wanted = movie_count.min(self.config.max_results.max(1));
+        let pages = wanted.div_ceil(PAGE_SIZE);
+        
+        if pages > 1 {
+            info!("YTS has {} movies for \"{}\"; fetching {} pages", movie_count, query, pages);
+            let mut rest = futures::stream::iter(2..=pages)
+                .map(|page| self.fetch_page(query, filter, page))
+                .buffered(self.config.max_concurrent_pages.max(1));
+            while let Some(page) = rest.next().await {
+                match page {
+                    Ok((_, page_movies)) => movies.extend(page_movies),
+                    // A missing page costs some results, not the whole search
+                    Err(e) => warn!("YTS page fetch failed: {}", e),
+                }
+            }
+        }
+        
+        // New uploads shift movies between pages while paging
+        let mut seen = HashSet::new();
+        movies.retain(|movie| seen.insert(movie.id));
+        movies.truncate(wanted as usize);
+        
+        // The quality filter picks movies that have it; drop their other torrents
+        let mut results = to_results(movies);
+        if let Some(quality) = &filter.quality {
+            let base = quality.split('.').next().unwrap_or(quality);
+            results.retain(|r| r.title.contains(&format!("[{}", base)));
+        }
+        info!("Found {} YTS results", results.len());
+        Ok(results)
+    }
+    
+    async fn fetch_page(&self, query: &str, filter: &YtsFilter, page: u32) -> Result<(u32, Vec<YtsMovie>)> {
         let search_url = format!("{}/list_movies.json", self.base_url);
-        info!("Searching YTS: {}", search_url);
+        info!("Searching YTS: {} (page {})", search_url, page);
+        
+        let mut params = vec![
+            ("query_term", query.to_string()),
+            ("limit", PAGE_SIZE.to_string()),
+            ("page", page.to_string()),
+            ("sort_by", "date_added".to_string()),
+            ("order_by", "desc".to_string()),
+        ];
+        params.extend(filter.quality.clone().map(|q| ("quality", q)));
+        params.extend(filter.minimum_rating.map(|r| ("minimum_rating", r.to_string())));
+        params.extend(filter.genre.clone().map(|g| ("genre", g)));
         
         let response = self.client
             .get(&search_url)
-            .query(&[
-                ("query_term", query),
-                ("limit", "50"),
-                ("sort_by", "date_added"),
-                ("order_by", "desc"),
-            ])
+            .query(&params)
             .send()
             .await?;
         
@@ -81,50 +237,58 @@ impl YtsScraper {
         
         // Debug: Save JSON to file for inspection
         if std::env::var("DEBUG_JSON").is_ok() {
-            std::fs::write("debug_yts_results.json", &json_content)?;
-            info!("Saved JSON to debug_yts_results.json");
+            let path = format!("debug_yts_results_{}.json", page);
+            std::fs::write(&path, &json_content)?;
+            info!("Saved JSON to {}", path);
         }
         
-        self.parse_api_response(&json_content)
+        parse_api_response(&json_content)
     }
+}
+
+/// The total match count and this page's movies.
+fn parse_api_response(json: &str) -> Result<(u32, Vec<YtsMovie>)> {
+    let response: YtsResponse = serde_json::from_str(json)?;
     
-    fn parse_api_response(&self, json: &str) -> Result<Vec<TorrentResult>> {
-        let response: YtsResponse = serde_json::from_str(json)?;
-        let mut results = Vec::new();
-        
-        if response.status != "ok" {
-            return Err(anyhow::anyhow!("YTS API returned error status: {}", response.status));
-        }
-        
-        let movies = response.data.movies.unwrap_or_default();
+    if response.status != "ok" {
+        return Err(anyhow::anyhow!("YTS API returned error status: {}", response.status));
+    }
+    
+    Ok((response.data.movie_count, response.data.movies.unwrap_or_default()))
+}
+
+/// One result per movie and quality.
+fn to_results(movies: Vec<YtsMovie>) -> Vec<TorrentResult> {
+    let mut results = Vec::new();
+    
+    for movie in movies {
+        let torrents = movie.torrents.unwrap_or_default();
         
-        for movie in movies {
-            let torrents = movie.torrents.unwrap_or_default();
+        for torrent in torrents {
+            // Generate magnet link from hash
+            let magnet_link = format!(
+                "magnet:?xt=urn:btih:{}&dn={}&tr=udp://open.demonii.com:1337&tr=udp://tracker.openbittorrent.com:80&tr=udp://tracker.coppersurfer.tk:6969&tr=udp://glotorrents.pw:6969/announce&tr=udp://tracker.opentrackr.org:1337/announce&tr=udp://torrent.gresille.org:80/announce&tr=udp://p4p.arenabg.com:1337&tr=udp://tracker.leechers-paradise.org:6969",
+                torrent.hash,
+                urlencoding::encode(&format!("{} ({}) [{}]", movie.title, movie.year, torrent.quality))
+            );
             
-            for torrent in torrents {
-                // Generate magnet link from hash
-                let magnet_link = format!(
-                    "magnet:?xt=urn:btih:{}&dn={}&tr=udp://open.demonii.com:1337&tr=udp://tracker.openbittorrent.com:80&tr=udp://tracker.coppersurfer.tk:6969&tr=udp://glotorrents.pw:6969/announce&tr=udp://tracker.opentrackr.org:1337/announce&tr=udp://torrent.gresille.org:80/announce&tr=udp://p4p.arenabg.com:1337&tr=udp://tracker.leechers-paradise.org:6969",
-                    torrent.hash,
-                    urlencoding::encode(&format!("{} ({}) [{}]", movie.title, movie.year, torrent.quality))
-                );
-                
-                let title = format!("{} ({}) [{}]", movie.title, movie.year, torrent.quality);
-                
-                results.push(TorrentResult {
-                    title,
-                    magnet_link,
-                    size: torrent.size,
-                    seeders: torrent.seeds,
-                    leechers: torrent.peers,
-                    uploaded: torrent.date_uploaded.or(movie.date_uploaded.clone()),
-                });
-            }
+            let title = format!("{} ({}) [{}]", movie.title, movie.year, torrent.quality);
+            
+            results.push(TorrentResult {
+                title,
+                magnet_link,
+                size: torrent.size,
+                seeders: torrent.seeds,
+                leechers: torrent.peers,
+                uploaded: torrent.date_uploaded.or(movie.date_uploaded.clone()),
+                category: Some("Video > Movies".to_string()),
+                uploader: None,
+                found_by: Vec::new(),
+            });
         }
-        
-        info!("Found {} YTS results", results.len());
-        Ok(results)
     }
+    
+    results
 }
 
 // Helper module for URL encoding